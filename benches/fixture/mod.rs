@@ -0,0 +1,304 @@
+//! Databases for the benchmarks to read, built in memory with the same layout SQLite
+//! writes so the benchmarks don't depend on fixture files or the `sqlite3` binary
+
+pub const TABLE: &str = "scores";
+pub const INDEX: &str = "scores_score";
+
+const PAGE_SIZE: usize = 4096;
+
+/// Bytes of the database header at the start of the first page
+const HEADER_SIZE: usize = 100;
+
+const INTERIOR_INDEX: u8 = 0x02;
+const INTERIOR_TABLE: u8 = 0x05;
+const LEAF_INDEX: u8 = 0x0a;
+const LEAF_TABLE: u8 = 0x0d;
+
+/// Children per interior page, few enough that their cells always fit
+const TABLE_FANOUT: usize = 200;
+const INDEX_FANOUT: usize = 100;
+
+enum Value<'a> {
+    Null,
+    Integer(i64),
+    Text(&'a str),
+}
+
+/// A database with `rows` rows in
+/// `scores(id INTEGER PRIMARY KEY, name TEXT, score INTEGER)` and an index on
+/// `score`, where row `id` is named `player {id}` and scores `id % 1000`
+pub fn scores_database(rows: i64) -> Vec<u8> {
+    // The first page holds the schema, which is only known once the trees are built
+    let mut pages = vec![Vec::new()];
+
+    let table_rows = (1..=rows).map(|id| {
+        let name = format!("player {id}");
+        let payload = record(&[Value::Null, Value::Text(&name), Value::Integer(id % 1000)]);
+        (id, payload)
+    });
+    let table_root = build_table(&mut pages, table_rows);
+
+    let mut entries: Vec<(i64, i64)> = (1..=rows).map(|id| (id % 1000, id)).collect();
+    entries.sort_unstable();
+    let entries = entries
+        .into_iter()
+        .map(|(score, id)| record(&[Value::Integer(score), Value::Integer(id)]));
+    let index_root = build_index(&mut pages, entries);
+
+    let table_sql =
+        format!("CREATE TABLE {TABLE}(id INTEGER PRIMARY KEY, name TEXT, score INTEGER)");
+    let index_sql = format!("CREATE INDEX {INDEX} ON {TABLE}(score)");
+    let schema = [
+        ("table", TABLE, table_root, table_sql),
+        ("index", INDEX, index_root, index_sql),
+    ];
+    let schema_cells: Vec<Vec<u8>> = schema
+        .iter()
+        .enumerate()
+        .map(|(idx, (kind, name, root, sql))| {
+            let payload = record(&[
+                Value::Text(kind),
+                Value::Text(name),
+                Value::Text(TABLE),
+                Value::Integer(i64::from(*root)),
+                Value::Text(sql),
+            ]);
+            table_leaf_cell(idx as i64 + 1, &payload)
+        })
+        .collect();
+
+    pages[0] = page(LEAF_TABLE, &schema_cells, None, HEADER_SIZE);
+    let header = header(pages.len());
+    pages[0][..HEADER_SIZE].copy_from_slice(&header);
+    pages.concat()
+}
+
+/// The database header for a database of `page_count` pages
+fn header(page_count: usize) -> [u8; HEADER_SIZE] {
+    let mut header = [0; HEADER_SIZE];
+    header[..16].copy_from_slice(b"SQLite format 3\0");
+    header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    // File format versions, no reserved space and the fixed payload fractions
+    header[18..24].copy_from_slice(&[1, 1, 0, 64, 32, 32]);
+    header[24..28].copy_from_slice(&1u32.to_be_bytes());
+    header[28..32].copy_from_slice(&(page_count as u32).to_be_bytes());
+    header[40..44].copy_from_slice(&1u32.to_be_bytes()); // schema cookie
+    header[44..48].copy_from_slice(&4u32.to_be_bytes()); // schema format
+    header[56..60].copy_from_slice(&1u32.to_be_bytes()); // UTF-8
+    header[92..96].copy_from_slice(&1u32.to_be_bytes());
+    header[96..100].copy_from_slice(&3_046_000u32.to_be_bytes());
+    header
+}
+
+/// Builds a table b-tree from rows in rowid order, returning its root page
+fn build_table(pages: &mut Vec<Vec<u8>>, rows: impl Iterator<Item = (i64, Vec<u8>)>) -> u32 {
+    // Each child along with the largest rowid under it
+    let mut children: Vec<(u32, i64)> = Vec::new();
+    let mut cells = Vec::new();
+    let mut last_row_id = 0;
+    for (row_id, payload) in rows {
+        let cell = table_leaf_cell(row_id, &payload);
+        if !fits(&cells, &cell, LEAF_TABLE) {
+            children.push((add_page(pages, LEAF_TABLE, &cells, None), last_row_id));
+            cells.clear();
+        }
+
+        cells.push(cell);
+        last_row_id = row_id;
+    }
+    children.push((add_page(pages, LEAF_TABLE, &cells, None), last_row_id));
+
+    while children.len() > 1 {
+        children = children
+            .chunks(TABLE_FANOUT)
+            .map(|group| {
+                let (right, keys) = group.split_last().expect("chunks aren't empty");
+                let cells: Vec<Vec<u8>> = keys
+                    .iter()
+                    .map(|(child, key)| {
+                        let mut cell = child.to_be_bytes().to_vec();
+                        cell.extend(varint(*key as u64));
+                        cell
+                    })
+                    .collect();
+                (
+                    add_page(pages, INTERIOR_TABLE, &cells, Some(right.0)),
+                    right.1,
+                )
+            })
+            .collect();
+    }
+
+    children[0].0
+}
+
+/// Builds an index b-tree from entries in key order, returning its root page
+///
+/// Index b-trees keep entries in their interior pages too, so the entry between
+/// two pages moves up to the level above as the divider between them.
+fn build_index(pages: &mut Vec<Vec<u8>>, entries: impl Iterator<Item = Vec<u8>>) -> u32 {
+    let mut children = Vec::new();
+    let mut dividers = Vec::new();
+    let mut cells = Vec::new();
+    let mut entries = entries.peekable();
+    while let Some(entry) = entries.next() {
+        let mut cell = varint(entry.len() as u64);
+        cell.extend(&entry);
+        if !fits(&cells, &cell, LEAF_INDEX) {
+            children.push(add_page(pages, LEAF_INDEX, &cells, None));
+            cells.clear();
+            if entries.peek().is_some() {
+                dividers.push(entry);
+                continue;
+            }
+        }
+
+        cells.push(cell);
+    }
+    children.push(add_page(pages, LEAF_INDEX, &cells, None));
+
+    while children.len() > 1 {
+        let mut parents = Vec::new();
+        let mut parent_dividers = Vec::new();
+        for (group, start) in children
+            .chunks(INDEX_FANOUT)
+            .zip((0..).step_by(INDEX_FANOUT))
+        {
+            let (right, lefts) = group.split_last().expect("chunks aren't empty");
+            let cells: Vec<Vec<u8>> = lefts
+                .iter()
+                .zip(&dividers[start..])
+                .map(|(child, divider)| {
+                    let mut cell = child.to_be_bytes().to_vec();
+                    cell.extend(varint(divider.len() as u64));
+                    cell.extend(divider);
+                    cell
+                })
+                .collect();
+            parents.push(add_page(pages, INTERIOR_INDEX, &cells, Some(*right)));
+
+            if let Some(divider) = dividers.get(start + group.len() - 1) {
+                parent_dividers.push(divider.clone());
+            }
+        }
+
+        children = parents;
+        dividers = parent_dividers;
+    }
+
+    children[0]
+}
+
+fn table_leaf_cell(row_id: i64, payload: &[u8]) -> Vec<u8> {
+    let mut cell = varint(payload.len() as u64);
+    cell.extend(varint(row_id as u64));
+    cell.extend(payload);
+    cell
+}
+
+/// Whether a leaf holding `cells` has room for `cell` as well
+fn fits(cells: &[Vec<u8>], cell: &[u8], page_type: u8) -> bool {
+    let used: usize = cells.iter().map(|cell| cell.len() + 2).sum();
+    header_size(page_type) + used + cell.len() + 2 <= PAGE_SIZE
+}
+
+fn header_size(page_type: u8) -> usize {
+    match page_type {
+        INTERIOR_INDEX | INTERIOR_TABLE => 12,
+        _ => 8,
+    }
+}
+
+/// Appends a page to the database, returning its page number counting from 1
+fn add_page(
+    pages: &mut Vec<Vec<u8>>,
+    page_type: u8,
+    cells: &[Vec<u8>],
+    right_child: Option<u32>,
+) -> u32 {
+    pages.push(page(page_type, cells, right_child, 0));
+    pages.len() as u32
+}
+
+/// A b-tree page with the cells packed against its end, `header_offset` bytes in
+/// for the first page
+fn page(
+    page_type: u8,
+    cells: &[Vec<u8>],
+    right_child: Option<u32>,
+    header_offset: usize,
+) -> Vec<u8> {
+    let mut page = vec![0; PAGE_SIZE];
+    let mut content = PAGE_SIZE;
+    let mut pointer = header_offset + header_size(page_type);
+    for cell in cells {
+        content -= cell.len();
+        page[content..content + cell.len()].copy_from_slice(cell);
+        page[pointer..pointer + 2].copy_from_slice(&(content as u16).to_be_bytes());
+        pointer += 2;
+    }
+
+    let header = &mut page[header_offset..];
+    header[0] = page_type;
+    header[3..5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    header[5..7].copy_from_slice(&(content as u16).to_be_bytes());
+    if let Some(right_child) = right_child {
+        header[8..12].copy_from_slice(&right_child.to_be_bytes());
+    }
+
+    page
+}
+
+/// A record: the size of its header, the serial type of each value and then the
+/// values themselves
+fn record(values: &[Value]) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body: Vec<u8> = Vec::new();
+    for value in values {
+        match value {
+            Value::Null => types.extend(varint(0)),
+            Value::Integer(integer) => {
+                let bytes = integer.to_be_bytes();
+                let (serial_type, size) = match integer {
+                    -128..=127 => (1, 1),
+                    -32768..=32767 => (2, 2),
+                    -2147483648..=2147483647 => (4, 4),
+                    _ => (6, 8),
+                };
+                types.extend(varint(serial_type));
+                body.extend(&bytes[8 - size..]);
+            }
+            Value::Text(text) => {
+                types.extend(varint(13 + 2 * text.len() as u64));
+                body.extend(text.as_bytes());
+            }
+        }
+    }
+
+    // The size includes itself, which is a single byte for these small headers
+    let mut record = varint(types.len() as u64 + 1);
+    record.extend(types);
+    record.extend(body);
+    record
+}
+
+/// Encodes a varint: seven bits to a byte with the high bit marking that more
+/// follow, except that the ninth byte holds a full eight bits
+pub fn varint(value: u64) -> Vec<u8> {
+    if value >> 56 != 0 {
+        let mut bytes: Vec<u8> = (0..8)
+            .map(|idx| ((value >> (8 + 7 * (7 - idx))) as u8 & 0x7f) | 0x80)
+            .collect();
+        bytes.push(value as u8);
+        return bytes;
+    }
+
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest != 0 {
+        bytes.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.reverse();
+    bytes
+}
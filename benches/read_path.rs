@@ -0,0 +1,72 @@
+use codecrafters_sqlite::sqlite::{parse_varint, SqliteReader};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+mod fixture;
+
+/// Rows in the benchmark table, enough for its b-tree to be three pages deep
+const ROWS: i64 = 200_000;
+
+fn database() -> SqliteReader {
+    SqliteReader::from_bytes(fixture::scores_database(ROWS))
+        .expect("the generated database is valid")
+}
+
+fn full_scan(c: &mut Criterion) {
+    let db = database();
+    let query = format!("SELECT name, score FROM {}", fixture::TABLE);
+
+    let mut group = c.benchmark_group("full scan");
+    group.throughput(Throughput::Elements(ROWS as u64));
+    group.bench_function("select two columns", |b| {
+        b.iter(|| db.select(black_box(&query)).expect("query runs"))
+    });
+    group.finish();
+}
+
+fn index_lookup(c: &mut Criterion) {
+    let db = database();
+    let query = format!("SELECT name FROM {} WHERE score = 500", fixture::TABLE);
+
+    c.bench_function("index lookup", |b| {
+        b.iter(|| db.select(black_box(&query)).expect("query runs"))
+    });
+}
+
+fn count(c: &mut Criterion) {
+    let db = database();
+    let query = format!("SELECT COUNT(*) FROM {}", fixture::TABLE);
+
+    c.bench_function("count", |b| {
+        b.iter(|| db.select(black_box(&query)).expect("query runs"))
+    });
+}
+
+fn varint_decoding(c: &mut Criterion) {
+    // Every length of varint from one byte to nine
+    let values: Vec<u64> = (0..10_000u64)
+        .map(|n| n.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (n % 64))
+        .collect();
+    let bytes: Vec<u8> = values
+        .iter()
+        .flat_map(|&value| fixture::varint(value))
+        .collect();
+
+    let mut group = c.benchmark_group("varint");
+    group.throughput(Throughput::Elements(values.len() as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            let mut buf = black_box(bytes.as_slice());
+            let mut sum = 0u64;
+            while !buf.is_empty() {
+                let (value, consumed) = parse_varint(buf);
+                sum = sum.wrapping_add(value);
+                buf = &buf[consumed..];
+            }
+            sum
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, full_scan, index_lookup, count, varint_decoding);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+#![no_main]
+
+use codecrafters_sqlite::sqlite::{
+    cell::Overflow,
+    page::BTreePage,
+    pager::{MemoryPager, Pager},
+    DatabaseHeader, HEADER_SIZE,
+};
+use libfuzzer_sys::fuzz_target;
+
+// The input is a whole database file, so overflow chains lead into fuzzed pages too
+fuzz_target!(|data: &[u8]| {
+    let Ok(header) = DatabaseHeader::new(data) else {
+        return;
+    };
+    let Ok(pager) = MemoryPager::new(data.to_vec()) else {
+        return;
+    };
+
+    let overflow = Overflow::new(&pager, usize::from(header.reserved_space));
+    for page_no in 0..pager.page_count() {
+        let page = pager.get_page(page_no).expect("page is within the database");
+        let page = if page_no == 0 { &page[HEADER_SIZE..] } else { page };
+        let _ = BTreePage::new(page, page_no, overflow);
+    }
+});
@@ -0,0 +1,35 @@
+#![no_main]
+
+use codecrafters_sqlite::sqlite::{
+    cell::{IndexLeafCell, InteriorIndexCell, InteriorTableCell, LeafCell, Overflow},
+    pager::MemoryPager,
+};
+use libfuzzer_sys::fuzz_target;
+
+const PAGE_SIZE: usize = 512;
+
+// The first byte picks the kind of cell and the rest is the cell itself
+fuzz_target!(|data: &[u8]| {
+    let Some((kind, cell)) = data.split_first() else {
+        return;
+    };
+
+    let pager = MemoryPager::new(database(cell)).expect("the database has a valid page size");
+    let overflow = Overflow::new(&pager, 0);
+    match kind % 4 {
+        0 => drop(LeafCell::new(cell, overflow)),
+        1 => drop(InteriorTableCell::new(cell)),
+        2 => drop(InteriorIndexCell::new(cell, overflow)),
+        _ => drop(IndexLeafCell::new(cell, overflow)),
+    }
+});
+
+/// A database whose pages after the first hold `bytes`, so that overflow chains
+/// lead into the fuzzed input
+fn database(bytes: &[u8]) -> Vec<u8> {
+    let mut database = vec![0; PAGE_SIZE];
+    database[..16].copy_from_slice(b"SQLite format 3\0");
+    database[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    database.extend_from_slice(bytes);
+    database
+}
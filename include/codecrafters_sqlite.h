@@ -0,0 +1,77 @@
+#ifndef CODECRAFTERS_SQLITE_H
+#define CODECRAFTERS_SQLITE_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The call succeeded
+#define CSQL_OK 0
+
+// The call failed, with the reason given by `csql_errmsg`
+#define CSQL_ERROR 1
+
+// The row callback returned non-zero, stopping `csql_exec`
+#define CSQL_ABORT 4
+
+// The call was made with a null pointer or a database that failed to open
+#define CSQL_MISUSE 21
+
+// A database opened by `csql_open`, along with the error of the last call made on it
+typedef struct csql_database csql_database;
+
+// Called by `csql_exec` with each row of a query: the `user_data` it was given, the
+// number of columns, each value as text with NULL as a null pointer, and the
+// column names
+//
+// The strings only live until the callback returns. Returning non-zero stops the
+// query.
+typedef int (*csql_callback)(void *user_data, int columns, char **values, char **names);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Opens the database file at `path`, storing a handle to it in `*db`
+//
+// A handle is stored even when opening fails, so the reason can be read with
+// `csql_errmsg`, and has to be freed with `csql_close` either way.
+//
+// # Safety
+//
+// `path` has to be a null-terminated string and `db` has to point to writable
+// memory for a pointer.
+int csql_open(const char *path, struct csql_database **db);
+
+// Runs each statement in `sql`, calling `callback` with every row of every query
+//
+// # Safety
+//
+// `db` has to be a handle from `csql_open` that hasn't been closed and `sql` a
+// null-terminated string. `callback` may be null to ignore the rows.
+int csql_exec(struct csql_database *db, const char *sql, csql_callback callback, void *user_data);
+
+// The error of the last call made with `db`, or "not an error" if it succeeded
+//
+// The string lives until the next call made with `db`.
+//
+// # Safety
+//
+// `db` has to be null or a handle from `csql_open` that hasn't been closed.
+const char *csql_errmsg(const struct csql_database *db);
+
+// Closes a database opened by `csql_open`, doing nothing when `db` is null
+//
+// # Safety
+//
+// `db` has to be null or a handle from `csql_open` that hasn't been closed.
+void csql_close(struct csql_database *db);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CODECRAFTERS_SQLITE_H */
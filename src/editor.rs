@@ -0,0 +1,231 @@
+use codecrafters_sqlite::sqlite::{
+    sql::{self, CreateStatement},
+    SqliteReader,
+};
+use rustyline::{
+    completion::Completer, highlight::Highlighter, hint::Hinter, validate::Validator, Context,
+    Helper,
+};
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+/// File in the home directory the commands of each session are kept in
+const HISTORY_FILE: &str = ".codecrafters_sqlite_history";
+
+/// The dot commands understood by the command line
+const DOT_COMMANDS: &[&str] = &[
+    ".backup",
+    ".blob",
+    ".btree",
+    ".cellhex",
+    ".dbinfo",
+    ".dbstat",
+    ".diff",
+    ".exit",
+    ".export",
+    ".headers",
+    ".limit",
+    ".map",
+    ".mode",
+    ".nullvalue",
+    ".once",
+    ".output",
+    ".page",
+    ".pager",
+    ".quit",
+    ".read",
+    ".recover",
+    ".schema",
+    ".sequence",
+    ".tables",
+    ".timer",
+    ".verify-header",
+    ".version",
+];
+
+/// The SQL keywords of the statements the parser understands
+const KEYWORDS: &[&str] = &[
+    "ANALYZE", "AND", "AS", "ASC", "ATTACH", "BETWEEN", "BY", "CAST", "COLLATE", "DATABASE",
+    "DESC", "DETACH", "ESCAPE", "FROM", "GLOB", "IN", "IS", "LIKE", "NOT", "NULL", "OR", "ORDER",
+    "PRAGMA", "SELECT", "WHERE",
+];
+
+/// Where the history of commands is kept between sessions, or `None` when there's no
+/// home directory to keep it in
+pub fn history_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").filter(|home| !home.is_empty())?;
+    Some(PathBuf::from(home).join(HISTORY_FILE))
+}
+
+// Colours the keywords, strings, numbers and comments of SQL are shown in
+const KEYWORD_STYLE: &str = "\x1b[1;34m";
+const STRING_STYLE: &str = "\x1b[32m";
+const NUMBER_STYLE: &str = "\x1b[33m";
+const COMMENT_STYLE: &str = "\x1b[2m";
+const RESET_STYLE: &str = "\x1b[0m";
+
+/// Completes dot commands at the start of a line, and the SQL keywords along with the
+/// names of the tables and columns of the database anywhere else, highlighting the
+/// SQL as it's typed
+#[derive(Debug, Default)]
+pub struct SqlHelper {
+    /// Tables, indexes, views and the columns of tables, sorted without repeats
+    names: Vec<String>,
+}
+
+impl SqlHelper {
+    /// Picks up the names in the schema of `db`, leaving only the keywords when it
+    /// can't be read
+    pub fn new(db: &SqliteReader) -> Self {
+        let Ok(schema) = db.schema() else {
+            return Self::default();
+        };
+
+        let mut names = Vec::new();
+        for entry in schema.entries() {
+            names.push(entry.name.clone());
+            if let Ok((_, CreateStatement::Table(table))) = sql::create_statement(&entry.sql) {
+                names.extend(table.columns.into_iter().map(|column| column.name));
+            }
+        }
+        names.sort();
+        names.dedup();
+
+        Self { names }
+    }
+
+    /// The words starting with `prefix`, ignoring case, with keywords written in the
+    /// case the prefix is typed in
+    fn candidates(&self, prefix: &str) -> Vec<String> {
+        let matches = |word: &str| {
+            word.len() >= prefix.len()
+                && word.is_char_boundary(prefix.len())
+                && word[..prefix.len()].eq_ignore_ascii_case(prefix)
+        };
+        let lowercase = prefix.chars().any(|c| c.is_ascii_lowercase());
+
+        let keywords = KEYWORDS.iter().filter(|keyword| matches(keyword));
+        let keywords = keywords.map(|keyword| match lowercase {
+            true => keyword.to_lowercase(),
+            false => keyword.to_string(),
+        });
+        let names = self.names.iter().filter(|name| matches(name)).cloned();
+
+        let mut candidates: Vec<String> = names.chain(keywords).collect();
+        candidates.dedup();
+        candidates
+    }
+}
+
+impl Completer for SqlHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+            .map_or(0, |(idx, c)| idx + c.len_utf8());
+
+        // A dot command is only ever the first word of its line
+        if before.trim_start().starts_with('.') && !before.trim_start().contains(' ') {
+            let start = before.len() - before.trim_start().len();
+            let prefix = &before[start..];
+            let commands = DOT_COMMANDS
+                .iter()
+                .filter(|command| command.starts_with(prefix))
+                .map(|command| command.to_string());
+            return Ok((start, commands.collect()));
+        }
+
+        Ok((start, self.candidates(&before[start..])))
+    }
+}
+
+impl Hinter for SqlHelper {
+    type Hint = String;
+}
+
+impl Highlighter for SqlHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        // Dot commands aren't SQL, and `NO_COLOR` asks for no colours at all
+        if line.trim_start().starts_with('.') || std::env::var_os("NO_COLOR").is_some() {
+            return Cow::Borrowed(line);
+        }
+
+        let mut highlighted = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            let (len, style) = match c {
+                '\'' => (quoted_len(rest, '\''), Some(STRING_STYLE)),
+                '"' | '`' => (quoted_len(rest, c), None),
+                '-' if rest.starts_with("--") => {
+                    (rest.find('\n').unwrap_or(rest.len()), Some(COMMENT_STYLE))
+                }
+                c if c.is_ascii_digit() => (word_len(rest, '.'), Some(NUMBER_STYLE)),
+                c if c.is_alphabetic() || c == '_' => {
+                    let len = word_len(rest, '_');
+                    let keyword = KEYWORDS
+                        .iter()
+                        .any(|keyword| keyword.eq_ignore_ascii_case(&rest[..len]));
+                    (len, keyword.then_some(KEYWORD_STYLE))
+                }
+                c => (c.len_utf8(), None),
+            };
+
+            let (token, next) = rest.split_at(len);
+            match style {
+                Some(style) => {
+                    highlighted.push_str(style);
+                    highlighted.push_str(token);
+                    highlighted.push_str(RESET_STYLE);
+                }
+                None => highlighted.push_str(token),
+            }
+            rest = next;
+        }
+
+        Cow::Owned(highlighted)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        // Any character typed can start or end a token
+        true
+    }
+}
+
+impl Validator for SqlHelper {}
+
+impl Helper for SqlHelper {}
+
+/// Length of the string or quoted name starting `text`, running to the end of the line
+/// when it isn't closed yet
+fn quoted_len(text: &str, quote: char) -> usize {
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((idx, c)) = chars.next() {
+        if c == quote {
+            // A doubled quote stands for the quote itself
+            match chars.peek() {
+                Some((_, next)) if *next == quote => {
+                    chars.next();
+                }
+                _ => return idx + c.len_utf8(),
+            }
+        }
+    }
+
+    text.len()
+}
+
+/// Length of the word or number starting `text`, which runs over letters, digits
+/// and `extra`
+fn word_len(text: &str, extra: char) -> usize {
+    text.find(|c: char| !(c.is_alphanumeric() || c == extra))
+        .unwrap_or(text.len())
+}
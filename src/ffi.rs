@@ -0,0 +1,226 @@
+//! A C interface to the reader, for embedding it in programs written in other
+//! languages
+//!
+//! `include/codecrafters_sqlite.h` is generated from this module by running
+//! `cbindgen --config cbindgen.toml --output include/codecrafters_sqlite.h src/ffi.rs`.
+
+use anyhow::{anyhow, Result};
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::sqlite::{cell::RecordValue, database::Database, result::ResultSet, sql};
+
+/// The call succeeded
+pub const CSQL_OK: c_int = 0;
+/// The call failed, with the reason given by `csql_errmsg`
+pub const CSQL_ERROR: c_int = 1;
+/// The row callback returned non-zero, stopping `csql_exec`
+pub const CSQL_ABORT: c_int = 4;
+/// The call was made with a null pointer or a database that failed to open
+pub const CSQL_MISUSE: c_int = 21;
+
+/// Called by `csql_exec` with each row of a query: the `user_data` it was given, the
+/// number of columns, each value as text with NULL as a null pointer, and the
+/// column names
+///
+/// The strings only live until the callback returns. Returning non-zero stops the
+/// query.
+pub type CsqlCallback = Option<
+    unsafe extern "C" fn(
+        user_data: *mut c_void,
+        columns: c_int,
+        values: *mut *mut c_char,
+        names: *mut *mut c_char,
+    ) -> c_int,
+>;
+
+/// A database opened by `csql_open`, along with the error of the last call made on it
+pub struct CsqlDatabase {
+    /// `None` when the database failed to open
+    database: Option<Database>,
+    error: Option<CString>,
+}
+
+impl CsqlDatabase {
+    /// Records the outcome of a call, returning its result code
+    fn finish(&mut self, result: Result<c_int>) -> c_int {
+        match result {
+            Ok(code) => {
+                self.error = None;
+                code
+            }
+            Err(e) => {
+                self.error = Some(c_string(format!("{e:#}").into_bytes()));
+                CSQL_ERROR
+            }
+        }
+    }
+}
+
+/// Opens the database file at `path`, storing a handle to it in `*db`
+///
+/// A handle is stored even when opening fails, so the reason can be read with
+/// `csql_errmsg`, and has to be freed with `csql_close` either way.
+///
+/// # Safety
+///
+/// `path` has to be a null-terminated string and `db` has to point to writable
+/// memory for a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn csql_open(path: *const c_char, db: *mut *mut CsqlDatabase) -> c_int {
+    if path.is_null() || db.is_null() {
+        return CSQL_MISUSE;
+    }
+
+    let path = CStr::from_ptr(path);
+    let mut handle = CsqlDatabase {
+        database: None,
+        error: None,
+    };
+    let result = catch(|| {
+        let path = path.to_str().map_err(|_| anyhow!("path is not utf-8"))?;
+        handle.database = Some(Database::open(path)?);
+        Ok(CSQL_OK)
+    });
+    let code = handle.finish(result);
+
+    *db = Box::into_raw(Box::new(handle));
+    code
+}
+
+/// Runs each statement in `sql`, calling `callback` with every row of every query
+///
+/// # Safety
+///
+/// `db` has to be a handle from `csql_open` that hasn't been closed and `sql` a
+/// null-terminated string. `callback` may be null to ignore the rows.
+#[no_mangle]
+pub unsafe extern "C" fn csql_exec(
+    db: *mut CsqlDatabase,
+    sql: *const c_char,
+    callback: CsqlCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(handle) = db.as_mut() else {
+        return CSQL_MISUSE;
+    };
+    let Some(database) = handle.database.as_mut() else {
+        return CSQL_MISUSE;
+    };
+    if sql.is_null() {
+        return CSQL_MISUSE;
+    }
+
+    let sql = CStr::from_ptr(sql);
+    let result = catch(|| {
+        let sql = sql.to_str().map_err(|_| anyhow!("sql is not utf-8"))?;
+        for text in sql::split_statements(sql) {
+            let Some(result) = database.execute_statement(text)? else {
+                continue;
+            };
+
+            if !report_rows(&result, callback, user_data) {
+                return Ok(CSQL_ABORT);
+            }
+        }
+
+        Ok(CSQL_OK)
+    });
+
+    let code = handle.finish(result);
+    if code == CSQL_ABORT {
+        handle.error = Some(c"query aborted".into());
+    }
+    code
+}
+
+/// The error of the last call made with `db`, or "not an error" if it succeeded
+///
+/// The string lives until the next call made with `db`.
+///
+/// # Safety
+///
+/// `db` has to be null or a handle from `csql_open` that hasn't been closed.
+#[no_mangle]
+pub unsafe extern "C" fn csql_errmsg(db: *const CsqlDatabase) -> *const c_char {
+    match db.as_ref() {
+        Some(handle) => handle.error.as_deref().unwrap_or(c"not an error").as_ptr(),
+        None => c"no database handle".as_ptr(),
+    }
+}
+
+/// Closes a database opened by `csql_open`, doing nothing when `db` is null
+///
+/// # Safety
+///
+/// `db` has to be null or a handle from `csql_open` that hasn't been closed.
+#[no_mangle]
+pub unsafe extern "C" fn csql_close(db: *mut CsqlDatabase) {
+    if !db.is_null() {
+        drop(Box::from_raw(db));
+    }
+}
+
+/// Passes each row to `callback`, returning false if it asked to stop
+unsafe fn report_rows(result: &ResultSet, callback: CsqlCallback, user_data: *mut c_void) -> bool {
+    let Some(callback) = callback else {
+        return true;
+    };
+
+    let names: Vec<CString> = result
+        .columns
+        .iter()
+        .map(|name| c_string(name.clone().into_bytes()))
+        .collect();
+    let mut name_ptrs: Vec<*mut c_char> =
+        names.iter().map(|name| name.as_ptr().cast_mut()).collect();
+
+    for row in result.rows.iter() {
+        let values: Vec<Option<CString>> = row.iter().map(value_text).collect();
+        let mut value_ptrs: Vec<*mut c_char> = values
+            .iter()
+            .map(|value| {
+                value
+                    .as_ref()
+                    .map_or(ptr::null_mut(), |v| v.as_ptr().cast_mut())
+            })
+            .collect();
+
+        let columns = c_int::try_from(row.len()).unwrap_or(c_int::MAX);
+        if callback(
+            user_data,
+            columns,
+            value_ptrs.as_mut_ptr(),
+            name_ptrs.as_mut_ptr(),
+        ) != 0
+        {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// A value as the text handed to C, where blobs are their raw bytes and NULL has none
+fn value_text(value: &RecordValue) -> Option<CString> {
+    match value {
+        RecordValue::Null => None,
+        RecordValue::Blob(blob) => Some(c_string(blob.clone())),
+        other => Some(c_string(other.to_string().into_bytes())),
+    }
+}
+
+/// A C string of `bytes`, cut short at the first NUL the way C would read it
+fn c_string(mut bytes: Vec<u8>) -> CString {
+    if let Some(nul) = bytes.iter().position(|&byte| byte == 0) {
+        bytes.truncate(nul);
+    }
+
+    CString::new(bytes).expect("NUL bytes were cut off")
+}
+
+/// Runs `f`, turning a panic into an error instead of unwinding into C
+fn catch(f: impl FnOnce() -> Result<c_int>) -> Result<c_int> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| Err(anyhow!("internal error")))
+}
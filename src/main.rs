@@ -1,27 +1,674 @@
-use anyhow::Result;
-use clap::Parser;
-use sqlite::SqliteReader;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use codecrafters_sqlite::sqlite::{
+    database::Database,
+    error::{DecodeError, QueryError},
+    interrupt::Interrupted,
+    options::OpenOptions,
+    output::{self, Mode, Output},
+    SqliteReader,
+};
+use editor::SqlHelper;
+use rustyline::{error::ReadlineError, history::DefaultHistory, CompletionType, Config, Editor};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
 
-mod sqlite;
+mod editor;
 
 #[derive(Debug, Parser)]
+#[command(
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    disable_help_subcommand = true
+)]
 struct Sqlite {
-    /// Name of the Database to load
-    dbname: String,
+    #[command(subcommand)]
+    tool: Option<Tool>,
 
-    /// Command to execute
-    command: String,
+    /// Name of the Database to load, or `-` to read it from standard input
+    #[arg(required = true)]
+    dbname: Option<String>,
+
+    /// Commands to execute in turn, stopping at the first to fail, or none to read
+    /// them from standard input
+    command: Vec<String>,
+
+    /// Recover deleted rows from free space, the same as `.recover`
+    #[arg(long, conflicts_with = "command")]
+    carve: bool,
+
+    /// Print the rows of queries as list, table, box, quote, csv, tabs or json, the
+    /// same as `.mode`
+    #[arg(long, value_name = "MODE")]
+    format: Option<Mode>,
+
+    /// Script of dot commands and SQL to run before the command or session, the same
+    /// as `.read`
+    #[arg(long, value_name = "FILE")]
+    init: Option<PathBuf>,
+
+    /// Passphrase of a database encrypted by SQLCipher
+    #[arg(long)]
+    key: Option<String>,
+
+    /// Stop printing the rows of a query after this many, the same as `.limit`
+    #[arg(long)]
+    max_rows: Option<usize>,
+
+    /// Show the rows of each query through `$PAGER`, the same as `.pager on`
+    #[arg(long)]
+    pager: bool,
+
+    /// Open the database read only, refusing statements that would write to it, which
+    /// is the default
+    #[arg(long, conflicts_with = "readwrite")]
+    readonly: bool,
+
+    /// Open the database for reading and writing, failing when the file can't be
+    /// written
+    #[arg(long)]
+    readwrite: bool,
+
+    /// Abort any statement still running after this many seconds, exiting with 7
+    #[arg(long, value_name = "SECONDS", value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+
+    /// Log the pages, b-tree descents and index probes of each query to standard
+    /// error, unless `RUST_LOG` picks what to log
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+// Commands run on databases without opening a session on any of them. A database
+// named like one of them has to be given as a path, such as `./diff`.
+#[derive(Debug, Subcommand)]
+enum Tool {
+    /// Print the SQL that turns the OLD database into NEW, the same as `.diff` run on
+    /// OLD
+    Diff {
+        /// Database the SQL applies to
+        old: PathBuf,
+
+        /// Database the SQL turns OLD into
+        new: PathBuf,
+
+        /// Only list the schema objects and count the rows that differ
+        #[arg(long)]
+        summary: bool,
+    },
+}
+
+/// Settings changed by dot commands that last for the rest of the session
+#[derive(Debug, Default)]
+struct Session {
+    pager: bool,
+    output: Output,
+    /// The first command to fail, which the process exits with once the session ends
+    /// however many fail after it
+    failure: Option<Failure>,
+}
+
+impl Session {
+    /// Prints why a command failed, remembering it for the exit status unless an
+    /// earlier one has failed already
+    fn report(&mut self, e: &anyhow::Error) {
+        eprintln!("Error: {e:#}");
+        self.failure.get_or_insert(Failure::of(e));
+    }
+}
+
+/// Why the process failed, told apart by its exit status so scripts can react to it
+///
+/// Command line arguments that can't be parsed exit with 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// Anything not given a status of its own, such as a file that can't be opened
+    Error = 1,
+    /// SQL the parser doesn't understand, or uses syntax that isn't supported
+    Parse = 3,
+    /// A statement naming a table or column the database doesn't have
+    NoSuchName = 4,
+    /// A database whose pages, cells or records can't be decoded
+    Corrupt = 5,
+    /// A statement that would write to a database opened read only
+    ReadOnly = 6,
+    /// A statement still running at the `--timeout`
+    Interrupted = 7,
+}
+
+impl Failure {
+    fn of(e: &anyhow::Error) -> Self {
+        for cause in e.chain() {
+            match cause.downcast_ref::<QueryError>() {
+                Some(QueryError::Parse { .. } | QueryError::Unsupported(_)) => return Self::Parse,
+                Some(QueryError::NoSuchTable(_) | QueryError::NoSuchColumn(_)) => {
+                    return Self::NoSuchName
+                }
+                Some(QueryError::ReadOnly(_)) => return Self::ReadOnly,
+                None => {}
+            }
+            if cause.is::<DecodeError>() {
+                return Self::Corrupt;
+            }
+            if cause.is::<Interrupted>() {
+                return Self::Interrupted;
+            }
+        }
+
+        Self::Error
+    }
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> Self {
+        ExitCode::from(failure as u8)
+    }
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let cli = Sqlite::parse();
-    let db = SqliteReader::new(cli.dbname)?;
+    init_tracing(cli.verbose);
+
+    let mut session = Session {
+        pager: cli.pager,
+        ..Session::default()
+    };
+    let result = match &cli.tool {
+        Some(Tool::Diff { old, new, summary }) => diff(old, new, *summary),
+        None => run(&cli, &mut session),
+    };
+    if let Err(e) = result {
+        session.report(&e);
+    }
+
+    session.failure.map_or(ExitCode::SUCCESS, ExitCode::from)
+}
+
+/// Opens the database and runs the command, or the session when there's none
+///
+/// Only failures that end the run are returned, those of the commands of a session
+/// or script are reported as they happen.
+fn run(cli: &Sqlite, session: &mut Session) -> Result<()> {
+    let dbname = cli.dbname.as_deref().context("no database given")?;
+    let mut database = if dbname == "-" {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .read_to_end(&mut bytes)
+            .context("reading the database from standard input")?;
+        Database::from_bytes(bytes)?
+    } else if let Some(key) = &cli.key {
+        open_sqlcipher(dbname, key)?
+    } else {
+        let options = OpenOptions::new().read_only(!cli.readwrite);
+        Database::open_with(dbname, options)?
+    };
+    database.set_max_rows(cli.max_rows);
+    if let Some(mode) = cli.format {
+        let mut format = database.output_format().clone();
+        format.set_mode(mode);
+        database.set_output_format(format);
+    }
+    database.set_timeout(cli.timeout);
+
+    if let Some(init) = &cli.init {
+        read_script(&mut database, session, init)?;
+    }
+
+    match (cli.command.as_slice(), cli.carve) {
+        ([], true) => run_command(&mut database, session, ".recover"),
+        ([], false) => repl(&mut database, session),
+        (commands, _) => {
+            for command in commands {
+                run_command(&mut database, session, command)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Prints the SQL that turns the database at `old` into the one at `new`, or with
+/// `summary` only what differs
+fn diff(old: &Path, new: &Path, summary: bool) -> Result<()> {
+    let old = SqliteReader::new(old).with_context(|| format!("opening {}", old.display()))?;
+    let new = SqliteReader::new(new).with_context(|| format!("opening {}", new.display()))?;
+    old.diff(&new, summary, &mut io::stdout().lock())
+}
+
+fn parse_timeout(seconds: &str) -> Result<Duration, String> {
+    let seconds: f64 = seconds.parse().map_err(|e| format!("{e}"))?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("{e}"))
+}
+
+/// Logs to standard error when asked to with `--verbose` or `RUST_LOG`
+fn init_tracing(verbose: bool) {
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) if verbose => EnvFilter::new("codecrafters_sqlite=trace"),
+        Err(_) => return,
+    };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .init();
+}
+
+/// Reads commands from standard input until `.quit` or the end of the input,
+/// reporting errors without stopping
+///
+/// At a terminal lines are edited with completion on TAB and SQL highlighting, and
+/// kept in a history that lasts between sessions. Either way a statement can span
+/// lines up to the one ending it with a `;`.
+fn repl(database: &mut Database, session: &mut Session) -> Result<()> {
+    // Commands piped in are run as a script, without prompts
+    if !io::stdin().is_terminal() {
+        return run_script(database, session, io::stdin().lock().lines());
+    }
+
+    // TAB lists the completions when there's more than one, the way readline does
+    let config = Config::builder()
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = Editor::<SqlHelper, DefaultHistory>::with_config(config)?;
+    editor.set_helper(Some(SqlHelper::new(database.main())));
+    let history = editor::history_path();
+    if let Some(history) = &history {
+        // There's no history yet the first time
+        let _ = editor.load_history(history);
+    }
+
+    // A statement runs on over more lines, prompted for with `...>`, up to the line
+    // ending it with a `;`
+    let mut sql = String::new();
+    loop {
+        let prompt = match sql.is_empty() {
+            true => "sqlite> ",
+            false => "   ...> ",
+        };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            // Ctrl-C abandons the statement being typed rather than the session
+            Err(ReadlineError::Interrupted) => {
+                sql.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        let trimmed = line.trim();
+        if sql.is_empty() {
+            match trimmed {
+                "" => continue,
+                ".quit" | ".exit" => {
+                    editor.add_history_entry(trimmed)?;
+                    break;
+                }
+                command if command.starts_with('.') => {
+                    editor.add_history_entry(command)?;
+                    if let Err(e) = run_command(database, session, command) {
+                        session.report(&e);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        sql.push_str(&line);
+        sql.push('\n');
+        if !trimmed.ends_with(';') {
+            continue;
+        }
+
+        let statement = sql.trim();
+        editor.add_history_entry(statement)?;
+        if let Err(e) = run_command(database, session, statement) {
+            session.report(&e);
+        }
+        sql.clear();
+    }
+
+    if let Some(history) = &history {
+        if let Err(e) = editor.save_history(history) {
+            eprintln!("Error: saving the history to {}: {e}", history.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn run_command(database: &mut Database, session: &mut Session, command: &str) -> Result<()> {
+    // Results only go through the pager when they aren't redirected to a file
+    let paged = session.pager && !session.output.is_redirected();
+    let mut out = session.output.writer()?;
+    let db = database.main();
+    let (name, args) = command
+        .split_once(char::is_whitespace)
+        .unwrap_or((command, ""));
+
+    match name {
+        ".dbinfo" => db.dbinfo(&mut out)?,
+        ".tables" => {
+            let internal = args.split_whitespace().any(|arg| arg == "--all");
+            let pattern = args.split_whitespace().find(|arg| *arg != "--all");
+            // Names are laid out for the terminal they're shown on, if any
+            let width = match session.output.is_redirected() || !io::stdout().is_terminal() {
+                true => None,
+                false => terminal_width(),
+            };
+            let width = width.unwrap_or(output::DEFAULT_TERMINAL_WIDTH);
+            db.tables(pattern, internal, width, &mut out)?
+        }
+        ".schema" => db.show_schema(pattern(args), &mut out)?,
+        ".sequence" => db.show_sequence(pattern(args), &mut out)?,
+        ".recover" => db.recover(&mut out)?,
+        ".dbstat" => db.dbstat(&mut out)?,
+        ".verify-header" => db.verify_header(&mut out)?,
+        ".version" => db.version(&mut out)?,
+        ".page" => {
+            let page_no = args.trim().parse().context("usage: .page N")?;
+            db.page_info(page_no, &mut out)?
+        }
+        ".cellhex" => {
+            let usage = "usage: .cellhex PAGE CELL";
+            let mut args = args.split_whitespace();
+            let (Some(page_no), Some(cell_no), None) = (args.next(), args.next(), args.next())
+            else {
+                bail!(usage);
+            };
+
+            let page_no = page_no.parse().context(usage)?;
+            let cell_no = cell_no.parse().context(usage)?;
+            db.cell_hex(page_no, cell_no, &mut out)?
+        }
+        ".diff" => {
+            let mut args = args.split_whitespace();
+            let summary = args.clone().any(|arg| arg == "--summary");
+            let other = args
+                .find(|arg| *arg != "--summary")
+                .context("usage: .diff OTHER [--summary]")?;
+            db.diff(&SqliteReader::new(other)?, summary, &mut out)?
+        }
+        ".export" => {
+            let usage = "usage: .export PARQUET FILE TABLE|QUERY";
+            let mut args = args.trim().splitn(3, char::is_whitespace);
+            let (Some(format), Some(file), Some(source)) = (args.next(), args.next(), args.next())
+            else {
+                bail!(usage);
+            };
+
+            if !format.eq_ignore_ascii_case("parquet") {
+                bail!("unsupported export format '{format}' - {usage}");
+            }
+
+            export_parquet(db, source.trim(), file, &mut out)?
+        }
+        ".backup" => {
+            let file = args.trim();
+            if file.is_empty() {
+                bail!("usage: .backup FILE");
+            }
+
+            let mut pages = 0;
+            database.backup_to(file, |copied, _| pages = copied)?;
+            writeln!(out, "copied {pages} pages to {file}")?;
+        }
+        ".btree" => {
+            let mut args = args.split_whitespace();
+            let dot = args.clone().any(|arg| arg == "--dot");
+            let name = args
+                .find(|arg| *arg != "--dot")
+                .context("usage: .btree TABLE [--dot]")?;
+            db.btree(name, dot, &mut out)?
+        }
+        ".map" => match args.trim() {
+            "" => db.map(false, &mut out)?,
+            "--json" => db.map(true, &mut out)?,
+            _ => bail!("usage: .map [--json]"),
+        },
+        ".limit" => match args.trim() {
+            "" => match database.max_rows() {
+                Some(max_rows) => writeln!(out, "limit: {max_rows} rows")?,
+                None => writeln!(out, "limit: off")?,
+            },
+            "off" | "0" => database.set_max_rows(None),
+            rows => {
+                let rows = rows.parse().context("usage: .limit [N|off]")?;
+                database.set_max_rows(Some(rows));
+            }
+        },
+        ".timer" => match args.trim() {
+            "on" => database.set_timer(true),
+            "off" => database.set_timer(false),
+            _ => bail!("usage: .timer on|off"),
+        },
+        ".mode" => {
+            let usage = "usage: .mode [list|table|box|quote|csv|tabs|json] [--wrap N]";
+            let mut format = database.output_format().clone();
+            let mut args = args.split_whitespace();
+            match (args.next(), args.next(), args.next()) {
+                (None, _, _) => writeln!(out, "current output mode: {}", format.mode)?,
+                (Some(mode), wrap, width) => {
+                    format.set_mode(mode.parse().map_err(|e| anyhow!("{e} - {usage}"))?);
+                    match (wrap, width) {
+                        (None, None) => {}
+                        (Some("--wrap"), Some(width)) => {
+                            format.max_width = width.parse().context(usage)?;
+                        }
+                        _ => bail!(usage),
+                    }
+                    database.set_output_format(format);
+                }
+            }
+        }
+        ".headers" => {
+            let mut format = database.output_format().clone();
+            format.headers = match args.trim() {
+                "on" => true,
+                "off" => false,
+                _ => bail!("usage: .headers on|off"),
+            };
+            database.set_output_format(format);
+        }
+        ".nullvalue" => {
+            let mut format = database.output_format().clone();
+            format.null_value = args.trim().to_string();
+            database.set_output_format(format);
+        }
+        ".blob" => {
+            let usage = "usage: .blob [text|hex|base64|size]";
+            let mut format = database.output_format().clone();
+            match args.trim() {
+                "" => writeln!(out, "current blob format: {}", format.blob)?,
+                blob => {
+                    format.blob = blob.parse().map_err(|e| anyhow!("{e} - {usage}"))?;
+                    database.set_output_format(format);
+                }
+            }
+        }
+        ".read" => {
+            let file = args.trim();
+            if file.is_empty() {
+                bail!("usage: .read FILE");
+            }
+
+            out.flush()?;
+            read_script(database, session, Path::new(file))?
+        }
+        ".pager" => match args.trim() {
+            "on" => session.pager = true,
+            "off" => session.pager = false,
+            _ => bail!("usage: .pager on|off"),
+        },
+        ".output" | ".once" => {
+            let usage = format!("usage: {name} [-a] [FILE]");
+            let mut args = args.split_whitespace();
+            let (append, file) = match (args.next(), args.next(), args.next()) {
+                (None, _, _) => (false, None),
+                (Some("-a" | "--append"), Some(file), None) => (true, Some(file)),
+                (Some(file), None, _) if !file.starts_with('-') => (false, Some(file)),
+                _ => bail!(usage),
+            };
+
+            // As in sqlite3, `stdout` is standard output rather than a file of that name
+            match file {
+                None | Some("stdout") if name == ".output" => session.output.reset(),
+                Some(file) => session
+                    .output
+                    .redirect(Path::new(file), append, name == ".once")
+                    .with_context(|| format!("opening {file}"))?,
+                None => bail!(usage),
+            }
+        }
+        _ if paged => page(database, command)?,
+        _ => database.execute_to(command, &mut out)?,
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Runs the dot commands and SQL statements in the file at `path`, the same as
+/// [`run_script`]
+fn read_script(database: &mut Database, session: &mut Session, path: &Path) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("reading {}", path.display()))?;
+    let lines = BufReader::new(file).lines();
+    run_script(database, session, lines).with_context(|| format!("reading {}", path.display()))
+}
 
-    match cli.command.as_str() {
-        ".dbinfo" => db.dbinfo(),
-        ".tables" => db.tables()?,
-        query => db.query(query)?,
+/// Runs the dot commands and SQL statements of a script in turn, reporting errors
+/// without stopping, until `.quit` or the end of the script
+///
+/// A dot command takes up a line of its own, while a statement runs on to the line
+/// ending it with a `;`.
+fn run_script(
+    database: &mut Database,
+    session: &mut Session,
+    lines: impl Iterator<Item = io::Result<String>>,
+) -> Result<()> {
+    let mut sql = String::new();
+    for line in lines {
+        let line = line?;
+        let trimmed = line.trim();
+        if sql.is_empty() {
+            match trimmed {
+                "" => continue,
+                ".quit" | ".exit" => return Ok(()),
+                command if command.starts_with('.') => {
+                    if let Err(e) = run_command(database, session, command) {
+                        session.report(&e);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        sql.push_str(&line);
+        sql.push('\n');
+        if trimmed.ends_with(';') {
+            if let Err(e) = run_command(database, session, sql.trim()) {
+                session.report(&e);
+            }
+            sql.clear();
+        }
+    }
+
+    // The last statement can go without its `;`
+    if !sql.trim().is_empty() {
+        if let Err(e) = run_command(database, session, sql.trim()) {
+            session.report(&e);
+        }
     }
 
     Ok(())
 }
+
+/// Runs the statements with their rows shown through `$PAGER`, or `less` when it
+/// isn't set
+fn page(database: &mut Database, sql: &str) -> Result<()> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next().context("$PAGER is empty")?;
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("starting the pager '{pager}'"))?;
+
+    let mut input = child.stdin.take().context("the pager has no input")?;
+    let result = database.execute_to(sql, &mut input);
+    drop(input);
+    child.wait()?;
+
+    // Quitting the pager before the end closes the pipe, which isn't a failure
+    match result {
+        Err(e)
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        other => other,
+    }
+}
+
+/// Columns of the terminal standard output is shown on
+#[cfg(unix)]
+fn terminal_width() -> Option<usize> {
+    // Safety: `winsize` is plain data that may be zeroed
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+
+    // Safety: standard output stays open for the duration of the call and `size` is
+    // a valid `winsize`
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == -1 {
+        return None;
+    }
+    Some(size.ws_col as usize).filter(|&width| width > 0)
+}
+
+#[cfg(not(unix))]
+fn terminal_width() -> Option<usize> {
+    None
+}
+
+/// The optional LIKE pattern given to `.tables` and `.schema`
+fn pattern(args: &str) -> Option<&str> {
+    Some(args.trim()).filter(|pattern| !pattern.is_empty())
+}
+
+#[cfg(feature = "sqlcipher")]
+fn open_sqlcipher(path: &str, key: &str) -> Result<Database> {
+    Ok(Database::new(SqliteReader::open_sqlcipher(
+        path,
+        key.as_bytes(),
+    )?))
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+fn open_sqlcipher(_path: &str, _key: &str) -> Result<Database> {
+    bail!("encrypted databases need the crate built with the `sqlcipher` feature")
+}
+
+#[cfg(feature = "parquet")]
+fn export_parquet(db: &SqliteReader, source: &str, file: &str, out: &mut impl Write) -> Result<()> {
+    let rows = db.export_parquet(source, file)?;
+    writeln!(out, "wrote {rows} rows to {file}")?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn export_parquet(
+    _db: &SqliteReader,
+    _source: &str,
+    _file: &str,
+    _out: &mut impl Write,
+) -> Result<()> {
+    bail!("parquet export needs the crate built with the `parquet` feature")
+}
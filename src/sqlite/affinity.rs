@@ -0,0 +1,213 @@
+use super::cell::RecordValue;
+
+/// Type affinity of a column, derived from its declared type
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Affinity {
+    Integer,
+    Real,
+    Numeric,
+    Text,
+    Blob,
+}
+
+impl Affinity {
+    /// Applies SQLite's affinity rules to a declared column type, in order
+    pub fn from_declared_type(datatype: &str) -> Self {
+        let datatype = datatype.to_ascii_uppercase();
+        if datatype.contains("INT") {
+            Self::Integer
+        } else if ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|t| datatype.contains(t))
+        {
+            Self::Text
+        } else if datatype.contains("BLOB") || datatype.is_empty() {
+            Self::Blob
+        } else if ["REAL", "FLOA", "DOUB"]
+            .iter()
+            .any(|t| datatype.contains(t))
+        {
+            Self::Real
+        } else {
+            Self::Numeric
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Self::Integer | Self::Real | Self::Numeric)
+    }
+
+    /// Affinity a value of a column with this affinity is converted to before it's
+    /// compared with a column of the `other` affinity
+    ///
+    /// Between two columns SQLite only converts text to a number when the other column
+    /// is numeric, never a number to text.
+    pub fn compared_with(&self, other: Affinity) -> Option<Affinity> {
+        (other.is_numeric() && !self.is_numeric()).then_some(Self::Numeric)
+    }
+
+    /// Converts a literal compared against a column of this affinity.
+    ///
+    /// Numeric columns turn well-formed numeric text into numbers and text columns
+    /// turn numbers into text; anything else is compared as-is.
+    pub fn apply(&self, value: &RecordValue) -> RecordValue {
+        match (self, value) {
+            (affinity, RecordValue::String(text)) if affinity.is_numeric() => {
+                let text = text.trim();
+                if let Ok(integer) = text.parse::<i64>() {
+                    RecordValue::I64(integer)
+                } else if let Ok(real) = text.parse::<f64>() {
+                    affinity.apply(&RecordValue::F64(real))
+                } else {
+                    value.clone()
+                }
+            }
+            // Whole reals that fit are stored as integers, as SQLite does
+            (Self::Integer | Self::Numeric, RecordValue::F64(real))
+                if real.fract() == 0.0
+                    && (-9.223_372_036_854_776e18..9.223_372_036_854_776e18).contains(real) =>
+            {
+                RecordValue::I64(*real as i64)
+            }
+            (Self::Text, RecordValue::Null | RecordValue::String(_) | RecordValue::Blob(_)) => {
+                value.clone()
+            }
+            (Self::Text, number) => RecordValue::String(number.to_string()),
+            _ => value.clone(),
+        }
+    }
+
+    /// Reads a value stored in a record or index key of a column with this affinity
+    ///
+    /// SQLite stores whole REAL values as integers to save space, which read back as
+    /// reals.
+    pub fn stored(&self, value: RecordValue) -> RecordValue {
+        match value.as_f64() {
+            Some(real) if *self == Self::Real => RecordValue::F64(real),
+            _ => value,
+        }
+    }
+
+    /// Converts a value the way `CAST(value AS type)` does for a type of this
+    /// affinity.
+    ///
+    /// Unlike [`Affinity::apply`] the conversion always happens: text is read as the
+    /// longest number it starts with, or zero when it doesn't start with one.
+    pub fn cast(&self, value: &RecordValue) -> RecordValue {
+        if *value == RecordValue::Null {
+            return RecordValue::Null;
+        }
+
+        match self {
+            Self::Blob => match value {
+                RecordValue::Blob(_) => value.clone(),
+                other => RecordValue::Blob(cast_text(other).into_bytes()),
+            },
+            Self::Text => RecordValue::String(cast_text(value)),
+            Self::Integer => match value {
+                // Saturates at the bounds of an integer, like SQLite
+                RecordValue::F64(real) => RecordValue::I64(*real as i64),
+                other => match other.as_i64() {
+                    Some(integer) => RecordValue::I64(integer),
+                    None => RecordValue::I64(integer_prefix(&cast_text(other))),
+                },
+            },
+            Self::Real => match value.as_f64() {
+                Some(real) => RecordValue::F64(real),
+                None => RecordValue::F64(real_prefix(&cast_text(value)).unwrap_or(0.0)),
+            },
+            Self::Numeric => match value {
+                RecordValue::F64(_) => value.clone(),
+                other => match other.as_i64() {
+                    Some(integer) => RecordValue::I64(integer),
+                    None => numeric_prefix(&cast_text(other)),
+                },
+            },
+        }
+    }
+}
+
+/// The value as text, reading blobs as UTF-8
+fn cast_text(value: &RecordValue) -> String {
+    match value {
+        RecordValue::Blob(blob) => String::from_utf8_lossy(blob).into_owned(),
+        RecordValue::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The leading run of text that reads as a number: an optional sign, digits with
+/// an optional fraction, then an optional exponent
+fn number_prefix(text: &str) -> &str {
+    let bytes = text.as_bytes();
+    let digits = |mut idx: usize| {
+        while bytes.get(idx).is_some_and(u8::is_ascii_digit) {
+            idx += 1;
+        }
+        idx
+    };
+
+    let mut end = usize::from(matches!(bytes.first(), Some(b'+' | b'-')));
+    let start = end;
+    end = digits(end);
+    if bytes.get(end) == Some(&b'.') {
+        end = digits(end + 1);
+    }
+    if end == start || &text[start..end] == "." {
+        return "";
+    }
+
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let mut exponent = end + 1;
+        if matches!(bytes.get(exponent), Some(b'+' | b'-')) {
+            exponent += 1;
+        }
+        let after = digits(exponent);
+        if after > exponent {
+            end = after;
+        }
+    }
+
+    &text[..end]
+}
+
+/// The integer the text starts with, saturating at the bounds of an integer
+fn integer_prefix(text: &str) -> i64 {
+    let text = text.trim_start();
+    let (negative, digits) = match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+
+    let mut value: i64 = 0;
+    for digit in digits.bytes().take_while(u8::is_ascii_digit) {
+        let digit = i64::from(digit - b'0');
+        value = if negative {
+            value.saturating_mul(10).saturating_sub(digit)
+        } else {
+            value.saturating_mul(10).saturating_add(digit)
+        };
+    }
+
+    value
+}
+
+fn real_prefix(text: &str) -> Option<f64> {
+    number_prefix(text.trim_start()).parse().ok()
+}
+
+/// The number the text starts with, kept as an integer when it is one, or zero
+fn numeric_prefix(text: &str) -> RecordValue {
+    let prefix = number_prefix(text.trim_start());
+    if let Ok(integer) = prefix.parse::<i64>() {
+        return RecordValue::I64(integer);
+    }
+
+    match prefix.parse::<f64>() {
+        // Reals with no fractional part that fit in an integer become one
+        Ok(real) if real.fract() == 0.0 && real.abs() < 9.2e18 => RecordValue::I64(real as i64),
+        Ok(real) => RecordValue::F64(real),
+        Err(_) => RecordValue::I64(0),
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+
+use super::{
+    cell::RecordValue,
+    collation::Collation,
+    cursor::BtCursor,
+    error::QueryError,
+    pager::Pager,
+    result::ResultSet,
+    schema::{SchemaTable, SqliteSchema},
+    sql::{self, CreateStatement, IndexedColumn},
+    stat::Stat1Row,
+    SqliteReader,
+};
+
+impl<P: Pager> SqliteReader<P> {
+    /// Computes the `sqlite_stat1` rows `ANALYZE`, or `ANALYZE target` for a single
+    /// table or index, would write, answering with them
+    ///
+    /// Unlike SQLite's `ANALYZE` nothing is written to the file, as there is no
+    /// writer yet. The rows are only kept by this reader, whose planner uses them in
+    /// place of those the file has for the same tables and indexes until it's
+    /// dropped.
+    pub fn analyze(&self, target: Option<&str>) -> Result<ResultSet> {
+        let schema = self.schema()?;
+        let mut stat1 = self.stat1_rows(&schema)?;
+
+        let rows = match target {
+            None => {
+                stat1.clear();
+                let mut rows = Vec::new();
+                for table in schema.entries() {
+                    rows.extend(self.table_stats(&schema, table)?);
+                }
+                rows
+            }
+            Some(target) => {
+                let Some(entry) = schema.fetch_table(target) else {
+                    return Err(QueryError::NoSuchTable(target.to_string()).into());
+                };
+
+                if entry.is_index() {
+                    stat1.retain(|row| row.index.as_deref() != Some(entry.name.as_str()));
+                    let Some(table) = schema.fetch_table(&entry.table_name) else {
+                        return Err(QueryError::NoSuchTable(entry.table_name.clone()).into());
+                    };
+
+                    self.table_stats(&schema, table)?
+                        .into_iter()
+                        .filter(|row| row.index.as_deref() == Some(entry.name.as_str()))
+                        .collect()
+                } else {
+                    stat1.retain(|row| row.table != entry.name);
+                    self.table_stats(&schema, entry)?
+                }
+            }
+        };
+
+        stat1.extend(rows.iter().cloned());
+        *self.analyzed.write().expect("stat lock poisoned") = Some(stat1);
+
+        let columns = ["tbl", "idx", "stat"];
+        let mut result = ResultSet::new(columns.map(String::from).to_vec());
+        for row in rows {
+            result.rows.push(vec![
+                RecordValue::String(row.table),
+                row.index.map_or(RecordValue::Null, RecordValue::String),
+                RecordValue::String(row.stat),
+            ]);
+        }
+
+        Ok(result)
+    }
+
+    /// The `sqlite_stat1` rows for a table: one for each of its indexes, or when it
+    /// has none one counting its rows, and none at all when it's empty
+    ///
+    /// Like SQLite, SQLite's own tables, virtual tables and views aren't analyzed.
+    fn table_stats(&self, schema: &SqliteSchema, table: &SchemaTable) -> Result<Vec<Stat1Row>> {
+        let Ok((_, CreateStatement::Table(create))) = sql::create_statement(&table.sql) else {
+            return Ok(Vec::new());
+        };
+        if table.is_internal() {
+            return Ok(Vec::new());
+        }
+
+        let mut indexes: Vec<(String, usize, Vec<IndexedColumn>)> = Vec::new();
+        if create.without_rowid {
+            // The table b-tree is itself the index of the primary key
+            let Some(key) = create.unique_keys.iter().find(|key| key.primary_key) else {
+                bail!("WITHOUT ROWID table {} has no PRIMARY KEY", table.name);
+            };
+            indexes.push((
+                table.name.clone(),
+                table.root_page as usize,
+                key.columns.clone(),
+            ));
+        }
+
+        let unique = schema.unique_indexes(table);
+        for index in schema.entries() {
+            if !index.is_index() || index.table_name != table.name {
+                continue;
+            }
+
+            let columns = match unique.iter().find(|(unique, _)| unique.name == index.name) {
+                Some((_, columns)) => columns.clone(),
+                None => index.index_keys(&create),
+            };
+            indexes.push((index.name.clone(), index.root_page as usize, columns));
+        }
+
+        let table_rows = match create.without_rowid {
+            true => self.index_stat(indexes[0].1, &indexes[0].2)?.0,
+            false => self.count_rows(table.root_page as usize)?,
+        };
+        if table_rows == 0 {
+            return Ok(Vec::new());
+        }
+
+        if indexes.is_empty() {
+            return Ok(vec![Stat1Row {
+                table: table.name.clone(),
+                index: None,
+                stat: table_rows.to_string(),
+            }]);
+        }
+
+        let mut rows = Vec::with_capacity(indexes.len());
+        for (name, root_page, columns) in indexes {
+            let (entries, distinct) = self.index_stat(root_page, &columns)?;
+            let mut stat = entries.to_string();
+            for distinct in distinct {
+                // The average rows sharing a prefix, rounded up
+                let average = match distinct {
+                    0 => 0,
+                    distinct => entries.div_ceil(distinct),
+                };
+                stat.push_str(&format!(" {average}"));
+            }
+
+            rows.push(Stat1Row {
+                table: table.name.clone(),
+                index: Some(name),
+                stat,
+            });
+        }
+
+        Ok(rows)
+    }
+
+    /// Walks an index counting its entries and the distinct values of each prefix of
+    /// its key, comparing with the collation of each key column and NULLs as equal
+    fn index_stat(&self, root_page: usize, columns: &[IndexedColumn]) -> Result<(u64, Vec<u64>)> {
+        let mut entries = 0;
+        let mut distinct = vec![0; columns.len()];
+        let mut previous: Option<Vec<RecordValue>> = None;
+
+        let mut cursor = BtCursor::index(self, root_page);
+        cursor.first()?;
+        while let Some((entry, _)) = cursor.entry() {
+            self.interrupt.check()?;
+            entries += 1;
+
+            // The first column that differs from the previous entry starts a new
+            // distinct value for every prefix at least that long
+            let first_change = match &previous {
+                None => 0,
+                Some(previous) => columns
+                    .iter()
+                    .enumerate()
+                    .position(|(i, column)| {
+                        let lhs = previous.get(i).unwrap_or(&RecordValue::Null);
+                        let rhs = entry.get(i).unwrap_or(&RecordValue::Null);
+                        let collation = column.collation.as_ref().unwrap_or(&Collation::Binary);
+                        lhs.sort_cmp(rhs, collation) != Ordering::Equal
+                    })
+                    .unwrap_or(columns.len()),
+            };
+            for count in distinct[first_change..].iter_mut() {
+                *count += 1;
+            }
+
+            previous = Some(entry[..columns.len().min(entry.len())].to_vec());
+            cursor.next()?;
+        }
+
+        Ok((entries, distinct))
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+use super::{
+    database::Database,
+    open_pager,
+    options::OpenOptions,
+    pager::{Pager, PENDING_BYTE},
+    SqliteReader,
+};
+
+impl<P: Pager> SqliteReader<P> {
+    /// Copies every page of the database to a new file at `path`, the same as
+    /// `sqlite3_backup` would, calling `progress` with the number of pages copied so
+    /// far and the total after each one
+    ///
+    /// A database opened from a file is read again while holding a shared lock on
+    /// it, the same lock SQLite takes to read, so no other connection can change it
+    /// part way through the copy. The copy includes the transactions committed to
+    /// the write-ahead log, making it a complete database by itself.
+    pub fn backup_to(
+        &self,
+        path: impl AsRef<Path>,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let Some(source) = &self.path else {
+            return copy_pages(&self.pager, path, &mut progress);
+        };
+
+        let db = File::open(source)?;
+        let _lock =
+            SharedLock::acquire(&db).with_context(|| format!("locking {}", source.display()))?;
+        let pager = open_pager(source, &db, &OpenOptions::default())?;
+        copy_pages(&pager, path, &mut progress)
+    }
+}
+
+impl Database {
+    /// Copies the main database to a new file at `path`, see [`SqliteReader::backup_to`]
+    pub fn backup_to(
+        &self,
+        path: impl AsRef<Path>,
+        progress: impl FnMut(usize, usize),
+    ) -> Result<()> {
+        self.main().backup_to(path, progress)
+    }
+}
+
+fn copy_pages(
+    pager: &impl Pager,
+    path: &Path,
+    progress: &mut impl FnMut(usize, usize),
+) -> Result<()> {
+    let mut file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+
+    let total = pager.page_count();
+    for page in 0..total {
+        let bytes = pager
+            .get_page(page)
+            .with_context(|| format!("reading page {}", page + 1))?;
+        file.write_all(bytes)
+            .with_context(|| format!("writing page {}", page + 1))?;
+        progress(page + 1, total);
+    }
+
+    file.sync_all()?;
+    Ok(())
+}
+
+/// The byte reserved for writers follows the pending byte, then the range readers
+/// lock
+const SHARED_FIRST: u64 = PENDING_BYTE + 2;
+const SHARED_SIZE: u64 = 510;
+
+/// A shared lock on a database file following SQLite's locking protocol, which
+/// writers wait on before changing the file
+///
+/// Readers first lock the pending byte so they can't slip in once a writer is
+/// waiting for the lock, then lock the shared range and let go of the pending byte.
+/// The lock is released when dropped. SQLite only takes these locks on Unix, so
+/// elsewhere this does nothing.
+struct SharedLock<'a> {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    file: &'a File,
+}
+
+impl<'a> SharedLock<'a> {
+    fn acquire(file: &'a File) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use super::lock::{busy, lock, READ, UNLOCK};
+
+            lock(file, READ, PENDING_BYTE, 1).map_err(busy)?;
+            let shared = lock(file, READ, SHARED_FIRST, SHARED_SIZE).map_err(busy);
+            lock(file, UNLOCK, PENDING_BYTE, 1)?;
+            shared?;
+        }
+
+        Ok(Self { file })
+    }
+}
+
+impl Drop for SharedLock<'_> {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            let _ = super::lock::lock(self.file, super::lock::UNLOCK, SHARED_FIRST, SHARED_SIZE);
+        }
+    }
+}
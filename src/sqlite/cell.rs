@@ -1,9 +1,118 @@
 use super::{
+    collation::Collation,
+    error::{ensure_remaining, DecodeError, QueryError},
+    page::BTreePageType,
+    pager::Pager,
     parse_varint,
-    sql::{ColumnDefinition, Condition},
+    sql::{same_name, ColumnDefinition, Condition, IndexedColumn, SortOrder},
 };
 use bytes::Buf;
-use std::fmt::Write;
+use std::{borrow::Cow, cmp::Ordering};
+
+/// The database pages that payloads too large for their b-tree page spill over into
+#[derive(Clone, Copy)]
+pub struct Overflow<'a> {
+    pager: &'a dyn Pager,
+    usable_size: usize,
+}
+
+impl<'a> Overflow<'a> {
+    pub fn new(pager: &'a dyn Pager, reserved_space: usize) -> Self {
+        Self {
+            pager,
+            usable_size: pager.page_size() - reserved_space,
+        }
+    }
+
+    /// Most payload bytes a table leaf cell keeps on its own page
+    fn max_table_local(&self) -> usize {
+        self.usable_size - 35
+    }
+
+    /// Most payload bytes an index cell keeps on its own page
+    fn max_index_local(&self) -> usize {
+        ((self.usable_size - 12) * 64 / 255) - 23
+    }
+
+    /// Usable bytes of each page, leaving out the space reserved at the end
+    pub fn usable_size(&self) -> usize {
+        self.usable_size
+    }
+
+    /// Number of payload bytes of `cell` stored on overflow pages
+    pub fn spilled_bytes(&self, cell: &DatabaseCell) -> usize {
+        let max_local = match cell {
+            DatabaseCell::Leaf(_) => self.max_table_local(),
+            DatabaseCell::IndexLeaf(_) | DatabaseCell::InteriorIndex(_) => self.max_index_local(),
+            DatabaseCell::InteriorTable(_) => return 0,
+        };
+
+        let payload_size = cell.payload_size() as usize;
+        payload_size - self.local_size(payload_size, max_local)
+    }
+
+    /// Number of payload bytes stored on the b-tree page, the rest following in the
+    /// chain of overflow pages
+    fn local_size(&self, payload_size: usize, max_local: usize) -> usize {
+        if payload_size <= max_local {
+            return payload_size;
+        }
+
+        let min_local = ((self.usable_size - 12) * 32 / 255) - 23;
+        let local = min_local + (payload_size - min_local) % (self.usable_size - 4);
+        if local <= max_local {
+            local
+        } else {
+            min_local
+        }
+    }
+
+    /// Reads a payload starting at `buf`, following its overflow chain when it
+    /// doesn't fit on the page
+    ///
+    /// Returns the payload and its first overflow page, if any.
+    fn payload<'b>(
+        &self,
+        buf: &'b [u8],
+        payload_size: usize,
+        max_local: usize,
+    ) -> Result<(Cow<'b, [u8]>, Option<u32>), DecodeError> {
+        let local = self.local_size(payload_size, max_local);
+        if local == payload_size {
+            ensure_remaining(buf, payload_size)?;
+            return Ok((Cow::Borrowed(&buf[..payload_size]), None));
+        }
+
+        // Each overflow page holds all but its next page pointer, so a payload needing
+        // more pages than the database has is corrupt rather than worth allocating
+        let spilled = payload_size - local;
+        let capacity = self.pager.page_count() as u64 * (self.usable_size - 4) as u64;
+        if spilled as u64 > capacity {
+            return Err(DecodeError::PayloadTooLarge(payload_size as u64));
+        }
+
+        ensure_remaining(buf, local + 4)?;
+        let mut payload = Vec::with_capacity(payload_size);
+        payload.extend_from_slice(&buf[..local]);
+
+        let first_page = (&buf[local..]).get_u32();
+        let mut next_page = first_page;
+        while payload.len() < payload_size {
+            let mut page = (next_page as usize)
+                .checked_sub(1)
+                .filter(|&page| !self.pager.is_lock_byte_page(page))
+                .and_then(|page| self.pager.get_page(page).ok())
+                .map(|page| &page[..self.usable_size])
+                .ok_or(DecodeError::InvalidOverflowPage(next_page))?;
+            next_page = page.get_u32();
+
+            let take = (payload_size - payload.len()).min(page.len());
+            payload.extend_from_slice(&page[..take]);
+        }
+
+        Ok((Cow::Owned(payload), Some(first_page)))
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum DatabaseCell {
@@ -13,222 +122,502 @@ pub enum DatabaseCell {
     InteriorIndex(InteriorIndexCell),
 }
 
+impl DatabaseCell {
+    /// Size of the record the cell holds, including any part on overflow pages
+    pub fn payload_size(&self) -> u64 {
+        match self {
+            Self::Leaf(leaf) => leaf.payload_size,
+            Self::IndexLeaf(leaf) => leaf.payload_size,
+            Self::InteriorIndex(index_cell) => index_cell.payload_size,
+            Self::InteriorTable(_) => 0,
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
-pub(crate) struct LeafCell {
-    pub row_id: u64,
+pub struct LeafCell {
+    pub row_id: i64,
     serial_types: Vec<RecordSerialType>,
     pub payload: Vec<RecordValue>,
-    overflow_page: Option<u32>,
+    pub payload_size: u64,
+    pub overflow_page: Option<u32>,
 }
 
 impl LeafCell {
-    pub fn new(mut buf: &[u8]) -> Self {
-        let (payload_size, consumed) = parse_varint(buf);
-        buf.advance(consumed);
-
-        let (row_id, consumed) = parse_varint(buf);
-        buf.advance(consumed);
-
-        let mut payload = &buf[..payload_size as usize];
-        let (payload_header_size, consumed) = parse_varint(payload);
-        payload.advance(consumed);
-
-        let mut serial_types = vec![];
-        let mut remaining_header_bytes = payload_header_size as usize - consumed;
-        while remaining_header_bytes > 0 {
-            let (value, consumed) = parse_varint(payload);
-            payload.advance(consumed);
-            remaining_header_bytes -= consumed;
-            serial_types.push(RecordSerialType::from(value));
-        }
+    pub fn new(mut buf: &[u8], overflow: Overflow) -> Result<Self, DecodeError> {
+        let payload_size = read_varint(&mut buf)?;
+        let row_id = read_varint(&mut buf)? as i64;
 
-        let payload = &buf[payload_header_size as usize..payload_size as usize];
-        let payload_values = serial_types_to_record_values(&serial_types, payload);
+        let (record, overflow_page) =
+            overflow.payload(buf, payload_size as usize, overflow.max_table_local())?;
+        let (serial_types, payload) = parse_record(&record)?;
 
-        Self {
+        Ok(Self {
             row_id,
             serial_types,
-            payload: payload_values,
-            overflow_page: None, // Not used in this challenge
+            payload,
+            payload_size,
+            overflow_page,
+        })
+    }
+
+    /// The value of the column at `idx` of `columns`, reading the rowid for the
+    /// `INTEGER PRIMARY KEY` column which SQLite stores as `NULL` in the record
+    ///
+    /// Rows written before a column was added with `ALTER TABLE` end before it, and
+    /// read as the column's default. `VIRTUAL` generated columns aren't in the record
+    /// at all, and are computed from the rest of the row.
+    pub fn column_value(&self, idx: usize, columns: &[ColumnDefinition]) -> RecordValue {
+        let column = &columns[idx];
+        if let Some(generated) = column.generated.as_ref().filter(|_| column.is_virtual()) {
+            let value = generated.expr.as_ref().and_then(|expr| {
+                expr.evaluate(
+                    &|name| match columns.iter().position(|c| same_name(&c.name, name)) {
+                        Some(idx) => Ok(self.column_value(idx, columns)),
+                        None => Err(QueryError::NoSuchColumn(name.to_string()).into()),
+                    },
+                )
+                .ok()
+            });
+
+            return column.affinity().apply(&value.unwrap_or(RecordValue::Null));
         }
+
+        let record_idx = columns[..idx].iter().filter(|c| !c.is_virtual()).count();
+        let value = match self.payload.get(record_idx) {
+            Some(RecordValue::Null) | None if column.is_rowid_alias() => {
+                return RecordValue::I64(self.row_id)
+            }
+            Some(value) => value.clone(),
+            None => column.default_value().unwrap_or(RecordValue::Null),
+        };
+
+        column.affinity().stored(value)
     }
 
+    /// The values of `search_cols` for this row, or `None` if the row doesn't satisfy
+    /// every one of the conditions
     pub fn query_row(
         &self,
         search_cols: &[String],
         schema_cols: &[ColumnDefinition],
-        condition: &Option<Condition>,
-    ) -> Result<String, String> {
-        let mut output = String::new();
-        let mut iter = search_cols.iter().peekable();
-        if let Some(ref cond) = condition {
-            let Some(idx) = schema_cols.iter().position(|c| c.name == cond.column) else {
-                return Err(format!("error: no such column '{}'", cond.column));
+        conditions: &[Condition],
+    ) -> Result<Option<Vec<RecordValue>>, QueryError> {
+        for cond in conditions.iter() {
+            let Some(idx) = schema_cols
+                .iter()
+                .position(|c| same_name(&c.name, &cond.column))
+            else {
+                return Err(QueryError::NoSuchColumn(cond.column.clone()));
             };
 
-            let value = &self.payload[idx];
-            if value.to_string() != cond.value {
-                return Ok(String::new());
+            let column = &schema_cols[idx];
+            let value = self.column_value(idx, schema_cols);
+            let collation = cond.collation.as_ref().unwrap_or(&column.collation);
+            let literal = column.affinity().apply(&cond.value);
+            if !cond.operator.test(&value, &literal, collation) {
+                return Ok(None);
             }
         }
 
-        while let Some(s_col) = iter.next() {
-            let Some(idx) = schema_cols.iter().position(|c| &c.name == s_col) else {
-                return Err(format!("error: no such column '{s_col}'"));
+        let mut values = Vec::with_capacity(search_cols.len());
+        for s_col in search_cols.iter() {
+            let Some(idx) = schema_cols.iter().position(|c| same_name(&c.name, s_col)) else {
+                return Err(QueryError::NoSuchColumn(s_col.clone()));
             };
-            let value = &self.payload[idx];
-
-            // Temporary
-            if *value == RecordValue::Null && s_col == "id" {
-                write!(output, "{}", self.row_id).unwrap();
-            } else {
-                write!(output, "{value}").unwrap();
-            }
-            if iter.peek().is_some() {
-                write!(output, "|").unwrap();
-            }
+            values.push(self.column_value(idx, schema_cols));
         }
 
-        Ok(output)
+        Ok(Some(values))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct InteriorTableCell {
-    pub row_id: u64,
+    pub row_id: i64,
     pub left_child: u32,
 }
 
 impl InteriorTableCell {
-    pub fn new(mut buf: &[u8]) -> Self {
+    pub fn new(mut buf: &[u8]) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 4)?;
         let left_child = buf.get_u32();
-        let (row_id, consumed) = parse_varint(buf);
-        buf.advance(consumed);
+        let row_id = read_varint(&mut buf)? as i64;
 
-        Self {
-            left_child: left_child - 1,
+        Ok(Self {
+            left_child: left_child
+                .checked_sub(1)
+                .ok_or(DecodeError::InvalidChildPage(left_child))?,
             row_id,
-        }
+        })
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct InteriorIndexCell {
     pub left_child: u32,
-    pub key: String,
-    pub row_id: u64,
+    pub columns: Vec<RecordValue>,
+    pub row_id: i64,
+    pub payload_size: u64,
+    pub overflow_page: Option<u32>,
 }
 
 impl InteriorIndexCell {
-    pub fn new(mut buf: &[u8]) -> Self {
+    pub fn new(mut buf: &[u8], overflow: Overflow) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 4)?;
         let left_child = buf.get_u32();
-        let (payload_size, consumed) = parse_varint(buf);
-        buf.advance(consumed);
-
-        let mut payload = &buf[..payload_size as usize];
-        let (record_header_size, consumed) = parse_varint(payload);
-        payload.advance(consumed);
-
-        let record_offset = record_header_size as usize - consumed;
-        let mut serial_type_bytes = &payload[..record_offset];
-        let record_values_bytes = &payload[record_offset..];
-
-        let mut serial_types = vec![];
-        while serial_type_bytes.remaining() > 0 {
-            let (value, consumed) = parse_varint(serial_type_bytes);
-            serial_type_bytes.advance(consumed);
-            serial_types.push(RecordSerialType::from(value));
-        }
-
-        let payload_values = serial_types_to_record_values(&serial_types, record_values_bytes);
-        let key = match &payload_values[0] {
-            RecordValue::String(key) => key.to_owned(),
-            RecordValue::Null => "".to_string(),
-            other => panic!("iic - expected string or null for payload -> found {other:#?}"),
-        };
+        let payload_size = read_varint(&mut buf)?;
 
-        let row_id = match &payload_values[1] {
-            RecordValue::I8(value) => *value as u64,
-            RecordValue::I16(value) => *value as u64,
-            RecordValue::I24(value) => *value as u64,
-            RecordValue::I32(value) => *value as u64,
-            RecordValue::I48(value) => *value as u64,
-            RecordValue::I64(value) => *value as u64,
-            RecordValue::Bool(value) => {
-                if *value {
-                    1u64
-                } else {
-                    0u64
-                }
-            }
-            other => panic!("only supporting numeric ids - {other:#?}"),
-        };
+        let (record, overflow_page) =
+            overflow.payload(buf, payload_size as usize, overflow.max_index_local())?;
+        let (columns, row_id) = parse_index_record(&record)?;
 
-        Self {
-            left_child: left_child - 1,
-            key,
+        Ok(Self {
+            left_child: left_child
+                .checked_sub(1)
+                .ok_or(DecodeError::InvalidChildPage(left_child))?,
+            columns,
             row_id,
-        }
+            payload_size,
+            overflow_page,
+        })
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct IndexLeafCell {
-    pub key: String,
-    pub row_id: u64,
+    pub columns: Vec<RecordValue>,
+    pub row_id: i64,
+    pub payload_size: u64,
+    pub overflow_page: Option<u32>,
 }
 
 impl IndexLeafCell {
-    pub fn new(mut buf: &[u8]) -> Self {
-        let (payload_size, consumed) = parse_varint(buf);
-        buf.advance(consumed);
-
-        let mut payload = &buf[..payload_size as usize];
-        let (record_header_size, consumed) = parse_varint(payload);
-        payload.advance(consumed);
-
-        let record_offset = record_header_size as usize - consumed;
-        let mut serial_type_bytes = &payload[..record_offset];
-        let record_values_bytes = &payload[record_offset..];
-
-        let mut serial_types = vec![];
-        while serial_type_bytes.remaining() > 0 {
-            let (value, consumed) = parse_varint(serial_type_bytes);
-            serial_type_bytes.advance(consumed);
-            serial_types.push(RecordSerialType::from(value));
+    pub fn new(mut buf: &[u8], overflow: Overflow) -> Result<Self, DecodeError> {
+        let payload_size = read_varint(&mut buf)?;
+
+        let (record, overflow_page) =
+            overflow.payload(buf, payload_size as usize, overflow.max_index_local())?;
+        let (columns, row_id) = parse_index_record(&record)?;
+
+        Ok(Self {
+            columns,
+            row_id,
+            payload_size,
+            overflow_page,
+        })
+    }
+}
+
+/// A run of a cell's bytes and what they encode
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellField {
+    /// Where the field starts counting from the start of the cell, or `None` when
+    /// it's on the cell's overflow pages
+    pub offset: Option<usize>,
+    pub bytes: Vec<u8>,
+    pub description: String,
+}
+
+/// Characters of a value shown in a cell's fields before the rest is left out
+const MAX_LITERAL_CHARS: usize = 40;
+
+/// Splits the cell at the start of `buf`, on a page of type `page_type`, into its
+/// header fields, the varints of its record header and each of its values
+///
+/// Values that can't be decoded are described by why, so a damaged record is still
+/// laid out as far as its header allows.
+pub fn cell_fields(
+    mut buf: &[u8],
+    page_type: BTreePageType,
+    overflow: Overflow,
+) -> Result<Vec<CellField>, DecodeError> {
+    let start = buf;
+    let mut fields = Vec::new();
+    let mut field = |buf: &[u8], before: &[u8], description: String| {
+        let offset = start.len() - before.len();
+        fields.push(CellField {
+            offset: Some(offset),
+            bytes: start[offset..start.len() - buf.len()].to_vec(),
+            description,
+        });
+    };
+
+    if matches!(
+        page_type,
+        BTreePageType::InteriorTable | BTreePageType::InteriorIndex
+    ) {
+        let before = buf;
+        ensure_remaining(buf, 4)?;
+        let left_child = buf.get_u32();
+        field(buf, before, format!("left child page {left_child}"));
+    }
+
+    let mut payload_size = 0;
+    if page_type != BTreePageType::InteriorTable {
+        let before = buf;
+        payload_size = read_varint(&mut buf)?;
+        field(buf, before, format!("payload size {payload_size}"));
+    }
+
+    if matches!(
+        page_type,
+        BTreePageType::LeafTable | BTreePageType::InteriorTable
+    ) {
+        let before = buf;
+        let row_id = read_varint(&mut buf)? as i64;
+        field(buf, before, format!("rowid {row_id}"));
+    }
+    if page_type == BTreePageType::InteriorTable {
+        return Ok(fields);
+    }
+
+    let max_local = match page_type {
+        BTreePageType::LeafTable => overflow.max_table_local(),
+        _ => overflow.max_index_local(),
+    };
+    let payload_size = payload_size as usize;
+    let (record, overflow_page) = overflow.payload(buf, payload_size, max_local)?;
+    let local = overflow.local_size(payload_size, max_local);
+    let record_start = start.len() - buf.len();
+
+    // Only the bytes on the page are shown, those past it being on overflow pages
+    let mut record_field = |offset: usize, end: usize, mut description: String| {
+        let (offset, end) = (offset.min(record.len()), end.min(record.len()));
+        let on_page =
+            (offset < local || offset == end && offset <= local).then_some(record_start + offset);
+        let spilled = end - end.min(local).max(offset);
+        match spilled {
+            0 => {}
+            1 => description.push_str(" (1 byte on overflow pages)"),
+            _ => description.push_str(&format!(" ({spilled} bytes on overflow pages)")),
         }
+        fields.push(CellField {
+            offset: on_page,
+            bytes: record[offset..end.min(local).max(offset)].to_vec(),
+            description,
+        });
+    };
+
+    let mut header = &record[..];
+    let header_size = read_varint(&mut header)?;
+    let mut offset = record.len() - header.len();
+    record_field(0, offset, format!("record header size {header_size}"));
+
+    let header_end = (header_size as usize).clamp(offset, record.len());
+    let mut serial_types = Vec::new();
+    while offset < header_end {
+        let mut rest = &record[offset..header_end];
+        let serial_type = read_varint(&mut rest)?;
+        let end = header_end - rest.len();
+        let kind = RecordSerialType::from(serial_type);
+        record_field(offset, end, format!("serial type {serial_type}: {kind}"));
+        serial_types.push(kind);
+        offset = end;
+    }
 
-        let payload_values = serial_types_to_record_values(&serial_types, record_values_bytes);
-        let RecordValue::String(key) = &payload_values[0] else {
-            panic!(
-                "unexpected serial type in index leaf cell - {}",
-                &payload_values[0]
-            );
+    let index = page_type != BTreePageType::LeafTable;
+    for (i, kind) in serial_types.iter().enumerate() {
+        // Index records end with the rowid of the row they point at
+        let name = match index && i == serial_types.len() - 1 {
+            true => "rowid".to_string(),
+            false => format!("column {i}"),
         };
-
-        let row_id = match &payload_values[1] {
-            RecordValue::I8(value) => *value as u64,
-            RecordValue::I16(value) => *value as u64,
-            RecordValue::I24(value) => *value as u64,
-            RecordValue::I32(value) => *value as u64,
-            RecordValue::I48(value) => *value as u64,
-            RecordValue::I64(value) => *value as u64,
-            RecordValue::Bool(value) => {
-                if *value {
-                    1u64
-                } else {
-                    0u64
+        let mut rest = &record[offset.min(record.len())..];
+        let description = match read_value(kind, &mut rest) {
+            Ok(value) => {
+                let mut literal = value.to_sql_literal();
+                if let Some((end, _)) = literal.char_indices().nth(MAX_LITERAL_CHARS) {
+                    literal.truncate(end);
+                    literal.push_str("...");
                 }
+                format!("{name}: {literal}")
             }
-            other => panic!("only supporting numeric ids - {other:#?}"),
+            Err(e) => format!("{name}: {e}"),
         };
+        let end = offset + kind.size();
+        record_field(offset, end, description);
+        offset = end;
+    }
 
-        Self {
-            row_id,
-            key: key.to_owned(),
+    if let Some(first_page) = overflow_page {
+        let pointer = record_start + local;
+        fields.push(CellField {
+            offset: Some(pointer),
+            bytes: start[pointer..pointer + 4].to_vec(),
+            description: format!("first overflow page {first_page}"),
+        });
+    }
+
+    Ok(fields)
+}
+
+/// Splits an index record into its indexed columns and the trailing rowid of the
+/// table row it points at
+fn parse_index_record(payload: &[u8]) -> Result<(Vec<RecordValue>, i64), DecodeError> {
+    let (_, mut values) = parse_record(payload)?;
+    let row_id = values.pop().ok_or(DecodeError::MissingRowId)?;
+    let row_id = row_id
+        .as_i64()
+        .ok_or_else(|| DecodeError::InvalidRowId(format!("{row_id:?}")))?;
+
+    Ok((values, row_id))
+}
+
+/// Decodes a record: a header of serial types followed by the values they describe
+fn parse_record(payload: &[u8]) -> Result<(Vec<RecordSerialType>, Vec<RecordValue>), DecodeError> {
+    let (serial_types, body) = parse_record_header(payload)?;
+    let values = serial_types_to_record_values(&serial_types, body)?;
+    Ok((serial_types, values))
+}
+
+/// Compares two serialized index records the way they are ordered in an index with
+/// the key `columns`, decoding only as many fields as it takes to tell them apart
+///
+/// Fields past the end of `columns`, such as the trailing rowid, compare in ascending
+/// binary order. Fields past the end of the shorter record are ignored, so a key
+/// prefix compares equal to every record starting with it.
+pub fn compare_records(
+    lhs: &[u8],
+    rhs: &[u8],
+    columns: &[IndexedColumn],
+) -> Result<Ordering, DecodeError> {
+    let (lhs_types, mut lhs_body) = parse_record_header(lhs)?;
+    let (rhs_types, mut rhs_body) = parse_record_header(rhs)?;
+
+    for (i, (lhs_type, rhs_type)) in lhs_types.iter().zip(rhs_types.iter()).enumerate() {
+        let lhs_value = read_value(lhs_type, &mut lhs_body)?;
+        let rhs_value = read_value(rhs_type, &mut rhs_body)?;
+        let ordering = compare_field(&lhs_value, &rhs_value, columns.get(i));
+        if ordering != Ordering::Equal {
+            return Ok(ordering);
         }
     }
+
+    Ok(Ordering::Equal)
+}
+
+/// Compares two decoded index keys field by field, like [`compare_records`]
+pub fn compare_keys(
+    lhs: &[RecordValue],
+    rhs: &[RecordValue],
+    columns: &[IndexedColumn],
+) -> Ordering {
+    lhs.iter()
+        .zip(rhs.iter())
+        .enumerate()
+        .map(|(i, (lhs, rhs))| compare_field(lhs, rhs, columns.get(i)))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+fn compare_field(lhs: &RecordValue, rhs: &RecordValue, column: Option<&IndexedColumn>) -> Ordering {
+    let Some(column) = column else {
+        return lhs.sort_cmp(rhs, &Collation::Binary);
+    };
+
+    let collation = column.collation.as_ref().unwrap_or(&Collation::Binary);
+    let ordering = lhs.sort_cmp(rhs, collation);
+    match column.order {
+        SortOrder::Asc => ordering,
+        SortOrder::Desc => ordering.reverse(),
+    }
+}
+
+/// Tries to read a record left behind by a deleted cell from the front of `buf`,
+/// returning its values and how many bytes it spans
+///
+/// Nothing records where a leftover record ends, so it is taken to end where its
+/// header says the values do. Records using the reserved serial types, or holding
+/// nothing but `NULL`s, are rejected as noise.
+pub fn carve_record(buf: &[u8]) -> Option<(Vec<RecordValue>, usize)> {
+    let (header_size, consumed) = parse_varint(buf);
+    let header_size = header_size as usize;
+    if consumed == 0 || header_size <= consumed || header_size > buf.len() {
+        return None;
+    }
+
+    let (serial_types, _) = parse_record_header(&buf[..header_size]).ok()?;
+    carve_values(&serial_types, &buf[header_size..])
+        .map(|(values, len)| (values, header_size + len))
+}
+
+/// Like [`carve_record`] for a record whose header size was overwritten, with `buf`
+/// starting at its first serial type and the record known to have `columns` values
+pub fn carve_headless_record(buf: &[u8], columns: usize) -> Option<(Vec<RecordValue>, usize)> {
+    let mut serial_types = Vec::with_capacity(columns);
+    let mut header = buf;
+    for _ in 0..columns {
+        let (value, consumed) = parse_varint(header);
+        if consumed == 0 {
+            return None;
+        }
+
+        header.advance(consumed);
+        serial_types.push(RecordSerialType::from(value));
+    }
+
+    let header_size = buf.len() - header.len();
+    carve_values(&serial_types, header).map(|(values, len)| (values, header_size + len))
+}
+
+fn carve_values(
+    serial_types: &[RecordSerialType],
+    body: &[u8],
+) -> Option<(Vec<RecordValue>, usize)> {
+    if serial_types.contains(&RecordSerialType::Internal) {
+        return None;
+    }
+
+    let body_size = serial_types
+        .iter()
+        .try_fold(0usize, |size, st| size.checked_add(st.size()))?;
+    let values = serial_types_to_record_values(serial_types, body.get(..body_size)?).ok()?;
+    if values.iter().all(|value| *value == RecordValue::Null) {
+        return None;
+    }
+
+    Some((values, body_size))
+}
+
+/// Splits a record into the serial types in its header and the body holding the values
+fn parse_record_header(payload: &[u8]) -> Result<(Vec<RecordSerialType>, &[u8]), DecodeError> {
+    let (header_size, consumed) = parse_varint(payload);
+    let header_size = header_size as usize;
+    if header_size < consumed || header_size > payload.len() {
+        return Err(DecodeError::InvalidRecordHeader {
+            header_size,
+            payload_size: payload.len(),
+        });
+    }
+
+    let mut serial_type_bytes = &payload[consumed..header_size];
+    let mut serial_types = vec![];
+    while serial_type_bytes.has_remaining() {
+        serial_types.push(RecordSerialType::from(read_varint(&mut serial_type_bytes)?));
+    }
+
+    Ok((serial_types, &payload[header_size..]))
+}
+
+/// Reads a varint from the front of `buf`, failing if `buf` ends partway through it
+fn read_varint(buf: &mut &[u8]) -> Result<u64, DecodeError> {
+    let (value, consumed) = parse_varint(buf);
+    // Every byte before the ninth has its high bit set when another follows
+    let terminated = match buf[..consumed].last() {
+        Some(last) => consumed == 9 || last & 0x80 == 0,
+        None => false,
+    };
+    if !terminated {
+        return Err(DecodeError::Truncated {
+            needed: consumed + 1,
+            remaining: buf.len(),
+        });
+    }
+
+    buf.advance(consumed);
+    Ok(value)
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -246,6 +635,78 @@ pub enum RecordValue {
     String(String),
 }
 
+impl RecordValue {
+    /// Compares two values for a WHERE condition, where `NULL` never compares
+    pub fn compare(&self, other: &RecordValue, collation: &Collation) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Null, _) | (_, Self::Null) => None,
+            _ => Some(self.sort_cmp(other, collation)),
+        }
+    }
+
+    /// Orders two values the way SQLite sorts them: `NULL`s first, then numbers,
+    /// then text in collation order and finally blobs
+    pub fn sort_cmp(&self, other: &RecordValue, collation: &Collation) -> Ordering {
+        match (self, other) {
+            (Self::String(lhs), Self::String(rhs)) => collation.compare(lhs, rhs),
+            (Self::Blob(lhs), Self::Blob(rhs)) => lhs.cmp(rhs),
+            _ => match (self.as_i64(), other.as_i64()) {
+                (Some(lhs), Some(rhs)) => lhs.cmp(&rhs),
+                _ => match (self.as_f64(), other.as_f64()) {
+                    (Some(lhs), Some(rhs)) => lhs.total_cmp(&rhs),
+                    _ => self.storage_class().cmp(&other.storage_class()),
+                },
+            },
+        }
+    }
+
+    fn storage_class(&self) -> u8 {
+        match self {
+            Self::Null => 0,
+            Self::String(_) => 2,
+            Self::Blob(_) => 3,
+            _ => 1,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::I8(value) => Some(i64::from(*value)),
+            Self::I16(value) => Some(i64::from(*value)),
+            Self::I24(value) | Self::I32(value) => Some(i64::from(*value)),
+            Self::I48(value) | Self::I64(value) => Some(*value),
+            Self::Bool(value) => Some(i64::from(*value)),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::F64(value) => Some(*value),
+            other => other.as_i64().map(|value| value as f64),
+        }
+    }
+
+    /// The value written as an SQL literal that evaluates back to it
+    pub fn to_sql_literal(&self) -> String {
+        match self {
+            Self::Null => "NULL".to_string(),
+            Self::Bool(value) => i64::from(*value).to_string(),
+            Self::F64(value) if value.is_nan() => "NULL".to_string(),
+            Self::F64(value) if value.is_infinite() => {
+                let sign = if value.is_sign_negative() { "-" } else { "" };
+                format!("{sign}1e999")
+            }
+            Self::Blob(blob) => {
+                let hex: String = blob.iter().map(|byte| format!("{byte:02X}")).collect();
+                format!("X'{hex}'")
+            }
+            Self::String(s) => format!("'{}'", s.replace('\'', "''")),
+            other => other.to_string(),
+        }
+    }
+}
+
 impl std::fmt::Display for RecordValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -256,8 +717,11 @@ impl std::fmt::Display for RecordValue {
             Self::I32(i32) => write!(f, "{i32}"),
             Self::I48(i48) => write!(f, "{i48}"),
             Self::I64(i64) => write!(f, "{i64}"),
+            // Whole reals keep a trailing `.0` like SQLite prints them
+            Self::F64(f64) if f64.fract() == 0.0 && f64.abs() < 1e15 => write!(f, "{f64:.1}"),
             Self::F64(f64) => write!(f, "{f64}"),
-            Self::Bool(bool) => write!(f, "{bool}"),
+            Self::Bool(bool) => write!(f, "{}", i64::from(*bool)),
+            Self::Blob(blob) if blob.len() == 1 => write!(f, "blob (1 byte)"),
             Self::Blob(blob) => write!(f, "blob ({} bytes)", blob.len()),
             Self::String(s) => write!(f, "{s}"),
         }
@@ -278,9 +742,48 @@ enum RecordSerialType {
     True,
     Blob(usize),
     String(usize),
+    /// Reserved for internal use, never written to ordinary database files
     Internal,
 }
 
+impl RecordSerialType {
+    /// Number of bytes the value takes up in the record body
+    fn size(&self) -> usize {
+        match self {
+            Self::Null | Self::False | Self::True | Self::Internal => 0,
+            Self::I8 => 1,
+            Self::I16 => 2,
+            Self::I24 => 3,
+            Self::I32 => 4,
+            Self::I48 => 6,
+            Self::I64 | Self::F64 => 8,
+            Self::Blob(size) | Self::String(size) => *size,
+        }
+    }
+}
+
+impl std::fmt::Display for RecordSerialType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, "NULL"),
+            Self::I8 => write!(f, "8-bit integer"),
+            Self::I16 => write!(f, "16-bit integer"),
+            Self::I24 => write!(f, "24-bit integer"),
+            Self::I32 => write!(f, "32-bit integer"),
+            Self::I48 => write!(f, "48-bit integer"),
+            Self::I64 => write!(f, "64-bit integer"),
+            Self::F64 => write!(f, "64-bit float"),
+            Self::False => write!(f, "integer 0"),
+            Self::True => write!(f, "integer 1"),
+            Self::Blob(1) => write!(f, "blob of 1 byte"),
+            Self::Blob(size) => write!(f, "blob of {size} bytes"),
+            Self::String(1) => write!(f, "text of 1 byte"),
+            Self::String(size) => write!(f, "text of {size} bytes"),
+            Self::Internal => write!(f, "reserved"),
+        }
+    }
+}
+
 impl From<u64> for RecordSerialType {
     fn from(value: u64) -> Self {
         match value {
@@ -295,9 +798,8 @@ impl From<u64> for RecordSerialType {
             8 => Self::False,
             9 => Self::True,
             10 | 11 => Self::Internal,
-            value if value >= 12 && value % 2 == 0 => Self::Blob(((value - 12) / 2) as usize),
-            value if value >= 13 && value % 2 != 0 => Self::String(((value - 13) / 2) as usize),
-            _ => panic!("invalid value for record serial type: {value}"),
+            value if value % 2 == 0 => Self::Blob(((value - 12) / 2) as usize),
+            value => Self::String(((value - 13) / 2) as usize),
         }
     }
 }
@@ -305,51 +807,62 @@ impl From<u64> for RecordSerialType {
 fn serial_types_to_record_values(
     serial_types: &[RecordSerialType],
     mut buf: &[u8],
-) -> Vec<RecordValue> {
+) -> Result<Vec<RecordValue>, DecodeError> {
     let values = serial_types
         .iter()
-        .map(|st| match *st {
-            RecordSerialType::Null => RecordValue::Null,
-            RecordSerialType::I8 => RecordValue::I8(buf.get_i8()),
-            RecordSerialType::I16 => RecordValue::I16(buf.get_i16()),
-            RecordSerialType::I24 => {
-                let buf: [u8; 3] = [buf.get_u8(), buf.get_u8(), buf.get_u8()];
-                let sign = if buf[0] & 0x80 != 0 { 0xFF } else { 0x00 };
-                let bytes = [sign, buf[0], buf[1], buf[2]];
-                RecordValue::I24(i32::from_be_bytes(bytes))
-            }
-            RecordSerialType::I32 => RecordValue::I32(buf.get_i32()),
-            RecordSerialType::I48 => {
-                let buf: [u8; 6] = [
-                    buf.get_u8(),
-                    buf.get_u8(),
-                    buf.get_u8(),
-                    buf.get_u8(),
-                    buf.get_u8(),
-                    buf.get_u8(),
-                ];
-                let sign = if buf[0] & 0x80 != 0 { 0xFF } else { 0x00 };
-                let bytes = [sign, sign, buf[0], buf[1], buf[2], buf[3], buf[4], buf[5]];
-                RecordValue::I48(i64::from_be_bytes(bytes))
-            }
-            RecordSerialType::I64 => RecordValue::I64(buf.get_i64()),
-            RecordSerialType::F64 => RecordValue::F64(buf.get_f64()),
-            RecordSerialType::False => RecordValue::Bool(false),
-            RecordSerialType::True => RecordValue::Bool(true),
-            RecordSerialType::Blob(size) => {
-                let mut blob = vec![0u8; size];
-                buf.copy_to_slice(&mut blob);
-                RecordValue::Blob(blob)
-            }
-            RecordSerialType::String(size) => {
-                let bytes: Vec<u8> = (0..size).map(|_| buf.get_u8()).collect();
-                RecordValue::String(String::from_utf8(bytes).expect("not utf8"))
-            }
-            _ => todo!("deal with internal"),
-        })
-        .collect::<Vec<RecordValue>>();
+        .map(|st| read_value(st, &mut buf))
+        .collect::<Result<Vec<RecordValue>, DecodeError>>()?;
 
-    assert!(buf.remaining() == 0);
+    if buf.has_remaining() {
+        return Err(DecodeError::TrailingBytes(buf.remaining()));
+    }
+
+    Ok(values)
+}
+
+/// Reads the value of serial type `st` from the front of `buf`
+fn read_value(st: &RecordSerialType, buf: &mut &[u8]) -> Result<RecordValue, DecodeError> {
+    ensure_remaining(buf, st.size())?;
+    let value = match *st {
+        // Reserved types carry no data, so read them like SQLite does as NULL
+        RecordSerialType::Null | RecordSerialType::Internal => RecordValue::Null,
+        RecordSerialType::I8 => RecordValue::I8(buf.get_i8()),
+        RecordSerialType::I16 => RecordValue::I16(buf.get_i16()),
+        RecordSerialType::I24 => {
+            let buf: [u8; 3] = [buf.get_u8(), buf.get_u8(), buf.get_u8()];
+            let sign = if buf[0] & 0x80 != 0 { 0xFF } else { 0x00 };
+            let bytes = [sign, buf[0], buf[1], buf[2]];
+            RecordValue::I24(i32::from_be_bytes(bytes))
+        }
+        RecordSerialType::I32 => RecordValue::I32(buf.get_i32()),
+        RecordSerialType::I48 => {
+            let buf: [u8; 6] = [
+                buf.get_u8(),
+                buf.get_u8(),
+                buf.get_u8(),
+                buf.get_u8(),
+                buf.get_u8(),
+                buf.get_u8(),
+            ];
+            let sign = if buf[0] & 0x80 != 0 { 0xFF } else { 0x00 };
+            let bytes = [sign, sign, buf[0], buf[1], buf[2], buf[3], buf[4], buf[5]];
+            RecordValue::I48(i64::from_be_bytes(bytes))
+        }
+        RecordSerialType::I64 => RecordValue::I64(buf.get_i64()),
+        RecordSerialType::F64 => RecordValue::F64(buf.get_f64()),
+        RecordSerialType::False => RecordValue::Bool(false),
+        RecordSerialType::True => RecordValue::Bool(true),
+        RecordSerialType::Blob(size) => {
+            let mut blob = vec![0u8; size];
+            buf.copy_to_slice(&mut blob);
+            RecordValue::Blob(blob)
+        }
+        RecordSerialType::String(size) => {
+            let bytes = buf[..size].to_vec();
+            buf.advance(size);
+            RecordValue::String(String::from_utf8(bytes).map_err(|_| DecodeError::InvalidText)?)
+        }
+    };
 
-    values
+    Ok(value)
 }
@@ -0,0 +1,74 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+type CollationFn = dyn Fn(&str, &str) -> Ordering + Send + Sync;
+
+/// Collations registered through [`register_collation`], keyed by lowercase name
+static CUSTOM_COLLATIONS: LazyLock<RwLock<HashMap<String, Arc<CollationFn>>>> =
+    LazyLock::new(Default::default);
+
+/// Registers a collating sequence that `COLLATE <name>` in table, index and query
+/// definitions can refer to, replacing any previous registration of the name.
+///
+/// The comparison must be a consistent total order as it is used both to search
+/// indexes built by SQLite and to sort results.
+pub fn register_collation(
+    name: &str,
+    compare: impl Fn(&str, &str) -> Ordering + Send + Sync + 'static,
+) {
+    CUSTOM_COLLATIONS
+        .write()
+        .expect("collation registry poisoned")
+        .insert(name.to_ascii_lowercase(), Arc::new(compare));
+}
+
+/// Collating sequence used to compare text
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Collation {
+    #[default]
+    Binary,
+    NoCase,
+    RTrim,
+    /// A collation registered with [`register_collation`]
+    Custom(String),
+}
+
+impl Collation {
+    /// Resolves a collation name, returning `None` if it is neither built in nor
+    /// registered
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        match name.as_str() {
+            "binary" => Some(Self::Binary),
+            "nocase" => Some(Self::NoCase),
+            "rtrim" => Some(Self::RTrim),
+            _ => CUSTOM_COLLATIONS
+                .read()
+                .expect("collation registry poisoned")
+                .contains_key(&name)
+                .then_some(Self::Custom(name)),
+        }
+    }
+
+    pub fn compare(&self, lhs: &str, rhs: &str) -> Ordering {
+        match self {
+            Self::Binary => lhs.cmp(rhs),
+            // Like SQLite, only ASCII characters are folded
+            Self::NoCase => lhs
+                .bytes()
+                .map(|b| b.to_ascii_lowercase())
+                .cmp(rhs.bytes().map(|b| b.to_ascii_lowercase())),
+            Self::RTrim => lhs.trim_end_matches(' ').cmp(rhs.trim_end_matches(' ')),
+            Self::Custom(name) => {
+                let collation = CUSTOM_COLLATIONS
+                    .read()
+                    .expect("collation registry poisoned")
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_else(|| panic!("collation '{name}' is no longer registered"));
+                collation(lhs, rhs)
+            }
+        }
+    }
+}
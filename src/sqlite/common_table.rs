@@ -0,0 +1,84 @@
+use anyhow::{bail, Result};
+use std::sync::Arc;
+use tracing::debug;
+
+use super::{
+    pager::Pager,
+    result::ResultSet,
+    sql::{self, SelectStatement},
+    vtab::MemoryTable,
+    SqliteReader,
+};
+
+impl<P: Pager> SqliteReader<P> {
+    /// Runs a query with a `WITH` clause, materializing each of its tables in turn so
+    /// later ones and the query itself can read them, then dropping them again
+    pub fn with_common_tables(&self, statement: &SelectStatement) -> Result<ResultSet> {
+        for (i, table) in statement.with.iter().enumerate() {
+            if statement.with[..i]
+                .iter()
+                .any(|earlier| sql::same_name(&earlier.name, &table.name))
+            {
+                bail!("duplicate WITH table name: {}", table.name);
+            }
+            if table.select.reads(&table.name) {
+                bail!("circular reference: {}", table.name);
+            }
+        }
+
+        let scope = self.scoped_tables().len();
+        let result = self.materialize(statement);
+        self.common_tables
+            .write()
+            .expect("common table lock poisoned")
+            .truncate(scope);
+
+        result
+    }
+
+    /// The table a `WITH` clause in scope names `name`, the innermost if more than
+    /// one does
+    pub fn common_table(&self, name: &str) -> Option<Arc<MemoryTable>> {
+        self.scoped_tables()
+            .iter()
+            .rev()
+            .find(|(table, _)| sql::same_name(table, name))
+            .map(|(_, rows)| Arc::clone(rows))
+    }
+
+    fn scoped_tables(&self) -> std::sync::RwLockReadGuard<'_, Vec<(String, Arc<MemoryTable>)>> {
+        self.common_tables
+            .read()
+            .expect("common table lock poisoned")
+    }
+
+    fn materialize(&self, statement: &SelectStatement) -> Result<ResultSet> {
+        for table in statement.with.iter() {
+            let result = self.execute_select(&table.select)?;
+            let columns = match table.columns.is_empty() {
+                true => result.columns,
+                false if table.columns.len() != result.columns.len() => bail!(
+                    "table {} has {} values for {} columns",
+                    table.name,
+                    result.columns.len(),
+                    table.columns.len()
+                ),
+                false => table.columns.clone(),
+            };
+
+            debug!(table = %table.name, rows = result.rows.len(), "materialized common table");
+            self.common_tables
+                .write()
+                .expect("common table lock poisoned")
+                .push((
+                    table.name.clone(),
+                    Arc::new(MemoryTable::new(columns, result.rows)),
+                ));
+        }
+
+        self.execute_select(&SelectStatement {
+            with: Vec::new(),
+            ..statement.clone()
+        })
+    }
+}
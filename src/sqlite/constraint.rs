@@ -0,0 +1,136 @@
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use thiserror::Error;
+
+use super::{
+    cell::{self, RecordValue},
+    cursor::BtCursor,
+    error::QueryError,
+    function,
+    pager::Pager,
+    sql::{same_name, CreateTable},
+    SqliteReader,
+};
+
+/// A row that would break one of its table's constraints, the way SQLite fails with
+/// `SQLITE_CONSTRAINT`
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ConstraintError {
+    /// Names the constraint, or gives its expression when it has no name
+    #[error("CHECK constraint failed: {0}")]
+    Check(String),
+    /// Gives the columns of a UNIQUE index already holding the key, as `table.column`
+    #[error("UNIQUE constraint failed: {0}")]
+    Unique(String),
+    /// Gives the rowid column already holding the rowid, as `table.column`
+    #[error("UNIQUE constraint failed: {0}")]
+    PrimaryKey(String),
+}
+
+impl CreateTable {
+    /// Fails with the first CHECK constraint a row breaks, looking up its columns
+    /// with `column`
+    ///
+    /// Only a constraint that is false fails, one that is NULL passes. This is for a
+    /// writer to call before inserting or updating a row, and as there is no writer
+    /// yet nothing enforces CHECK constraints.
+    pub fn check_row(&self, column: &dyn Fn(&str) -> Result<RecordValue>) -> Result<()> {
+        for check in self.checks.iter() {
+            let Some(expr) = &check.expr else {
+                bail!("unsupported CHECK constraint: {}", check.sql);
+            };
+
+            if function::truth(&expr.evaluate(column)?) == Some(false) {
+                let name = check.name.as_ref().unwrap_or(&check.sql);
+                return Err(ConstraintError::Check(name.clone()).into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<P: Pager> SqliteReader<P> {
+    /// Fails when inserting `row` into `table` as `row_id` would repeat a rowid or
+    /// the key of a UNIQUE index
+    ///
+    /// `row` holds a value for each column of the table, with that of an `INTEGER
+    /// PRIMARY KEY` taken from `row_id` instead. As in SQLite, keys holding a NULL
+    /// never clash. This is for a writer to call before inserting a row, and as
+    /// there is no writer yet nothing enforces UNIQUE or PRIMARY KEY constraints.
+    pub fn check_unique(&self, table: &str, row_id: i64, row: &[RecordValue]) -> Result<()> {
+        let schema = self.schema()?;
+        let Some(entry) = schema.fetch_table(table).filter(|entry| entry.is_table()) else {
+            return Err(QueryError::NoSuchTable(table.to_string()).into());
+        };
+
+        let create = entry.columns();
+        if row.len() != create.columns.len() {
+            bail!(
+                "table {table} has {} columns but {} values were supplied",
+                create.columns.len(),
+                row.len()
+            );
+        }
+
+        let mut cursor = BtCursor::table(self, entry.root_page as usize);
+        cursor.seek_rowid(row_id)?;
+        if cursor.row_id() == Some(row_id) {
+            let column = create.rowid_alias().map_or("rowid", |c| c.name.as_str());
+            return Err(ConstraintError::PrimaryKey(format!("{table}.{column}")).into());
+        }
+
+        let column = |name: &str| {
+            let idx = create
+                .columns
+                .iter()
+                .position(|c| same_name(&c.name, name))
+                .ok_or_else(|| QueryError::NoSuchColumn(name.to_string()))?;
+
+            Ok(match create.columns[idx].is_rowid_alias() {
+                true => RecordValue::I64(row_id),
+                false => row[idx].clone(),
+            })
+        };
+
+        for (index, key_columns) in schema.unique_indexes(entry) {
+            // A partial index only holds the keys of rows satisfying its WHERE clause
+            if let Some(filter) = index.index_filter() {
+                let Some(expr) = &filter.expr else {
+                    bail!("unsupported partial index WHERE clause: {}", filter.sql);
+                };
+
+                if function::truth(&expr.evaluate(&column)?) != Some(true) {
+                    continue;
+                }
+            }
+
+            let key = key_columns
+                .iter()
+                .map(|key_column| match &key_column.expr {
+                    Some(expr) => expr.evaluate(&column),
+                    None => column(&key_column.name),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if key.contains(&RecordValue::Null) {
+                continue;
+            }
+
+            let key_cmp = |entry: &[RecordValue]| cell::compare_keys(entry, &key, &key_columns);
+            let mut cursor = BtCursor::index(self, index.root_page as usize);
+            cursor.seek(key_cmp)?;
+            if cursor
+                .entry()
+                .is_some_and(|(entry, _)| key_cmp(entry) == Ordering::Equal)
+            {
+                let columns: Vec<String> = key_columns
+                    .iter()
+                    .map(|column| format!("{table}.{}", column.name))
+                    .collect();
+                return Err(ConstraintError::Unique(columns.join(", ")).into());
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,407 @@
+use anyhow::{Context, Result};
+use std::cmp::Ordering;
+use tracing::trace;
+
+use super::{
+    cell::{DatabaseCell, LeafCell, RecordValue},
+    error::DecodeError,
+    page::{BTreePage, BTreePageType},
+    pager::Pager,
+    planner::Direction,
+    SqliteReader,
+};
+
+/// A page on the path from the root to the cursor position
+///
+/// On an interior page `idx` is the child the path continues into, unless it is the
+/// last frame in which case the cursor sits on the index entry in cell `idx`. On a
+/// leaf page it is the cell the cursor sits on.
+#[derive(Debug)]
+struct Frame {
+    page: BTreePage,
+    idx: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Edge {
+    First,
+    Last,
+}
+
+/// Pages a path from the root may hold before the b-tree is taken to be corrupt,
+/// which is as deep as SQLite lets a cursor go
+pub const MAX_DEPTH: usize = 20;
+
+/// Whether a b-tree holds the rows of a table or the entries of an index
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Tree {
+    Table,
+    Index,
+}
+
+/// A position within a table or index b-tree that can be moved entry by entry
+///
+/// Table b-trees only hold rows in their leaves, whereas index b-trees also keep
+/// entries in their interior cells which the cursor visits in key order between the
+/// children on either side. Once moved past either end the cursor is no longer valid.
+///
+/// Every page the cursor reads has to be of the kind of b-tree it was opened on, and
+/// no deeper than [`MAX_DEPTH`], or moving fails with a [`DecodeError`].
+pub struct BtCursor<'a, P: Pager = Box<dyn Pager>> {
+    reader: &'a SqliteReader<P>,
+    root: usize,
+    tree: Tree,
+    stack: Vec<Frame>,
+}
+
+impl<'a, P: Pager> BtCursor<'a, P> {
+    /// A cursor over the rows of the table b-tree rooted at `root`
+    pub fn table(reader: &'a SqliteReader<P>, root: usize) -> Self {
+        Self::new(reader, root, Tree::Table)
+    }
+
+    /// A cursor over the entries of the index b-tree rooted at `root`
+    pub fn index(reader: &'a SqliteReader<P>, root: usize) -> Self {
+        Self::new(reader, root, Tree::Index)
+    }
+
+    fn new(reader: &'a SqliteReader<P>, root: usize, tree: Tree) -> Self {
+        Self {
+            reader,
+            root,
+            tree,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Whether the cursor is positioned on an entry
+    pub fn is_valid(&self) -> bool {
+        !self.stack.is_empty()
+    }
+
+    pub fn cell(&self) -> Option<&DatabaseCell> {
+        let frame = self.stack.last()?;
+        frame.page.cells.get(frame.idx)
+    }
+
+    /// The table row under the cursor
+    pub fn row(&self) -> Option<&LeafCell> {
+        match self.cell()? {
+            DatabaseCell::Leaf(leaf) => Some(leaf),
+            _ => None,
+        }
+    }
+
+    /// The key columns and rowid of the index entry under the cursor
+    pub fn entry(&self) -> Option<(&[RecordValue], i64)> {
+        match self.cell()? {
+            DatabaseCell::IndexLeaf(leaf) => Some((&leaf.columns, leaf.row_id)),
+            DatabaseCell::InteriorIndex(index_cell) => {
+                Some((&index_cell.columns, index_cell.row_id))
+            }
+            _ => None,
+        }
+    }
+
+    /// The rowid of the row or index entry under the cursor
+    pub fn row_id(&self) -> Option<i64> {
+        match self.cell()? {
+            DatabaseCell::Leaf(leaf) => Some(leaf.row_id),
+            DatabaseCell::IndexLeaf(leaf) => Some(leaf.row_id),
+            DatabaseCell::InteriorIndex(index_cell) => Some(index_cell.row_id),
+            DatabaseCell::InteriorTable(_) => None,
+        }
+    }
+
+    pub fn first(&mut self) -> Result<()> {
+        self.stack.clear();
+        let root = self.load(self.root)?;
+        self.descend(root, Edge::First)
+    }
+
+    pub fn last(&mut self) -> Result<()> {
+        self.stack.clear();
+        let root = self.load(self.root)?;
+        self.descend(root, Edge::Last)
+    }
+
+    /// Moves to the first entry when scanning in `direction`
+    pub fn start(&mut self, direction: Direction) -> Result<()> {
+        match direction {
+            Direction::Forward => self.first(),
+            Direction::Reverse => self.last(),
+        }
+    }
+
+    /// Moves one entry along in `direction`
+    pub fn step(&mut self, direction: Direction) -> Result<()> {
+        match direction {
+            Direction::Forward => self.next(),
+            Direction::Reverse => self.prev(),
+        }
+    }
+
+    // Moving can fail on reading a page, which `Iterator::next` has no way to report
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<()> {
+        self.reader.interrupt_handle().check()?;
+        let Some(frame) = self.stack.last_mut() else {
+            return Ok(());
+        };
+
+        frame.idx += 1;
+        if is_leaf(&frame.page) {
+            if frame.idx < frame.page.cells.len() {
+                return Ok(());
+            }
+
+            return self.climb(Edge::First);
+        }
+
+        // Everything in the right child of an index entry comes before the next entry
+        let child = child_page(&frame.page, frame.idx)?;
+        let page = self.load(child)?;
+        self.descend(page, Edge::First)
+    }
+
+    pub fn prev(&mut self) -> Result<()> {
+        self.reader.interrupt_handle().check()?;
+        let Some(frame) = self.stack.last_mut() else {
+            return Ok(());
+        };
+
+        if is_leaf(&frame.page) {
+            if frame.idx > 0 {
+                frame.idx -= 1;
+                return Ok(());
+            }
+
+            return self.climb(Edge::Last);
+        }
+
+        let child = child_page(&frame.page, frame.idx)?;
+        let page = self.load(child)?;
+        self.descend(page, Edge::Last)
+    }
+
+    /// Moves to the row with the smallest rowid not below `row_id`
+    ///
+    /// Seeks landing on the leaf the cursor is already on are answered without
+    /// going back to the root, so seeking ascending rowids reads each page once.
+    pub fn seek_rowid(&mut self, row_id: i64) -> Result<()> {
+        debug_assert_eq!(self.tree, Tree::Table, "seeking a rowid in an index");
+        if let Some(frame) = self.stack.last_mut() {
+            let leaf_row_id = |cell: &DatabaseCell| match cell {
+                DatabaseCell::Leaf(leaf) => Some(leaf.row_id),
+                _ => None,
+            };
+
+            let cells = &frame.page.cells;
+            let on_leaf = frame.page.page_type() == BTreePageType::LeafTable
+                && cells
+                    .first()
+                    .and_then(leaf_row_id)
+                    .is_some_and(|first| first <= row_id)
+                && cells
+                    .last()
+                    .and_then(leaf_row_id)
+                    .is_some_and(|last| last >= row_id);
+
+            if on_leaf {
+                trace!(row_id, "seek stayed on the current leaf");
+                frame.idx = cells.partition_point(|cell| {
+                    leaf_row_id(cell).is_some_and(|leaf_row_id| leaf_row_id < row_id)
+                });
+                return Ok(());
+            }
+        }
+
+        // Only table pages are read, whose cells are all one of these
+        self.seek_cell(|cell| match cell {
+            DatabaseCell::Leaf(leaf) => leaf.row_id.cmp(&row_id),
+            DatabaseCell::InteriorTable(table_cell) => table_cell.row_id.cmp(&row_id),
+            DatabaseCell::IndexLeaf(_) | DatabaseCell::InteriorIndex(_) => Ordering::Greater,
+        })
+    }
+
+    /// Moves to the first index entry whose key `cmp` doesn't order before the sought key
+    pub fn seek(&mut self, cmp: impl Fn(&[RecordValue]) -> Ordering) -> Result<()> {
+        debug_assert_eq!(self.tree, Tree::Index, "seeking a key in a table");
+        // Only index pages are read, whose cells are all one of these
+        self.seek_cell(|cell| match cell {
+            DatabaseCell::IndexLeaf(leaf) => cmp(&leaf.columns),
+            DatabaseCell::InteriorIndex(index_cell) => cmp(&index_cell.columns),
+            DatabaseCell::Leaf(_) | DatabaseCell::InteriorTable(_) => Ordering::Greater,
+        })
+    }
+
+    /// Moves to the last index entry whose key `cmp` doesn't order after the sought key
+    pub fn seek_last(&mut self, cmp: impl Fn(&[RecordValue]) -> Ordering) -> Result<()> {
+        self.seek(|key| match cmp(key) {
+            Ordering::Greater => Ordering::Greater,
+            _ => Ordering::Less,
+        })?;
+
+        if self.is_valid() {
+            self.prev()
+        } else {
+            self.last()
+        }
+    }
+
+    /// Descends from the root to the first cell `cmp` doesn't order as `Less`
+    fn seek_cell(&mut self, cmp: impl Fn(&DatabaseCell) -> Ordering) -> Result<()> {
+        trace!(root = self.root + 1, "descending b-tree");
+        self.stack.clear();
+        let mut page = self.load(self.root)?;
+        loop {
+            let idx = page
+                .cells
+                .partition_point(|cell| cmp(cell) == Ordering::Less);
+
+            if is_leaf(&page) {
+                let exhausted = idx == page.cells.len();
+                self.stack.push(Frame { page, idx });
+                if exhausted {
+                    return self.climb(Edge::First);
+                }
+
+                return Ok(());
+            }
+
+            let child = child_page(&page, idx)?;
+            trace!(page = child + 1, cell = idx, "descending into child");
+            self.stack.push(Frame { page, idx });
+            page = self.load(child)?;
+        }
+    }
+
+    /// Follows the first or last child of each page down to a leaf
+    fn descend(&mut self, mut page: BTreePage, edge: Edge) -> Result<()> {
+        loop {
+            if is_leaf(&page) {
+                let Some(last_idx) = page.cells.len().checked_sub(1) else {
+                    // Only an empty table or index has an empty leaf
+                    self.stack.push(Frame { page, idx: 0 });
+                    return self.climb(edge);
+                };
+
+                let idx = match edge {
+                    Edge::First => 0,
+                    Edge::Last => last_idx,
+                };
+
+                self.stack.push(Frame { page, idx });
+                return Ok(());
+            }
+
+            let idx = match edge {
+                Edge::First => 0,
+                Edge::Last => page.cells.len(),
+            };
+
+            let child = child_page(&page, idx)?;
+            self.stack.push(Frame { page, idx });
+            page = self.load(child)?;
+        }
+    }
+
+    /// Leaves the exhausted page on top of the stack for the next entry in an
+    /// ancestor, landing on `edge` of the neighbouring subtree
+    fn climb(&mut self, edge: Edge) -> Result<()> {
+        self.stack.pop();
+        while let Some(frame) = self.stack.last_mut() {
+            let neighbour = match edge {
+                Edge::First if frame.idx < frame.page.cells.len() => Some(frame.idx),
+                Edge::Last if frame.idx > 0 => Some(frame.idx - 1),
+                _ => None,
+            };
+
+            let Some(neighbour) = neighbour else {
+                self.stack.pop();
+                continue;
+            };
+
+            // The divider between two children of an index page is itself an entry
+            if frame.page.page_type() == BTreePageType::InteriorIndex {
+                frame.idx = neighbour;
+                return Ok(());
+            }
+
+            frame.idx = match edge {
+                Edge::First => neighbour + 1,
+                Edge::Last => neighbour,
+            };
+
+            let child = child_page(&frame.page, frame.idx)?;
+            let page = self.load(child)?;
+            return self.descend(page, edge);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the page to go below the pages on the stack, which has to belong to the
+    /// kind of b-tree being walked
+    ///
+    /// A b-tree deeper than [`MAX_DEPTH`] can only come of a child pointer looping
+    /// back to one of its ancestors, which would otherwise be followed forever.
+    fn load(&self, idx: usize) -> Result<BTreePage> {
+        check_depth(self.root, self.stack.len())?;
+        let page = self.reader.page(idx)?;
+        let (leaf, interior) = match self.tree {
+            Tree::Table => (BTreePageType::LeafTable, BTreePageType::InteriorTable),
+            Tree::Index => (BTreePageType::LeafIndex, BTreePageType::InteriorIndex),
+        };
+
+        if page.page_type() != leaf && page.page_type() != interior {
+            return Err(DecodeError::UnexpectedPageType {
+                expected: leaf,
+                found: page.page_type(),
+            }
+            .into());
+        }
+
+        Ok(page)
+    }
+}
+
+/// Fails unless a page `depth` pages below `root` is within [`MAX_DEPTH`] of it, for
+/// walks of a b-tree that don't go through a cursor
+pub fn check_depth(root: usize, depth: usize) -> Result<(), DecodeError> {
+    if depth >= MAX_DEPTH {
+        return Err(DecodeError::TreeTooDeep {
+            root: root + 1,
+            depth: MAX_DEPTH,
+        });
+    }
+
+    Ok(())
+}
+
+fn is_leaf(page: &BTreePage) -> bool {
+    matches!(
+        page.page_type(),
+        BTreePageType::LeafTable | BTreePageType::LeafIndex
+    )
+}
+
+/// The child to the left of cell `idx`, or the rightmost child past the last cell
+fn child_page(page: &BTreePage, idx: usize) -> Result<usize> {
+    let child = match page.cells.get(idx) {
+        Some(DatabaseCell::InteriorTable(table_cell)) => table_cell.left_child,
+        Some(DatabaseCell::InteriorIndex(index_cell)) => index_cell.left_child,
+        Some(_) => {
+            return Err(DecodeError::UnexpectedPageType {
+                expected: BTreePageType::InteriorTable,
+                found: page.page_type(),
+            }
+            .into())
+        }
+        None => page
+            .right_page_pointer()
+            .context("interior page has no right page pointer")?,
+    };
+
+    Ok(child as usize)
+}
@@ -0,0 +1,426 @@
+use anyhow::{anyhow, bail, Result};
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::time::Duration;
+use tracing::debug;
+
+use super::{
+    cell::RecordValue,
+    error::QueryError,
+    interrupt::{Deadline, InterruptHandle},
+    metrics::QueryMetrics,
+    options::OpenOptions,
+    output::{self, OutputFormat},
+    result::{ColumnMetadata, ResultSet},
+    sql::{self, SelectStatement, Statement},
+    SqliteReader,
+};
+
+/// Name the database a connection was opened on goes by in qualified names
+pub const MAIN: &str = "main";
+
+/// A connection to a database along with the databases attached to it
+///
+/// Tables are looked up in the database their name is qualified with, or when it
+/// isn't qualified in the main database followed by each attached database in the
+/// order they were attached.
+///
+/// Queries only need `&self`, so one `Arc<Database>` can be shared between threads
+/// running queries at the same time through [`Database::select`].
+pub struct Database {
+    main: SqliteReader,
+    attached: Vec<(String, SqliteReader)>,
+    /// Most rows of a query printed by [`Database::execute_to`], with the rest counted
+    max_rows: Option<usize>,
+    /// Print the metrics of each query after its rows
+    timer: bool,
+    /// How [`Database::execute_to`] prints rows
+    format: OutputFormat,
+    last_query: Option<QueryMetrics>,
+    /// Longest each statement run by [`Database::execute_to`] may take
+    timeout: Option<Duration>,
+}
+
+// Sharing a database between threads relies on every part of it staying thread safe
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Database>();
+};
+
+impl Database {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(SqliteReader::new(path)?))
+    }
+
+    /// A connection to the database at `path`, opened the way `options` asks
+    pub fn open_with(path: impl AsRef<Path>, options: OpenOptions) -> Result<Self> {
+        Ok(Self::new(SqliteReader::open_with(path, &options)?))
+    }
+
+    /// A connection to a database read from the bytes of a database file
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        Ok(Self::new(SqliteReader::from_bytes(bytes)?))
+    }
+
+    pub fn new(main: SqliteReader) -> Self {
+        Self {
+            main,
+            attached: Vec::new(),
+            max_rows: None,
+            timer: false,
+            format: OutputFormat::default(),
+            last_query: None,
+            timeout: None,
+        }
+    }
+
+    pub fn main(&self) -> &SqliteReader {
+        &self.main
+    }
+
+    /// Opens the database at `path` so its tables can be referred to as `name.table`
+    pub fn attach(&mut self, path: impl AsRef<Path>, name: &str) -> Result<()> {
+        if name.eq_ignore_ascii_case(MAIN) || self.attached(name).is_some() {
+            bail!("database {name} is already in use");
+        }
+
+        let mut reader = SqliteReader::open_with(path, self.main.options())?;
+        reader.set_interrupt_handle(self.interrupt_handle());
+        self.attached.push((name.to_string(), reader));
+        Ok(())
+    }
+
+    pub fn detach(&mut self, name: &str) -> Result<()> {
+        let Some(idx) = self
+            .attached
+            .iter()
+            .position(|(attached, _)| attached.eq_ignore_ascii_case(name))
+        else {
+            bail!("no such database '{name}'");
+        };
+
+        self.attached.remove(idx);
+        Ok(())
+    }
+
+    /// The attached database going by `name`
+    pub fn attached(&self, name: &str) -> Option<&SqliteReader> {
+        self.attached
+            .iter()
+            .find(|(attached, _)| attached.eq_ignore_ascii_case(name))
+            .map(|(_, reader)| reader)
+    }
+
+    /// Stops printing the rows of a query after `max_rows` of them, or never when
+    /// `None`
+    pub fn set_max_rows(&mut self, max_rows: Option<usize>) {
+        self.max_rows = max_rows;
+    }
+
+    pub fn max_rows(&self) -> Option<usize> {
+        self.max_rows
+    }
+
+    /// Prints how long each query took and how much it read after its rows
+    pub fn set_timer(&mut self, timer: bool) {
+        self.timer = timer;
+    }
+
+    /// How [`Database::execute_to`] prints the rows of each query
+    pub fn output_format(&self) -> &OutputFormat {
+        &self.format
+    }
+
+    pub fn set_output_format(&mut self, format: OutputFormat) {
+        self.format = format;
+    }
+
+    /// What the last query run took
+    pub fn last_query(&self) -> Option<QueryMetrics> {
+        self.last_query
+    }
+
+    /// Interrupts each statement run by [`Database::execute_to`] that takes longer than
+    /// `timeout`, or none when `None`
+    ///
+    /// The timeout is kept by a thread of its own, so it needs a target with threads.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// A handle that stops the queries running on this database and its attached
+    /// databases, which stays interrupted until reset
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.main.interrupt_handle().clone()
+    }
+
+    /// Calls `handler` every `steps` cursor steps of a query, interrupting the query
+    /// when it breaks, see [`InterruptHandle::set_progress_handler`]
+    pub fn set_progress_handler(
+        &self,
+        steps: u64,
+        handler: impl FnMut() -> ControlFlow<()> + Send + 'static,
+    ) {
+        self.main
+            .interrupt_handle()
+            .set_progress_handler(steps, handler);
+    }
+
+    /// Runs each statement in `sql` in turn, handing each row of every query to `row`
+    /// as it's produced
+    ///
+    /// Once `row` breaks the query stops, and the statements after it aren't run.
+    /// Rows of a plain scan of a table are read one at a time rather than all being
+    /// read first, see [`SqliteReader::select_each`].
+    pub fn execute(
+        &mut self,
+        sql: &str,
+        mut row: impl FnMut(&[RecordValue]) -> ControlFlow<()>,
+    ) -> Result<()> {
+        for text in sql::split_statements(sql) {
+            let (statement, _deadline) = self.prepare(text)?;
+            let flow = match statement {
+                Statement::Select(select) => {
+                    let reader = self.route(&select)?;
+                    let measurement = reader.metrics().start();
+                    let mut flow = ControlFlow::Continue(());
+                    let rows = reader.select_each(&select, &mut |values| {
+                        flow = row(values);
+                        flow
+                    })?;
+                    self.last_query = Some(measurement.finish(rows));
+                    flow
+                }
+                statement => match self.run_statement(statement)? {
+                    Some(result) => result.rows.iter().try_for_each(|values| row(values)),
+                    None => ControlFlow::Continue(()),
+                },
+            };
+
+            if flow.is_break() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs each statement in `sql` in turn, writing the rows of every query to `out`
+    ///
+    /// A query can be wrapped in `format(mode, query)`, as in `format(csv, SELECT ...)`,
+    /// to print its rows in another mode than the one set with
+    /// [`set_output_format`](Self::set_output_format).
+    ///
+    /// Past the row limit the rest of a query's rows are left out, with a line
+    /// saying how many there were. A statement still running at the timeout fails
+    /// with [`Interrupted`](super::interrupt::Interrupted).
+    pub fn execute_to(&mut self, sql: &str, out: &mut dyn Write) -> Result<()> {
+        for text in sql::split_statements(sql) {
+            // A query wrapped in `format(mode, query)` is printed in that mode alone
+            let (format, text) = match sql::format_modifier(text) {
+                Some((mode, query)) => {
+                    let mut format = self.format.clone();
+                    format.set_mode(mode.parse().map_err(|e: String| anyhow!(e))?);
+                    (Some(format), query)
+                }
+                None => (None, text),
+            };
+
+            let Some(mut result) = self.execute_statement(text)? else {
+                continue;
+            };
+
+            // sqlite3 draws a query plan as a tree whatever the output mode
+            if sql::explains_query_plan(text) {
+                output::write_query_plan(&result, out)?;
+                continue;
+            }
+
+            let hidden = self.max_rows.map_or(0, |max| result.truncate(max));
+            format
+                .as_ref()
+                .unwrap_or(&self.format)
+                .write(&result, out)?;
+            if hidden > 0 {
+                writeln!(out, "... {} rows shown, {hidden} more", result.rows.len())?;
+            }
+            if let Some(metrics) = self.last_query.filter(|_| self.timer) {
+                writeln!(out, "{metrics}")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs a single statement, returning the rows when it is a query
+    pub fn execute_statement(&mut self, text: &str) -> Result<Option<ResultSet>> {
+        let (statement, _deadline) = self.prepare(text)?;
+        self.run_statement(statement)
+    }
+
+    /// Parses a statement about to run, starting the clock on its timeout
+    fn prepare(&mut self, text: &str) -> Result<(Statement, Option<Deadline>)> {
+        // Nothing borrows the pages cached by the last statement anymore
+        self.main.shrink_cache();
+        for (_, reader) in self.attached.iter_mut() {
+            reader.shrink_cache();
+        }
+
+        let interrupt = self.interrupt_handle();
+        interrupt.reset();
+        let deadline = self
+            .timeout
+            .map(|timeout| interrupt.interrupt_after(timeout));
+
+        if let Some(keyword) = sql::write_keyword(text).filter(|_| self.main.options().read_only) {
+            return Err(QueryError::ReadOnly(keyword.to_uppercase()).into());
+        }
+
+        debug!(sql = text, "parsing statement");
+        let statement = sql::parse_statement(text)?;
+        Ok((statement, deadline))
+    }
+
+    fn run_statement(&mut self, statement: Statement) -> Result<Option<ResultSet>> {
+        match statement {
+            Statement::Select(select) => {
+                let reader = self.route(&select)?;
+                let measurement = reader.metrics().start();
+                let mut result = reader.execute_select(&select)?;
+                self.last_query = Some(measurement.finish(result.rows.len()));
+                self.describe_result(&select, &mut result)?;
+                Ok(Some(result))
+            }
+            Statement::ExplainQueryPlan(select) => {
+                self.route(&select)?.explain_query_plan(&select).map(Some)
+            }
+            Statement::Attach { path, name } => self.attach(path, &name).map(|_| None),
+            Statement::Detach(name) => self.detach(&name).map(|_| None),
+            Statement::Pragma {
+                schema,
+                name,
+                argument,
+            } => {
+                // A pragma naming a table runs on the database holding it
+                let table = argument.as_deref().unwrap_or_default();
+                let reader = self.route_table(schema.as_deref(), table)?;
+                reader.pragma(&name, argument.as_deref()).map(Some)
+            }
+            Statement::Analyze { schema, target } => self.analyze(schema, target).map(Some),
+        }
+    }
+
+    /// Analyzes the table or index `target` in the database holding it, or every table
+    /// of the database `schema` names, or of every database when neither is given,
+    /// keeping the statistics for this connection without writing them to the files
+    fn analyze(&self, schema: Option<String>, target: Option<String>) -> Result<ResultSet> {
+        let (schema, target) = match (schema, target) {
+            (None, Some(name)) if self.database(&name).is_some() => (Some(name), None),
+            other => other,
+        };
+
+        match (schema, target) {
+            (Some(schema), None) => match self.database(&schema) {
+                Some(reader) => reader.analyze(None),
+                None => bail!("no such database '{schema}'"),
+            },
+            (schema, Some(target)) => self
+                .route_table(schema.as_deref(), &target)?
+                .analyze(Some(&target)),
+            (None, None) => {
+                let mut result = self.main.analyze(None)?;
+                for (_, reader) in self.attached.iter() {
+                    result.rows.extend(reader.analyze(None)?.rows);
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    /// The main database or the attached database going by `name`
+    fn database(&self, name: &str) -> Option<&SqliteReader> {
+        match name.eq_ignore_ascii_case(MAIN) {
+            true => Some(&self.main),
+            false => self.attached(name),
+        }
+    }
+
+    pub fn select(&self, query: &str) -> Result<ResultSet> {
+        let statement = sql::parse_select(query)?;
+
+        self.run_select(&statement)
+    }
+
+    fn run_select(&self, statement: &SelectStatement) -> Result<ResultSet> {
+        let mut result = self.route(statement)?.execute_select(statement)?;
+        self.describe_result(statement, &mut result)?;
+        Ok(result)
+    }
+
+    /// Fills in where each column of `result` comes from, renaming those read
+    /// straight from a table to the name the table declares them with
+    fn describe_result(&self, statement: &SelectStatement, result: &mut ResultSet) -> Result<()> {
+        result.metadata = self.column_metadata(statement)?;
+        for (name, metadata) in result.columns.iter_mut().zip(&result.metadata) {
+            if metadata.column.is_some() {
+                name.clone_from(&metadata.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// The declared type and origin of each column `query` answers with, for rows
+    /// handed over one at a time by [`Database::execute`]
+    pub fn describe(&self, query: &str) -> Result<Vec<ColumnMetadata>> {
+        let statement = sql::parse_select(query)?;
+
+        self.column_metadata(&statement)
+    }
+
+    /// [`SqliteReader::column_metadata`] along with the name of the database the query
+    /// reads from
+    fn column_metadata(&self, statement: &SelectStatement) -> Result<Vec<ColumnMetadata>> {
+        let reader = self.route(statement)?;
+        let database = match std::ptr::eq(reader, &self.main) {
+            true => MAIN,
+            false => self
+                .attached
+                .iter()
+                .find(|(_, attached)| std::ptr::eq(attached, reader))
+                .map_or(MAIN, |(name, _)| name.as_str()),
+        };
+
+        let mut metadata = reader.column_metadata(statement)?;
+        for column in metadata.iter_mut().filter(|column| column.table.is_some()) {
+            column.database = Some(database.to_string());
+        }
+        Ok(metadata)
+    }
+
+    /// The database holding the table a query reads from
+    fn route(&self, statement: &SelectStatement) -> Result<&SqliteReader> {
+        self.route_table(statement.schema.as_deref(), &statement.table)
+    }
+
+    /// The database `schema` names, or when there isn't one the first holding `table`
+    fn route_table(&self, schema: Option<&str>, table: &str) -> Result<&SqliteReader> {
+        match schema {
+            Some(name) if name.eq_ignore_ascii_case(MAIN) => Ok(&self.main),
+            Some(name) => self
+                .attached(name)
+                .ok_or_else(|| anyhow!("no such database '{name}'")),
+            None => {
+                for reader in std::iter::once(&self.main)
+                    .chain(self.attached.iter().map(|(_, reader)| reader))
+                {
+                    if reader.schema()?.fetch_table(table).is_some() {
+                        return Ok(reader);
+                    }
+                }
+
+                Ok(&self.main)
+            }
+        }
+    }
+}
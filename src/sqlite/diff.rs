@@ -0,0 +1,373 @@
+use anyhow::Result;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+use super::{
+    cell::RecordValue,
+    collation::Collation,
+    cursor::BtCursor,
+    pager::Pager,
+    schema::SchemaTable,
+    sql::{quote_identifier, CreateTable},
+    SqliteReader,
+};
+
+/// One step in turning a database into another
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Create {
+        kind: &'static str,
+        name: String,
+        sql: String,
+    },
+    Drop {
+        kind: &'static str,
+        name: String,
+    },
+    Insert {
+        table: String,
+        key: String,
+        row_id: i64,
+        columns: Vec<String>,
+        values: Vec<RecordValue>,
+    },
+    Delete {
+        table: String,
+        key: String,
+        row_id: i64,
+    },
+    Update {
+        table: String,
+        key: String,
+        row_id: i64,
+        changes: Vec<(String, RecordValue)>,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Create { sql, .. } => write!(f, "{sql};"),
+            Self::Drop { kind, name } => {
+                write!(
+                    f,
+                    "DROP {} {};",
+                    kind.to_uppercase(),
+                    quote_identifier(name)
+                )
+            }
+            Self::Insert {
+                table,
+                key,
+                row_id,
+                columns,
+                values,
+            } => {
+                let mut names = Vec::new();
+                let mut literals = Vec::new();
+                if !columns.contains(key) {
+                    names.push(quote_identifier(key));
+                    literals.push(row_id.to_string());
+                }
+                names.extend(columns.iter().map(|column| quote_identifier(column)));
+                literals.extend(values.iter().map(|value| value.to_sql_literal()));
+
+                write!(
+                    f,
+                    "INSERT INTO {}({}) VALUES({});",
+                    quote_identifier(table),
+                    names.join(","),
+                    literals.join(",")
+                )
+            }
+            Self::Delete { table, key, row_id } => write!(
+                f,
+                "DELETE FROM {} WHERE {}={row_id};",
+                quote_identifier(table),
+                quote_identifier(key)
+            ),
+            Self::Update {
+                table,
+                key,
+                row_id,
+                changes,
+            } => {
+                let assignments: Vec<String> = changes
+                    .iter()
+                    .map(|(column, value)| {
+                        format!("{}={}", quote_identifier(column), value.to_sql_literal())
+                    })
+                    .collect();
+
+                write!(
+                    f,
+                    "UPDATE {} SET {} WHERE {}={row_id};",
+                    quote_identifier(table),
+                    assignments.join(","),
+                    quote_identifier(key)
+                )
+            }
+        }
+    }
+}
+
+/// Rows added, deleted and changed in one table
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct RowCounts {
+    added: usize,
+    deleted: usize,
+    changed: usize,
+}
+
+impl<P: Pager> SqliteReader<P> {
+    /// Prints the SQL that turns this database into `other`, or with `summary` only
+    /// the schema objects and number of rows that differ
+    pub fn diff<Q: Pager>(
+        &self,
+        other: &SqliteReader<Q>,
+        summary: bool,
+        out: &mut dyn io::Write,
+    ) -> Result<()> {
+        let changes = self.changes(other)?;
+        if !summary {
+            for change in changes.iter() {
+                writeln!(out, "{change}")?;
+            }
+
+            return Ok(());
+        }
+
+        let mut counts: BTreeMap<&str, RowCounts> = BTreeMap::new();
+        for change in changes.iter() {
+            match change {
+                Change::Create { kind, name, .. } => writeln!(out, "+ {kind} {name}")?,
+                Change::Drop { kind, name } => writeln!(out, "- {kind} {name}")?,
+                Change::Insert { table, .. } => counts.entry(table).or_default().added += 1,
+                Change::Delete { table, .. } => counts.entry(table).or_default().deleted += 1,
+                Change::Update { table, .. } => counts.entry(table).or_default().changed += 1,
+            }
+        }
+
+        for (table, counts) in counts {
+            writeln!(
+                out,
+                "{table}: {} added, {} deleted, {} changed",
+                counts.added, counts.deleted, counts.changed
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The schema changes followed by the row changes that turn this database into
+    /// `other`, in an order they can be applied in
+    ///
+    /// Rows are matched up by rowid, so tables and indexes whose definition changed
+    /// are dropped and created again with all their rows. Internal `sqlite_` tables
+    /// are left out.
+    pub fn changes<Q: Pager>(&self, other: &SqliteReader<Q>) -> Result<Vec<Change>> {
+        let old_schema = self.schema()?;
+        let new_schema = other.schema()?;
+        let user_entries = |entry: &&SchemaTable| !entry.is_internal();
+
+        let recreated = |entry: &SchemaTable| {
+            new_schema
+                .fetch_table(&entry.name)
+                .map_or(true, |new| new.sql != entry.sql)
+        };
+
+        let mut changes = Vec::new();
+        let dropped_tables: Vec<&str> = old_schema
+            .entries()
+            .filter(user_entries)
+            .filter(|entry| entry.is_table() && recreated(entry))
+            .map(|entry| entry.name.as_str())
+            .collect();
+
+        for entry in old_schema.entries().filter(user_entries) {
+            // Dropping a table drops its indexes along with it
+            let table_dropped = dropped_tables.contains(&entry.table_name.as_str());
+            if recreated(entry) && (entry.is_table() || !table_dropped) {
+                changes.push(Change::Drop {
+                    kind: kind(entry),
+                    name: entry.name.clone(),
+                });
+            }
+        }
+
+        let created = |entry: &SchemaTable| {
+            old_schema
+                .fetch_table(&entry.name)
+                .map_or(true, |old| old.sql != entry.sql)
+        };
+
+        for entry in new_schema.entries().filter(user_entries) {
+            if entry.is_table() && created(entry) {
+                changes.push(create(entry));
+                let table = entry.columns();
+                let columns = column_names(&table);
+                let key = row_key(&table);
+                let mut cursor = BtCursor::table(other, entry.root_page as usize);
+                cursor.first()?;
+                while let Some(row_id) = cursor.row_id() {
+                    changes.push(Change::Insert {
+                        table: entry.name.clone(),
+                        key: key.clone(),
+                        row_id,
+                        columns: columns.clone(),
+                        values: row_values(&cursor, &table),
+                    });
+                    cursor.next()?;
+                }
+            }
+        }
+
+        for entry in new_schema.entries().filter(user_entries) {
+            let table_created = dropped_tables.contains(&entry.table_name.as_str())
+                || old_schema.fetch_table(&entry.table_name).is_none();
+            if !entry.is_table() && (created(entry) || table_created) {
+                changes.push(create(entry));
+            }
+        }
+
+        for entry in old_schema.entries().filter(user_entries) {
+            if entry.is_table() && !recreated(entry) {
+                let new_entry = new_schema
+                    .fetch_table(&entry.name)
+                    .expect("table should not be recreated");
+                self.row_changes(other, entry, new_entry, &mut changes)?;
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Walks both copies of a table in rowid order, matching up rows with the same rowid
+    fn row_changes<Q: Pager>(
+        &self,
+        other: &SqliteReader<Q>,
+        old: &SchemaTable,
+        new: &SchemaTable,
+        changes: &mut Vec<Change>,
+    ) -> Result<()> {
+        let old_table = old.columns();
+        let new_table = new.columns();
+        let columns = column_names(&old_table);
+        let key = row_key(&old_table);
+
+        let mut old_cursor = BtCursor::table(self, old.root_page as usize);
+        let mut new_cursor = BtCursor::table(other, new.root_page as usize);
+        old_cursor.first()?;
+        new_cursor.first()?;
+
+        loop {
+            let order = match (old_cursor.row_id(), new_cursor.row_id()) {
+                (None, None) => break,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(old_id), Some(new_id)) => old_id.cmp(&new_id),
+            };
+
+            match order {
+                Ordering::Less => {
+                    changes.push(Change::Delete {
+                        table: old.name.clone(),
+                        key: key.clone(),
+                        row_id: old_cursor.row_id().expect("cursor should be on a row"),
+                    });
+                    old_cursor.next()?;
+                }
+                Ordering::Greater => {
+                    changes.push(Change::Insert {
+                        table: new.name.clone(),
+                        key: key.clone(),
+                        row_id: new_cursor.row_id().expect("cursor should be on a row"),
+                        columns: columns.clone(),
+                        values: row_values(&new_cursor, &new_table),
+                    });
+                    new_cursor.next()?;
+                }
+                Ordering::Equal => {
+                    let old_values = row_values(&old_cursor, &old_table);
+                    let new_values = row_values(&new_cursor, &new_table);
+                    let differences: Vec<(String, RecordValue)> = columns
+                        .iter()
+                        .zip(old_values.iter().zip(new_values))
+                        .filter(|(_, (old_value, new_value))| !same_value(old_value, new_value))
+                        .map(|(column, (_, new_value))| (column.clone(), new_value))
+                        .collect();
+
+                    if !differences.is_empty() {
+                        changes.push(Change::Update {
+                            table: old.name.clone(),
+                            key: key.clone(),
+                            row_id: old_cursor.row_id().expect("cursor should be on a row"),
+                            changes: differences,
+                        });
+                    }
+
+                    old_cursor.next()?;
+                    new_cursor.next()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn kind(entry: &SchemaTable) -> &'static str {
+    if entry.is_index() {
+        "index"
+    } else {
+        "table"
+    }
+}
+
+fn create(entry: &SchemaTable) -> Change {
+    Change::Create {
+        kind: kind(entry),
+        name: entry.name.clone(),
+        sql: entry.sql.clone(),
+    }
+}
+
+/// The columns rows are written with, leaving out generated columns which SQLite
+/// computes itself
+fn column_names(table: &CreateTable) -> Vec<String> {
+    table
+        .columns
+        .iter()
+        .filter(|column| column.generated.is_none())
+        .map(|column| column.name.clone())
+        .collect()
+}
+
+/// The column rows are identified by in the SQL: the `INTEGER PRIMARY KEY` if the
+/// table has one, otherwise the rowid itself
+fn row_key(table: &CreateTable) -> String {
+    table
+        .rowid_alias()
+        .map_or_else(|| "rowid".to_string(), |column| column.name.clone())
+}
+
+/// The value of each of the [`column_names`] in the row under the cursor, with the
+/// rowid filled in for an `INTEGER PRIMARY KEY` and columns added after the row was
+/// written read as their default
+fn row_values<P: Pager>(cursor: &BtCursor<P>, table: &CreateTable) -> Vec<RecordValue> {
+    let row = cursor.row().expect("cursor should be on a table row");
+    table
+        .columns
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| column.generated.is_none())
+        .map(|(i, _)| row.column_value(i, &table.columns))
+        .collect()
+}
+
+/// Whether two values are the same regardless of the width they are stored with
+fn same_value(lhs: &RecordValue, rhs: &RecordValue) -> bool {
+    lhs.sort_cmp(rhs, &Collation::Binary) == Ordering::Equal
+}
@@ -0,0 +1,108 @@
+use thiserror::Error;
+
+use super::page::BTreePageType;
+
+/// Failure to decode the on-disk format of a page, cell or record
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("unexpected end of data: needed {needed} bytes but only {remaining} remain")]
+    Truncated { needed: usize, remaining: usize },
+
+    #[error("record header size {header_size} is outside its {payload_size} byte payload")]
+    InvalidRecordHeader {
+        header_size: usize,
+        payload_size: usize,
+    },
+
+    #[error("record values overrun their payload by {0} bytes")]
+    TrailingBytes(usize),
+
+    #[error("text value is not valid utf-8")]
+    InvalidText,
+
+    #[error("index record has no rowid")]
+    MissingRowId,
+
+    #[error("index record has no key before its rowid")]
+    MissingIndexKey,
+
+    #[error("index record rowid is not an integer - found {0}")]
+    InvalidRowId(String),
+
+    #[error("invalid b-tree page type {0}")]
+    InvalidPageType(u8),
+
+    /// Pages count from 1, so a child pointer of 0 points nowhere
+    #[error("b-tree child pointer {0} is not a page")]
+    InvalidChildPage(u32),
+
+    #[error("freelist page {0} is outside the database file")]
+    InvalidFreelistPage(u32),
+
+    #[error("overflow page {0} is outside the database file")]
+    InvalidOverflowPage(u32),
+
+    #[error("cell offset {offset} is outside the {page_size} byte page")]
+    InvalidCellOffset { offset: usize, page_size: usize },
+
+    #[error("payload of {0} bytes is larger than the database")]
+    PayloadTooLarge(u64),
+
+    #[error("expected {expected} but found {found}")]
+    UnexpectedPageType {
+        expected: BTreePageType,
+        found: BTreePageType,
+    },
+
+    /// A b-tree this deep can only come of a child pointer looping back to an ancestor
+    #[error("b-tree rooted at page {root} is deeper than {depth} pages")]
+    TreeTooDeep { root: usize, depth: usize },
+
+    #[error("invalid sqlite_schema entry: {0}")]
+    InvalidSchemaEntry(String),
+}
+
+/// Failure of a statement down to its SQL rather than the database it runs against
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    /// Gives the statement along with what the parser made of it
+    #[error("parsing '{sql}': {reason}")]
+    Parse { sql: String, reason: String },
+
+    /// Gives a clause or operator SQLite has that statements here can't use yet,
+    /// such as `GROUP BY`
+    #[error("unsupported syntax: {0}")]
+    Unsupported(String),
+
+    #[error("no such table '{0}'")]
+    NoSuchTable(String),
+
+    #[error("no such column '{0}'")]
+    NoSuchColumn(String),
+
+    /// Gives the keyword of a statement that would write to a database opened read
+    /// only, which is refused before it's parsed
+    #[error("{0} can't run on a read-only database")]
+    ReadOnly(String),
+}
+
+impl QueryError {
+    pub fn parse(sql: &str, reason: impl std::fmt::Display) -> Self {
+        Self::Parse {
+            sql: sql.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Fails with [`DecodeError::Truncated`] unless `buf` holds at least `needed` bytes
+pub fn ensure_remaining(buf: &[u8], needed: usize) -> Result<(), DecodeError> {
+    if buf.len() < needed {
+        return Err(DecodeError::Truncated {
+            needed,
+            remaining: buf.len(),
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,300 @@
+use anyhow::{bail, Result};
+
+use super::{
+    cell::RecordValue,
+    error::QueryError,
+    membership::Strategy,
+    pager::Pager,
+    planner::{self, ScanPlan},
+    result::ResultSet,
+    schema::{SchemaTable, SqliteSchema},
+    sql::{self, InSource, SelectOperation, SelectStatement},
+    vtab, SqliteReader,
+};
+
+/// The rows of `EXPLAIN QUERY PLAN` built up so far, each step naming the one it
+/// is part of
+struct QuerySteps {
+    result: ResultSet,
+    /// Subqueries described so far, which are numbered in the order they appear
+    subqueries: usize,
+    /// Tables named by the `WITH` clauses in scope, innermost last
+    common_tables: Vec<String>,
+}
+
+impl QuerySteps {
+    /// Adds a step under `parent`, or at the top for 0, returning its id
+    fn step(&mut self, parent: i64, detail: String) -> i64 {
+        let id = self.result.rows.len() as i64 + 1;
+        self.result.rows.push(vec![
+            RecordValue::I64(id),
+            RecordValue::I64(parent),
+            RecordValue::I64(0),
+            RecordValue::String(detail),
+        ]);
+        id
+    }
+}
+
+impl<P: Pager> SqliteReader<P> {
+    /// How a query would be answered, as the `id`, `parent`, `notused` and `detail`
+    /// rows SQLite answers `EXPLAIN QUERY PLAN` with, without reading any rows
+    pub fn explain_query_plan(&self, statement: &SelectStatement) -> Result<ResultSet> {
+        let columns = ["id", "parent", "notused", "detail"];
+        let mut steps = QuerySteps {
+            result: ResultSet::new(columns.map(String::from).to_vec()),
+            subqueries: 0,
+            common_tables: Vec::new(),
+        };
+        self.describe_query(statement, 0, &mut steps)?;
+
+        Ok(steps.result)
+    }
+
+    fn describe_query(
+        &self,
+        statement: &SelectStatement,
+        parent: i64,
+        steps: &mut QuerySteps,
+    ) -> Result<()> {
+        let scope = steps.common_tables.len();
+        for common in statement.with.iter() {
+            let id = steps.step(parent, format!("MATERIALIZE {}", common.name));
+            self.describe_query(&common.select, id, steps)?;
+            steps.common_tables.push(common.name.clone());
+        }
+
+        if let Some(values) = &statement.values {
+            let detail = match &values[..] {
+                [row] if row.is_empty() => "SCAN CONSTANT ROW".to_string(),
+                rows => format!("SCAN {}-ROW VALUES CLAUSE", rows.len()),
+            };
+            steps.step(parent, detail);
+            steps.common_tables.truncate(scope);
+            return Ok(());
+        }
+
+        let schema = self.schema()?;
+        let name = &statement.table;
+        let common = statement.schema.is_none()
+            && steps
+                .common_tables
+                .iter()
+                .any(|table| sql::same_name(table, name));
+        let table = match schema.fetch_table(name) {
+            _ if common => None,
+            Some(table) if table.virtual_table().is_some() => None,
+            Some(table) if !table.is_table() => bail!("'{name}' isn't a table with rowids"),
+            Some(table) => Some(table),
+            None if vtab::virtual_table(name).is_some() => None,
+            None => return Err(QueryError::NoSuchTable(name.clone()).into()),
+        };
+
+        let filtered = !statement.where_clause.is_empty() || statement.filters_rows();
+        match (table, &statement.operation) {
+            _ if statement.join.is_some() => self.describe_join(statement, parent, steps)?,
+            (None, _) if common => {
+                steps.step(parent, format!("SCAN {name}"));
+            }
+            (None, _) => {
+                steps.step(parent, format!("SCAN {name} VIRTUAL TABLE"));
+            }
+            (Some(table), Some(operation)) if !filtered => {
+                let detail = aggregate_detail(&schema, table, operation);
+                steps.step(parent, detail);
+            }
+            (Some(table), _) => {
+                let base = base_rows(statement);
+                let stats = self.stats(&schema)?;
+                let (detail, sort) = match planner::expression_index(&schema, &stats, statement) {
+                    Some((index, filter)) => (
+                        format!(
+                            "SEARCH {} USING INDEX {} (<expr>{}?)",
+                            table.name, index.name, filter.operator
+                        ),
+                        !statement.order_by.is_empty(),
+                    ),
+                    None => {
+                        let plan = planner::plan(&schema, &stats, &base);
+                        (scan_detail(&plan.scan, table, &base), plan.sort)
+                    }
+                };
+                steps.step(parent, detail);
+                if sort {
+                    steps.step(parent, "USE TEMP B-TREE FOR ORDER BY".to_string());
+                }
+            }
+        }
+
+        let table_schema = table.map(|table| table.columns());
+        for filter in statement.in_filters.iter() {
+            let strategy = Strategy::of(&filter.source, &filter.collation(table_schema.as_ref()));
+            match &filter.source {
+                InSource::List(list) => {
+                    let detail = match strategy {
+                        Strategy::HashSet => format!(
+                            "USE TEMP HASH SET FOR {} IN LIST OF {} VALUES",
+                            filter.expr,
+                            list.len()
+                        ),
+                        Strategy::Compare => format!(
+                            "COMPARE {} WITH IN LIST OF {} VALUES IN TURN",
+                            filter.expr,
+                            list.len()
+                        ),
+                    };
+                    steps.step(parent, detail);
+                }
+                InSource::Subquery(subquery) => {
+                    let id = self.describe_subquery(statement, subquery, "LIST", parent, steps)?;
+                    if strategy == Strategy::HashSet {
+                        steps.step(id, "CREATE TEMP HASH SET".to_string());
+                    }
+                }
+            }
+        }
+
+        for filter in statement.exists_filters.iter() {
+            self.describe_subquery(statement, &filter.subquery, "SCALAR", parent, steps)?;
+        }
+
+        steps.common_tables.truncate(scope);
+        Ok(())
+    }
+
+    /// A numbered subquery of `outer` and the steps answering it, returning the id of
+    /// its step
+    ///
+    /// A correlated subquery is described as it is run for each outer row, searching
+    /// by the values it compares with.
+    fn describe_subquery(
+        &self,
+        outer: &SelectStatement,
+        subquery: &SelectStatement,
+        kind: &str,
+        parent: i64,
+        steps: &mut QuerySteps,
+    ) -> Result<i64> {
+        steps.subqueries += 1;
+        let (correlated, bound) = match self.correlate(outer, subquery)? {
+            Some(correlated) => {
+                let (_, bound) = correlated.bind(&|_| Ok(RecordValue::Null))?;
+                ("CORRELATED ", bound)
+            }
+            None => ("", subquery.clone()),
+        };
+
+        let detail = format!("{correlated}{kind} SUBQUERY {}", steps.subqueries);
+        let id = steps.step(parent, detail);
+        self.describe_query(&bound, id, steps)?;
+        Ok(id)
+    }
+
+    /// The query over each table of a join, then the hash table matching their rows
+    fn describe_join(
+        &self,
+        statement: &SelectStatement,
+        parent: i64,
+        steps: &mut QuerySteps,
+    ) -> Result<()> {
+        let Some(join) = &statement.join else {
+            return Ok(());
+        };
+
+        let plan = self.plan_join(&base_rows(statement), join)?;
+        for side in plan.sides.iter() {
+            self.describe_query(&side.statement, parent, steps)?;
+        }
+        steps.step(
+            parent,
+            format!(
+                "USE TEMP HASH TABLE FOR JOIN ON {} = {}",
+                join.on.0, join.on.1
+            ),
+        );
+        if !statement.order_by.is_empty() {
+            steps.step(parent, "USE TEMP B-TREE FOR ORDER BY".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// The rows a query is answered from, before its row filters and `IN` tests are
+/// checked and its values computed
+fn base_rows(statement: &SelectStatement) -> SelectStatement {
+    let base = match statement.operation {
+        Some(_) => super::aggregated_rows(statement),
+        None => statement.clone(),
+    };
+    SelectStatement {
+        projection: None,
+        row_filters: Vec::new(),
+        in_filters: Vec::new(),
+        column_filters: Vec::new(),
+        exists_filters: Vec::new(),
+        ..base
+    }
+}
+
+/// How `COUNT`/`MIN`/`MAX` over a whole table is answered, from cell counts or the
+/// edge of a b-tree rather than by reading every row
+fn aggregate_detail(
+    schema: &SqliteSchema,
+    table: &SchemaTable,
+    operation: &SelectOperation,
+) -> String {
+    let column = match operation {
+        SelectOperation::Count => return format!("SCAN {}", table.name),
+        SelectOperation::Min(column) | SelectOperation::Max(column) => column,
+    };
+
+    let table_schema = table.columns();
+    if table_schema
+        .rowid_alias()
+        .is_some_and(|alias| sql::same_name(&alias.name, column))
+    {
+        return format!("SEARCH {}", table.name);
+    }
+
+    let collation = table_schema.column_collation(column);
+    match schema.fetch_index(&table.name, column, &collation, &[]) {
+        Some(index) => format!("SEARCH {} USING COVERING INDEX {}", table.name, index.name),
+        None => format!("SCAN {}", table.name),
+    }
+}
+
+/// The step for the scan a query was planned with, naming the indexes it uses and
+/// the key each is searched by
+fn scan_detail(scan: &ScanPlan, table: &SchemaTable, statement: &SelectStatement) -> String {
+    let table = &table.name;
+    match scan {
+        ScanPlan::FullTable => format!("SCAN {table}"),
+        ScanPlan::Index(index) => match probe(index, statement) {
+            Some(key) => format!("SEARCH {table} USING INDEX {} {key}", index.name),
+            None => format!("SCAN {table} USING INDEX {}", index.name),
+        },
+        ScanPlan::CoveringIndex(index) => match probe(index, statement) {
+            Some(key) => format!("SEARCH {table} USING COVERING INDEX {} {key}", index.name),
+            None => format!("SCAN {table} USING COVERING INDEX {}", index.name),
+        },
+        ScanPlan::IndexIntersection(lhs, rhs) => format!(
+            "SEARCH {table} USING INTERSECTION OF INDEX {} {} AND INDEX {} {}",
+            lhs.name,
+            probe(lhs, statement).unwrap_or_default(),
+            rhs.name,
+            probe(rhs, statement).unwrap_or_default(),
+        ),
+    }
+}
+
+/// The comparison an index is searched by, as in `(customer=?)`, or `None` when it
+/// is only walked for its order
+fn probe(index: &SchemaTable, statement: &SelectStatement) -> Option<String> {
+    let key = index.indexed_columns().into_iter().next()?;
+    let condition = statement.where_clause.iter().find(|condition| {
+        sql::same_name(&condition.column, &key.name) && condition.operator.is_comparison()
+    })?;
+
+    Some(format!("({}{}?)", key.name, condition.operator))
+}
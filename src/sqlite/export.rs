@@ -0,0 +1,237 @@
+use anyhow::Result;
+use arrow_array::{
+    builder::{BinaryBuilder, Float64Builder, Int64Builder, StringBuilder},
+    ArrayRef, NullArray, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use std::{fs::File, path::Path, sync::Arc};
+
+use super::{cell::RecordValue, cursor::BtCursor, pager::Pager, schema::SchemaTable, SqliteReader};
+
+/// Rows buffered before they are written out as a row group
+const BATCH_ROWS: usize = 8192;
+
+/// The Arrow type a column is exported as
+///
+/// SQLite columns can hold values of any type, so a column starts out as `Null`
+/// and is widened by every value it holds: integers and reals make a `Real`
+/// column and anything else mixed together is written as text.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ColumnKind {
+    Null,
+    Integer,
+    Real,
+    Text,
+    Blob,
+}
+
+impl ColumnKind {
+    fn of(value: &RecordValue) -> Self {
+        match value {
+            RecordValue::Null => Self::Null,
+            RecordValue::F64(_) => Self::Real,
+            RecordValue::String(_) => Self::Text,
+            RecordValue::Blob(_) => Self::Blob,
+            _ => Self::Integer,
+        }
+    }
+
+    fn widen(self, value: &RecordValue) -> Self {
+        match (self, Self::of(value)) {
+            (kind, Self::Null) | (Self::Null, kind) => kind,
+            (lhs, rhs) if lhs == rhs => lhs,
+            (Self::Integer, Self::Real) | (Self::Real, Self::Integer) => Self::Real,
+            _ => Self::Text,
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            Self::Null => DataType::Null,
+            Self::Integer => DataType::Int64,
+            Self::Real => DataType::Float64,
+            Self::Text => DataType::Utf8,
+            Self::Blob => DataType::Binary,
+        }
+    }
+}
+
+impl<P: Pager> SqliteReader<P> {
+    /// Writes a table, or the result of a query, to a Parquet file and returns how
+    /// many rows were written
+    ///
+    /// Tables are read twice, once to settle the type of each column and again to
+    /// write the rows a batch at a time, so they never have to fit in memory.
+    pub fn export_parquet(&self, source: &str, path: impl AsRef<Path>) -> Result<usize> {
+        let schema = self.schema()?;
+        if let Some(table) = schema.fetch_table(source).filter(|table| table.is_table()) {
+            let columns: Vec<String> = table
+                .columns()
+                .columns
+                .into_iter()
+                .map(|column| column.name)
+                .collect();
+
+            let mut kinds = vec![ColumnKind::Null; columns.len()];
+            self.scan_table(table, &mut |row| {
+                widen_kinds(&mut kinds, &row);
+                Ok(())
+            })?;
+
+            let mut writer = BatchWriter::new(path.as_ref(), &columns, kinds)?;
+            self.scan_table(table, &mut |row| writer.push(row))?;
+            return writer.finish();
+        }
+
+        let result = self.select(source)?;
+        let mut kinds = vec![ColumnKind::Null; result.columns.len()];
+        for row in result.rows.iter() {
+            widen_kinds(&mut kinds, row);
+        }
+
+        let mut writer = BatchWriter::new(path.as_ref(), &result.columns, kinds)?;
+        for row in result.rows {
+            writer.push(row)?;
+        }
+
+        writer.finish()
+    }
+
+    /// Calls `visit` with every column of every row of the table in rowid order
+    fn scan_table(
+        &self,
+        table: &SchemaTable,
+        visit: &mut impl FnMut(Vec<RecordValue>) -> Result<()>,
+    ) -> Result<()> {
+        let table_schema = table.columns();
+        let mut cursor = BtCursor::table(self, table.root_page as usize);
+        cursor.first()?;
+        while let Some(row) = cursor.row() {
+            let values = (0..table_schema.columns.len())
+                .map(|idx| row.column_value(idx, &table_schema.columns))
+                .collect();
+
+            visit(values)?;
+            cursor.next()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn widen_kinds(kinds: &mut [ColumnKind], row: &[RecordValue]) {
+    for (kind, value) in kinds.iter_mut().zip(row.iter()) {
+        *kind = kind.widen(value);
+    }
+}
+
+/// Buffers rows and writes them to the Parquet file in record batches
+struct BatchWriter {
+    writer: ArrowWriter<File>,
+    schema: SchemaRef,
+    kinds: Vec<ColumnKind>,
+    pending: Vec<Vec<RecordValue>>,
+    written: usize,
+}
+
+impl BatchWriter {
+    fn new(path: &Path, columns: &[String], kinds: Vec<ColumnKind>) -> Result<Self> {
+        let fields: Vec<Field> = columns
+            .iter()
+            .zip(kinds.iter())
+            .map(|(name, kind)| Field::new(name, kind.data_type(), true))
+            .collect();
+
+        let schema = Arc::new(Schema::new(fields));
+        let writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), None)?;
+
+        Ok(Self {
+            writer,
+            schema,
+            kinds,
+            pending: Vec::with_capacity(BATCH_ROWS),
+            written: 0,
+        })
+    }
+
+    fn push(&mut self, row: Vec<RecordValue>) -> Result<()> {
+        self.pending.push(row);
+        if self.pending.len() == BATCH_ROWS {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let arrays: Vec<ArrayRef> = self
+            .kinds
+            .iter()
+            .enumerate()
+            .map(|(idx, kind)| build_array(*kind, self.pending.iter().map(|row| &row[idx])))
+            .collect();
+
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+        self.writer.write(&batch)?;
+        self.written += self.pending.len();
+        self.pending.clear();
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<usize> {
+        self.flush()?;
+        self.writer.close()?;
+        Ok(self.written)
+    }
+}
+
+/// Builds the Arrow array for one column of a batch, converting each value to the
+/// column's type
+fn build_array<'a>(kind: ColumnKind, values: impl Iterator<Item = &'a RecordValue>) -> ArrayRef {
+    match kind {
+        ColumnKind::Null => Arc::new(NullArray::new(values.count())),
+        ColumnKind::Integer => {
+            let mut builder = Int64Builder::new();
+            for value in values {
+                builder.append_option(value.as_i64());
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnKind::Real => {
+            let mut builder = Float64Builder::new();
+            for value in values {
+                builder.append_option(value.as_f64());
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnKind::Text => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                match value {
+                    RecordValue::Null => builder.append_null(),
+                    RecordValue::String(s) => builder.append_value(s),
+                    RecordValue::Blob(blob) => builder.append_value(String::from_utf8_lossy(blob)),
+                    RecordValue::Bool(b) => builder.append_value(i64::from(*b).to_string()),
+                    value => builder.append_value(value.to_string()),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnKind::Blob => {
+            let mut builder = BinaryBuilder::new();
+            for value in values {
+                match value {
+                    RecordValue::Blob(blob) => builder.append_value(blob),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::{bail, Result};
+
+use super::{
+    error::QueryError,
+    pager::Pager,
+    result::ResultSet,
+    sql::{Condition, CreateVirtualTable, OrderingTerm, SelectOperation, SelectStatement},
+    SqliteReader,
+};
+
+/// Where an FTS5 table keeps the values of its columns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fts5Content {
+    /// The `%_content` shadow table, which stores the columns as `c0`, `c1`, ...
+    /// against an `id` holding the rowid
+    Internal,
+    /// Another table named with `content=`, whose columns go by the same names and
+    /// whose rowid is the column named with `content_rowid=`
+    External {
+        table: String,
+        rowid: Option<String>,
+    },
+    /// `content=''`, where only the full-text index is kept and not the values
+    Contentless,
+}
+
+/// An FTS5 table declared in the schema
+///
+/// The full-text index itself lives in the `%_data` and `%_idx` shadow tables,
+/// which aren't read. Queries against the table are answered from wherever it
+/// keeps its content instead, so `MATCH` isn't supported.
+#[derive(Debug, Clone)]
+pub struct Fts5Table {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub content: Fts5Content,
+}
+
+impl Fts5Table {
+    /// Reads the declaration of an FTS5 table, or `None` for any other module
+    pub fn new(declaration: &CreateVirtualTable) -> Option<Self> {
+        if !declaration.module.eq_ignore_ascii_case("fts5") {
+            return None;
+        }
+
+        let mut columns = Vec::new();
+        let mut content = None;
+        let mut content_rowid = None;
+        for argument in declaration.arguments.iter() {
+            match argument.split_once('=') {
+                Some((option, value)) => {
+                    let value = unquote(value.trim());
+                    match option.trim().to_ascii_lowercase().as_str() {
+                        "content" => content = Some(value),
+                        "content_rowid" => content_rowid = Some(value),
+                        _ => {}
+                    }
+                }
+                // Columns may be followed by UNINDEXED
+                None => {
+                    let name = argument.split_whitespace().next().unwrap_or_default();
+                    columns.push(unquote(name));
+                }
+            }
+        }
+
+        let content = match content {
+            None => Fts5Content::Internal,
+            Some(table) if table.is_empty() => Fts5Content::Contentless,
+            Some(table) => Fts5Content::External {
+                table,
+                rowid: content_rowid,
+            },
+        };
+
+        Some(Self {
+            name: declaration.name.clone(),
+            columns,
+            content,
+        })
+    }
+
+    /// The column of the content table a column of the FTS5 table is read from
+    fn content_column(&self, column: &str) -> Result<String> {
+        // The hidden column named after the table can't be read outside of MATCH
+        if column.eq_ignore_ascii_case("rowid") {
+            return match &self.content {
+                Fts5Content::Internal => Ok("id".to_string()),
+                Fts5Content::External {
+                    rowid: Some(rowid), ..
+                } => Ok(rowid.clone()),
+                _ => bail!("the rowid of fts5 table '{}' can't be read", self.name),
+            };
+        }
+
+        let Some(idx) = self
+            .columns
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(column))
+        else {
+            return Err(QueryError::NoSuchColumn(column.to_string()).into());
+        };
+
+        match &self.content {
+            Fts5Content::Internal => Ok(format!("c{idx}")),
+            _ => Ok(self.columns[idx].clone()),
+        }
+    }
+
+    /// The same query made against the table the content is kept in
+    fn content_statement(&self, statement: &SelectStatement) -> Result<SelectStatement> {
+        let table = match &self.content {
+            Fts5Content::Internal => format!("{}_content", self.name),
+            Fts5Content::External { table, .. } => table.clone(),
+            Fts5Content::Contentless => {
+                bail!(
+                    "fts5 table '{}' is contentless, so its values aren't kept",
+                    self.name
+                )
+            }
+        };
+
+        let operation = match &statement.operation {
+            Some(SelectOperation::Count) => Some(SelectOperation::Count),
+            Some(SelectOperation::Min(column)) => {
+                Some(SelectOperation::Min(self.content_column(column)?))
+            }
+            Some(SelectOperation::Max(column)) => {
+                Some(SelectOperation::Max(self.content_column(column)?))
+            }
+            None => None,
+        };
+
+        let mut where_clause = Vec::with_capacity(statement.where_clause.len());
+        for condition in statement.where_clause.iter() {
+            where_clause.push(Condition {
+                column: self.content_column(&condition.column)?,
+                operator: condition.operator,
+                value: condition.value.clone(),
+                collation: condition.collation.clone(),
+            });
+        }
+
+        let mut order_by = Vec::with_capacity(statement.order_by.len());
+        for term in statement.order_by.iter() {
+            order_by.push(OrderingTerm {
+                column: self.content_column(&term.column)?,
+                order: term.order,
+                collation: term.collation.clone(),
+            });
+        }
+
+        Ok(SelectStatement {
+            with: Vec::new(),
+            values: None,
+            operation,
+            columns: statement
+                .columns
+                .iter()
+                .map(|column| self.content_column(column))
+                .collect::<Result<_>>()?,
+            projection: None,
+            schema: statement.schema.clone(),
+            table,
+            alias: None,
+            join: None,
+            where_clause,
+            row_filters: Vec::new(),
+            in_filters: Vec::new(),
+            column_filters: Vec::new(),
+            exists_filters: Vec::new(),
+            order_by,
+        })
+    }
+}
+
+impl<P: Pager> SqliteReader<P> {
+    /// Answers a query against an FTS5 table from the table holding its content
+    pub fn fts5_select(&self, table: &Fts5Table, statement: &SelectStatement) -> Result<ResultSet> {
+        let mut result = self.execute_select(&table.content_statement(statement)?)?;
+        result.columns = match &statement.operation {
+            Some(SelectOperation::Count) => vec!["count(*)".to_string()],
+            Some(SelectOperation::Min(column)) => vec![format!("min({column})")],
+            Some(SelectOperation::Max(column)) => vec![format!("max({column})")],
+            None => statement.columns.clone(),
+        };
+
+        Ok(result)
+    }
+}
+
+/// Strips the quotes from a quoted option value or column name
+fn unquote(value: &str) -> String {
+    for quote in ['\'', '"', '`'] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            return inner.replace(&format!("{quote}{quote}"), &quote.to_string());
+        }
+    }
+
+    value.to_string()
+}
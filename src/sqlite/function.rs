@@ -0,0 +1,452 @@
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+    affinity::Affinity,
+    cell::RecordValue,
+    collation::Collation,
+    json, printf,
+    sql::{BinaryOperator, Expr},
+    SQLITE_VERSION,
+};
+
+type ScalarFn = dyn Fn(&[RecordValue]) -> Result<RecordValue> + Send + Sync;
+
+type CoreFn = fn(&[RecordValue]) -> Result<RecordValue>;
+
+/// The core functions for handling NULLs
+const CORE_FUNCTIONS: [(&str, CoreFn); 3] = [
+    ("coalesce", coalesce),
+    ("ifnull", ifnull),
+    ("nullif", nullif),
+];
+
+/// The core functions for handling text
+const TEXT_FUNCTIONS: [(&str, CoreFn); 4] = [
+    ("hex", hex),
+    ("length", length),
+    ("lower", lower),
+    ("upper", upper),
+];
+
+/// The current time in UTC, which `DEFAULT CURRENT_TIMESTAMP` and friends call
+const TIME_FUNCTIONS: [(&str, CoreFn); 3] = [
+    ("current_date", current_date),
+    ("current_time", current_time),
+    ("current_timestamp", current_timestamp),
+];
+
+/// Functions describing the library itself
+const LIBRARY_FUNCTIONS: [(&str, CoreFn); 1] = [("sqlite_version", sqlite_version)];
+
+/// Scalar functions queries can call, keyed by lowercase name, starting out with
+/// the built in functions
+static FUNCTIONS: LazyLock<RwLock<HashMap<String, Arc<ScalarFn>>>> = LazyLock::new(|| {
+    let mut functions: HashMap<String, Arc<ScalarFn>> = HashMap::new();
+    let builtins = CORE_FUNCTIONS
+        .into_iter()
+        .chain(TEXT_FUNCTIONS)
+        .chain(TIME_FUNCTIONS)
+        .chain(LIBRARY_FUNCTIONS)
+        .chain(json::FUNCTIONS)
+        .chain(printf::FUNCTIONS);
+    for (name, function) in builtins {
+        functions.insert(name.to_string(), Arc::new(function));
+    }
+
+    RwLock::new(functions)
+});
+
+/// Registers a scalar function that queries can call by `name`, replacing any
+/// previous registration of the name including a built in one
+///
+/// The function is handed the value of each argument and checks for itself that it
+/// was called with the right number of them.
+pub fn register_function(
+    name: &str,
+    function: impl Fn(&[RecordValue]) -> Result<RecordValue> + Send + Sync + 'static,
+) {
+    FUNCTIONS
+        .write()
+        .expect("function registry poisoned")
+        .insert(name.to_ascii_lowercase(), Arc::new(function));
+}
+
+pub fn function(name: &str) -> Option<Arc<ScalarFn>> {
+    FUNCTIONS
+        .read()
+        .expect("function registry poisoned")
+        .get(&name.to_ascii_lowercase())
+        .cloned()
+}
+
+impl Expr {
+    /// Computes the expression for a row, looking up the columns it refers to with
+    /// `column`
+    pub fn evaluate(&self, column: &dyn Fn(&str) -> Result<RecordValue>) -> Result<RecordValue> {
+        match self {
+            Self::Column(name) => column(name),
+            Self::Literal(value) => Ok(value.clone()),
+            Self::Function { name, args } => {
+                let Some(function) = function(name) else {
+                    bail!("no such function: {name}");
+                };
+
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(column))
+                    .collect::<Result<Vec<_>>>()?;
+                function(&args)
+            }
+            Self::Cast { expr, type_name } => {
+                let value = expr.evaluate(column)?;
+                Ok(Affinity::from_declared_type(type_name).cast(&value))
+            }
+            Self::Compare { lhs, operator, rhs } => {
+                let lhs = lhs.evaluate(column)?;
+                let rhs = rhs.evaluate(column)?;
+                if !operator.is_null_safe()
+                    && (lhs == RecordValue::Null || rhs == RecordValue::Null)
+                {
+                    return Ok(RecordValue::Null);
+                }
+
+                Ok(truth_value(Some(operator.test(
+                    &lhs,
+                    &rhs,
+                    &Collation::Binary,
+                ))))
+            }
+            Self::Binary { lhs, operator, rhs } => {
+                let lhs = lhs.evaluate(column)?;
+                let rhs = rhs.evaluate(column)?;
+                if lhs == RecordValue::Null || rhs == RecordValue::Null {
+                    return Ok(RecordValue::Null);
+                }
+
+                binary(*operator, &lhs, &rhs)
+            }
+            Self::And(lhs, rhs) => {
+                let lhs = truth(&lhs.evaluate(column)?);
+                let rhs = truth(&rhs.evaluate(column)?);
+                Ok(truth_value(match (lhs, rhs) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                }))
+            }
+            Self::Or(lhs, rhs) => {
+                let lhs = truth(&lhs.evaluate(column)?);
+                let rhs = truth(&rhs.evaluate(column)?);
+                Ok(truth_value(match (lhs, rhs) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                }))
+            }
+            Self::Not(expr) => Ok(truth_value(truth(&expr.evaluate(column)?).map(|b| !b))),
+            Self::IsNull { expr, negated } => {
+                let is_null = expr.evaluate(column)? == RecordValue::Null;
+                Ok(truth_value(Some(is_null != *negated)))
+            }
+            Self::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = expr.evaluate(column)?;
+                if value == RecordValue::Null {
+                    return Ok(RecordValue::Null);
+                }
+
+                // Not finding the value among NULLs leaves it unknown rather than false
+                let mut found = Some(false);
+                for item in list.iter() {
+                    match value.compare(&item.evaluate(column)?, &Collation::Binary) {
+                        Some(Ordering::Equal) => {
+                            found = Some(true);
+                            break;
+                        }
+                        Some(_) => {}
+                        None => found = None,
+                    }
+                }
+
+                Ok(truth_value(found.map(|found| found != *negated)))
+            }
+        }
+    }
+
+    /// Fails for the first function called that isn't registered, so a query can be
+    /// rejected before any rows are read
+    pub fn check_functions(&self) -> Result<()> {
+        match self {
+            Self::Function { name, args } => {
+                if function(name).is_none() {
+                    bail!("no such function: {name}");
+                }
+
+                for arg in args.iter() {
+                    arg.check_functions()?;
+                }
+            }
+            Self::Cast { expr, .. } | Self::Not(expr) | Self::IsNull { expr, .. } => {
+                expr.check_functions()?
+            }
+            Self::Compare { lhs, rhs, .. }
+            | Self::Binary { lhs, rhs, .. }
+            | Self::And(lhs, rhs)
+            | Self::Or(lhs, rhs) => {
+                lhs.check_functions()?;
+                rhs.check_functions()?;
+            }
+            Self::InList { expr, list, .. } => {
+                expr.check_functions()?;
+                for item in list.iter() {
+                    item.check_functions()?;
+                }
+            }
+            Self::Column(_) | Self::Literal(_) => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a value counts as true, the way SQLite reads it as a number, or `None`
+/// for NULL
+pub fn truth(value: &RecordValue) -> Option<bool> {
+    match value {
+        RecordValue::Null => None,
+        value => Affinity::Numeric
+            .cast(value)
+            .as_f64()
+            .map(|number| number != 0.0),
+    }
+}
+
+/// The 1, 0 or NULL a boolean expression evaluates to
+/// Applies an operator to two values that aren't NULL, reading text as the number
+/// it starts with for arithmetic
+///
+/// Integers stay integers unless the result overflows, and dividing by zero is NULL.
+fn binary(operator: BinaryOperator, lhs: &RecordValue, rhs: &RecordValue) -> Result<RecordValue> {
+    match operator {
+        BinaryOperator::Concat => {
+            let text = |value| match Affinity::Text.cast(value) {
+                RecordValue::String(text) => text,
+                _ => unreachable!("a cast to text is text"),
+            };
+            return Ok(RecordValue::String(text(lhs) + &text(rhs)));
+        }
+        BinaryOperator::ExtractJson => return json::extract_json(lhs, rhs),
+        BinaryOperator::ExtractValue => return json::extract_value(lhs, rhs),
+        _ => {}
+    }
+
+    let lhs = Affinity::Numeric.cast(lhs);
+    let rhs = Affinity::Numeric.cast(rhs);
+    if let (Some(lhs), Some(rhs)) = (lhs.as_i64(), rhs.as_i64()) {
+        let integer = match operator {
+            BinaryOperator::Add => lhs.checked_add(rhs),
+            BinaryOperator::Subtract => lhs.checked_sub(rhs),
+            BinaryOperator::Multiply => lhs.checked_mul(rhs),
+            BinaryOperator::Divide | BinaryOperator::Remainder if rhs == 0 => {
+                return Ok(RecordValue::Null)
+            }
+            BinaryOperator::Divide => lhs.checked_div(rhs),
+            BinaryOperator::Remainder => Some(lhs.checked_rem(rhs).unwrap_or(0)),
+            BinaryOperator::Concat | BinaryOperator::ExtractJson | BinaryOperator::ExtractValue => {
+                unreachable!("text operators are handled above")
+            }
+        };
+
+        if let Some(integer) = integer {
+            return Ok(RecordValue::I64(integer));
+        }
+    }
+
+    let (Some(lhs), Some(rhs)) = (lhs.as_f64(), rhs.as_f64()) else {
+        return Ok(RecordValue::Null);
+    };
+    let real = match operator {
+        BinaryOperator::Add => lhs + rhs,
+        BinaryOperator::Subtract => lhs - rhs,
+        BinaryOperator::Multiply => lhs * rhs,
+        BinaryOperator::Divide if rhs == 0.0 => return Ok(RecordValue::Null),
+        BinaryOperator::Divide => lhs / rhs,
+        // Like SQLite, the remainder of reals is that of their integer parts
+        BinaryOperator::Remainder => match (lhs as i64, rhs as i64) {
+            (_, 0) => return Ok(RecordValue::Null),
+            (lhs, rhs) => lhs.checked_rem(rhs).unwrap_or(0) as f64,
+        },
+        BinaryOperator::Concat | BinaryOperator::ExtractJson | BinaryOperator::ExtractValue => {
+            unreachable!("text operators are handled above")
+        }
+    };
+
+    Ok(RecordValue::F64(real))
+}
+
+fn truth_value(value: Option<bool>) -> RecordValue {
+    value.map_or(RecordValue::Null, |value| {
+        RecordValue::I64(i64::from(value))
+    })
+}
+
+/// `coalesce(X, Y, ...)` - the first argument that isn't NULL
+fn coalesce(args: &[RecordValue]) -> Result<RecordValue> {
+    if args.len() < 2 {
+        bail!("wrong number of arguments to function coalesce()");
+    }
+
+    Ok(first_not_null(args))
+}
+
+/// `ifnull(X, Y)` - X, or Y when X is NULL
+fn ifnull(args: &[RecordValue]) -> Result<RecordValue> {
+    if args.len() != 2 {
+        bail!("wrong number of arguments to function ifnull()");
+    }
+
+    Ok(first_not_null(args))
+}
+
+/// `nullif(X, Y)` - X, or NULL when X and Y are equal
+fn nullif(args: &[RecordValue]) -> Result<RecordValue> {
+    let [lhs, rhs] = args else {
+        bail!("wrong number of arguments to function nullif()");
+    };
+
+    Ok(match lhs.compare(rhs, &Collation::Binary) {
+        Some(Ordering::Equal) => RecordValue::Null,
+        _ => lhs.clone(),
+    })
+}
+
+fn first_not_null(args: &[RecordValue]) -> RecordValue {
+    args.iter()
+        .find(|value| **value != RecordValue::Null)
+        .cloned()
+        .unwrap_or(RecordValue::Null)
+}
+
+/// `length(X)` - the characters in text, or the bytes in a blob
+fn length(args: &[RecordValue]) -> Result<RecordValue> {
+    let [value] = args else {
+        bail!("wrong number of arguments to function length()");
+    };
+
+    Ok(match value {
+        RecordValue::Null => RecordValue::Null,
+        RecordValue::Blob(blob) => RecordValue::I64(blob.len() as i64),
+        RecordValue::String(text) => RecordValue::I64(text.chars().count() as i64),
+        number => RecordValue::I64(number.to_string().chars().count() as i64),
+    })
+}
+
+/// `hex(X)` - the bytes of a blob, or of any other value as text, in uppercase hex
+/// digits, with nothing at all for NULL
+fn hex(args: &[RecordValue]) -> Result<RecordValue> {
+    let [value] = args else {
+        bail!("wrong number of arguments to function hex()");
+    };
+
+    let bytes = match value {
+        RecordValue::Null => Vec::new(),
+        RecordValue::Blob(blob) => blob.clone(),
+        value => match Affinity::Text.cast(value) {
+            RecordValue::String(text) => text.into_bytes(),
+            other => other.to_string().into_bytes(),
+        },
+    };
+    let hex = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+    Ok(RecordValue::String(hex))
+}
+
+/// `lower(X)` - text with its ASCII letters in lowercase, as SQLite does without ICU
+fn lower(args: &[RecordValue]) -> Result<RecordValue> {
+    change_case("lower", args, str::to_ascii_lowercase)
+}
+
+/// `upper(X)` - text with its ASCII letters in uppercase
+fn upper(args: &[RecordValue]) -> Result<RecordValue> {
+    change_case("upper", args, str::to_ascii_uppercase)
+}
+
+fn change_case(
+    name: &str,
+    args: &[RecordValue],
+    change: fn(&str) -> String,
+) -> Result<RecordValue> {
+    let [value] = args else {
+        bail!("wrong number of arguments to function {name}()");
+    };
+
+    Ok(match Affinity::Text.cast(value) {
+        RecordValue::String(text) => RecordValue::String(change(&text)),
+        other => other,
+    })
+}
+
+/// `sqlite_version()` - the release of SQLite whose behaviour is followed
+fn sqlite_version(args: &[RecordValue]) -> Result<RecordValue> {
+    if !args.is_empty() {
+        bail!("wrong number of arguments to function sqlite_version()");
+    }
+
+    Ok(RecordValue::String(SQLITE_VERSION.to_string()))
+}
+
+/// `CURRENT_DATE` - today as `YYYY-MM-DD`
+fn current_date(args: &[RecordValue]) -> Result<RecordValue> {
+    let (date, _) = now("current_date", args)?;
+    Ok(RecordValue::String(date))
+}
+
+/// `CURRENT_TIME` - the time of day as `HH:MM:SS`
+fn current_time(args: &[RecordValue]) -> Result<RecordValue> {
+    let (_, time) = now("current_time", args)?;
+    Ok(RecordValue::String(time))
+}
+
+/// `CURRENT_TIMESTAMP` - the date and time as `YYYY-MM-DD HH:MM:SS`
+fn current_timestamp(args: &[RecordValue]) -> Result<RecordValue> {
+    let (date, time) = now("current_timestamp", args)?;
+    Ok(RecordValue::String(format!("{date} {time}")))
+}
+
+/// The current UTC date and time of day, formatted the way SQLite formats them
+fn now(name: &str, args: &[RecordValue]) -> Result<(String, String)> {
+    if !args.is_empty() {
+        bail!("wrong number of arguments to function {name}()");
+    }
+
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let (days, seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+    // Days since 1970-01-01 to a civil date, counting in 400 year eras from 0000-03-01
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    Ok((
+        format!("{year:04}-{month:02}-{day:02}"),
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+    ))
+}
@@ -0,0 +1,492 @@
+use anyhow::{bail, Result};
+use std::fmt::{self, Write};
+use std::io;
+
+use super::{
+    cell::{self, DatabaseCell, RecordValue},
+    cursor, page,
+    pager::Pager,
+    SqliteReader,
+};
+
+impl<P: Pager> SqliteReader<P> {
+    /// Prints the header, cell pointer array, freeblock chain and a summary of each
+    /// cell of page `page_no`, counting pages from 1
+    pub fn page_info(&self, page_no: usize, out: &mut dyn io::Write) -> Result<()> {
+        if page_no == 0 || page_no > self.page_count() {
+            bail!("page {page_no} is out of range 1-{}", self.page_count());
+        }
+
+        let idx = page_no - 1;
+        let buf = self.page_bytes(idx)?;
+        let header = self.page_header(idx)?;
+        let page = self.page(idx)?;
+
+        writeln!(out, "page {page_no}: {}", header.page_type)?;
+        writeln!(out, "first freeblock: {}", header.first_freeblock_offset)?;
+        writeln!(out, "number of cells: {}", header.total_cells)?;
+        writeln!(out, "cell content area: {}", page::cell_content_area(buf))?;
+        writeln!(
+            out,
+            "fragmented free bytes: {}",
+            header.fragmented_free_bytes
+        )?;
+        if let Some(rp) = header.rightmost_pointer {
+            writeln!(out, "right-most pointer: {}", rp + 1)?;
+        }
+
+        let pointers = page::cell_pointers(buf, &header)?;
+        let pointer_list: Vec<String> = pointers.iter().map(|p| p.to_string()).collect();
+        writeln!(out, "cell pointers: {}", pointer_list.join(" "))?;
+
+        let freeblocks: Vec<String> = page::freeblocks(buf, &header, idx)
+            .into_iter()
+            .map(|freeblock| format!("{} ({} bytes)", freeblock.offset, freeblock.size))
+            .collect();
+        if freeblocks.is_empty() {
+            writeln!(out, "freeblocks: none")?;
+        } else {
+            writeln!(out, "freeblocks: {}", freeblocks.join(", "))?;
+        }
+
+        for (i, (offset, cell)) in pointers.iter().zip(page.cells.iter()).enumerate() {
+            writeln!(out, "cell {i} at {offset}: {}", cell_summary(cell)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Prints the bytes of cell `cell_no` of page `page_no`, counting cells from 0,
+    /// with what each run of them encodes
+    ///
+    /// Each row gives the offset in the page of a field, its bytes in hex and the
+    /// varint, serial type or value it holds. Bytes on overflow pages have no offset.
+    pub fn cell_hex(&self, page_no: usize, cell_no: usize, out: &mut dyn io::Write) -> Result<()> {
+        if page_no == 0 || page_no > self.page_count() {
+            bail!("page {page_no} is out of range 1-{}", self.page_count());
+        }
+
+        let idx = page_no - 1;
+        let buf = self.page_bytes(idx)?;
+        let header = self.page_header(idx)?;
+        let pointers = page::cell_pointers(buf, &header)?;
+        let Some(&pointer) = pointers.get(cell_no) else {
+            bail!("page {page_no} has {} cells", pointers.len());
+        };
+        let Some(cell) = page::page_offset(pointer, idx).and_then(|offset| buf.get(offset..))
+        else {
+            bail!("cell {cell_no} at {pointer} is outside the page");
+        };
+
+        writeln!(
+            out,
+            "page {page_no} cell {cell_no} at {pointer}: {}",
+            header.page_type
+        )?;
+        for field in cell::cell_fields(cell, header.page_type, self.overflow())? {
+            let offset = field.offset.map(|offset| pointer + offset);
+            let mut rows = field.bytes.chunks(HEX_ROW_BYTES);
+            let first = rows.next().unwrap_or_default();
+            let offset = offset.map_or("-".to_string(), |offset| offset.to_string());
+            writeln!(
+                out,
+                "{offset:>5}  {:<width$}  {}",
+                hex(first),
+                field.description,
+                width = HEX_ROW_BYTES * 3 - 1
+            )?;
+            for row in rows {
+                writeln!(out, "{:>5}  {}", "", hex(row))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints the b-tree of a table or index as an indented outline of its pages, or
+    /// as a Graphviz digraph when `dot` is set
+    ///
+    /// Interior pages list their children with the divider keys between them, and
+    /// leaves show how many cells they hold.
+    pub fn btree(&self, name: &str, dot: bool, out: &mut dyn io::Write) -> Result<()> {
+        let schema = self.schema()?;
+        let Some(entry) = schema.fetch_table(name) else {
+            bail!("no such table or index '{name}'");
+        };
+
+        if entry.is_virtual() {
+            bail!("'{name}' is a virtual table, which has no b-tree");
+        }
+
+        let root = entry.root_page as usize;
+        let mut output = String::new();
+        if dot {
+            writeln!(output, "digraph \"{name}\" {{")?;
+            writeln!(output, "    node [shape=record];")?;
+            self.btree_dot(root, root, 0, &mut output)?;
+            writeln!(output, "}}")?;
+        } else {
+            self.btree_outline(root, root, 0, &mut output)?;
+        }
+
+        out.write_all(output.as_bytes())?;
+        Ok(())
+    }
+
+    /// Prints how many pages each table and index takes up along with how many bytes
+    /// of them hold records and how many are unused, like the `dbstat` virtual table
+    pub fn dbstat(&self, out: &mut dyn io::Write) -> Result<()> {
+        let schema = self.schema()?;
+        let mut entries = vec![("sqlite_schema", 0)];
+        entries.extend(
+            schema
+                .entries()
+                .filter(|entry| !entry.is_virtual())
+                .map(|entry| (entry.name.as_str(), entry.root_page as usize)),
+        );
+
+        writeln!(out, "name|pages|payload|unused|overflow pages")?;
+        for (name, root) in entries {
+            let usage = self.space_usage(root)?;
+            writeln!(
+                out,
+                "{name}|{}|{}|{}|{}",
+                usage.pages, usage.payload, usage.unused, usage.overflow_pages
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Prints the release of SQLite that last wrote the database, along with what its
+    /// schema format and file format versions mean
+    pub fn version(&self, out: &mut dyn io::Write) -> Result<()> {
+        let header = &self.database_header;
+        writeln!(
+            out,
+            "last written by: SQLite {}",
+            release(header.sqlite_version_number)
+        )?;
+
+        let schema_format = match header.schema_format_number {
+            1 => "the original format",
+            2 => "rows may have fewer columns than their table",
+            3 => "added columns may have non-NULL defaults",
+            4 => "descending indexes and 0 and 1 stored without a body",
+            _ => "unknown",
+        };
+        writeln!(
+            out,
+            "schema format: {} ({schema_format})",
+            header.schema_format_number
+        )?;
+
+        for (name, version) in [
+            ("write", header.write_version),
+            ("read", header.read_version),
+        ] {
+            let journal = match version {
+                1 => "rollback journal",
+                2 => "write-ahead log",
+                _ => "unknown",
+            };
+            writeln!(out, "{name} version: {version} ({journal})")?;
+        }
+
+        Ok(())
+    }
+
+    /// Prints each way the database header disagrees with itself or with the file, or
+    /// `ok` when it doesn't
+    pub fn verify_header(&self, out: &mut dyn io::Write) -> Result<()> {
+        let discrepancies = self.header_discrepancies();
+        if discrepancies.is_empty() {
+            writeln!(out, "ok")?;
+        }
+        for discrepancy in discrepancies {
+            writeln!(out, "{discrepancy}")?;
+        }
+
+        Ok(())
+    }
+
+    /// The ways the fields of the database header disagree with each other or with the
+    /// pages of the file
+    pub fn header_discrepancies(&self) -> Vec<HeaderDiscrepancy> {
+        let header = &self.database_header;
+        let mut discrepancies = Vec::new();
+
+        // The header stores a 65536 byte page size as 1
+        let page_size = header.page_size;
+        if page_size != 1 && (page_size < 512 || !page_size.is_power_of_two()) {
+            discrepancies.push(HeaderDiscrepancy::InvalidPageSize(page_size));
+        }
+
+        // SQLite only trusts the size in the header when the version that wrote it
+        // also bumped the change counter, and otherwise goes by the file's length
+        if header.file_change_counter != header.version_valid_for_number {
+            discrepancies.push(HeaderDiscrepancy::StaleDatabaseSize {
+                change_counter: header.file_change_counter,
+                valid_for: header.version_valid_for_number,
+            });
+        } else if header.in_header_database_size as usize != self.page_count() {
+            discrepancies.push(HeaderDiscrepancy::DatabaseSize {
+                header: header.in_header_database_size,
+                file: self.page_count(),
+            });
+        }
+
+        discrepancies
+    }
+
+    /// Sums up the space used by the b-tree rooted at `root` and its overflow pages
+    pub fn space_usage(&self, root: usize) -> Result<SpaceUsage> {
+        let overflow = self.overflow();
+        let overflow_capacity = overflow.usable_size() - 4;
+
+        let mut usage = SpaceUsage::default();
+        for page_idx in self.btree_pages(root)? {
+            let buf = self.page_bytes(page_idx)?;
+            let header = self.page_header(page_idx)?;
+            usage.pages += 1;
+            usage.unused += page::unused_bytes(buf, &header, page_idx);
+
+            for cell in self.page(page_idx)?.cells.iter() {
+                usage.payload += cell.payload_size();
+
+                let spilled = overflow.spilled_bytes(cell);
+                let overflow_pages = spilled.div_ceil(overflow_capacity);
+                usage.pages += overflow_pages;
+                usage.overflow_pages += overflow_pages;
+                usage.unused += overflow_pages * overflow_capacity - spilled;
+            }
+        }
+
+        Ok(usage)
+    }
+
+    fn btree_outline(
+        &self,
+        root: usize,
+        page_idx: usize,
+        depth: usize,
+        output: &mut String,
+    ) -> Result<()> {
+        cursor::check_depth(root, depth)?;
+        let indent = "  ".repeat(depth);
+        let page = self.page(page_idx)?;
+        if page.right_page_pointer().is_none() {
+            let cells = page.cells.len();
+            writeln!(output, "{indent}page {}: leaf, {cells} cells", page_idx + 1)?;
+            return Ok(());
+        }
+
+        let children = page.cells.len() + 1;
+        writeln!(
+            output,
+            "{indent}page {}: interior, {children} children",
+            page_idx + 1
+        )?;
+        for cell in page.cells.iter() {
+            let (left_child, divider) = divider(cell);
+            self.btree_outline(root, left_child as usize, depth + 1, output)?;
+            writeln!(output, "{indent}  {divider}")?;
+        }
+
+        if let Some(rp) = page.right_page_pointer() {
+            self.btree_outline(root, rp as usize, depth + 1, output)?;
+        }
+
+        Ok(())
+    }
+
+    fn btree_dot(
+        &self,
+        root: usize,
+        page_idx: usize,
+        depth: usize,
+        output: &mut String,
+    ) -> Result<()> {
+        cursor::check_depth(root, depth)?;
+        let page_no = page_idx + 1;
+        let page = self.page(page_idx)?;
+        if page.right_page_pointer().is_none() {
+            let cells = page.cells.len();
+            writeln!(
+                output,
+                "    p{page_no} [label=\"page {page_no}|{cells} cells\"];"
+            )?;
+            return Ok(());
+        }
+
+        let mut label = format!("page {page_no}");
+        let mut children = Vec::new();
+        for cell in page.cells.iter() {
+            let (left_child, divider) = divider(cell);
+            write!(label, "|{}", escape_record_label(&divider))?;
+            children.push(left_child);
+        }
+        children.extend(page.right_page_pointer());
+
+        writeln!(output, "    p{page_no} [label=\"{label}\"];")?;
+        for child in children {
+            writeln!(output, "    p{page_no} -> p{};", child + 1)?;
+            self.btree_dot(root, child as usize, depth + 1, output)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Space taken up by the pages of one table or index
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SpaceUsage {
+    /// B-tree and overflow pages
+    pub pages: usize,
+    pub overflow_pages: usize,
+    /// Bytes of records stored, wherever they are stored
+    pub payload: u64,
+    pub unused: usize,
+}
+
+/// A way the database header disagrees with itself or with the file it heads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderDiscrepancy {
+    /// Neither a power of two from 512 to 32768 nor the 1 standing for 65536
+    InvalidPageSize(u16),
+    /// The change counter moved on without the version-valid-for number, so a version
+    /// of SQLite too old to keep the database size up to date last wrote the file
+    StaleDatabaseSize { change_counter: u32, valid_for: u32 },
+    /// The header's database size in pages differs from the pages in the file
+    DatabaseSize { header: u32, file: usize },
+}
+
+impl fmt::Display for HeaderDiscrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPageSize(size) => {
+                write!(f, "page size {size} isn't a power of two from 512 to 65536")
+            }
+            Self::StaleDatabaseSize {
+                change_counter,
+                valid_for,
+            } => write!(
+                f,
+                "file change counter {change_counter} differs from version-valid-for \
+                 number {valid_for}, so the database size in the header is stale"
+            ),
+            Self::DatabaseSize { header, file } => write!(
+                f,
+                "header gives the database size as {header} pages but the file has {file}"
+            ),
+        }
+    }
+}
+
+/// Bytes of a cell shown on each row of `.cellhex`
+const HEX_ROW_BYTES: usize = 8;
+
+/// Bytes written in hex separated by spaces
+fn hex(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    hex.join(" ")
+}
+
+/// The release a version number such as 3045001 stands for, 3.45.1 in that case
+pub fn release(version_number: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        version_number / 1_000_000,
+        version_number / 1000 % 1000,
+        version_number % 1000
+    )
+}
+
+/// The left child of an interior cell and a description of its divider key
+fn divider(cell: &DatabaseCell) -> (u32, String) {
+    match cell {
+        DatabaseCell::InteriorTable(table_cell) => (
+            table_cell.left_child,
+            format!("rowid {}", table_cell.row_id),
+        ),
+        DatabaseCell::InteriorIndex(index_cell) => (
+            index_cell.left_child,
+            format!(
+                "key {}, rowid {}",
+                format_key(&index_cell.columns),
+                index_cell.row_id
+            ),
+        ),
+        other => panic!("expected interior cell - found {other:#?}"),
+    }
+}
+
+/// Escapes the characters Graphviz treats specially in record labels
+fn escape_record_label(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '|' | '{' | '}' | '<' | '>' | '"' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+fn cell_summary(cell: &DatabaseCell) -> Result<String> {
+    let mut summary = String::new();
+    let (payload_size, overflow_page) = match cell {
+        DatabaseCell::Leaf(leaf) => {
+            write!(summary, "table leaf, rowid {}", leaf.row_id)?;
+            (leaf.payload_size, leaf.overflow_page)
+        }
+        DatabaseCell::InteriorTable(table_cell) => {
+            write!(
+                summary,
+                "interior table, left child {}, rowid {}",
+                table_cell.left_child + 1,
+                table_cell.row_id
+            )?;
+            return Ok(summary);
+        }
+        DatabaseCell::IndexLeaf(leaf) => {
+            write!(
+                summary,
+                "index leaf, key {}, rowid {}",
+                format_key(&leaf.columns),
+                leaf.row_id
+            )?;
+            (leaf.payload_size, leaf.overflow_page)
+        }
+        DatabaseCell::InteriorIndex(index_cell) => {
+            write!(
+                summary,
+                "interior index, left child {}, key {}, rowid {}",
+                index_cell.left_child + 1,
+                format_key(&index_cell.columns),
+                index_cell.row_id
+            )?;
+            (index_cell.payload_size, index_cell.overflow_page)
+        }
+    };
+
+    write!(summary, ", payload {payload_size} bytes")?;
+    if let Some(overflow_page) = overflow_page {
+        write!(summary, ", overflow page {overflow_page}")?;
+    }
+
+    Ok(summary)
+}
+
+/// Longest key shown in a cell summary before it is cut short
+const MAX_KEY_CHARS: usize = 40;
+
+fn format_key(columns: &[RecordValue]) -> String {
+    let columns: Vec<String> = columns.iter().map(|value| value.to_string()).collect();
+    let key = columns.join("|");
+    match key.char_indices().nth(MAX_KEY_CHARS) {
+        Some((end, _)) => format!("{}...", &key[..end]),
+        None => key,
+    }
+}
@@ -0,0 +1,122 @@
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use thiserror::Error;
+
+/// The error a query stopped by an [`InterruptHandle`] fails with
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("interrupted")]
+pub struct Interrupted;
+
+/// Called back every so many steps of a query, breaking to interrupt it
+pub type ProgressHandler = Box<dyn FnMut() -> ControlFlow<()> + Send>;
+
+/// Stops the queries of a database partway through, from any thread
+///
+/// Cursors check the handle each time they move, so a query ends with
+/// [`Interrupted`] at its next row rather than straight away. Clones share the
+/// same flag and progress handler.
+#[derive(Clone, Default)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+    progress: Arc<Progress>,
+}
+
+impl std::fmt::Debug for InterruptHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InterruptHandle")
+            .field("interrupted", &self.is_interrupted())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The progress handler and how often to call it, counting the steps since it was
+/// set
+#[derive(Default)]
+struct Progress {
+    /// Steps between calls, with 0 meaning there is no handler
+    every: AtomicU64,
+    steps: AtomicU64,
+    handler: Mutex<Option<ProgressHandler>>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Relaxed)
+    }
+
+    /// Clears an interrupt so the next query can run
+    pub fn reset(&self) {
+        self.interrupted.store(false, Ordering::Relaxed);
+    }
+
+    /// Calls `handler` every `steps` cursor steps while a query runs, the way
+    /// `sqlite3_progress_handler` does, interrupting the query when it breaks
+    ///
+    /// A GUI can use this to keep responding while a long query runs and to cancel
+    /// it. Setting `steps` to 0 removes the handler.
+    pub fn set_progress_handler(
+        &self,
+        steps: u64,
+        handler: impl FnMut() -> ControlFlow<()> + Send + 'static,
+    ) {
+        let mut current = self
+            .progress
+            .handler
+            .lock()
+            .expect("progress lock poisoned");
+        *current = (steps > 0).then(|| Box::new(handler) as ProgressHandler);
+        self.progress.steps.store(0, Ordering::Relaxed);
+        self.progress.every.store(steps, Ordering::Relaxed);
+    }
+
+    /// Counts a step of a query, calling the progress handler when it's due, and
+    /// fails with [`Interrupted`] once the handle has been interrupted
+    pub fn check(&self) -> Result<(), Interrupted> {
+        let every = self.progress.every.load(Ordering::Relaxed);
+        if every > 0 && (self.progress.steps.fetch_add(1, Ordering::Relaxed) + 1) % every == 0 {
+            let mut handler = self
+                .progress
+                .handler
+                .lock()
+                .expect("progress lock poisoned");
+            if let Some(handler) = handler.as_mut() {
+                if handler().is_break() {
+                    self.interrupt();
+                }
+            }
+        }
+
+        if self.is_interrupted() {
+            return Err(Interrupted);
+        }
+
+        Ok(())
+    }
+
+    /// Interrupts after `timeout` unless the returned guard is dropped first
+    pub fn interrupt_after(&self, timeout: Duration) -> Deadline {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        let handle = self.clone();
+        thread::spawn(move || {
+            // Dropping the guard disconnects the channel, ending the wait early
+            if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(timeout) {
+                handle.interrupt();
+            }
+        });
+
+        Deadline { _cancel: cancel }
+    }
+}
+
+/// A pending [`InterruptHandle::interrupt_after`], called off when dropped
+pub struct Deadline {
+    _cancel: Sender<()>,
+}
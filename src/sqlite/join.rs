@@ -0,0 +1,308 @@
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::{
+    affinity::Affinity,
+    cell::RecordValue,
+    collation::Collation,
+    error::QueryError,
+    membership::HashKey,
+    pager::Pager,
+    result::ResultSet,
+    sql::{self, ColumnDefinition, CreateTable, Join, SelectStatement, SortOrder},
+    SqliteReader,
+};
+
+/// One of the two tables of a join
+pub struct JoinSide {
+    /// Name its columns are qualified with, the alias or else the table's own
+    pub name: String,
+    /// Reads the columns the join needs from the table, the ON column first, keeping
+    /// only the rows passing the WHERE comparisons on this table alone
+    pub statement: SelectStatement,
+    table_schema: CreateTable,
+}
+
+impl JoinSide {
+    /// The declared definition of a column the side reads
+    fn column(&self, name: &str) -> Option<&ColumnDefinition> {
+        self.table_schema
+            .columns
+            .iter()
+            .find(|column| sql::same_name(&column.name, name))
+    }
+
+    /// Adds a column to those the side reads, returning where it is in each row
+    fn read(&mut self, name: &str) -> usize {
+        let columns = &mut self.statement.columns;
+        match columns
+            .iter()
+            .position(|column| sql::same_name(column, name))
+        {
+            Some(position) => position,
+            None => {
+                columns.push(name.to_string());
+                columns.len() - 1
+            }
+        }
+    }
+}
+
+/// How the rows of a join are put together from the rows read from each table
+pub struct JoinPlan {
+    pub sides: [JoinSide; 2],
+    /// Side and position in its rows of each column the query refers to, the
+    /// selected columns followed by any only sorted by
+    sources: Vec<(usize, usize)>,
+    /// Collation the ON columns are compared with
+    collation: Collation,
+    /// Affinity the ON column of each side is converted to before comparing
+    affinities: [Option<Affinity>; 2],
+}
+
+impl JoinPlan {
+    /// The table and declared definition of a column the joined query refers to
+    pub fn column(&self, reference: &str) -> Option<(&str, &ColumnDefinition)> {
+        let (side, name) = resolve(&self.sides, reference).ok()?;
+        let side = &self.sides[side];
+        Some((&side.statement.table, side.column(&name)?))
+    }
+}
+
+impl<P: Pager> SqliteReader<P> {
+    /// Splits a query over two joined tables into a query over each, pushing down the
+    /// WHERE comparisons that only need one of them
+    pub fn plan_join(&self, statement: &SelectStatement, join: &Join) -> Result<JoinPlan> {
+        let same_database = match (&statement.schema, &join.schema) {
+            (_, None) => true,
+            (Some(lhs), Some(rhs)) => sql::same_name(lhs, rhs),
+            (None, Some(_)) => false,
+        };
+        if !same_database {
+            bail!("the tables of a join must be in the same database");
+        }
+
+        let schema = self.schema()?;
+        let side = |schema_name: &Option<String>, table: &str, alias: &Option<String>| {
+            if schema_name.is_none() && self.common_table(table).is_some() {
+                bail!("joins can't read the WITH table {table} yet");
+            }
+            let Some(found) = schema.fetch_table(table) else {
+                return Err(QueryError::NoSuchTable(table.to_string()).into());
+            };
+            if !found.is_table() || found.virtual_table().is_some() {
+                bail!("'{table}' isn't a table with rowids");
+            }
+
+            Ok(JoinSide {
+                name: alias.clone().unwrap_or_else(|| table.to_string()),
+                statement: SelectStatement {
+                    with: Vec::new(),
+                    values: None,
+                    operation: None,
+                    columns: Vec::new(),
+                    projection: None,
+                    schema: schema_name.clone(),
+                    table: found.name.clone(),
+                    alias: None,
+                    join: None,
+                    where_clause: Vec::new(),
+                    row_filters: Vec::new(),
+                    in_filters: Vec::new(),
+                    column_filters: Vec::new(),
+                    exists_filters: Vec::new(),
+                    order_by: Vec::new(),
+                },
+                table_schema: found.columns(),
+            })
+        };
+        let mut sides = [
+            side(&statement.schema, &statement.table, &statement.alias)?,
+            side(&join.schema, &join.table, &join.alias)?,
+        ];
+        if sql::same_name(&sides[0].name, &sides[1].name) {
+            bail!("ambiguous table name: {}", sides[0].name);
+        }
+
+        // Each side reads its ON column first
+        let (lhs, lhs_column) = resolve(&sides, &join.on.0)?;
+        let (rhs, rhs_column) = resolve(&sides, &join.on.1)?;
+        if lhs == rhs {
+            bail!("a join has to compare a column of each table");
+        }
+        sides[lhs].read(&lhs_column);
+        sides[rhs].read(&rhs_column);
+
+        let lhs_definition = sides[lhs].column(&lhs_column).expect("resolved");
+        let rhs_definition = sides[rhs].column(&rhs_column).expect("resolved");
+        let collation = match &lhs_definition.collation {
+            Collation::Binary => rhs_definition.collation.clone(),
+            collation => collation.clone(),
+        };
+        let mut affinities = [None, None];
+        affinities[lhs] = lhs_definition
+            .affinity()
+            .compared_with(rhs_definition.affinity());
+        affinities[rhs] = rhs_definition
+            .affinity()
+            .compared_with(lhs_definition.affinity());
+
+        for condition in statement.where_clause.iter() {
+            let (side, column) = resolve(&sides, &condition.column)?;
+            let mut condition = condition.clone();
+            condition.column = column;
+            sides[side].statement.where_clause.push(condition);
+        }
+
+        let referenced = statement
+            .columns
+            .iter()
+            .chain(statement.order_by.iter().map(|term| &term.column));
+        let mut sources = Vec::new();
+        for reference in referenced {
+            let (side, column) = resolve(&sides, reference)?;
+            sources.push((side, sides[side].read(&column)));
+        }
+
+        Ok(JoinPlan {
+            sides,
+            sources,
+            collation,
+            affinities,
+        })
+    }
+
+    /// The rows of a query over two joined tables, with the columns it selects
+    ///
+    /// A hash table is built over the ON column of whichever table has fewer rows
+    /// passing its WHERE comparisons, then probed with each row of the other, so each
+    /// row is read once rather than once for every row of the other table.
+    pub fn join_rows(&self, statement: &SelectStatement, join: &Join) -> Result<ResultSet> {
+        let plan = self.plan_join(statement, join)?;
+        let [lhs, rhs] = &plan.sides;
+        let rows = [
+            self.execute_select(&lhs.statement)?.rows,
+            self.execute_select(&rhs.statement)?.rows,
+        ];
+
+        let key = |side: usize, row: &[RecordValue]| match plan.affinities[side] {
+            Some(affinity) => affinity.apply(&row[0]),
+            None => row[0].clone(),
+        };
+        let (build, probe) = match rows[0].len() <= rows[1].len() {
+            true => (0, 1),
+            false => (1, 0),
+        };
+
+        let mut pairs = Vec::new();
+        let mut pair = |built: usize, probed: usize| match build {
+            0 => pairs.push((built, probed)),
+            _ => pairs.push((probed, built)),
+        };
+        if let Collation::Custom(_) = plan.collation {
+            // A registered collation has no key to hash by, so every pair is compared
+            for (i, probed) in rows[probe].iter().enumerate() {
+                let probed = key(probe, probed);
+                for (j, built) in rows[build].iter().enumerate() {
+                    let equal = key(build, built).compare(&probed, &plan.collation);
+                    if equal == Some(Ordering::Equal) {
+                        pair(j, i);
+                    }
+                }
+            }
+        } else {
+            let mut table: HashMap<HashKey, Vec<usize>> = HashMap::new();
+            for (i, row) in rows[build].iter().enumerate() {
+                if let Some(hash_key) = HashKey::new(&key(build, row), &plan.collation) {
+                    table.entry(hash_key).or_default().push(i);
+                }
+            }
+
+            for (i, row) in rows[probe].iter().enumerate() {
+                let Some(hash_key) = HashKey::new(&key(probe, row), &plan.collation) else {
+                    continue;
+                };
+                for &j in table.get(&hash_key).into_iter().flatten() {
+                    pair(j, i);
+                }
+            }
+        }
+
+        let mut joined: Vec<Vec<RecordValue>> = pairs
+            .into_iter()
+            .map(|(lhs, rhs)| {
+                let sides = [&rows[0][lhs], &rows[1][rhs]];
+                plan.sources
+                    .iter()
+                    .map(|&(side, position)| sides[side][position].clone())
+                    .collect()
+            })
+            .collect();
+
+        if !statement.order_by.is_empty() {
+            let terms: Vec<_> = statement
+                .order_by
+                .iter()
+                .enumerate()
+                .map(|(i, term)| {
+                    let position = statement.columns.len() + i;
+                    let (side, column) = resolve(&plan.sides, &term.column)?;
+                    let collation = term
+                        .collation
+                        .clone()
+                        .unwrap_or_else(|| plan.sides[side].table_schema.column_collation(&column));
+                    Ok((position, collation, term.order))
+                })
+                .collect::<Result<_>>()?;
+
+            joined.sort_by(|a, b| {
+                terms
+                    .iter()
+                    .map(|(position, collation, order)| {
+                        let ordering = a[*position].sort_cmp(&b[*position], collation);
+                        match order {
+                            SortOrder::Asc => ordering,
+                            SortOrder::Desc => ordering.reverse(),
+                        }
+                    })
+                    .find(|ordering| *ordering != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+
+        let mut result = ResultSet::new(statement.columns.clone());
+        result.rows = joined
+            .into_iter()
+            .map(|mut row| {
+                row.truncate(statement.columns.len());
+                row
+            })
+            .collect();
+        Ok(result)
+    }
+}
+
+/// The side of a join and declared name of a column, which is qualified with the
+/// name of its table or else found in exactly one of them
+fn resolve(sides: &[JoinSide; 2], reference: &str) -> Result<(usize, String)> {
+    let found: Vec<_> = match reference.split_once('.') {
+        Some((qualifier, column)) => sides
+            .iter()
+            .enumerate()
+            .filter(|(_, side)| sql::same_name(&side.name, qualifier))
+            .filter_map(|(i, side)| side.column(column).map(|column| (i, column)))
+            .collect(),
+        None => sides
+            .iter()
+            .enumerate()
+            .filter_map(|(i, side)| side.column(reference).map(|column| (i, column)))
+            .collect(),
+    };
+
+    match found[..] {
+        [(side, column)] => Ok((side, column.name.clone())),
+        [] => Err(QueryError::NoSuchColumn(reference.to_string()).into()),
+        _ => bail!("ambiguous column name: {reference}"),
+    }
+}
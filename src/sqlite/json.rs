@@ -0,0 +1,259 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+use super::cell::RecordValue;
+
+type JsonFn = fn(&[RecordValue]) -> Result<RecordValue>;
+
+/// The JSON functions built in to the function registry
+pub const FUNCTIONS: [(&str, JsonFn); 5] = [
+    ("json", json),
+    ("json_array_length", json_array_length),
+    ("json_extract", json_extract),
+    ("json_type", json_type),
+    ("json_valid", json_valid),
+];
+
+/// `json(X)` - the JSON text X with the whitespace removed
+fn json(args: &[RecordValue]) -> Result<RecordValue> {
+    let [document] = args else {
+        bail!("wrong number of arguments to function json()");
+    };
+
+    Ok(match parse(document)? {
+        Some(value) => RecordValue::String(value.to_string()),
+        None => RecordValue::Null,
+    })
+}
+
+/// `json_valid(X)` - 1 if X is well-formed JSON text and 0 otherwise
+fn json_valid(args: &[RecordValue]) -> Result<RecordValue> {
+    let [document] = args else {
+        bail!("wrong number of arguments to function json_valid()");
+    };
+
+    Ok(match document {
+        RecordValue::Null => RecordValue::Null,
+        document => RecordValue::I64(i64::from(parse(document).is_ok())),
+    })
+}
+
+/// `json_extract(X, P1, P2, ...)` - the SQL value at path P1, or a JSON array of
+/// the values at each path when given more than one
+fn json_extract(args: &[RecordValue]) -> Result<RecordValue> {
+    let [document, paths @ ..] = args else {
+        bail!("wrong number of arguments to function json_extract()");
+    };
+    if paths.is_empty() {
+        bail!("wrong number of arguments to function json_extract()");
+    }
+
+    let Some(value) = parse(document)? else {
+        return Ok(RecordValue::Null);
+    };
+
+    if let [path] = paths {
+        return Ok(lookup(&value, path)?.map_or(RecordValue::Null, to_record_value));
+    }
+
+    let values = paths
+        .iter()
+        .map(|path| Ok(lookup(&value, path)?.cloned().unwrap_or(Value::Null)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordValue::String(Value::Array(values).to_string()))
+}
+
+/// `X -> P` - the element at path P as JSON text, where P can also be an object
+/// label or an array index
+pub fn extract_json(document: &RecordValue, path: &RecordValue) -> Result<RecordValue> {
+    let Some(value) = parse(document)? else {
+        return Ok(RecordValue::Null);
+    };
+
+    Ok(match lookup(&value, &abbreviated_path(path))? {
+        Some(element) => RecordValue::String(element.to_string()),
+        None => RecordValue::Null,
+    })
+}
+
+/// `X ->> P` - the element at path P as an SQL value, the same as `json_extract`
+/// with the path of `->`
+pub fn extract_value(document: &RecordValue, path: &RecordValue) -> Result<RecordValue> {
+    json_extract(&[document.clone(), abbreviated_path(path)])
+}
+
+/// The full path for the right side of `->` and `->>`, where an integer N is
+/// `$[N]`, counting from the end when negative, and text not starting with `$` is
+/// an array subscript like `[2]` or the label of an object member
+fn abbreviated_path(path: &RecordValue) -> RecordValue {
+    let path = match path {
+        RecordValue::String(path) if path.starts_with('$') => path.clone(),
+        RecordValue::String(path)
+            if path.len() >= 3 && path.starts_with('[') && path.ends_with(']') =>
+        {
+            format!("${path}")
+        }
+        RecordValue::String(label) => format!("$.\"{label}\""),
+        index => match index.as_i64() {
+            Some(index) if index < 0 => format!("$[#{index}]"),
+            Some(index) => format!("$[{index}]"),
+            None => format!("$.\"{index}\""),
+        },
+    };
+    RecordValue::String(path)
+}
+
+/// `json_array_length(X[, P])` - the number of elements in the array at P, or 0
+/// if it isn't an array
+fn json_array_length(args: &[RecordValue]) -> Result<RecordValue> {
+    let Some(value) = at_optional_path(args, "json_array_length")? else {
+        return Ok(RecordValue::Null);
+    };
+
+    let length = value.as_array().map_or(0, Vec::len);
+    Ok(RecordValue::I64(length as i64))
+}
+
+/// `json_type(X[, P])` - the type of the element at P: null, true, false, integer,
+/// real, text, array or object
+fn json_type(args: &[RecordValue]) -> Result<RecordValue> {
+    let Some(value) = at_optional_path(args, "json_type")? else {
+        return Ok(RecordValue::Null);
+    };
+
+    let name = match value {
+        Value::Null => "null",
+        Value::Bool(true) => "true",
+        Value::Bool(false) => "false",
+        Value::Number(number) if number.is_f64() => "real",
+        Value::Number(_) => "integer",
+        Value::String(_) => "text",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    };
+    Ok(RecordValue::String(name.to_string()))
+}
+
+/// The element of `X` at the optional path `P` for the functions taking `(X[, P])`,
+/// or `None` when X is NULL or nothing is at the path
+fn at_optional_path(args: &[RecordValue], function: &str) -> Result<Option<Value>> {
+    let (document, path) = match args {
+        [document] => (document, None),
+        [document, path] => (document, Some(path)),
+        _ => bail!("wrong number of arguments to function {function}()"),
+    };
+
+    let Some(value) = parse(document)? else {
+        return Ok(None);
+    };
+
+    match path {
+        Some(path) => Ok(lookup(&value, path)?.cloned()),
+        None => Ok(Some(value)),
+    }
+}
+
+/// Parses JSON text, where NULL gives `None`
+fn parse(document: &RecordValue) -> Result<Option<Value>> {
+    let text = match document {
+        RecordValue::Null => return Ok(None),
+        RecordValue::String(text) => text.clone(),
+        RecordValue::Blob(_) => bail!("malformed JSON"),
+        // Numbers are JSON text as they are
+        other => other.to_sql_literal(),
+    };
+
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|_| anyhow!("malformed JSON"))
+}
+
+/// One step along a JSON path
+enum Step {
+    Key(String),
+    Index(usize),
+    /// `[#-N]`, counting back from the end of the array
+    FromEnd(usize),
+}
+
+/// The element of `value` at a path such as `$.items[0].name`, or `None` if there
+/// is nothing there
+fn lookup<'a>(value: &'a Value, path: &RecordValue) -> Result<Option<&'a Value>> {
+    let RecordValue::String(path) = path else {
+        bail!("bad JSON path: {}", path.to_sql_literal());
+    };
+
+    let steps = parse_path(path).ok_or_else(|| anyhow!("bad JSON path: '{path}'"))?;
+    let mut current = value;
+    for step in steps {
+        let next = match (step, current) {
+            (Step::Key(key), Value::Object(object)) => object.get(&key),
+            (Step::Index(idx), Value::Array(array)) => array.get(idx),
+            (Step::FromEnd(back), Value::Array(array)) => {
+                array.len().checked_sub(back).and_then(|idx| array.get(idx))
+            }
+            _ => None,
+        };
+
+        match next {
+            Some(next) => current = next,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(current))
+}
+
+fn parse_path(path: &str) -> Option<Vec<Step>> {
+    let mut rest = path.strip_prefix('$')?;
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let (key, remaining) = match after.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted.find('"')?;
+                    (&quoted[..end], &quoted[end + 1..])
+                }
+                None => {
+                    let end = after.find(['.', '[']).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+
+            if key.is_empty() && !after.starts_with('"') {
+                return None;
+            }
+            steps.push(Step::Key(key.to_string()));
+            rest = remaining;
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            let index = after[..end].trim();
+            let step = match index.strip_prefix('#') {
+                Some("") => Step::FromEnd(0),
+                Some(back) => Step::FromEnd(back.trim().strip_prefix('-')?.trim().parse().ok()?),
+                None => Step::Index(index.parse().ok()?),
+            };
+            steps.push(step);
+            rest = &after[end + 1..];
+        } else {
+            return None;
+        }
+    }
+
+    Some(steps)
+}
+
+/// The SQL value a JSON element is extracted as: booleans become integers, and
+/// arrays and objects are kept as JSON text
+fn to_record_value(value: &Value) -> RecordValue {
+    match value {
+        Value::Null => RecordValue::Null,
+        Value::Bool(b) => RecordValue::I64(i64::from(*b)),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => RecordValue::I64(integer),
+            None => RecordValue::F64(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(text) => RecordValue::String(text.clone()),
+        other => RecordValue::String(other.to_string()),
+    }
+}
@@ -0,0 +1,35 @@
+use std::{fs::File, io, os::unix::io::AsRawFd};
+
+pub const READ: libc::c_short = libc::F_RDLCK as libc::c_short;
+pub const WRITE: libc::c_short = libc::F_WRLCK as libc::c_short;
+pub const UNLOCK: libc::c_short = libc::F_UNLCK as libc::c_short;
+
+/// Takes or releases an advisory lock on `len` bytes of the file from `start`,
+/// without waiting if someone else holds a conflicting lock
+pub fn lock(file: &File, kind: libc::c_short, start: u64, len: u64) -> io::Result<()> {
+    // Safety: `flock` is plain data that may be zeroed, which also clears any
+    // platform specific fields
+    let mut region: libc::flock = unsafe { std::mem::zeroed() };
+    region.l_type = kind;
+    region.l_whence = libc::SEEK_SET as libc::c_short;
+    region.l_start = start as libc::off_t;
+    region.l_len = len as libc::off_t;
+
+    // Safety: the descriptor stays open for the duration of the call and
+    // `region` is a valid `flock`
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETLK, &region) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Another connection holding the lock is reported the way SQLite reports it
+pub fn busy(e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::PermissionDenied => {
+            io::Error::new(io::ErrorKind::WouldBlock, "database is locked")
+        }
+        _ => e,
+    }
+}
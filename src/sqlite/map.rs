@@ -0,0 +1,263 @@
+use anyhow::{bail, Result};
+use bytes::Buf;
+use serde_json::{json, Value};
+use std::fmt;
+use std::io;
+
+use super::{
+    cell::DatabaseCell,
+    error::DecodeError,
+    page::{self, BTreePageType},
+    pager::Pager,
+    SqliteReader,
+};
+
+/// What a page of the database file is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageKind {
+    BTree(BTreePageType),
+    /// Holds the part of a payload too large for its b-tree page
+    Overflow,
+    FreelistTrunk,
+    FreelistLeaf,
+    /// Records the parent of each page of an auto-vacuum database
+    PointerMap,
+    /// The page holding the bytes SQLite locks on, which is never written
+    LockByte,
+    /// Reachable from neither a b-tree nor the freelist
+    Unused,
+}
+
+impl fmt::Display for PageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BTree(BTreePageType::InteriorIndex) => write!(f, "index_interior"),
+            Self::BTree(BTreePageType::InteriorTable) => write!(f, "table_interior"),
+            Self::BTree(BTreePageType::LeafIndex) => write!(f, "index_leaf"),
+            Self::BTree(BTreePageType::LeafTable) => write!(f, "table_leaf"),
+            Self::Overflow => write!(f, "overflow"),
+            Self::FreelistTrunk => write!(f, "freelist_trunk"),
+            Self::FreelistLeaf => write!(f, "freelist_leaf"),
+            Self::PointerMap => write!(f, "pointer_map"),
+            Self::LockByte => write!(f, "lock_byte"),
+            Self::Unused => write!(f, "unused"),
+        }
+    }
+}
+
+/// One page of the database file as laid out by `.map`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedPage {
+    /// Counting from 1, as SQLite numbers pages
+    pub page_no: usize,
+    pub kind: PageKind,
+    /// The page pointing at this one, a b-tree page's parent or the page before it in
+    /// an overflow chain or the freelist
+    pub parent: Option<usize>,
+    pub cells: usize,
+    /// Usable bytes holding neither headers nor content
+    pub free_bytes: usize,
+    /// The table or index whose b-tree the page belongs to, overflow pages included
+    pub owner: Option<String>,
+}
+
+impl MappedPage {
+    fn to_json(&self) -> Value {
+        json!({
+            "page": self.page_no,
+            "type": self.kind.to_string(),
+            "parent": self.parent,
+            "cells": self.cells,
+            "free_bytes": self.free_bytes,
+            "owner": self.owner,
+        })
+    }
+}
+
+impl<P: Pager> SqliteReader<P> {
+    /// Prints what each page of the file is used for, the page pointing at it, how
+    /// many cells and free bytes it has and the table or index it belongs to, as a
+    /// JSON array of objects when `json` is set
+    pub fn map(&self, json: bool, out: &mut dyn io::Write) -> Result<()> {
+        let pages = self.page_map()?;
+        if json {
+            let pages: Vec<Value> = pages.iter().map(MappedPage::to_json).collect();
+            writeln!(out, "{}", serde_json::to_string_pretty(&pages)?)?;
+            return Ok(());
+        }
+
+        writeln!(out, "page|type|parent|cells|free bytes|owner")?;
+        for page in pages {
+            let parent = page.parent.map(|parent| parent.to_string());
+            writeln!(
+                out,
+                "{}|{}|{}|{}|{}|{}",
+                page.page_no,
+                page.kind,
+                parent.unwrap_or_default(),
+                page.cells,
+                page.free_bytes,
+                page.owner.unwrap_or_default()
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Every page of the file in order, found by walking each b-tree with its
+    /// overflow chains and then the freelist
+    pub fn page_map(&self) -> Result<Vec<MappedPage>> {
+        let mut pages: Vec<Option<MappedPage>> = vec![None; self.page_count()];
+        let mut place = |page: MappedPage| {
+            let slot = page
+                .page_no
+                .checked_sub(1)
+                .and_then(|idx| pages.get_mut(idx));
+            let Some(slot) = slot else {
+                bail!("page {} is outside the database file", page.page_no);
+            };
+            if let Some(earlier) = slot {
+                bail!(
+                    "page {} is used as both {} and {}",
+                    page.page_no,
+                    earlier.kind,
+                    page.kind
+                );
+            }
+
+            *slot = Some(page);
+            Ok(())
+        };
+
+        let schema = self.schema()?;
+        let mut btrees = vec![("sqlite_schema".to_string(), 0)];
+        btrees.extend(
+            schema
+                .entries()
+                .filter(|entry| entry.has_btree())
+                .map(|entry| (entry.name.clone(), entry.root_page as usize)),
+        );
+
+        let overflow = self.overflow();
+        let capacity = overflow.usable_size() - 4;
+        for (owner, root) in btrees {
+            let mut pending = vec![(root, None)];
+            while let Some((idx, parent)) = pending.pop() {
+                let buf = self.page_bytes(idx)?;
+                let header = self.page_header(idx)?;
+                let page = self.page(idx)?;
+                place(MappedPage {
+                    page_no: idx + 1,
+                    kind: PageKind::BTree(header.page_type),
+                    parent,
+                    cells: page.cells.len(),
+                    free_bytes: page::unused_bytes(buf, &header, idx),
+                    owner: Some(owner.clone()),
+                })?;
+
+                pending.extend(
+                    page.right_page_pointer()
+                        .map(|rp| (rp as usize, Some(idx + 1))),
+                );
+                for cell in page.cells.iter().rev() {
+                    let (left_child, overflow_page) = match cell {
+                        DatabaseCell::InteriorTable(cell) => (Some(cell.left_child), None),
+                        DatabaseCell::InteriorIndex(cell) => {
+                            (Some(cell.left_child), cell.overflow_page)
+                        }
+                        DatabaseCell::Leaf(cell) => (None, cell.overflow_page),
+                        DatabaseCell::IndexLeaf(cell) => (None, cell.overflow_page),
+                    };
+                    pending.extend(left_child.map(|child| (child as usize, Some(idx + 1))));
+
+                    // Each overflow page holds the next one's number, then its share
+                    let mut next = overflow_page.unwrap_or_default() as usize;
+                    let mut parent = idx + 1;
+                    let mut spilled = overflow.spilled_bytes(cell);
+                    while next != 0 && spilled > 0 {
+                        let held = spilled.min(capacity);
+                        place(MappedPage {
+                            page_no: next,
+                            kind: PageKind::Overflow,
+                            parent: Some(parent),
+                            cells: 0,
+                            free_bytes: capacity - held,
+                            owner: Some(owner.clone()),
+                        })?;
+
+                        spilled -= held;
+                        parent = next;
+                        next = self.pager.get_page(next - 1)?.get_u32() as usize;
+                    }
+                }
+            }
+        }
+
+        let mut trunk = self.database_header.freelist_trunk_page_page_no as usize;
+        let mut parent = None;
+        while trunk != 0 {
+            let mut buf = self.page_bytes(trunk - 1)?;
+            let next_trunk = buf.get_u32() as usize;
+            let leaf_count = buf.get_u32() as usize;
+            if buf.remaining() < leaf_count * 4 {
+                bail!("freelist trunk page {trunk} lists more leaves than it can hold");
+            }
+
+            place(MappedPage {
+                page_no: trunk,
+                kind: PageKind::FreelistTrunk,
+                parent,
+                cells: leaf_count,
+                free_bytes: overflow.usable_size() - 8 - 4 * leaf_count,
+                owner: None,
+            })?;
+            for _ in 0..leaf_count {
+                let leaf = buf.get_u32();
+                if leaf == 0 || leaf as usize > self.page_count() {
+                    return Err(DecodeError::InvalidFreelistPage(leaf).into());
+                }
+
+                place(MappedPage {
+                    page_no: leaf as usize,
+                    kind: PageKind::FreelistLeaf,
+                    parent: Some(trunk),
+                    cells: 0,
+                    free_bytes: overflow.usable_size(),
+                    owner: None,
+                })?;
+            }
+
+            parent = Some(trunk);
+            trunk = next_trunk;
+        }
+
+        // Auto-vacuum databases keep a pointer map page after the first page, then
+        // after every run of pages it has room for the parents of
+        let auto_vacuum = self.database_header.largest_root_b_tree_page != 0;
+        let pointer_map_interval = overflow.usable_size() / 5 + 1;
+        Ok(pages
+            .into_iter()
+            .enumerate()
+            .map(|(idx, page)| {
+                page.unwrap_or_else(|| {
+                    let kind = if self.pager.is_lock_byte_page(idx) {
+                        PageKind::LockByte
+                    } else if auto_vacuum && idx > 0 && (idx - 1) % pointer_map_interval == 0 {
+                        PageKind::PointerMap
+                    } else {
+                        PageKind::Unused
+                    };
+
+                    MappedPage {
+                        page_no: idx + 1,
+                        kind,
+                        parent: None,
+                        cells: 0,
+                        free_bytes: 0,
+                        owner: None,
+                    }
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,146 @@
+use super::cell::RecordValue;
+use super::collation::Collation;
+use super::sql::InSource;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Lists of at least this many values are put in a hash set rather than compared
+/// with each value in turn
+pub const HASH_SET_THRESHOLD: usize = 8;
+
+/// How the values of an `IN` term are tested against each row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Compare the value with each of the list in turn
+    Compare,
+    /// Look the value up in a hash set built once before the scan
+    HashSet,
+}
+
+impl Strategy {
+    /// The strategy for testing against `source` with `collation`
+    ///
+    /// A subquery's rows are always hashed, since how many there are isn't known
+    /// until it has run. Lists referring to the columns can't be built ahead of the
+    /// scan, and values compared with a registered collation have no key to hash by.
+    pub fn of(source: &InSource, collation: &Collation) -> Self {
+        if matches!(collation, Collation::Custom(_)) {
+            return Self::Compare;
+        }
+
+        match source {
+            InSource::Subquery(_) => Self::HashSet,
+            InSource::List(list) if list.iter().any(|item| !item.columns().is_empty()) => {
+                Self::Compare
+            }
+            InSource::List(list) if list.len() >= HASH_SET_THRESHOLD => Self::HashSet,
+            InSource::List(_) => Self::Compare,
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Compare => write!(f, "compare in turn"),
+            Self::HashSet => write!(f, "temp hash set"),
+        }
+    }
+}
+
+/// A value as a hash set or hash join stores it, equal to another exactly when
+/// SQLite's comparison under the collation they're hashed with finds them equal
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    /// The bits of a real with a fractional part, or too large for an integer
+    Real(u64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl HashKey {
+    /// `None` for `NULL`, which is never equal to anything
+    ///
+    /// A registered collation can't be hashed by, so compares text as-is.
+    pub fn new(value: &RecordValue, collation: &Collation) -> Option<Self> {
+        match value {
+            RecordValue::Null => None,
+            RecordValue::String(text) => Some(Self::Text(match collation {
+                Collation::NoCase => text.to_ascii_lowercase(),
+                Collation::RTrim => text.trim_end_matches(' ').to_string(),
+                _ => text.clone(),
+            })),
+            RecordValue::Blob(blob) => Some(Self::Blob(blob.clone())),
+            RecordValue::F64(real)
+                if real.fract() == 0.0
+                    && (-9.223_372_036_854_776e18..9.223_372_036_854_776e18).contains(real) =>
+            {
+                Some(Self::Integer(*real as i64))
+            }
+            RecordValue::F64(real) => Some(Self::Real(real.to_bits())),
+            integer => integer.as_i64().map(Self::Integer),
+        }
+    }
+}
+
+enum Members {
+    List(Vec<RecordValue>),
+    HashSet(HashSet<HashKey>),
+}
+
+/// The values an `IN` term tests against, gathered before the scan
+pub struct InSet {
+    members: Members,
+    collation: Collation,
+    /// Whether any of the values is `NULL`, which leaves a value not found unknown
+    /// rather than false
+    has_null: bool,
+    /// Nothing is in an empty list, not even `NULL`
+    empty: bool,
+}
+
+impl InSet {
+    pub fn new(values: Vec<RecordValue>, collation: Collation, strategy: Strategy) -> Self {
+        let has_null = values.contains(&RecordValue::Null);
+        let empty = values.is_empty();
+        let members = match strategy {
+            Strategy::Compare => Members::List(values),
+            Strategy::HashSet => Members::HashSet(
+                values
+                    .iter()
+                    .filter_map(|value| HashKey::new(value, &collation))
+                    .collect(),
+            ),
+        };
+
+        Self {
+            members,
+            collation,
+            has_null,
+            empty,
+        }
+    }
+
+    /// Whether `value` is one of the values, or `None` when that is unknown because
+    /// it or one of the values is `NULL`
+    pub fn contains(&self, value: &RecordValue) -> Option<bool> {
+        if self.empty {
+            return Some(false);
+        }
+
+        let found = match &self.members {
+            Members::List(values) => values.iter().any(|member| {
+                value.compare(member, &self.collation) == Some(std::cmp::Ordering::Equal)
+            }),
+            Members::HashSet(keys) => keys.contains(&HashKey::new(value, &self.collation)?),
+        };
+
+        match (value, found) {
+            (RecordValue::Null, _) => None,
+            (_, true) => Some(true),
+            (_, false) if self.has_null => None,
+            (_, false) => Some(false),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use web_time::Instant;
+
+/// Running counts of the work a reader has done, across every query it has run
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// B-tree pages read, not counting the overflow pages payloads continue on
+    pages_read: AtomicU64,
+    cells_decoded: AtomicU64,
+}
+
+impl Metrics {
+    pub fn record_page(&self, cells: usize) {
+        self.pages_read.fetch_add(1, Ordering::Relaxed);
+        self.cells_decoded
+            .fetch_add(cells as u64, Ordering::Relaxed);
+    }
+
+    pub fn pages_read(&self) -> u64 {
+        self.pages_read.load(Ordering::Relaxed)
+    }
+
+    pub fn cells_decoded(&self) -> u64 {
+        self.cells_decoded.load(Ordering::Relaxed)
+    }
+
+    /// Starts measuring a query from the counts as they are now
+    pub fn start(&self) -> Measurement<'_> {
+        Measurement {
+            metrics: self,
+            started: Instant::now(),
+            pages_read: self.pages_read(),
+            cells_decoded: self.cells_decoded(),
+        }
+    }
+}
+
+/// A query being measured, finished once its rows are known
+pub struct Measurement<'a> {
+    metrics: &'a Metrics,
+    started: Instant,
+    pages_read: u64,
+    cells_decoded: u64,
+}
+
+impl Measurement<'_> {
+    pub fn finish(self, rows: usize) -> QueryMetrics {
+        QueryMetrics {
+            elapsed: self.started.elapsed(),
+            pages_read: self.metrics.pages_read() - self.pages_read,
+            cells_decoded: self.metrics.cells_decoded() - self.cells_decoded,
+            rows,
+        }
+    }
+}
+
+/// The work one query took
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryMetrics {
+    pub elapsed: Duration,
+    pub pages_read: u64,
+    pub cells_decoded: u64,
+    /// Rows of the result, before any limit on how many are printed
+    pub rows: usize,
+}
+
+/// The line `.timer on` prints after each query
+impl fmt::Display for QueryMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Run Time: real {:.3} pages {} cells {} rows {}",
+            self.elapsed.as_secs_f64(),
+            self.pages_read,
+            self.cells_decoded,
+            self.rows
+        )
+    }
+}
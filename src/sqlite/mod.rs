@@ -1,20 +1,94 @@
-use anyhow::Result;
-use cell::{DatabaseCell, LeafCell};
-use memmap2::Mmap;
+use anyhow::{bail, Context, Result};
+use cell::{DatabaseCell, LeafCell, Overflow, RecordValue};
+use cursor::BtCursor;
+use error::{ensure_remaining, DecodeError, QueryError};
+use fts5::Fts5Table;
+use interrupt::InterruptHandle;
+use metrics::Metrics;
+use options::OpenOptions;
+use output::write_columns;
+use planner::{Direction, QueryPlan, ScanPlan};
+use result::{ColumnMetadata, ResultSet};
+use rtree::RTreeTable;
 use schema::{SchemaTable, SqliteSchema};
-use sql::{CreateTable, SelectStatement};
-use std::{fmt::Write, fs::File, path::Path};
+use sequence::SqliteSequence;
+use sql::{
+    CreateTable, CreateVirtualTable, Expr, ExprCondition, IndexedColumn, Operator, OrderingTerm,
+    SelectOperation, SelectStatement, SortOrder,
+};
+use stat::{SqliteStats, Stat1Row};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+use tracing::{debug, trace};
 
 use bytes::{Buf, Bytes};
 
+pub mod affinity;
+pub mod analyze;
+pub mod backup;
 pub mod cell;
+pub mod collation;
+pub mod common_table;
+pub mod constraint;
+pub mod cursor;
+pub mod database;
+pub mod diff;
+pub mod error;
+pub mod explain;
+#[cfg(feature = "parquet")]
+pub mod export;
+pub mod fts5;
+pub mod function;
+pub mod inspect;
+pub mod interrupt;
+pub mod join;
+pub mod json;
+#[cfg(unix)]
+mod lock;
+pub mod map;
+pub mod membership;
+pub mod metrics;
+pub mod options;
+pub mod output;
 pub mod page;
+pub mod pager;
+pub mod pattern;
+pub mod planner;
+pub mod pragma;
+pub mod printf;
+pub mod recover;
+pub mod result;
+pub mod rtree;
 pub mod schema;
+pub mod sequence;
+pub mod shm;
 pub mod sql;
+#[cfg(feature = "sqlcipher")]
+pub mod sqlcipher;
+pub mod stat;
+pub mod subquery;
+pub mod vtab;
+pub mod wal;
 
-use page::{BTreePage, BTreePageType};
+use page::{BTreePage, BTreePageHeader, BTreePageType};
+use pager::{FilePager, MemoryPager, PageCodec, Pager, HEADER_MAGIC};
+use wal::{Wal, WalPager};
 
-const HEADER_SIZE: usize = 100;
+/// Bytes of the database header at the start of the first page
+pub const HEADER_SIZE: usize = 100;
+
+/// The release of SQLite whose file format and functions are followed
+pub const SQLITE_VERSION: &str = "3.46.0";
+
+/// [`SQLITE_VERSION`] as the number new databases record as the version that last
+/// wrote them
+pub const SQLITE_VERSION_NUMBER: u32 = 3_046_000;
 
 #[allow(dead_code)]
 #[derive(Debug, Copy, Clone)]
@@ -23,7 +97,8 @@ pub struct DatabaseHeader {
     pub page_size: u16,
     write_version: u8,
     read_version: u8,
-    reserved_space: u8,
+    /// Bytes at the end of every page set aside for extensions
+    pub reserved_space: u8,
     max_payload: u8,
     min_payload: u8,
     leaf_payload: u8,
@@ -45,12 +120,13 @@ pub struct DatabaseHeader {
 }
 
 impl DatabaseHeader {
-    pub fn new(buf: &[u8]) -> Self {
-        let mut buf = Bytes::copy_from_slice(buf);
+    pub fn new(buf: &[u8]) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, HEADER_SIZE)?;
+        let mut buf = Bytes::copy_from_slice(&buf[..HEADER_SIZE]);
         let mut magic = [0; 16];
         let mut reserved_expansion = [0; 20];
 
-        Self {
+        Ok(Self {
             magic: {
                 buf.copy_to_slice(&mut magic);
                 magic
@@ -80,112 +156,1199 @@ impl DatabaseHeader {
             },
             version_valid_for_number: buf.get_u32(),
             sqlite_version_number: buf.get_u32(),
-        }
+        })
+    }
+
+    /// Identifies the file format of the application the database belongs to, set
+    /// with `PRAGMA application_id`
+    pub fn application_id(&self) -> u32 {
+        self.application_id
+    }
+
+    /// The format the `application_id` is registered for, if it's a known one
+    pub fn application_name(&self) -> Option<&'static str> {
+        APPLICATION_IDS
+            .iter()
+            .find(|(id, _)| *id == self.application_id)
+            .map(|(_, name)| *name)
+    }
+
+    /// Left for the application to use as it likes, set with `PRAGMA user_version`,
+    /// which treats it as signed
+    pub fn user_version(&self) -> i32 {
+        self.user_version as i32
+    }
+}
+
+/// Application ids of the file formats built on SQLite that its `magic.txt`
+/// registers
+const APPLICATION_IDS: &[(u32, &str)] = &[
+    (0x0f05_5111, "Fossil repository"),
+    (0x0f05_5112, "Fossil checkout"),
+    (0x0f05_5113, "Fossil global configuration"),
+    (0x4265_4462, "Bentley Systems BeSQLite database"),
+    (0x4265_4c6e, "Bentley Systems localization file"),
+    (0x4573_7269, "Esri spatially-enabled database"),
+    (0x4750_3130, "GeoPackage 1.0"),
+    (0x4750_3131, "GeoPackage 1.1"),
+    (0x4750_4b47, "GeoPackage"),
+    (0x4d50_4258, "MBTiles tileset"),
+];
+
+/// Pages of the database file at `path`, already opened as `db`, along with the
+/// pages committed to its write-ahead log unless `options` leaves them out
+fn open_pager(path: &Path, db: &File, options: &OpenOptions) -> Result<Box<dyn Pager>> {
+    // Other connections to a database in WAL mode checkpoint the log into the file
+    // and can truncate it, which a mapping of the file doesn't survive
+    let read_options = OpenOptions {
+        mmap: options.mmap && !wal::in_wal_mode(path),
+        ..options.clone()
+    };
+
+    // SQLite reads an empty file as a new database nothing has been written to yet
+    let pager: Box<dyn Pager> = match db.metadata()?.len() {
+        0 => Box::new(MemoryPager::new(empty_database(NEW_PAGE_SIZE))?),
+        _ => file_pager(db, &read_options)?,
+    };
+
+    if !options.wal {
+        return Ok(pager);
     }
+
+    match Wal::open(path, pager.page_size()).context("reading the write-ahead log")? {
+        Some(wal) => Ok(Box::new(WalPager::new(pager, wal))),
+        None => Ok(pager),
+    }
+}
+
+/// Pages of the database file `db`, read the way `options` asks
+fn file_pager(db: &File, options: &OpenOptions) -> Result<Box<dyn Pager>> {
+    #[cfg(not(target_family = "wasm"))]
+    let pager: Box<dyn Pager> = match (options.mmap, options.mmap_window) {
+        (false, _) => Box::new(FilePager::new(db.try_clone()?)?),
+        (true, Some(window)) => Box::new(pager::WindowedPager::new(db.try_clone()?, window)?),
+        (true, None) => match pager::MmapPager::new(db) {
+            Ok(pager) => Box::new(pager),
+            Err(_) => Box::new(FilePager::new(db.try_clone()?)?),
+        },
+    };
+    // Files can't be mapped at all on WebAssembly
+    #[cfg(target_family = "wasm")]
+    let pager: Box<dyn Pager> = {
+        let _ = options;
+        Box::new(FilePager::new(db.try_clone()?)?)
+    };
+
+    Ok(pager)
 }
 
-pub struct SqliteReader {
-    reader: Mmap,
+/// Path that opens a new empty database held in memory instead of a file
+pub const MEMORY_PATH: &str = ":memory:";
+
+/// Page size of databases created in memory or read from an empty file, the same as
+/// SQLite's default
+const NEW_PAGE_SIZE: u16 = 4096;
+
+pub struct SqliteReader<P: Pager = Box<dyn Pager>> {
+    pager: P,
+    /// The file the database was opened from, if any
+    path: Option<PathBuf>,
     pub database_header: DatabaseHeader,
+    metrics: Metrics,
+    interrupt: InterruptHandle,
+    /// The `sqlite_stat1` rows as of the last `ANALYZE` run on this reader, which the
+    /// planner uses in place of those in the file as they are never written to it
+    analyzed: RwLock<Option<Vec<Stat1Row>>>,
+    /// Tables materialized for the `WITH` clauses of the queries running, innermost
+    /// last, which take precedence over tables in the schema of the same name
+    common_tables: RwLock<Vec<(String, Arc<vtab::MemoryTable>)>>,
+    options: OpenOptions,
 }
 
 impl SqliteReader {
+    /// Opens the database file at `path`, or a new empty database in memory for
+    /// [`MEMORY_PATH`]
+    ///
+    /// The file is memory mapped where possible and otherwise read a page at a time,
+    /// with any transactions committed to its write-ahead log read over the top.
     pub fn new(path: impl AsRef<Path>) -> Result<Self> {
-        let db = File::open(path)?;
-        // Safety: As this reader will only be instantiated in read contexts
-        // we can guarantee that no one else will be modifying the underlying
-        // file
-        let reader = unsafe { Mmap::map(&db)? };
-        let database_header = DatabaseHeader::new(&reader[0..HEADER_SIZE]);
+        Self::open_with(path, &OpenOptions::default())
+    }
+
+    /// Opens the database file at `path` the way `options` asks, or a new empty
+    /// database in memory for [`MEMORY_PATH`]
+    pub fn open_with(path: impl AsRef<Path>, options: &OpenOptions) -> Result<Self> {
+        let path = path.as_ref();
+        if path == Path::new(MEMORY_PATH) {
+            return Ok(Self::memory());
+        }
+
+        let db = File::options()
+            .read(true)
+            .write(!options.read_only)
+            .open(path)?;
+        let mut reader = SqliteReader::with_pager(open_pager(path, &db, options)?)?;
+        reader.path = Some(path.to_path_buf());
+        reader.options = options.clone();
+        Ok(reader)
+    }
+
+    /// Opens a database file whose pages are encoded, running each page through
+    /// `codec` as it is read
+    ///
+    /// The write-ahead log isn't read, as its frames would need decoding as well, and
+    /// a backup of the database holds the decoded pages.
+    pub fn with_codec(
+        path: impl AsRef<Path>,
+        page_size: usize,
+        codec: impl PageCodec + 'static,
+    ) -> Result<Self> {
+        let pager: Box<dyn Pager> =
+            Box::new(FilePager::with_codec(File::open(path)?, page_size, codec)?);
+
+        SqliteReader::with_pager(pager)
+    }
+
+    /// Reads a database from the bytes of a database file, such as one downloaded
+    /// or embedded in the program
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let pager: Box<dyn Pager> = Box::new(MemoryPager::new(bytes)?);
+        SqliteReader::with_pager(pager)
+    }
+
+    /// A new database in memory holding nothing but an empty schema table
+    pub fn memory() -> Self {
+        let pager = MemoryPager::new(empty_database(NEW_PAGE_SIZE))
+            .expect("an empty database has a valid page size");
+        let pager: Box<dyn Pager> = Box::new(pager);
+
+        SqliteReader::with_pager(pager).expect("an empty database has a header")
+    }
+}
+
+impl<P: Pager> SqliteReader<P> {
+    pub fn with_pager(pager: P) -> Result<Self> {
+        let first_page = pager.get_page(0).context("reading the database header")?;
+        let database_header =
+            DatabaseHeader::new(first_page).context("decoding the database header")?;
 
         Ok(Self {
-            reader,
+            pager,
+            path: None,
             database_header,
+            metrics: Metrics::default(),
+            interrupt: InterruptHandle::default(),
+            analyzed: RwLock::default(),
+            common_tables: RwLock::default(),
+            options: OpenOptions::default(),
         })
     }
 
-    pub fn page(&self, page: usize) -> BTreePage {
-        let page_size = usize::from(self.database_header.page_size);
-        let (start_offset, end_offset) = if page == 0 {
-            (HEADER_SIZE, page_size)
+    /// The options the database was opened with
+    pub fn options(&self) -> &OpenOptions {
+        &self.options
+    }
+
+    /// Drops the cached pages past the cache size the database was opened with,
+    /// which has to wait until no query is borrowing them
+    pub fn shrink_cache(&mut self) {
+        if let Some(pages) = self.options.cache_size {
+            self.pager.shrink_cache(pages);
+        }
+    }
+
+    /// The pages and cells read by every query run so far
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// The handle that stops this reader's queries partway through
+    pub fn interrupt_handle(&self) -> &InterruptHandle {
+        &self.interrupt
+    }
+
+    /// Shares `handle` with this reader, so interrupting it stops queries here too
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        self.interrupt = handle;
+    }
+
+    /// The bytes of a page, leaving out the database header on the first page
+    fn page_bytes(&self, page: usize) -> Result<&[u8]> {
+        if self.pager.is_lock_byte_page(page) {
+            bail!("page {} is the lock-byte page, not a b-tree page", page + 1);
+        }
+
+        let bytes = self
+            .pager
+            .get_page(page)
+            .with_context(|| format!("reading page {}", page + 1))?;
+
+        if page == 0 {
+            Ok(&bytes[HEADER_SIZE..])
         } else {
-            (page * page_size, (page + 1) * page_size)
+            Ok(bytes)
+        }
+    }
+
+    /// Where payloads too large for their b-tree page continue
+    fn overflow(&self) -> Overflow<'_> {
+        Overflow::new(
+            &self.pager,
+            usize::from(self.database_header.reserved_space),
+        )
+    }
+
+    pub fn page(&self, page_idx: usize) -> Result<BTreePage> {
+        let buf = self.page_bytes(page_idx)?;
+        let page = match self.options.lenient {
+            true => BTreePage::lenient(buf, page_idx, self.overflow()),
+            false => BTreePage::new(buf, page_idx, self.overflow()),
+        }
+        .with_context(|| format!("decoding page {}", page_idx + 1))?;
+
+        trace!(page = page_idx + 1, cells = page.cells.len(), "read page");
+        self.metrics.record_page(page.cells.len());
+        Ok(page)
+    }
+
+    pub fn page_header(&self, page: usize) -> Result<BTreePageHeader> {
+        BTreePageHeader::new(self.page_bytes(page)?, page)
+            .with_context(|| format!("decoding header of page {}", page + 1))
+    }
+
+    /// Number of pages in the database file
+    pub fn page_count(&self) -> usize {
+        self.pager.page_count()
+    }
+
+    /// Every page on the freelist, trunks included, which keep whatever they held
+    /// before being freed beyond the freelist bookkeeping
+    pub fn freelist_pages(&self) -> Result<Vec<usize>> {
+        let mut pages = Vec::new();
+        let mut trunk = self.database_header.freelist_trunk_page_page_no as usize;
+        let mut trunks_seen = 0;
+        while trunk != 0 {
+            trunks_seen += 1;
+            if trunks_seen > self.page_count() {
+                anyhow::bail!("freelist trunk pages form a cycle");
+            }
+
+            pages.push(trunk - 1);
+            let mut buf = self.page_bytes(trunk - 1)?;
+            let next_trunk = buf.get_u32() as usize;
+            let leaf_count = buf.get_u32() as usize;
+            if buf.remaining() < leaf_count * 4 {
+                anyhow::bail!("freelist trunk page {trunk} lists more leaves than it can hold");
+            }
+
+            for _ in 0..leaf_count {
+                let leaf = buf.get_u32();
+                match (leaf as usize).checked_sub(1) {
+                    Some(idx) if idx < self.page_count() => pages.push(idx),
+                    _ => return Err(DecodeError::InvalidFreelistPage(leaf).into()),
+                }
+            }
+            trunk = next_trunk;
+        }
+
+        Ok(pages)
+    }
+
+    /// Every page of the b-tree rooted at `root`, parents before their children
+    pub fn btree_pages(&self, root: usize) -> Result<Vec<usize>> {
+        let mut pages = vec![root];
+        let mut i = 0;
+        while let Some(&page) = pages.get(i) {
+            i += 1;
+            if pages.len() > self.page_count() {
+                anyhow::bail!("b-tree rooted at page {} contains a cycle", root + 1);
+            }
+
+            let page = self.page(page)?;
+            pages.extend(page.cells.iter().filter_map(|cell| match cell {
+                DatabaseCell::InteriorTable(table_cell) => Some(table_cell.left_child as usize),
+                DatabaseCell::InteriorIndex(index_cell) => Some(index_cell.left_child as usize),
+                _ => None,
+            }));
+            pages.extend(page.right_page_pointer().map(|rp| rp as usize));
+        }
+
+        Ok(pages)
+    }
+
+    /// The entries of `sqlite_schema`, whose b-tree grows interior pages like any
+    /// other table once its rows outgrow the first page
+    pub fn schema(&self) -> Result<SqliteSchema> {
+        let root = self.page_header(0).context("decoding the schema")?;
+        if !matches!(
+            root.page_type,
+            BTreePageType::LeafTable | BTreePageType::InteriorTable
+        ) {
+            let error = DecodeError::UnexpectedPageType {
+                expected: BTreePageType::LeafTable,
+                found: root.page_type,
+            };
+            return Err(anyhow::Error::new(error).context("decoding the schema"));
+        }
+
+        let mut cursor = BtCursor::table(self, 0);
+        cursor.first()?;
+        let mut rows = Vec::new();
+        while let Some(row) = cursor.cell() {
+            rows.push(row.clone());
+            cursor.next()?;
+        }
+
+        SqliteSchema::new(&rows).context("decoding the schema")
+    }
+
+    pub fn stats(&self, schema: &SqliteSchema) -> Result<SqliteStats> {
+        Ok(SqliteStats::new(&self.stat1_rows(schema)?))
+    }
+
+    /// The rows of `sqlite_stat1`, or those the last `ANALYZE` on this reader computed
+    pub fn stat1_rows(&self, schema: &SqliteSchema) -> Result<Vec<Stat1Row>> {
+        if let Some(rows) = self.analyzed.read().expect("stat lock poisoned").as_ref() {
+            return Ok(rows.clone());
+        }
+
+        let Some(stat_table) = schema.fetch_table(stat::STAT1_TABLE) else {
+            return Ok(Vec::new());
         };
 
-        assert!(start_offset < self.reader.len());
+        let rows = self.collect_rows(stat_table, Direction::Forward)?;
+        Ok(rows.iter().filter_map(Stat1Row::new).collect())
+    }
 
-        // TODO: Off by one somehow
-        assert!(end_offset < self.reader.len() + 1);
+    pub fn sequence(&self, schema: &SqliteSchema) -> Result<SqliteSequence> {
+        let Some(sequence_table) = schema.fetch_table(sequence::SEQUENCE_TABLE) else {
+            return Ok(SqliteSequence::default());
+        };
 
-        BTreePage::new(&self.reader[start_offset..end_offset], page)
+        let rows = self.collect_rows(sequence_table, Direction::Forward)?;
+        Ok(SqliteSequence::new(&rows))
     }
 
-    pub fn schema(&self) -> SqliteSchema {
-        let schema_page = self.page(0);
-        SqliteSchema::new(schema_page)
+    /// The rowid the next row inserted into `table` without one would be given
+    ///
+    /// An `AUTOINCREMENT` table never reuses a rowid, so carries on from the larger
+    /// of its `sqlite_sequence` entry and its largest rowid. Other tables carry on
+    /// from their largest rowid, and SQLite picks an unused rowid at random once that
+    /// is the largest possible, which is left to the caller.
+    pub fn next_rowid(&self, table: &str) -> Result<Option<i64>> {
+        let schema = self.schema()?;
+        let Some(entry) = schema.fetch_table(table).filter(|entry| entry.is_table()) else {
+            return Err(QueryError::NoSuchTable(table.to_string()).into());
+        };
+
+        let max_rowid = self.table_edge(entry, Direction::Reverse)?;
+        if entry.columns().is_autoincrement() {
+            return Ok(self.sequence(&schema)?.next_rowid(&entry.name, max_rowid));
+        }
+
+        Ok(max_rowid.unwrap_or(0).checked_add(1))
     }
 
-    pub fn dbinfo(&self) {
-        println!("database page size: {}", self.database_header.page_size);
+    /// Prints the last rowid given out by each `AUTOINCREMENT` table matching the
+    /// LIKE `pattern`, or all of them, which is 0 until a row is inserted
+    pub fn show_sequence(&self, pattern: Option<&str>, out: &mut dyn io::Write) -> Result<()> {
+        let schema = self.schema()?;
+        let sequence = self.sequence(&schema)?;
+        for entry in schema
+            .entries()
+            .filter(|entry| entry.is_table() && entry.columns().is_autoincrement())
+            .filter(|entry| {
+                pattern.map_or(true, |pattern| {
+                    pattern::like_text(&entry.name, pattern, None)
+                })
+            })
+        {
+            let last = sequence.last_rowid(&entry.name).unwrap_or(0);
+            writeln!(out, "{}|{last}", entry.name)?;
+        }
 
-        let page = self.page(0);
-        println!("number of tables: {}", page.header.total_cells);
+        Ok(())
     }
 
-    pub fn tables(&self) -> Result<()> {
-        let schema = self.schema();
-        let tables = schema.tables();
-        let mut output = String::new();
-        for table in tables.into_iter() {
-            if table.contains("sqlite") {
-                continue;
+    pub fn dbinfo(&self, out: &mut dyn io::Write) -> Result<()> {
+        // The header stores a 65536 byte page size as 1
+        writeln!(out, "database page size: {}", self.pager.page_size())?;
+        let header = &self.database_header;
+        writeln!(
+            out,
+            "database page count: {}",
+            header.in_header_database_size
+        )?;
+        writeln!(out, "freelist page count: {}", header.total_freelist_pages)?;
+
+        let schema = self.schema()?;
+        for (kind, plural) in [
+            ("table", "tables"),
+            ("index", "indexes"),
+            ("trigger", "triggers"),
+            ("view", "views"),
+        ] {
+            let count = schema
+                .entries()
+                .filter(|entry| entry.kind() == kind)
+                .count();
+            writeln!(out, "number of {plural}: {count}")?;
+        }
+
+        match header.application_name() {
+            Some(name) => writeln!(out, "application id: {} ({name})", header.application_id())?,
+            None => writeln!(out, "application id: {}", header.application_id())?,
+        }
+        writeln!(out, "user version: {}", header.user_version())?;
+
+        Ok(())
+    }
+
+    /// Prints the names of the tables and views matching the LIKE `pattern`, or all
+    /// of them, leaving out SQLite's own `sqlite_` tables unless `internal` is set
+    ///
+    /// The names are laid out in as many columns as fit in `width` characters.
+    pub fn tables(
+        &self,
+        pattern: Option<&str>,
+        internal: bool,
+        width: usize,
+        out: &mut dyn io::Write,
+    ) -> Result<()> {
+        let schema = self.schema()?;
+        let names: Vec<&str> = schema
+            .entries()
+            .filter(|entry| entry.is_relation() && (internal || !entry.is_internal()))
+            .map(|entry| entry.name.as_str())
+            .filter(|name| pattern.map_or(true, |pattern| pattern::like_text(name, pattern, None)))
+            .collect();
+
+        write_columns(&names, width, out)?;
+        Ok(())
+    }
+
+    /// Prints the SQL that created each object, in the order they were created, only
+    /// including those on tables matching the LIKE `pattern` when one is given
+    ///
+    /// Like `sqlite3`, the columns of views and virtual tables follow their SQL in a
+    /// comment, as their SQL may not spell them out.
+    pub fn show_schema(&self, pattern: Option<&str>, out: &mut dyn io::Write) -> Result<()> {
+        let schema = self.schema()?;
+        let mut entries: Vec<_> = schema
+            .entries()
+            .filter(|entry| !entry.sql.is_empty())
+            .filter(|entry| {
+                pattern.map_or(true, |pattern| {
+                    pattern::like_text(&entry.table_name, pattern, None)
+                })
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.row_id);
+
+        for entry in entries {
+            match self.schema_columns(&schema, entry) {
+                Some(columns) => {
+                    let columns: Vec<String> = columns
+                        .iter()
+                        .map(|name| sql::quote_identifier(name))
+                        .collect();
+                    writeln!(
+                        out,
+                        "{}\n/* {}({}) */;",
+                        entry.sql,
+                        sql::quote_identifier(&entry.name),
+                        columns.join(",")
+                    )?;
+                }
+                // sqlite3 shows the shadow tables of virtual tables, whose names
+                // are quoted, as created only if they don't exist
+                None => match entry.sql.strip_prefix("CREATE TABLE ") {
+                    Some(rest) if rest.starts_with(['\'', '"']) => {
+                        writeln!(out, "CREATE TABLE IF NOT EXISTS {rest};")?
+                    }
+                    _ => writeln!(out, "{};", entry.sql)?,
+                },
             }
+        }
 
-            write!(output, "{table} ")?;
+        Ok(())
+    }
+
+    /// The columns of a view or virtual table, or `None` for other entries and those
+    /// whose columns can't be worked out, such as a view whose query isn't supported
+    fn schema_columns(&self, schema: &SqliteSchema, entry: &SchemaTable) -> Option<Vec<String>> {
+        if let Some(declaration) = entry.virtual_table() {
+            return declared_virtual_columns(&declaration);
+        }
+
+        let view = entry.view()?;
+        if view.columns.is_some() {
+            return view.columns;
+        }
+        let statement = sql::parse_select(&view.select).ok()?;
+        let statement = self.expand_all(&statement).ok()?.unwrap_or(statement);
+        let reads_table = statement.join.is_none() && statement.values.is_none();
+        match schema.fetch_table(&statement.table) {
+            Some(table) if table.is_table() => check_columns(&statement, &table.columns()).ok()?,
+            None if reads_table
+                && statement.with.is_empty()
+                && vtab::virtual_table(&statement.table).is_none() =>
+            {
+                return None
+            }
+            _ => {}
         }
-        println!("{}", output.trim());
+        let metadata = self.column_metadata(&statement).ok()?;
+        Some(metadata.into_iter().map(|column| column.name).collect())
+    }
 
+    /// Runs a query and prints each row of its result with columns separated by `|`
+    pub fn query(&self, query: &str, out: &mut dyn io::Write) -> Result<()> {
+        write!(out, "{}", self.select(query)?)?;
         Ok(())
     }
 
     // Only supporting select statements for now
-    pub fn query(&self, query: &str) -> Result<()> {
-        let schema = self.schema();
-        let (_, statement) = sql::select_statement(query).unwrap();
+    pub fn select(&self, query: &str) -> Result<ResultSet> {
+        debug!(sql = query, "parsing query");
+        let statement = sql::parse_select(query)?;
+
+        if let Some(name) = statement
+            .schema
+            .as_ref()
+            .filter(|name| !name.eq_ignore_ascii_case(database::MAIN))
+        {
+            bail!("no such database '{name}'");
+        }
 
+        self.execute_select(&statement)
+    }
+
+    /// Runs a parsed SELECT against this database, whatever database its table was
+    /// qualified with
+    pub fn execute_select(&self, statement: &SelectStatement) -> Result<ResultSet> {
+        if !statement.with.is_empty() {
+            return self.with_common_tables(statement);
+        }
+
+        if let Some(expanded) = self.expand_all(statement)? {
+            return self.execute_select(&expanded);
+        }
+
+        let filtered = !statement.where_clause.is_empty() || statement.filters_rows();
+        if statement.operation.is_some() && (filtered || statement.join.is_some()) {
+            return self.filtered_aggregate(statement);
+        }
+
+        if statement.projection.is_some() || statement.filters_rows() || statement.join.is_some() {
+            return self.evaluate_select(statement);
+        }
+
+        if let Some(values) = &statement.values {
+            return vtab::select(&written_rows(values)?, statement);
+        }
+
+        if let Some(table) = statement
+            .schema
+            .is_none()
+            .then(|| self.common_table(&statement.table))
+            .flatten()
+        {
+            return vtab::select(table.as_ref(), statement);
+        }
+
+        let schema = self.schema()?;
         let Some(table) = schema.fetch_table(&statement.table) else {
-            eprintln!("error: no such table '{}'", statement.table);
-            return Ok(());
+            if let Some(table) = vtab::virtual_table(&statement.table) {
+                return vtab::select(table.as_ref(), statement);
+            }
+
+            return Err(QueryError::NoSuchTable(statement.table.clone()).into());
         };
 
-        match statement.where_clause {
-            Some(_) => match schema.fetch_index(&statement.table) {
-                Some(idx) => self.index_scan(idx, table, &statement),
-                None => self.full_table_scan(table, &statement),
-            },
-            None => self.full_table_scan(table, &statement),
+        if let Some(declaration) = table.virtual_table() {
+            return self.declared_virtual_select(&declaration, statement);
+        }
+
+        if !table.is_table() {
+            bail!("'{}' isn't a table with rowids", statement.table);
+        }
+        check_columns(statement, &table.columns())?;
+
+        match &statement.operation {
+            Some(SelectOperation::Min(column)) => {
+                return self.min_max(&schema, table, column, Direction::Forward)
+            }
+            Some(SelectOperation::Max(column)) => {
+                return self.min_max(&schema, table, column, Direction::Reverse)
+            }
+            _ => {}
+        }
+
+        let stats = self.stats(&schema)?;
+        let plan = planner::plan(&schema, &stats, statement);
+        debug!(
+            table = %statement.table,
+            scan = %plan.scan,
+            direction = ?plan.direction,
+            sort = plan.sort,
+            "planned query"
+        );
+        match plan.scan {
+            ScanPlan::Index(idx) => self.index_scan(idx, table, statement, &plan),
+            ScanPlan::CoveringIndex(idx) => {
+                self.covering_index_scan(idx, table, statement, plan.direction)
+            }
+            ScanPlan::IndexIntersection(lhs, rhs) => {
+                self.intersection_scan([lhs, rhs], table, statement, &plan)
+            }
+            ScanPlan::FullTable => self.full_table_scan(table, statement, &plan),
         }
     }
 
-    fn full_table_scan(&self, table: &SchemaTable, statement: &SelectStatement) -> Result<()> {
-        let table_page = self.page(table.root_page as usize);
-        if statement.operation.is_some() {
-            println!("{}", table_page.count());
-            return Ok(());
+    /// The query with `*` replaced by the columns of the table it reads, or `None`
+    /// when it doesn't select `*` or reads a table whose columns aren't known
+    fn expand_all(&self, statement: &SelectStatement) -> Result<Option<SelectStatement>> {
+        if !statement.selects_all() || statement.join.is_some() || statement.values.is_some() {
+            return Ok(None);
         }
 
+        let common = statement
+            .schema
+            .is_none()
+            .then(|| self.common_table(&statement.table))
+            .flatten();
+        let columns = match common {
+            Some(table) => vtab::VirtualTable::columns(table.as_ref()),
+            None => {
+                let schema = self.schema()?;
+                let columns = match schema.fetch_table(&statement.table) {
+                    Some(table) => match table.virtual_table() {
+                        Some(declaration) => declared_virtual_columns(&declaration),
+                        None if table.is_table() => Some(
+                            table
+                                .columns()
+                                .columns
+                                .into_iter()
+                                .map(|column| column.name)
+                                .collect(),
+                        ),
+                        None => None,
+                    },
+                    None => vtab::virtual_table(&statement.table).map(|table| table.columns()),
+                };
+                match columns {
+                    Some(columns) => columns,
+                    None => return Ok(None),
+                }
+            }
+        };
+
+        Ok(Some(statement.expand_all(&columns)))
+    }
+
+    /// The declared type and origin of each column a query answers with, leaving the
+    /// database for the caller to fill in
+    pub fn column_metadata(&self, statement: &SelectStatement) -> Result<Vec<ColumnMetadata>> {
+        if let Some(expanded) = self.expand_all(statement)? {
+            return self.column_metadata(&expanded);
+        }
+
+        let schema = self.schema()?;
+        let table = schema
+            .fetch_table(&statement.table)
+            .filter(|table| table.is_table() && table.virtual_table().is_none());
+        let create = table.map(|table| table.columns());
+        let join = match &statement.join {
+            Some(join) => Some(self.plan_join(statement, join)?),
+            None => None,
+        };
+
+        // A column read straight from a table is named as the table declares it,
+        // however the query spelled it
+        let origin = |name: &str| {
+            let mut metadata = ColumnMetadata::computed(name);
+            let found = match (&join, table, &create) {
+                (Some(join), _, _) => join.column(name),
+                (None, Some(table), Some(create)) => create
+                    .columns
+                    .iter()
+                    .find(|c| sql::same_name(&c.name, name))
+                    .map(|column| (table.name.as_str(), column)),
+                _ => None,
+            };
+            if let Some((table, column)) = found {
+                metadata.declared_type = Some(column.datatype.clone()).filter(|t| !t.is_empty());
+                metadata.name = column.name.clone();
+                metadata.table = Some(table.to_string());
+                metadata.column = Some(column.name.clone());
+            }
+            metadata
+        };
+
+        let metadata = match (&statement.operation, &statement.projection) {
+            (Some(SelectOperation::Count), _) => vec![ColumnMetadata::computed("count(*)")],
+            (Some(SelectOperation::Min(column)), _) => {
+                vec![ColumnMetadata::computed(&format!("min({column})"))]
+            }
+            (Some(SelectOperation::Max(column)), _) => {
+                vec![ColumnMetadata::computed(&format!("max({column})"))]
+            }
+            (None, Some(projection)) => projection
+                .iter()
+                .map(|expr| match expr {
+                    Expr::Column(name) => origin(name),
+                    expr => ColumnMetadata::computed(&expr.to_string()),
+                })
+                .collect(),
+            (None, None) => statement.columns.iter().map(|name| origin(name)).collect(),
+        };
+
+        Ok(metadata)
+    }
+
+    /// Runs a query handing each row to `row` as it's produced, stopping as soon as
+    /// `row` breaks, and answers with how many rows it was handed
+    ///
+    /// A query scanning a whole table in rowid order reads each row off the cursor
+    /// just before handing it over, so breaking early leaves the rest of the table
+    /// unread. Other queries are run to the end first.
+    pub fn select_each(
+        &self,
+        statement: &SelectStatement,
+        row: &mut dyn FnMut(&[RecordValue]) -> ControlFlow<()>,
+    ) -> Result<usize> {
+        if let Some(expanded) = self.expand_all(statement)? {
+            return self.select_each(&expanded, row);
+        }
+
+        let mut handed = 0;
+        let Some((root, table_schema, direction)) = self.streaming_scan(statement)? else {
+            for values in self.execute_select(statement)?.rows.iter() {
+                handed += 1;
+                if row(values).is_break() {
+                    break;
+                }
+            }
+
+            return Ok(handed);
+        };
+
+        let mut cursor = BtCursor::table(self, root);
+        cursor.start(direction)?;
+        while let Some(leaf) = cursor.row() {
+            let values = leaf.query_row(
+                &statement.columns,
+                &table_schema.columns,
+                &statement.where_clause,
+            )?;
+
+            if let Some(values) = values {
+                handed += 1;
+                if row(&values).is_break() {
+                    break;
+                }
+            }
+            cursor.step(direction)?;
+        }
+
+        Ok(handed)
+    }
+
+    /// The root page, columns and direction of a query that can be answered by
+    /// reading rows straight off a cursor over its table in the order it gives them
+    fn streaming_scan(
+        &self,
+        statement: &SelectStatement,
+    ) -> Result<Option<(usize, CreateTable, Direction)>> {
+        if statement.projection.is_some()
+            || statement.filters_rows()
+            || statement.join.is_some()
+            || statement.operation.is_some()
+        {
+            return Ok(None);
+        }
+
+        let schema = self.schema()?;
+        let Some(table) = schema
+            .fetch_table(&statement.table)
+            .filter(|table| table.is_table() && table.virtual_table().is_none())
+        else {
+            return Ok(None);
+        };
+
         let table_schema = table.columns();
-        let rows = self.traverse_rows(&table_page);
-        let cols: Vec<String> = rows
+        check_columns(statement, &table_schema)?;
+
+        let stats = self.stats(&schema)?;
+        let plan = planner::plan(&schema, &stats, statement);
+        match (plan.scan, plan.sort) {
+            (ScanPlan::FullTable, false) => Ok(Some((
+                table.root_page as usize,
+                table_schema,
+                plan.direction,
+            ))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Reads the columns a query's expressions refer to, then computes the
+    /// expressions for each row that passes the row filters and `IN` tests
+    fn evaluate_select(&self, statement: &SelectStatement) -> Result<ResultSet> {
+        let projection = match &statement.projection {
+            Some(projection) => projection.clone(),
+            None => statement
+                .columns
+                .iter()
+                .cloned()
+                .map(Expr::Column)
+                .collect(),
+        };
+
+        let filters = &statement.row_filters;
+        for expr in projection
             .iter()
-            .filter_map(|row| self.parse_row(statement, &table_schema, row))
+            .chain(filters.iter().map(|filter| &filter.expr))
+            .chain(statement.in_filters.iter().map(|filter| &filter.expr))
+        {
+            expr.check_functions()?;
+        }
+
+        let mut tests = self.row_tests(statement)?;
+        let mut base = SelectStatement {
+            projection: None,
+            row_filters: Vec::new(),
+            in_filters: Vec::new(),
+            column_filters: Vec::new(),
+            exists_filters: Vec::new(),
+            ..statement.clone()
+        };
+        for name in tests.outer_columns.iter() {
+            if !base
+                .columns
+                .iter()
+                .any(|column| sql::same_name(column, name))
+            {
+                base.columns.push(name.clone());
+            }
+        }
+        let rows = match &statement.join {
+            Some(join) => self.join_rows(&base, join)?,
+            None => match self.expression_index_scan(statement, &base)? {
+                Some(rows) => rows,
+                None => self.execute_select(&base)?,
+            },
+        };
+
+        let mut result = ResultSet::new(projection.iter().map(Expr::to_string).collect());
+        'rows: for row in rows.rows {
+            let column = |name: &str| {
+                base.columns
+                    .iter()
+                    .position(|column| sql::same_name(column, name))
+                    .map(|idx| row[idx].clone())
+                    .ok_or_else(|| QueryError::NoSuchColumn(name.to_string()).into())
+            };
+
+            for filter in filters.iter() {
+                let value = filter.expr.evaluate(&column)?;
+                let collation = filter.collation.clone().unwrap_or_default();
+                if !filter.operator.test(&value, &filter.value, &collation) {
+                    continue 'rows;
+                }
+            }
+
+            if !self.passes(&mut tests, &column)? {
+                continue;
+            }
+
+            let values = projection
+                .iter()
+                .map(|expr| expr.evaluate(&column))
+                .collect::<Result<Vec<_>>>()?;
+            result.rows.push(values);
+        }
+
+        Ok(result)
+    }
+
+    /// The `base` rows of a statement with a row filter an index on its expression can
+    /// answer, looking them up through the index rather than scanning the table
+    ///
+    /// The filters are still checked for every row found.
+    fn expression_index_scan(
+        &self,
+        statement: &SelectStatement,
+        base: &SelectStatement,
+    ) -> Result<Option<ResultSet>> {
+        let schema = self.schema()?;
+        let Some(table) = schema.fetch_table(&statement.table) else {
+            return Ok(None);
+        };
+
+        let stats = self.stats(&schema)?;
+        let Some((index, filter)) = planner::expression_index(&schema, &stats, statement) else {
+            return Ok(None);
+        };
+
+        debug!(table = %statement.table, index = %index.name, "planned expression index scan");
+        let Some(probe) = IndexProbe::for_filter(index, filter) else {
+            return Ok(None);
+        };
+
+        let mut row_ids = Vec::new();
+        self.search_index(index, Some(&probe), Direction::Forward, &mut |row_id, _| {
+            row_ids.push(row_id)
+        })?;
+        row_ids.sort_unstable();
+        row_ids.dedup();
+
+        let table_schema = table.columns();
+        let mut rows = self.fetch_rows(table, &row_ids)?;
+        if !base.order_by.is_empty() {
+            sort_rows(&mut rows, &base.order_by, &table_schema)?;
+        }
+
+        project_rows(base, &table_schema, &rows).map(Some)
+    }
+
+    /// Answers a query against a virtual table declared in the schema with the
+    /// module it was declared with
+    fn declared_virtual_select(
+        &self,
+        declaration: &CreateVirtualTable,
+        statement: &SelectStatement,
+    ) -> Result<ResultSet> {
+        if let Some(table) = Fts5Table::new(declaration) {
+            return self.fts5_select(&table, statement);
+        }
+
+        if let Some(table) = RTreeTable::new(declaration) {
+            return self.rtree_select(&table, statement);
+        }
+
+        bail!("no such module: {}", declaration.module)
+    }
+
+    /// Counts the rows of a table b-tree from the cell count in each leaf page
+    /// header, so none of the leaf records are decoded
+    fn count_rows(&self, root: usize) -> Result<u64> {
+        self.count_subtree_rows(root, root, 0)
+    }
+
+    /// Counts the rows under `page`, `depth` pages below the root, failing as the
+    /// cursor does on a b-tree deeper than [`cursor::MAX_DEPTH`]
+    fn count_subtree_rows(&self, root: usize, page: usize, depth: usize) -> Result<u64> {
+        cursor::check_depth(root, depth)?;
+        self.interrupt.check()?;
+        let header = self.page_header(page)?;
+        match header.page_type {
+            BTreePageType::LeafTable => Ok(u64::from(header.total_cells)),
+            BTreePageType::InteriorTable => {
+                let interior_page = self.page(page)?;
+                interior_page
+                    .cells
+                    .iter()
+                    .filter_map(|cell| match cell {
+                        DatabaseCell::InteriorTable(table_cell) => Some(table_cell.left_child),
+                        _ => None,
+                    })
+                    .chain(interior_page.right_page_pointer())
+                    .map(|child| self.count_subtree_rows(root, child as usize, depth + 1))
+                    .sum()
+            }
+            // An index page where the table b-tree should be means the file is corrupt
+            other => Err(DecodeError::UnexpectedPageType {
+                expected: BTreePageType::LeafTable,
+                found: other,
+            }
+            .into()),
+        }
+    }
+
+    /// Answers `COUNT`/`MIN`/`MAX` over only the rows passing the WHERE clause, which
+    /// means reading every one of them rather than the cell counts or edges of a
+    /// b-tree
+    fn filtered_aggregate(&self, statement: &SelectStatement) -> Result<ResultSet> {
+        let (name, column) = match &statement.operation {
+            Some(SelectOperation::Min(column)) => (format!("min({column})"), Some(column)),
+            Some(SelectOperation::Max(column)) => (format!("max({column})"), Some(column)),
+            _ => ("count(*)".to_string(), None),
+        };
+
+        let rows = self.execute_select(&aggregated_rows(statement))?.rows;
+
+        let Some(column) = column else {
+            return Ok(ResultSet::single(name, RecordValue::I64(rows.len() as i64)));
+        };
+
+        let schema = self.schema()?;
+        let collation = schema
+            .fetch_table(&statement.table)
+            .filter(|table| table.is_table())
+            .map(|table| table.columns().column_collation(column))
+            .unwrap_or_default();
+        let values = rows
+            .into_iter()
+            .map(|mut row| row.swap_remove(0))
+            .filter(|value| *value != RecordValue::Null);
+        let value = match statement.operation {
+            Some(SelectOperation::Min(_)) => {
+                values.min_by(|lhs, rhs| lhs.sort_cmp(rhs, &collation))
+            }
+            _ => values.max_by(|lhs, rhs| lhs.sort_cmp(rhs, &collation)),
+        };
+
+        Ok(ResultSet::single(name, value.unwrap_or(RecordValue::Null)))
+    }
+
+    /// Answers `MIN`/`MAX` by following only the leftmost or rightmost path of the
+    /// rowid or index b-tree, falling back to scanning every row otherwise
+    fn min_max(
+        &self,
+        schema: &SqliteSchema,
+        table: &SchemaTable,
+        column: &str,
+        direction: Direction,
+    ) -> Result<ResultSet> {
+        let table_schema = table.columns();
+        let rowid_alias = table_schema.rowid_alias().map(|c| c.name.as_str());
+        let collation = table_schema.column_collation(column);
+
+        let value = if rowid_alias.is_some_and(|alias| sql::same_name(alias, column)) {
+            self.table_edge(table, direction)?.map(RecordValue::I64)
+        } else if let Some(index) = schema.fetch_index(&table.name, column, &collation, &[]) {
+            // The smallest key of a descending index is its last
+            let direction = match index.indexed_columns().first().map(|key| key.order) {
+                Some(SortOrder::Desc) => direction.reversed(),
+                _ => direction,
+            };
+
+            self.index_edge(index, direction)?
+                .map(|key| table_schema.column_affinity(column).stored(key))
+        } else {
+            let Some(idx) = table_schema
+                .columns
+                .iter()
+                .position(|c| sql::same_name(&c.name, column))
+            else {
+                return Err(QueryError::NoSuchColumn(column.to_string()).into());
+            };
+
+            let values = self
+                .collect_rows(table, Direction::Forward)?
+                .into_iter()
+                .map(|row| row.column_value(idx, &table_schema.columns))
+                .filter(|value| *value != RecordValue::Null);
+
+            match direction {
+                Direction::Forward => values.min_by(|lhs, rhs| lhs.sort_cmp(rhs, &collation)),
+                Direction::Reverse => values.max_by(|lhs, rhs| lhs.sort_cmp(rhs, &collation)),
+            }
+        };
+
+        let function = match direction {
+            Direction::Forward => "min",
+            Direction::Reverse => "max",
+        };
+
+        Ok(ResultSet::single(
+            format!("{function}({column})"),
+            value.unwrap_or(RecordValue::Null),
+        ))
+    }
+
+    /// The first rowid of the table in `direction`
+    fn table_edge(&self, table: &SchemaTable, direction: Direction) -> Result<Option<i64>> {
+        let mut cursor = BtCursor::table(self, table.root_page as usize);
+        cursor.start(direction)?;
+        Ok(cursor.row_id())
+    }
+
+    /// The first non-`NULL` leading key of the index in `direction`
+    ///
+    /// `NULL`s sort first in an index so `MIN` may have to step past them, but
+    /// otherwise only the edge path of the tree is read.
+    fn index_edge(&self, index: &SchemaTable, direction: Direction) -> Result<Option<RecordValue>> {
+        let mut cursor = BtCursor::index(self, index.root_page as usize);
+        cursor.start(direction)?;
+        while let Some((columns, _)) = cursor.entry() {
+            match columns.first() {
+                Some(RecordValue::Null) => cursor.step(direction)?,
+                key => return Ok(key.cloned()),
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn full_table_scan(
+        &self,
+        table: &SchemaTable,
+        statement: &SelectStatement,
+        plan: &QueryPlan,
+    ) -> Result<ResultSet> {
+        if matches!(statement.operation, Some(SelectOperation::Count)) {
+            let count = self.count_rows(table.root_page as usize)?;
+            return Ok(ResultSet::single(
+                "count(*)".to_string(),
+                RecordValue::I64(count as i64),
+            ));
+        }
+
+        let table_schema = table.columns();
+        let mut rows = self.collect_rows(table, plan.direction)?;
+        if plan.sort {
+            sort_rows(&mut rows, &statement.order_by, &table_schema)?;
+        }
+
+        project_rows(statement, &table_schema, &rows)
+    }
+
+    /// Probes both indexes for the rowids matching their conditions and fetches only
+    /// the rows the two have in common, in rowid order
+    fn intersection_scan(
+        &self,
+        [lhs, rhs]: [&SchemaTable; 2],
+        table: &SchemaTable,
+        statement: &SelectStatement,
+        plan: &QueryPlan,
+    ) -> Result<ResultSet> {
+        let table_schema = table.columns();
+        let lhs = self.probe_row_ids(lhs, statement, &table_schema)?;
+        let rhs = self.probe_row_ids(rhs, statement, &table_schema)?;
+        let row_ids: Vec<i64> = lhs
+            .into_iter()
+            .filter(|row_id| rhs.binary_search(row_id).is_ok())
             .collect();
 
-        for result in cols {
-            println!("{result}");
+        debug!(rows = row_ids.len(), "intersected index probes");
+        let mut rows = self.fetch_rows(table, &row_ids)?;
+        if plan.sort {
+            sort_rows(&mut rows, &statement.order_by, &table_schema)?;
         }
 
-        Ok(())
+        project_rows(statement, &table_schema, &rows)
+    }
+
+    /// The rowids `index` holds for the statement's condition on its key, ascending
+    fn probe_row_ids(
+        &self,
+        index: &SchemaTable,
+        statement: &SelectStatement,
+        table_schema: &CreateTable,
+    ) -> Result<Vec<i64>> {
+        let mut row_ids = Vec::new();
+        self.search_index(
+            index,
+            IndexProbe::new(index, statement, table_schema).as_ref(),
+            Direction::Forward,
+            &mut |row_id, _| row_ids.push(row_id),
+        )?;
+        row_ids.sort_unstable();
+        row_ids.dedup();
+
+        Ok(row_ids)
     }
 
     fn index_scan(
@@ -193,170 +1356,470 @@ impl SqliteReader {
         index: &SchemaTable,
         table: &SchemaTable,
         statement: &SelectStatement,
-    ) -> Result<()> {
-        let index_page = self.page(index.root_page as usize);
+        plan: &QueryPlan,
+    ) -> Result<ResultSet> {
+        let table_schema = table.columns();
         let mut row_ids = Vec::new();
-        let search_key = &statement.where_clause.as_ref().unwrap().value;
-        self.search_index(&index_page, search_key, &mut row_ids);
+        self.search_index(
+            index,
+            IndexProbe::new(index, statement, &table_schema).as_ref(),
+            plan.direction,
+            &mut |row_id, _| row_ids.push(row_id),
+        )?;
 
-        let mut target_rows = Vec::new();
-        let table_page = self.page(table.root_page as usize);
-        for id in row_ids {
-            self.traverse_indexed_rows(&table_page, id, &mut target_rows);
-        }
+        let mut sorted_ids = row_ids.clone();
+        sorted_ids.sort_unstable();
+        sorted_ids.dedup();
 
-        let table_schema = table.columns();
-        let cols: Vec<String> = target_rows
+        let target_rows = self.fetch_rows(table, &sorted_ids)?;
+
+        // Emit in index order rather than the rowid order the rows were fetched in
+        let mut target_rows: HashMap<i64, LeafCell> = target_rows
+            .into_iter()
+            .map(|row| (row.row_id, row))
+            .collect();
+
+        let mut rows: Vec<LeafCell> = row_ids
             .iter()
-            .filter_map(|row| self.parse_row(statement, &table_schema, row))
+            .filter_map(|id| target_rows.remove(id))
             .collect();
 
-        for result in cols {
-            println!("{result}");
+        if plan.sort {
+            sort_rows(&mut rows, &statement.order_by, &table_schema)?;
         }
-        Ok(())
+
+        project_rows(statement, &table_schema, &rows)
     }
 
-    fn search_index(&self, page: &BTreePage, search_key: &str, row_ids: &mut Vec<u64>) {
-        match page.page_type() {
-            BTreePageType::InteriorIndex => {
-                let mut recursed_left = false;
-                for cell in page.cells.iter() {
-                    let DatabaseCell::InteriorIndex(index_cell) = cell else {
-                        panic!("expected an interior index cell - found {cell:#?}");
-                    };
-
-                    let index_key = index_cell.key.as_str();
-                    if search_key < index_key {
-                        let left_page = self.page(index_cell.left_child as usize);
-                        self.search_index(&left_page, search_key, row_ids);
-                        recursed_left = true;
-                    } else if index_key == search_key {
-                        row_ids.push(index_cell.row_id);
-                        let left_page = self.page(index_cell.left_child as usize);
-                        self.search_index(&left_page, search_key, row_ids);
-                        recursed_left = true;
-                    }
-                }
+    fn covering_index_scan(
+        &self,
+        index: &SchemaTable,
+        table: &SchemaTable,
+        statement: &SelectStatement,
+        direction: Direction,
+    ) -> Result<ResultSet> {
+        let table_schema = table.columns();
+        let rowid_alias = table_schema
+            .rowid_alias()
+            .map(|column| column.name.as_str());
+        let index_columns = index.index_columns();
 
-                if !recursed_left {
-                    if let Some(rp) = page.right_page_pointer() {
-                        let right_page = self.page(rp as usize);
-                        self.search_index(&right_page, search_key, row_ids);
-                    }
-                }
-            }
-            BTreePageType::LeafIndex => {
-                for cell in page.cells.iter() {
-                    let DatabaseCell::IndexLeaf(leaf) = cell else {
-                        panic!("expected index leaf cell - found {cell:#?}");
-                    };
-
-                    if leaf.key == search_key {
-                        row_ids.push(leaf.row_id);
+        // Where each column is in the key, or `None` for the rowid after it
+        let positions = statement
+            .columns
+            .iter()
+            .map(
+                |column| match index_columns.iter().position(|c| sql::same_name(c, column)) {
+                    Some(idx) => Ok(Some(idx)),
+                    None if rowid_alias.is_some_and(|alias| sql::same_name(alias, column)) => {
+                        Ok(None)
                     }
-                }
-            }
-            _ => {}
-        }
-    }
+                    None => bail!(
+                        "internal error: index {} doesn't cover {column}",
+                        index.name
+                    ),
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
 
-    fn traverse_indexed_rows(&self, page: &BTreePage, id: u64, target_rows: &mut Vec<LeafCell>) {
-        let cells = &page.cells;
-        match page.page_type() {
-            BTreePageType::InteriorTable => {
-                for cell in cells.iter() {
-                    let DatabaseCell::InteriorTable(table_cell) = cell else {
-                        panic!("expected interior table cell - found {cell:#?}");
-                    };
-
-                    if id <= table_cell.row_id {
-                        let left_page = self.page(table_cell.left_child as usize);
-                        return self.traverse_indexed_rows(&left_page, id, target_rows);
-                    }
-                }
+        let mut result = ResultSet::new(statement.columns.clone());
+        let probe = IndexProbe::new(index, statement, &table_schema);
+        self.search_index(index, probe.as_ref(), direction, &mut |row_id, columns| {
+            let row = statement
+                .columns
+                .iter()
+                .zip(&positions)
+                .map(|(column, position)| match position {
+                    Some(idx) => table_schema
+                        .column_affinity(column)
+                        .stored(columns.get(*idx).cloned().unwrap_or(RecordValue::Null)),
+                    None => RecordValue::I64(row_id),
+                })
+                .collect();
 
-                let Some(rp) = page.right_page_pointer() else {
-                    panic!("expected right page pointer - found none");
-                };
+            result.rows.push(row);
+        })?;
 
-                let right_page = self.page(rp as usize);
-                self.traverse_indexed_rows(&right_page, id, target_rows)
+        Ok(result)
+    }
+
+    /// Walks the index in `direction` calling `visit` with the rowid and key columns
+    /// of every entry satisfying the condition, or of every entry without one
+    fn search_index(
+        &self,
+        index: &SchemaTable,
+        probe: Option<&IndexProbe>,
+        direction: Direction,
+        visit: &mut impl FnMut(i64, &[RecordValue]),
+    ) -> Result<()> {
+        let mut cursor = BtCursor::index(self, index.root_page as usize);
+        match probe {
+            Some(probe) => {
+                debug!(
+                    index = %index.name,
+                    operator = ?probe.operator,
+                    value = %probe.value,
+                    "probing index"
+                );
+                probe.seek(&mut cursor, direction)?
             }
-            BTreePageType::LeafTable => {
-                let idx = match cells.binary_search_by(|cell| {
-                    let DatabaseCell::Leaf(leaf) = cell else {
-                        panic!("expected leaf cell - found {cell:#?}");
-                    };
-
-                    leaf.row_id.cmp(&id)
-                }) {
-                    Ok(idx) => idx,
-                    Err(_) => return,
-                };
+            None => {
+                debug!(index = %index.name, "scanning index");
+                cursor.start(direction)?
+            }
+        }
 
-                let DatabaseCell::Leaf(leaf) = &cells[idx] else {
-                    panic!("expected leaf cell - found {:#?}", &cells[idx]);
-                };
+        while let Some((columns, row_id)) = cursor.entry() {
+            if let Some(probe) = probe {
+                let key = columns.first().ok_or(DecodeError::MissingIndexKey)?;
+                if probe.is_past(key, direction) {
+                    break;
+                }
 
-                if id == leaf.row_id {
-                    target_rows.push(leaf.clone());
+                if !probe.matches(key) {
+                    cursor.step(direction)?;
+                    continue;
                 }
             }
-            other => panic!("expected table page - found {other:#?}"),
+
+            visit(row_id, columns);
+            cursor.step(direction)?;
         }
+
+        Ok(())
     }
 
-    // FIX: Rework this to be cleaner
-    fn traverse_rows(&self, page: &BTreePage) -> Vec<LeafCell> {
-        let mut rows = vec![];
-        let cells = &page.cells;
+    /// Collects every row of the table b-tree in rowid order, or reverse rowid order
+    fn collect_rows(&self, table: &SchemaTable, direction: Direction) -> Result<Vec<LeafCell>> {
+        let mut cursor = BtCursor::table(self, table.root_page as usize);
+        cursor.start(direction)?;
 
-        for cell in cells.iter() {
-            match cell {
-                DatabaseCell::Leaf(leaf) => rows.push(leaf.clone()),
-                DatabaseCell::InteriorTable(interior_table) => {
-                    let page = self.page(interior_table.left_child as usize);
-                    let interior_cells = self.traverse_rows(&page);
-                    rows.extend(interior_cells);
+        let mut rows = Vec::new();
+        while let Some(row) = cursor.row() {
+            rows.push(row.clone());
+            cursor.step(direction)?;
+        }
 
-                    if let Some(rpp) = page.right_page_pointer() {
-                        let right_page = self.page(rpp as usize);
-                        let interior_cells = self.traverse_rows(&right_page);
-                        rows.extend(interior_cells);
-                    }
-                }
-                _ => todo!("traversing rows"),
+        Ok(rows)
+    }
+
+    /// Fetches the rows for an ascending set of rowids, reusing the cursor's leaf
+    /// between neighbouring ids instead of seeking each from the root
+    fn fetch_rows(&self, table: &SchemaTable, row_ids: &[i64]) -> Result<Vec<LeafCell>> {
+        let mut cursor = BtCursor::table(self, table.root_page as usize);
+        let mut rows = Vec::with_capacity(row_ids.len());
+        for &id in row_ids.iter() {
+            cursor.seek_rowid(id)?;
+            if let Some(row) = cursor.row().filter(|row| row.row_id == id) {
+                rows.push(row.clone());
             }
         }
 
-        rows
+        Ok(rows)
     }
+}
 
-    fn parse_row(
-        &self,
+/// A WHERE condition on the leading column of an index, expressed in the order
+/// the index stores its keys so descending and collated indexes can be searched
+struct IndexProbe {
+    operator: Operator,
+    value: RecordValue,
+    key: IndexedColumn,
+}
+
+impl IndexProbe {
+    fn new(
+        index: &SchemaTable,
         statement: &SelectStatement,
         table_schema: &CreateTable,
-        row: &LeafCell,
-    ) -> Option<String> {
-        match row.query_row(
+    ) -> Option<Self> {
+        let key = index.index_keys(table_schema).into_iter().next()?;
+        let condition = statement.where_clause.iter().find(|condition| {
+            sql::same_name(&condition.column, &key.name) && condition.operator.is_comparison()
+        })?;
+        let column = table_schema
+            .columns
+            .iter()
+            .find(|c| sql::same_name(&c.name, &key.name))?;
+
+        Some(Self {
+            operator: match key.order {
+                SortOrder::Asc => condition.operator,
+                SortOrder::Desc => condition.operator.reversed(),
+            },
+            value: column.affinity().apply(&condition.value),
+            key,
+        })
+    }
+
+    /// Probes an index on the expression a row filter compares, whose values have no
+    /// affinity to convert the literal to
+    fn for_filter(index: &SchemaTable, filter: &ExprCondition) -> Option<Self> {
+        let key = index.indexed_columns().into_iter().next()?;
+
+        Some(Self {
+            operator: match key.order {
+                SortOrder::Asc => filter.operator,
+                SortOrder::Desc => filter.operator.reversed(),
+            },
+            value: filter.value.clone(),
+            key,
+        })
+    }
+
+    /// Compares a key against the probed value by position in the index
+    fn key_cmp(&self, key: &RecordValue) -> std::cmp::Ordering {
+        cell::compare_keys(
+            std::slice::from_ref(key),
+            std::slice::from_ref(&self.value),
+            std::slice::from_ref(&self.key),
+        )
+    }
+
+    fn matches(&self, key: &RecordValue) -> bool {
+        *key != RecordValue::Null && self.operator.matches(self.key_cmp(key))
+    }
+
+    /// Positions the cursor on the first key that can match when scanning in `direction`
+    fn seek<P: Pager>(&self, cursor: &mut BtCursor<P>, direction: Direction) -> Result<()> {
+        use std::cmp::Ordering::{Greater, Less};
+
+        // An entry without a key stops the seek, so the walk reaches it and reports it
+        let keyless = match direction {
+            Direction::Forward => Greater,
+            Direction::Reverse => Less,
+        };
+        let key_cmp = |key: &[RecordValue]| key.first().map_or(keyless, |key| self.key_cmp(key));
+        match (direction, self.operator) {
+            (Direction::Forward, Operator::Eq | Operator::GtEq) => cursor.seek(key_cmp),
+            (Direction::Forward, Operator::Gt) => cursor.seek(|key| match key_cmp(key) {
+                Greater => Greater,
+                _ => Less,
+            }),
+            (Direction::Reverse, Operator::Eq | Operator::LtEq) => cursor.seek_last(key_cmp),
+            (Direction::Reverse, Operator::Lt) => cursor.seek_last(|key| match key_cmp(key) {
+                Less => Less,
+                _ => Greater,
+            }),
+            _ => cursor.start(direction),
+        }
+    }
+
+    /// Whether `key` and every key after it in `direction` are beyond the matches
+    fn is_past(&self, key: &RecordValue, direction: Direction) -> bool {
+        use std::cmp::Ordering::{Greater, Less};
+
+        let ordering = self.key_cmp(key);
+        match (direction, self.operator) {
+            (Direction::Forward, Operator::Eq | Operator::LtEq) => ordering == Greater,
+            (Direction::Forward, Operator::Lt) => ordering != Less,
+            (Direction::Reverse, Operator::Eq | Operator::GtEq) => ordering == Less,
+            (Direction::Reverse, Operator::Gt) => ordering != Greater,
+            _ => false,
+        }
+    }
+}
+
+/// The query for the rows an aggregate with a WHERE clause is taken over, reading
+/// the aggregated column first and then any the row filters refer to
+fn aggregated_rows(statement: &SelectStatement) -> SelectStatement {
+    let mut columns: Vec<String> = match &statement.operation {
+        Some(SelectOperation::Min(column) | SelectOperation::Max(column)) => vec![column.clone()],
+        _ => Vec::new(),
+    };
+    let referenced = statement
+        .row_filters
+        .iter()
+        .flat_map(|filter| filter.expr.columns())
+        .chain(
+            statement
+                .in_filters
+                .iter()
+                .flat_map(|filter| filter.columns()),
+        )
+        .chain(
+            statement
+                .column_filters
+                .iter()
+                .flat_map(|filter| [filter.column.as_str(), filter.other.as_str()]),
+        );
+    for name in referenced {
+        if !columns.iter().any(|c| sql::same_name(c, name)) {
+            columns.push(name.to_string());
+        }
+    }
+
+    SelectStatement {
+        operation: None,
+        columns,
+        ..statement.clone()
+    }
+}
+
+/// The rows written out in a query, as a table whose columns are named `column1`,
+/// `column2` and so on
+fn written_rows(values: &[Vec<Expr>]) -> Result<vtab::MemoryTable> {
+    let width = values.first().map_or(0, Vec::len);
+    if values.iter().any(|row| row.len() != width) {
+        bail!("all VALUES must have the same number of terms");
+    }
+
+    let rows = values
+        .iter()
+        .map(|row| evaluate_constants(row))
+        .collect::<Result<_>>()?;
+    let columns = (1..=width).map(|i| format!("column{i}")).collect();
+    Ok(vtab::MemoryTable::new(columns, rows))
+}
+
+/// The values of a list that doesn't refer to any column
+fn evaluate_constants(list: &[Expr]) -> Result<Vec<RecordValue>> {
+    let no_columns = |name: &str| Err(QueryError::NoSuchColumn(name.to_string()).into());
+    list.iter().map(|item| item.evaluate(&no_columns)).collect()
+}
+
+/// Picks out the selected columns of the rows satisfying the WHERE condition
+/// The columns of a virtual table declared in the schema, in the order `*` selects
+/// them
+fn declared_virtual_columns(declaration: &CreateVirtualTable) -> Option<Vec<String>> {
+    if let Some(table) = RTreeTable::new(declaration) {
+        return Some([table.columns, table.auxiliary].concat());
+    }
+
+    Fts5Table::new(declaration).map(|table| table.columns)
+}
+
+/// Fails on the first column the query reads, tests or sorts by that the table
+/// doesn't have, before any of its rows are read
+fn check_columns(
+    statement: &SelectStatement,
+    table_schema: &CreateTable,
+) -> Result<(), QueryError> {
+    let read = statement.columns.iter();
+    let tested = statement
+        .where_clause
+        .iter()
+        .map(|condition| &condition.column);
+    let sorted = statement.order_by.iter().map(|term| &term.column);
+    match read.chain(tested).chain(sorted).find(|name| {
+        !table_schema
+            .columns
+            .iter()
+            .any(|column| sql::same_name(&column.name, name))
+    }) {
+        Some(name) => Err(QueryError::NoSuchColumn(name.clone())),
+        None => Ok(()),
+    }
+}
+
+fn project_rows(
+    statement: &SelectStatement,
+    table_schema: &CreateTable,
+    rows: &[LeafCell],
+) -> Result<ResultSet> {
+    let mut result = ResultSet::new(statement.columns.clone());
+    for row in rows.iter() {
+        let values = row.query_row(
             &statement.columns,
             &table_schema.columns,
             &statement.where_clause,
-        ) {
-            Ok(s) => {
-                if !s.is_empty() {
-                    Some(s)
-                } else {
-                    None
-                }
-            }
-            Err(e) => {
-                eprintln!("{e}");
-                None
+        )?;
+
+        result.rows.extend(values);
+    }
+
+    Ok(result)
+}
+
+/// Sorts rows by the ORDER BY terms, comparing values the way SQLite orders them
+fn sort_rows(
+    rows: &mut [LeafCell],
+    order_by: &[OrderingTerm],
+    table_schema: &CreateTable,
+) -> Result<(), QueryError> {
+    let mut keys = Vec::with_capacity(order_by.len());
+    for term in order_by.iter() {
+        let Some(idx) = table_schema
+            .columns
+            .iter()
+            .position(|c| sql::same_name(&c.name, &term.column))
+        else {
+            return Err(QueryError::NoSuchColumn(term.column.clone()));
+        };
+        let collation = term
+            .collation
+            .clone()
+            .unwrap_or_else(|| table_schema.columns[idx].collation.clone());
+        keys.push((idx, term.order, collation));
+    }
+
+    rows.sort_by(|lhs, rhs| {
+        for (idx, order, collation) in keys.iter() {
+            let columns = &table_schema.columns;
+            let ordering = lhs
+                .column_value(*idx, columns)
+                .sort_cmp(&rhs.column_value(*idx, columns), collation);
+
+            let ordering = match order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            };
+
+            if ordering != std::cmp::Ordering::Equal {
+                return ordering;
             }
         }
+
+        std::cmp::Ordering::Equal
+    });
+
+    Ok(())
+}
+
+/// The bytes of a database with a single page holding an empty `sqlite_schema`
+/// table, laid out the way SQLite creates a new database
+fn empty_database(page_size: u16) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(usize::from(page_size));
+    bytes.extend_from_slice(HEADER_MAGIC);
+    bytes.extend_from_slice(&page_size.to_be_bytes());
+    // File format versions, no reserved space and the fixed payload fractions
+    bytes.extend_from_slice(&[1, 1, 0, 64, 32, 32]);
+    let header_fields: [u32; 12] = [
+        1, // file change counter
+        1, // database size in pages
+        0, // first freelist trunk page
+        0, // freelist pages
+        0, // schema cookie
+        4, // schema format number
+        0, // default page cache size
+        0, // largest root b-tree page when auto-vacuuming
+        1, // UTF-8 text encoding
+        0, // user version
+        0, // incremental vacuum mode
+        0, // application id
+    ];
+    for field in header_fields {
+        bytes.extend_from_slice(&field.to_be_bytes());
     }
+    bytes.resize(92, 0);
+    bytes.extend_from_slice(&1u32.to_be_bytes()); // version-valid-for number
+    bytes.extend_from_slice(&SQLITE_VERSION_NUMBER.to_be_bytes());
+
+    // The schema table's root page is an empty table leaf whose content area
+    // starts at the end of the page
+    bytes.push(0x0d);
+    bytes.extend_from_slice(&[0, 0, 0, 0]);
+    bytes.extend_from_slice(&page_size.to_be_bytes());
+    bytes.push(0);
+    bytes.resize(usize::from(page_size), 0);
+
+    bytes
+}
+
+/// Reads a varint as the twos-complement 64-bit integer the format stores rowids as
+pub fn parse_signed_varint(buf: &[u8]) -> (i64, usize) {
+    let (varint, consumed) = parse_varint(buf);
+    (varint as i64, consumed)
 }
 
 pub fn parse_varint(buf: &[u8]) -> (u64, usize) {
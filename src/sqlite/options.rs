@@ -0,0 +1,93 @@
+/// How a database file is opened by [`Database::open_with`](super::database::Database::open_with)
+/// and [`SqliteReader::open_with`](super::SqliteReader::open_with)
+///
+/// The defaults are those [`Database::open`](super::database::Database::open) uses:
+/// read only, memory mapped where possible, with the write-ahead log read over the
+/// file and any page that fails to decode failing the query reading it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenOptions {
+    pub(crate) read_only: bool,
+    pub(crate) mmap: bool,
+    pub(crate) mmap_window: Option<usize>,
+    pub(crate) cache_size: Option<usize>,
+    pub(crate) wal: bool,
+    pub(crate) lenient: bool,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self {
+            read_only: true,
+            mmap: true,
+            mmap_window: None,
+            cache_size: None,
+            wal: true,
+            lenient: false,
+        }
+    }
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the file for writing as well as reading when `false`, failing when it
+    /// can't be written
+    ///
+    /// Nothing in this crate writes to the file yet, so this only checks that a
+    /// writer would be able to.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Reads pages through a buffer when `false`, rather than mapping the file into
+    /// memory
+    ///
+    /// A database with a write-ahead log or wal-index next to it is always read
+    /// through a buffer, as other connections can checkpoint into the file or
+    /// truncate it while it's open.
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Maps the file into memory a window of `bytes` at a time as its pages are read,
+    /// rather than mapping the whole file up front, for when address space or memory
+    /// is short
+    ///
+    /// Windows are rounded up to a multiple of 64 KiB. `None` maps the whole file.
+    pub fn mmap_window(mut self, bytes: Option<usize>) -> Self {
+        self.mmap_window = bytes;
+        self
+    }
+
+    /// Most pages read through a buffer to keep in memory between statements, with
+    /// `None` keeping every page read
+    ///
+    /// A statement can read more pages than this, with those past it dropped before
+    /// the next statement runs. Mapped pages are cached by the operating system
+    /// instead, so this only applies when [`mmap`](Self::mmap) is off.
+    pub fn cache_size(mut self, pages: Option<usize>) -> Self {
+        self.cache_size = pages;
+        self
+    }
+
+    /// Reads the database file alone when `false`, ignoring transactions committed to
+    /// its write-ahead log that haven't been checkpointed yet
+    pub fn wal(mut self, wal: bool) -> Self {
+        self.wal = wal;
+        self
+    }
+
+    /// Leaves out cells that fail to decode when `true`, logging a warning for each,
+    /// rather than failing the query reading them
+    ///
+    /// Rows of a corrupt database can still be read this way, but a query can then
+    /// quietly miss some of them.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+}
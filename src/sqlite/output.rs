@@ -0,0 +1,552 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, StdoutLock, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+
+use super::{cell::RecordValue, result::ResultSet};
+
+/// Bytes of output gathered before they are written to standard output
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Standard output for writing results to, buffered so that each row isn't locked
+/// and flushed on its own
+///
+/// Nothing written shows until the writer is flushed or dropped, so it needs
+/// flushing before anything else is printed or input is waited on.
+pub fn stdout() -> BufWriter<StdoutLock<'static>> {
+    BufWriter::with_capacity(BUFFER_SIZE, io::stdout().lock())
+}
+
+/// Where the output of each command goes: standard output, or a file it has been
+/// redirected to by `.output` or `.once`
+#[derive(Debug, Default)]
+pub struct Output {
+    redirect: Option<Redirect>,
+}
+
+#[derive(Debug)]
+struct Redirect {
+    file: File,
+    /// Only the next command writes to the file
+    once: bool,
+}
+
+impl Output {
+    /// Sends output to the file at `path` from now on, or only for the next command
+    /// when `once` is set, adding to the end of the file rather than replacing it
+    /// when `append` is set
+    pub fn redirect(&mut self, path: &Path, append: bool, once: bool) -> io::Result<()> {
+        let file = match append {
+            true => File::options().create(true).append(true).open(path)?,
+            false => File::create(path)?,
+        };
+
+        self.redirect = Some(Redirect { file, once });
+        Ok(())
+    }
+
+    /// Sends output back to standard output
+    pub fn reset(&mut self) {
+        self.redirect = None;
+    }
+
+    pub fn is_redirected(&self) -> bool {
+        self.redirect.is_some()
+    }
+
+    /// The writer for the next command's output, which uses up a `.once` redirect
+    pub fn writer(&mut self) -> io::Result<Box<dyn Write>> {
+        let file = match self.redirect.take() {
+            None => return Ok(Box::new(stdout())),
+            Some(redirect) if redirect.once => redirect.file,
+            Some(redirect) => {
+                let file = redirect.file.try_clone()?;
+                self.redirect = Some(redirect);
+                file
+            }
+        };
+
+        Ok(Box::new(BufWriter::with_capacity(BUFFER_SIZE, file)))
+    }
+}
+
+/// How the rows of a query are printed, chosen with `.mode`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Each row on its own line with columns separated by `|`
+    #[default]
+    List,
+    /// A grid drawn with ASCII borders, each column as wide as its widest value
+    Table,
+    /// The same grid as [`Mode::Table`] drawn with box-drawing characters
+    Box,
+    /// Each value written as the SQL literal for it, separated by commas, ready to be
+    /// pasted into an `INSERT`
+    Quote,
+    /// Comma-separated values, quoted where they hold anything a reader could trip on,
+    /// with rows ending in CRLF as RFC 4180 has them
+    Csv,
+    /// Values separated by tabs as they are, also known as `tsv`
+    Tabs,
+    /// An array with an object for each row, keyed by the column names
+    Json,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_ascii_lowercase().as_str() {
+            "list" => Ok(Self::List),
+            "table" => Ok(Self::Table),
+            "box" => Ok(Self::Box),
+            "quote" => Ok(Self::Quote),
+            "csv" => Ok(Self::Csv),
+            "tabs" | "tsv" => Ok(Self::Tabs),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown output mode '{mode}'")),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::List => write!(f, "list"),
+            Self::Table => write!(f, "table"),
+            Self::Box => write!(f, "box"),
+            Self::Quote => write!(f, "quote"),
+            Self::Csv => write!(f, "csv"),
+            Self::Tabs => write!(f, "tabs"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// How blobs are shown, chosen with `.blob`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlobFormat {
+    /// The bytes themselves up to the first NUL, as `sqlite3` prints them, with any
+    /// that aren't UTF-8 replaced
+    #[default]
+    Text,
+    /// A blob literal, as in `X'0A1B'`
+    Hex,
+    Base64,
+    /// Just the size, as in `blob (2 bytes)`
+    Size,
+}
+
+impl FromStr for BlobFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "hex" => Ok(Self::Hex),
+            "base64" => Ok(Self::Base64),
+            "size" => Ok(Self::Size),
+            _ => Err(format!("unknown blob format '{format}'")),
+        }
+    }
+}
+
+impl fmt::Display for BlobFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Hex => write!(f, "hex"),
+            Self::Base64 => write!(f, "base64"),
+            Self::Size => write!(f, "size"),
+        }
+    }
+}
+
+/// Width names are laid out in when the terminal's isn't known, the same as `sqlite3`
+/// always uses
+pub const DEFAULT_TERMINAL_WIDTH: usize = 80;
+
+/// Writes `names` in columns as wide as the longest, as many as fit in `width`
+/// characters, filling each column before the next the way `sqlite3` lists tables
+pub fn write_columns(names: &[&str], width: usize, out: &mut dyn Write) -> io::Result<()> {
+    let Some(longest) = names.iter().map(|name| name.chars().count()).max() else {
+        return Ok(());
+    };
+
+    let columns = (width / (longest + 2)).max(1);
+    let rows = names.len().div_ceil(columns);
+    for row in 0..rows {
+        for (i, name) in names.iter().enumerate().skip(row).step_by(rows) {
+            let separator = if i < rows { "" } else { "  " };
+            write!(out, "{separator}{name:<longest$}")?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Draws the `id`, `parent`, `notused` and `detail` rows of `EXPLAIN QUERY PLAN` as
+/// the tree `sqlite3` prints them as, each step under the one its parent names
+pub fn write_query_plan(result: &ResultSet, out: &mut dyn Write) -> io::Result<()> {
+    let steps: Vec<(i64, i64, String)> = result
+        .rows
+        .iter()
+        .filter_map(|row| match row.as_slice() {
+            [id, parent, _, RecordValue::String(detail)] => {
+                Some((id.as_i64()?, parent.as_i64()?, detail.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+
+    writeln!(out, "QUERY PLAN")?;
+    write_plan_steps(&steps, 0, "", out)
+}
+
+/// Draws the steps under `parent` as branches of the tree, each followed by its own
+fn write_plan_steps(
+    steps: &[(i64, i64, String)],
+    parent: i64,
+    prefix: &str,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let children: Vec<_> = steps.iter().filter(|step| step.1 == parent).collect();
+    for (i, (id, _, detail)) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (branch, indent) = if last { ("`--", "   ") } else { ("|--", "|  ") };
+        writeln!(out, "{prefix}{branch}{detail}")?;
+        write_plan_steps(steps, *id, &format!("{prefix}{indent}"), out)?;
+    }
+    Ok(())
+}
+
+/// Widest a column of a grid grows before its values are cut short, the same as
+/// the width `sqlite3` wraps them at
+pub const DEFAULT_MAX_WIDTH: usize = 60;
+
+/// How results are printed, changed by `.mode`, `.headers`, `.nullvalue` and `.blob`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputFormat {
+    pub mode: Mode,
+    /// Print the column names above the rows
+    pub headers: bool,
+    /// Widest a column of a grid can be, with longer values ending in an ellipsis
+    pub max_width: usize,
+    /// Shown in place of NULL, which is nothing at all by default like `sqlite3`
+    pub null_value: String,
+    pub blob: BlobFormat,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self {
+            mode: Mode::default(),
+            headers: false,
+            max_width: DEFAULT_MAX_WIDTH,
+            null_value: String::new(),
+            blob: BlobFormat::default(),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Switches to printing in `mode`, turning the column names on for a grid like
+    /// `sqlite3` does
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.headers |= matches!(mode, Mode::Table | Mode::Box);
+    }
+
+    /// Writes the columns and rows of `result`
+    ///
+    /// A grid has to see every row before drawing the first to know how wide to make
+    /// its columns, and draws nothing at all for a query without rows.
+    pub fn write(&self, result: &ResultSet, out: &mut dyn Write) -> io::Result<()> {
+        match self.mode {
+            Mode::List => {
+                if self.headers && !result.rows.is_empty() {
+                    writeln!(out, "{}", result.columns.join("|"))?;
+                }
+                for row in result.rows.iter() {
+                    let row: Vec<String> = row.iter().map(|value| self.render(value)).collect();
+                    writeln!(out, "{}", row.join("|"))?;
+                }
+                Ok(())
+            }
+            Mode::Quote => {
+                // Column names are quoted as strings, and NULL is always NULL
+                if self.headers && !result.rows.is_empty() {
+                    let names: Vec<String> = result
+                        .columns
+                        .iter()
+                        .map(|name| RecordValue::String(name.clone()).to_sql_literal())
+                        .collect();
+                    writeln!(out, "{}", names.join(","))?;
+                }
+                for row in result.rows.iter() {
+                    let row: Vec<String> = row.iter().map(RecordValue::to_sql_literal).collect();
+                    writeln!(out, "{}", row.join(","))?;
+                }
+                Ok(())
+            }
+            Mode::Csv => {
+                if self.headers && !result.rows.is_empty() {
+                    let names: Vec<String> = result.columns.iter().map(|name| csv(name)).collect();
+                    write!(out, "{}\r\n", names.join(","))?;
+                }
+                for row in result.rows.iter() {
+                    let row: Vec<String> = row
+                        .iter()
+                        .map(|value| match value {
+                            RecordValue::Null => self.null_value.clone(),
+                            value => csv(&self.render(value)),
+                        })
+                        .collect();
+                    write!(out, "{}\r\n", row.join(","))?;
+                }
+                Ok(())
+            }
+            Mode::Tabs => {
+                if self.headers && !result.rows.is_empty() {
+                    writeln!(out, "{}", result.columns.join("\t"))?;
+                }
+                for row in result.rows.iter() {
+                    let row: Vec<String> = row.iter().map(|value| self.render(value)).collect();
+                    writeln!(out, "{}", row.join("\t"))?;
+                }
+                Ok(())
+            }
+            Mode::Json => {
+                for (i, row) in result.rows.iter().enumerate() {
+                    let fields: Vec<String> = result
+                        .columns
+                        .iter()
+                        .zip(row.iter())
+                        .map(|(name, value)| format!("{}:{}", json_string(name), json(value)))
+                        .collect();
+                    let start = if i == 0 { "[" } else { ",\n" };
+                    write!(out, "{start}{{{}}}", fields.join(","))?;
+                }
+                if !result.rows.is_empty() {
+                    writeln!(out, "]")?;
+                }
+                Ok(())
+            }
+            Mode::Table => self.write_grid(result, &TABLE_BORDERS, out),
+            Mode::Box => self.write_grid(result, &BOX_BORDERS, out),
+        }
+    }
+
+    /// A value as it's shown in a row
+    pub fn render(&self, value: &RecordValue) -> String {
+        match value {
+            RecordValue::Null => self.null_value.clone(),
+            RecordValue::Blob(blob) => match self.blob {
+                BlobFormat::Text => {
+                    let end = blob
+                        .iter()
+                        .position(|&byte| byte == 0)
+                        .unwrap_or(blob.len());
+                    String::from_utf8_lossy(&blob[..end]).into_owned()
+                }
+                BlobFormat::Hex => value.to_sql_literal(),
+                BlobFormat::Base64 => BASE64_STANDARD.encode(blob),
+                BlobFormat::Size => value.to_string(),
+            },
+            // sqlite3 prints text as a C string, which ends at the first NUL
+            RecordValue::String(text) => match text.split_once('\0') {
+                Some((shown, _)) => shown.to_string(),
+                None => text.clone(),
+            },
+            value => value.to_string(),
+        }
+    }
+
+    fn write_grid(
+        &self,
+        result: &ResultSet,
+        borders: &Borders,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        if result.rows.is_empty() {
+            return Ok(());
+        }
+
+        let max_width = self.max_width.max(1);
+        let rows: Vec<Vec<String>> = result
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|value| truncate(&self.render(value), max_width))
+                    .collect()
+            })
+            .collect();
+        let headers: Vec<String> = result
+            .columns
+            .iter()
+            .map(|name| truncate(name, max_width))
+            .collect();
+
+        let mut widths: Vec<usize> = match self.headers {
+            true => headers.iter().map(|name| name.chars().count()).collect(),
+            false => vec![0; headers.len()],
+        };
+        for row in rows.iter() {
+            for (width, value) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(value.chars().count());
+            }
+        }
+
+        let rule = |[left, middle, right]: [char; 3]| {
+            let segments: Vec<String> = widths
+                .iter()
+                .map(|width| borders.horizontal.to_string().repeat(width + 2))
+                .collect();
+            format!("{left}{}{right}", segments.join(&middle.to_string()))
+        };
+        let line = |cells: Vec<String>| {
+            let vertical = borders.vertical;
+            format!(
+                "{vertical} {} {vertical}",
+                cells.join(&format!(" {vertical} "))
+            )
+        };
+
+        writeln!(out, "{}", rule(borders.top))?;
+        if self.headers {
+            let cells = headers
+                .iter()
+                .zip(widths.iter())
+                .map(|(name, &width)| format!("{name:^width$}"))
+                .collect();
+            writeln!(out, "{}", line(cells))?;
+            writeln!(out, "{}", rule(borders.middle))?;
+        }
+        for row in rows.iter() {
+            let cells = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(value, &width)| format!("{value:<width$}"))
+                .collect();
+            writeln!(out, "{}", line(cells))?;
+        }
+        writeln!(out, "{}", rule(borders.bottom))
+    }
+}
+
+/// The characters a grid is drawn with, with the left, middle and right corners of
+/// each horizontal rule
+struct Borders {
+    horizontal: char,
+    vertical: char,
+    top: [char; 3],
+    middle: [char; 3],
+    bottom: [char; 3],
+}
+
+const TABLE_BORDERS: Borders = Borders {
+    horizontal: '-',
+    vertical: '|',
+    top: ['+', '+', '+'],
+    middle: ['+', '+', '+'],
+    bottom: ['+', '+', '+'],
+};
+
+const BOX_BORDERS: Borders = Borders {
+    horizontal: '─',
+    vertical: '│',
+    top: ['┌', '┬', '┐'],
+    middle: ['├', '┼', '┤'],
+    bottom: ['└', '┴', '┘'],
+};
+
+/// A CSV field, quoted like `sqlite3` quotes it when it's empty or holds a control
+/// character, space, quote, comma or anything outside ASCII
+fn csv(value: &str) -> String {
+    let quoted = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c <= ' ' || c >= '\x7f' || matches!(c, '"' | '\'' | ','));
+    match quoted {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+/// A value as JSON, with a blob written as a string of its bytes
+fn json(value: &RecordValue) -> String {
+    match value {
+        RecordValue::Null => "null".to_string(),
+        RecordValue::String(text) => json_string(text),
+        RecordValue::Blob(blob) => {
+            // Bytes that aren't UTF-8 are escaped one at a time, as `sqlite3` does
+            let mut json = String::from('"');
+            for chunk in blob.utf8_chunks() {
+                push_json_chars(&mut json, chunk.valid());
+                for byte in chunk.invalid() {
+                    json.push_str(&format!("\\u{byte:04x}"));
+                }
+            }
+            json.push('"');
+            json
+        }
+        value => value.to_string(),
+    }
+}
+
+/// `text` as a JSON string
+fn json_string(text: &str) -> String {
+    let mut json = String::from('"');
+    push_json_chars(&mut json, text);
+    json.push('"');
+    json
+}
+
+/// Adds `text` to a JSON string, escaping quotes, backslashes and control characters
+/// the way `sqlite3` does
+fn push_json_chars(json: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                json.push('\\');
+                json.push(c);
+            }
+            '\x08' => json.push_str("\\b"),
+            '\x0c' => json.push_str("\\f"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' || c == '\x7f' => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+}
+
+/// Cuts `value` down to `width` characters, ending it with an ellipsis when it was
+/// longer, and keeps it to one line with its tabs spread to every eighth column
+fn truncate(value: &str, width: usize) -> String {
+    let mut line = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\t' => {
+                let column = line.chars().count();
+                line.push_str(&" ".repeat(8 - column % 8));
+            }
+            '\n' | '\r' => line.push(' '),
+            ch => line.push(ch),
+        }
+    }
+    let value = line;
+    if value.chars().count() <= width {
+        return value;
+    }
+
+    let mut truncated: String = value.chars().take(width - 1).collect();
+    truncated.push('…');
+    truncated
+}
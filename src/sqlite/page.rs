@@ -1,6 +1,10 @@
 use bytes::Buf;
+use tracing::warn;
 
-use super::cell::{DatabaseCell, IndexLeafCell, InteriorIndexCell, InteriorTableCell, LeafCell};
+use super::cell::{
+    DatabaseCell, IndexLeafCell, InteriorIndexCell, InteriorTableCell, LeafCell, Overflow,
+};
+use super::error::{ensure_remaining, DecodeError};
 use super::HEADER_SIZE;
 
 const LEAF_OFFSET: usize = 8;
@@ -15,6 +19,16 @@ pub enum BTreePageType {
     LeafTable = 13,
 }
 
+impl BTreePageType {
+    /// Size of the page header in bytes, which is where the cell pointer array begins
+    pub fn header_size(&self) -> usize {
+        match self {
+            Self::LeafTable | Self::LeafIndex => LEAF_OFFSET,
+            Self::InteriorIndex | Self::InteriorTable => INTERIOR_OFFSET,
+        }
+    }
+}
+
 impl std::fmt::Display for BTreePageType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -26,14 +40,16 @@ impl std::fmt::Display for BTreePageType {
     }
 }
 
-impl From<u8> for BTreePageType {
-    fn from(value: u8) -> Self {
+impl TryFrom<u8> for BTreePageType {
+    type Error = DecodeError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            2 => Self::InteriorIndex,
-            5 => Self::InteriorTable,
-            10 => Self::LeafIndex,
-            13 => Self::LeafTable,
-            _ => panic!("unsupported value for BTreePageType: {value}"),
+            2 => Ok(Self::InteriorIndex),
+            5 => Ok(Self::InteriorTable),
+            10 => Ok(Self::LeafIndex),
+            13 => Ok(Self::LeafTable),
+            _ => Err(DecodeError::InvalidPageType(value)),
         }
     }
 }
@@ -44,86 +60,96 @@ pub struct BTreePageHeader {
     pub page_type: BTreePageType,
     pub first_freeblock_offset: u16,
     pub total_cells: u16,
-    pub cell_content_offset: u16,
+    /// Start of the cell content area within the page buffer
+    pub cell_content_offset: u32,
     pub fragmented_free_bytes: u8,
     pub rightmost_pointer: Option<u32>,
 }
 
-#[allow(dead_code)]
-#[derive(Debug)]
-pub struct BTreePage {
-    page_no: usize,
-    pub header: BTreePageHeader,
-    pub cells: Vec<DatabaseCell>,
-}
-
-impl BTreePage {
-    pub fn new(buf: &[u8], page_no: usize) -> Self {
-        let page_type = BTreePageType::from(buf[0]);
-        let header_offset = match page_type {
-            BTreePageType::LeafTable | BTreePageType::LeafIndex => LEAF_OFFSET,
-            BTreePageType::InteriorIndex | BTreePageType::InteriorTable => INTERIOR_OFFSET,
-        };
-        let mut header_bytes = &buf[1..header_offset];
+impl BTreePageHeader {
+    /// Reads just the page header, leaving the cells undecoded
+    pub fn new(buf: &[u8], page_no: usize) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 1)?;
+        let page_type = BTreePageType::try_from(buf[0])?;
+        ensure_remaining(buf, page_type.header_size())?;
+        let mut header_bytes = &buf[1..page_type.header_size()];
 
-        let header = BTreePageHeader {
+        Ok(BTreePageHeader {
             page_type,
             first_freeblock_offset: header_bytes.get_u16(),
             total_cells: header_bytes.get_u16(),
             cell_content_offset: {
-                let value = header_bytes.get_u16();
-                if value == 0 {
-                    u16::MAX
-                } else if page_no == 0 {
-                    value - HEADER_SIZE as u16
-                } else {
-                    value
+                // A page of 65536 bytes with no cells stores its content area as 0
+                let offset = match header_bytes.get_u16() {
+                    0 => 65536,
+                    offset => u32::from(offset),
+                };
+                match page_no {
+                    0 => offset.saturating_sub(HEADER_SIZE as u32),
+                    _ => offset,
                 }
             },
             fragmented_free_bytes: header_bytes.get_u8(),
             rightmost_pointer: match page_type {
                 BTreePageType::InteriorTable | BTreePageType::InteriorIndex => {
                     let page_number = header_bytes.get_u32();
-                    Some(page_number - 1)
+                    Some(
+                        page_number
+                            .checked_sub(1)
+                            .ok_or(DecodeError::InvalidChildPage(page_number))?,
+                    )
                 }
                 _ => None,
             },
-        };
+        })
+    }
+}
 
-        let total_cells = usize::from(header.total_cells);
-        let mut cell_pointer_buf =
-            &buf[header_offset..header_offset + (2 * usize::from(header.total_cells))];
-
-        let cells: Vec<DatabaseCell> = (0..total_cells)
-            .map(|_| {
-                let offset = usize::from(cell_pointer_buf.get_u16());
-                let offset = if page_no == 0 {
-                    offset - HEADER_SIZE
-                } else {
-                    offset
-                };
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct BTreePage {
+    page_no: usize,
+    pub header: BTreePageHeader,
+    pub cells: Vec<DatabaseCell>,
+}
 
-                let cell_buf = &buf[offset..];
-                match page_type {
-                    BTreePageType::LeafTable => DatabaseCell::Leaf(LeafCell::new(cell_buf)),
-                    BTreePageType::InteriorTable => {
-                        DatabaseCell::InteriorTable(InteriorTableCell::new(cell_buf))
-                    }
-                    BTreePageType::InteriorIndex => {
-                        DatabaseCell::InteriorIndex(InteriorIndexCell::new(cell_buf))
-                    }
-                    BTreePageType::LeafIndex => {
-                        DatabaseCell::IndexLeaf(IndexLeafCell::new(cell_buf))
+impl BTreePage {
+    pub fn new(buf: &[u8], page_no: usize, overflow: Overflow) -> Result<Self, DecodeError> {
+        let header = BTreePageHeader::new(buf, page_no)?;
+        let cells = cell_pointers(buf, &header)?
+            .into_iter()
+            .map(|offset| decode_cell(buf, offset, page_no, header.page_type, overflow))
+            .collect::<Result<Vec<DatabaseCell>, DecodeError>>()?;
+
+        Ok(Self {
+            header,
+            page_no,
+            cells,
+        })
+    }
+
+    /// Decodes a page leaving out the cells that fail to decode, which only fails
+    /// when the header or cell pointers can't be read
+    pub fn lenient(buf: &[u8], page_no: usize, overflow: Overflow) -> Result<Self, DecodeError> {
+        let header = BTreePageHeader::new(buf, page_no)?;
+        let cells = cell_pointers(buf, &header)?
+            .into_iter()
+            .filter_map(|offset| {
+                match decode_cell(buf, offset, page_no, header.page_type, overflow) {
+                    Ok(cell) => Some(cell),
+                    Err(e) => {
+                        warn!(page = page_no + 1, offset, "skipping cell: {e}");
+                        None
                     }
                 }
             })
             .collect();
 
-        Self {
+        Ok(Self {
             header,
             page_no,
             cells,
-        }
+        })
     }
 
     pub fn page_type(&self) -> BTreePageType {
@@ -133,8 +159,112 @@ impl BTreePage {
     pub fn right_page_pointer(&self) -> Option<u32> {
         self.header.rightmost_pointer
     }
+}
+
+/// Decodes the cell at `offset` on a page of type `page_type`
+fn decode_cell(
+    buf: &[u8],
+    offset: usize,
+    page_no: usize,
+    page_type: BTreePageType,
+    overflow: Overflow,
+) -> Result<DatabaseCell, DecodeError> {
+    let cell_buf = page_offset(offset, page_no)
+        .and_then(|offset| buf.get(offset..))
+        .ok_or(DecodeError::InvalidCellOffset {
+            offset,
+            page_size: buf.len(),
+        })?;
+
+    Ok(match page_type {
+        BTreePageType::LeafTable => DatabaseCell::Leaf(LeafCell::new(cell_buf, overflow)?),
+        BTreePageType::InteriorTable => {
+            DatabaseCell::InteriorTable(InteriorTableCell::new(cell_buf)?)
+        }
+        BTreePageType::InteriorIndex => {
+            DatabaseCell::InteriorIndex(InteriorIndexCell::new(cell_buf, overflow)?)
+        }
+        BTreePageType::LeafIndex => {
+            DatabaseCell::IndexLeaf(IndexLeafCell::new(cell_buf, overflow)?)
+        }
+    })
+}
+
+/// A run of free space between cells, chained from the page header
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Freeblock {
+    /// Offset from the start of the page, as stored on disk
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// Reads the cell pointer array following the page header
+///
+/// Offsets are as stored on disk, counting from the start of the page even on the
+/// first page where `buf` begins after the database header.
+pub fn cell_pointers(buf: &[u8], header: &BTreePageHeader) -> Result<Vec<usize>, DecodeError> {
+    let start = header.page_type.header_size();
+    let total_cells = usize::from(header.total_cells);
+    ensure_remaining(buf, start + 2 * total_cells)?;
+
+    let mut pointers = &buf[start..start + 2 * total_cells];
+    Ok((0..total_cells)
+        .map(|_| usize::from(pointers.get_u16()))
+        .collect())
+}
+
+/// Follows the freeblock chain of a page, stopping at the first freeblock that
+/// doesn't fit in the page or that has been visited before
+pub fn freeblocks(buf: &[u8], header: &BTreePageHeader, page_no: usize) -> Vec<Freeblock> {
+    let mut freeblocks: Vec<Freeblock> = Vec::new();
+    let mut next = usize::from(header.first_freeblock_offset);
+    while next != 0 && freeblocks.iter().all(|freeblock| freeblock.offset != next) {
+        let Some(mut freeblock_header) =
+            page_offset(next, page_no).and_then(|start| buf.get(start..start + 4))
+        else {
+            break;
+        };
+
+        let offset = next;
+        next = usize::from(freeblock_header.get_u16());
+        freeblocks.push(Freeblock {
+            offset,
+            size: usize::from(freeblock_header.get_u16()),
+        });
+    }
+
+    freeblocks
+}
+
+/// Start of the cell content area as stored, where 0 stands for 65536
+pub fn cell_content_area(buf: &[u8]) -> usize {
+    match u16::from_be_bytes([buf[5], buf[6]]) {
+        0 => 65536,
+        offset => usize::from(offset),
+    }
+}
+
+/// Bytes of the page holding neither headers nor cells: the gap between the cell
+/// pointer array and the cell content area, the freeblocks and the fragments
+pub fn unused_bytes(buf: &[u8], header: &BTreePageHeader, page_no: usize) -> usize {
+    let pointers_end = header.page_type.header_size() + 2 * usize::from(header.total_cells);
+    let content_start = page_offset(cell_content_area(buf), page_no).unwrap_or_default();
+    let freeblocks: usize = freeblocks(buf, header, page_no)
+        .iter()
+        .map(|freeblock| freeblock.size)
+        .sum();
+
+    content_start.saturating_sub(pointers_end)
+        + freeblocks
+        + usize::from(header.fragmented_free_bytes)
+}
 
-    pub fn count(&self) -> usize {
-        self.cells.len()
+/// Converts an on-disk offset into one within the page buffer, which for the first
+/// page starts after the database header
+pub fn page_offset(offset: usize, page_no: usize) -> Option<usize> {
+    if page_no == 0 {
+        offset.checked_sub(HEADER_SIZE)
+    } else {
+        Some(offset)
     }
 }
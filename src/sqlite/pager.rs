@@ -0,0 +1,404 @@
+#[cfg(not(target_family = "wasm"))]
+use memmap2::{Mmap, MmapOptions};
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    sync::{Mutex, OnceLock},
+};
+
+/// Where the pages of a database are read from
+///
+/// Pagers are shared between threads, so any page cache has to be filled through
+/// `&self` with synchronised types, as [`FilePager`] does.
+pub trait Pager: Send + Sync {
+    /// Size of each page in bytes
+    fn page_size(&self) -> usize;
+
+    /// Number of whole pages in the database
+    fn page_count(&self) -> usize;
+
+    /// The bytes of page `n`, counting from 0, where page 0 starts with the
+    /// database header
+    fn get_page(&self, n: usize) -> io::Result<&[u8]>;
+
+    /// Whether page `n` is the lock-byte page, which SQLite never stores anything in
+    fn is_lock_byte_page(&self, n: usize) -> bool {
+        n == lock_byte_page(self.page_size())
+    }
+
+    /// Drops cached pages until no more than `pages` are left, for pagers that keep
+    /// the pages they read
+    fn shrink_cache(&mut self, _pages: usize) {}
+}
+
+impl<P: Pager + ?Sized> Pager for Box<P> {
+    fn page_size(&self) -> usize {
+        (**self).page_size()
+    }
+
+    fn page_count(&self) -> usize {
+        (**self).page_count()
+    }
+
+    fn get_page(&self, n: usize) -> io::Result<&[u8]> {
+        (**self).get_page(n)
+    }
+
+    fn shrink_cache(&mut self, pages: usize) {
+        (**self).shrink_cache(pages)
+    }
+}
+
+/// The bytes every database file starts with
+pub const HEADER_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// Offset of the byte SQLite locks while a writer waits for readers to finish,
+/// followed by the byte reserved for writers and the range readers lock
+pub const PENDING_BYTE: u64 = 0x4000_0000;
+
+/// The page holding the bytes SQLite locks, counting from 0, which only databases
+/// past 1 GiB reach and which is left out of every b-tree and the freelist
+pub fn lock_byte_page(page_size: usize) -> usize {
+    (PENDING_BYTE / page_size as u64) as usize
+}
+
+/// Decodes pages as they are read from the file, such as decrypting the pages of an
+/// encrypted database
+pub trait PageCodec: Send + Sync {
+    /// Decodes page `n`, counting from 0, in place
+    ///
+    /// The decoded first page has to start with the database header. Bytes the
+    /// codec keeps on every page for itself, such as an IV or MAC, should be left
+    /// at the end of the page and counted as reserved space in the header so they
+    /// aren't read as part of the b-tree.
+    fn decode(&self, n: usize, page: &mut [u8]) -> io::Result<()>;
+}
+
+/// Reads the page size from the start of a database, where 1 stands for 65536
+///
+/// A file that doesn't start with the header magic isn't a database, or is one that
+/// has been encrypted.
+pub fn header_page_size(header: &[u8]) -> io::Result<usize> {
+    let Some(&[high, low]) = header.get(16..18) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "file is too short to be a database",
+        ));
+    };
+    if !header.starts_with(HEADER_MAGIC) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "file is not a database, or is encrypted",
+        ));
+    }
+
+    match u16::from_be_bytes([high, low]) {
+        1 => Ok(65536),
+        size if size >= 512 && size.is_power_of_two() => Ok(usize::from(size)),
+        size => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid page size {size}"),
+        )),
+    }
+}
+
+fn past_end(n: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!("page {} is past the end of the database", n + 1),
+    )
+}
+
+/// Where page `n` starts in the file, worked out in 64 bits so that pages past
+/// 4 GiB can be found on 32-bit targets as well
+pub fn page_offset(n: usize, page_size: usize) -> io::Result<u64> {
+    (n as u64)
+        .checked_mul(page_size as u64)
+        .ok_or_else(|| past_end(n))
+}
+
+/// The bytes of page `n` within `bytes`, the whole database held in memory
+fn page_in(bytes: &[u8], n: usize, page_size: usize) -> io::Result<&[u8]> {
+    let start = usize::try_from(page_offset(n, page_size)?).map_err(|_| past_end(n))?;
+    start
+        .checked_add(page_size)
+        .and_then(|end| bytes.get(start..end))
+        .ok_or_else(|| past_end(n))
+}
+
+/// Number of whole pages in a file of `len` bytes, failing when there are more
+/// than can be addressed
+fn whole_pages(len: u64, page_size: usize) -> io::Result<usize> {
+    usize::try_from(len / page_size as u64).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("database of {len} bytes has too many pages to address"),
+        )
+    })
+}
+
+/// Pages read straight out of the database file mapped into memory, which isn't
+/// available on WebAssembly where [`MemoryPager`] reads them from bytes instead
+///
+/// A file too large for the address space, such as one past 4 GiB on a 32-bit
+/// target, can't be mapped and has to be read with [`FilePager`].
+#[cfg(not(target_family = "wasm"))]
+pub struct MmapPager {
+    mmap: Mmap,
+    page_size: usize,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl MmapPager {
+    pub fn new(file: &File) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        if usize::try_from(len).map_or(true, |len| len > isize::MAX as usize) {
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!("database of {len} bytes is too large to map"),
+            ));
+        }
+
+        // Safety: The mapping is only sound while nobody writes to or truncates the
+        // file. Nothing in this crate writes to it, and databases with a log or
+        // wal-index next to them, whose connections checkpoint into the file and
+        // can truncate it, are read with `FilePager` instead. A connection in
+        // rollback journal mode writing the file while it's mapped can still change
+        // or truncate it underneath, as with SQLite's own memory-mapped reads.
+        let mmap = unsafe { Mmap::map(file)? };
+        let page_size = header_page_size(&mmap)?;
+        Ok(Self { mmap, page_size })
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl Pager for MmapPager {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn page_count(&self) -> usize {
+        self.mmap.len() / self.page_size
+    }
+
+    fn get_page(&self, n: usize) -> io::Result<&[u8]> {
+        page_in(&self.mmap, n, self.page_size)
+    }
+}
+
+/// Pages read out of windows of the database file, each mapped into memory the first
+/// time one of its pages is asked for, so only the parts of the file a query reads
+/// take up address space
+///
+/// Windows stay mapped until the pager is dropped, but the kernel is told access is
+/// random so it doesn't read ahead, and can drop their pages again under memory
+/// pressure as they are backed by the file.
+#[cfg(not(target_family = "wasm"))]
+pub struct WindowedPager {
+    file: File,
+    page_size: usize,
+    page_count: usize,
+    /// Bytes in every window but the last, a whole number of pages
+    window_size: usize,
+    windows: Vec<OnceLock<Mmap>>,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl WindowedPager {
+    /// Smallest step windows are sized in, a multiple of every page size and of the
+    /// granularity mappings have to start at
+    pub const WINDOW_ALIGN: usize = 65536;
+
+    /// Maps `file` in windows of `window_size` bytes, rounded up to a multiple of
+    /// [`Self::WINDOW_ALIGN`]
+    pub fn new(mut file: File, window_size: usize) -> io::Result<Self> {
+        let mut header = [0; 18];
+        file.read_exact(&mut header)?;
+        let page_size = header_page_size(&header)?;
+
+        let len = file.metadata()?.len();
+        let page_count = whole_pages(len, page_size)?;
+        let window_size = window_size
+            .max(1)
+            .div_ceil(Self::WINDOW_ALIGN)
+            .saturating_mul(Self::WINDOW_ALIGN);
+        let window_count = len.div_ceil(window_size as u64) as usize;
+
+        Ok(Self {
+            file,
+            page_size,
+            page_count,
+            window_size,
+            windows: (0..window_count).map(|_| OnceLock::new()).collect(),
+        })
+    }
+
+    fn map_window(&self, w: usize) -> io::Result<Mmap> {
+        let start = w as u64 * self.window_size as u64;
+        let len = self.file.metadata()?.len();
+        let window_len = (len.saturating_sub(start)).min(self.window_size as u64) as usize;
+
+        // Safety: As with `MmapPager`, only files no connection in WAL mode shares are
+        // mapped, and nothing in this crate writes to them
+        let mmap = unsafe {
+            MmapOptions::new()
+                .offset(start)
+                .len(window_len)
+                .map(&self.file)?
+        };
+
+        #[cfg(unix)]
+        mmap.advise(memmap2::Advice::Random)?;
+        Ok(mmap)
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl Pager for WindowedPager {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn page_count(&self) -> usize {
+        self.page_count
+    }
+
+    fn get_page(&self, n: usize) -> io::Result<&[u8]> {
+        if n >= self.page_count {
+            return Err(past_end(n));
+        }
+
+        let offset = page_offset(n, self.page_size)?;
+        let w = (offset / self.window_size as u64) as usize;
+        let window = self.windows.get(w).ok_or_else(|| past_end(n))?;
+        let window = match window.get() {
+            Some(window) => window,
+            None => {
+                let mmap = self.map_window(w)?;
+                window.get_or_init(|| mmap)
+            }
+        };
+
+        page_in(
+            window,
+            n % (self.window_size / self.page_size),
+            self.page_size,
+        )
+    }
+}
+
+/// Pages read from the database file as they are first asked for and kept
+/// afterwards, for files that can't be memory mapped or have to be decoded
+pub struct FilePager {
+    file: Mutex<File>,
+    page_size: usize,
+    pages: Vec<OnceLock<Box<[u8]>>>,
+    codec: Option<Box<dyn PageCodec>>,
+}
+
+impl FilePager {
+    pub fn new(mut file: File) -> io::Result<Self> {
+        let mut header = [0; 18];
+        file.read_exact(&mut header)?;
+        let page_size = header_page_size(&header)?;
+        Self::open(file, page_size, None)
+    }
+
+    /// Pages of a file that are run through `codec` as they are read
+    ///
+    /// The header of an encoded file can't be read until the first page has been
+    /// decoded, so the page size has to be known up front.
+    pub fn with_codec(
+        file: File,
+        page_size: usize,
+        codec: impl PageCodec + 'static,
+    ) -> io::Result<Self> {
+        Self::open(file, page_size, Some(Box::new(codec)))
+    }
+
+    fn open(file: File, page_size: usize, codec: Option<Box<dyn PageCodec>>) -> io::Result<Self> {
+        let page_count = whole_pages(file.metadata()?.len(), page_size)?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+            page_size,
+            pages: (0..page_count).map(|_| OnceLock::new()).collect(),
+            codec,
+        })
+    }
+}
+
+impl Pager for FilePager {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    fn get_page(&self, n: usize) -> io::Result<&[u8]> {
+        let page = self.pages.get(n).ok_or_else(|| past_end(n))?;
+        if let Some(bytes) = page.get() {
+            return Ok(bytes);
+        }
+
+        let mut bytes = vec![0; self.page_size].into_boxed_slice();
+        {
+            let mut file = self.file.lock().expect("database file lock poisoned");
+            file.seek(SeekFrom::Start(page_offset(n, self.page_size)?))?;
+            file.read_exact(&mut bytes)?;
+        }
+
+        if let Some(codec) = &self.codec {
+            codec.decode(n, &mut bytes)?;
+        }
+
+        Ok(page.get_or_init(|| bytes))
+    }
+
+    /// Drops the highest numbered pages first, keeping the schema and the roots of
+    /// the tables created first
+    fn shrink_cache(&mut self, pages: usize) {
+        let mut cached = self
+            .pages
+            .iter()
+            .filter(|page| page.get().is_some())
+            .count();
+        for page in self.pages.iter_mut().rev() {
+            if cached <= pages {
+                break;
+            }
+            if page.take().is_some() {
+                cached -= 1;
+            }
+        }
+    }
+}
+
+/// Pages held in memory rather than read from a file
+pub struct MemoryPager {
+    bytes: Vec<u8>,
+    page_size: usize,
+}
+
+impl MemoryPager {
+    pub fn new(bytes: Vec<u8>) -> io::Result<Self> {
+        let page_size = header_page_size(&bytes)?;
+        Ok(Self { bytes, page_size })
+    }
+}
+
+impl Pager for MemoryPager {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn page_count(&self) -> usize {
+        self.bytes.len() / self.page_size
+    }
+
+    fn get_page(&self, n: usize) -> io::Result<&[u8]> {
+        page_in(&self.bytes, n, self.page_size)
+    }
+}
@@ -0,0 +1,159 @@
+use super::cell::RecordValue;
+
+/// One element of a LIKE or GLOB pattern
+enum Token {
+    /// `%` or `*`, any run of characters including none
+    Any,
+    /// `_` or `?`, exactly one character
+    One,
+    Literal(char),
+    /// `[...]` in GLOB, one character in (or with `^`, not in) the ranges
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Token {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        match self {
+            Self::Any | Self::One => true,
+            Self::Literal(literal) if ignore_case => literal.eq_ignore_ascii_case(&c),
+            Self::Literal(literal) => *literal == c,
+            Self::Class { negated, ranges } => {
+                ranges.iter().any(|(low, high)| (*low..=*high).contains(&c)) != *negated
+            }
+        }
+    }
+}
+
+/// `text LIKE pattern [ESCAPE escape]`, where `%` matches any run of characters and
+/// `_` any one character, ignoring the case of ASCII letters
+///
+/// Either side being NULL never matches.
+pub fn like(text: &RecordValue, pattern: &RecordValue, escape: Option<char>) -> bool {
+    match (as_text(text), as_text(pattern)) {
+        (Some(text), Some(pattern)) => like_text(&text, &pattern, escape),
+        _ => false,
+    }
+}
+
+/// [`like`] for text that is already known not to be NULL
+pub fn like_text(text: &str, pattern: &str, escape: Option<char>) -> bool {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if Some(c) == escape => match chars.next() {
+                Some(escaped) => Token::Literal(escaped),
+                // An escape with nothing after it can't match anything
+                None => return false,
+            },
+            '%' => Token::Any,
+            '_' => Token::One,
+            c => Token::Literal(c),
+        };
+        tokens.push(token);
+    }
+
+    matches(&tokens, text, true)
+}
+
+/// `text GLOB pattern`, where `*` matches any run of characters, `?` any one
+/// character and `[...]` one of a set of characters, respecting case
+///
+/// Either side being NULL never matches.
+pub fn glob(text: &RecordValue, pattern: &RecordValue) -> bool {
+    let (Some(text), Some(pattern)) = (as_text(text), as_text(pattern)) else {
+        return false;
+    };
+
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '*' => Token::Any,
+            '?' => Token::One,
+            '[' => {
+                let negated = chars.next_if_eq(&'^').is_some();
+                let mut ranges = Vec::new();
+                // A `]` straight after the opening bracket is one of the characters
+                if let Some(bracket) = chars.next_if_eq(&']') {
+                    ranges.push((bracket, bracket));
+                }
+
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(low) => {
+                            let high = match chars.peek() {
+                                Some('-') => {
+                                    chars.next();
+                                    match chars.next_if(|c| *c != ']') {
+                                        Some(high) => high,
+                                        // A trailing `-` is the character itself
+                                        None => {
+                                            ranges.push(('-', '-'));
+                                            low
+                                        }
+                                    }
+                                }
+                                _ => low,
+                            };
+                            ranges.push((low, high));
+                        }
+                        // An unclosed class can't match anything
+                        None => return false,
+                    }
+                }
+
+                Token::Class { negated, ranges }
+            }
+            c => Token::Literal(c),
+        };
+        tokens.push(token);
+    }
+
+    matches(&tokens, &text, false)
+}
+
+/// The value compared as text, with NULL having none
+fn as_text(value: &RecordValue) -> Option<String> {
+    match value {
+        RecordValue::Null => None,
+        RecordValue::String(text) => Some(text.clone()),
+        RecordValue::Blob(blob) => Some(String::from_utf8_lossy(blob).into_owned()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Matches the pattern against the whole of `text`, going back to the last `Any`
+/// to take one more character whenever the rest of the pattern fails
+fn matches(tokens: &[Token], text: &str, ignore_case: bool) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Token::Any) => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(token) if token.matches(text[t], ignore_case) => {
+                t += 1;
+                p += 1;
+            }
+            _ => match backtrack {
+                Some((any, start)) => {
+                    p = any + 1;
+                    t = start + 1;
+                    backtrack = Some((any, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    tokens[p..].iter().all(|token| matches!(token, Token::Any))
+}
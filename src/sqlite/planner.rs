@@ -0,0 +1,340 @@
+use super::schema::{SchemaTable, SqliteSchema};
+use super::sql::{same_name, CreateTable, ExprCondition, Operator, SelectStatement, SortOrder};
+use super::stat::SqliteStats;
+use std::fmt;
+
+/// Row estimate SQLite assumes for a table that has not been analyzed
+const DEFAULT_TABLE_ROWS: u64 = 1_048_576;
+
+/// Rows an equality lookup on an unanalyzed index is assumed to return
+const DEFAULT_EQ_ROWS: u64 = 10;
+
+/// A single-sided range predicate is assumed to keep 1 in N rows
+const RANGE_SELECTIVITY: u64 = 4;
+
+#[derive(Debug, Copy, Clone)]
+pub enum ScanPlan<'a> {
+    FullTable,
+    Index(&'a SchemaTable),
+    /// Every column the statement needs is part of the index key
+    CoveringIndex(&'a SchemaTable),
+    /// Two indexes probed for the rowids of different equality conditions, with only
+    /// the rows both find fetched from the table
+    IndexIntersection(&'a SchemaTable, &'a SchemaTable),
+}
+
+impl fmt::Display for ScanPlan<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FullTable => write!(f, "full table scan"),
+            Self::Index(index) => write!(f, "index {}", index.name),
+            Self::CoveringIndex(index) => write!(f, "covering index {}", index.name),
+            Self::IndexIntersection(lhs, rhs) => {
+                write!(f, "intersection of indexes {} and {}", lhs.name, rhs.name)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Reverse,
+}
+
+impl Direction {
+    pub fn reversed(&self) -> Self {
+        match self {
+            Self::Forward => Self::Reverse,
+            Self::Reverse => Self::Forward,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct QueryPlan<'a> {
+    pub scan: ScanPlan<'a>,
+    /// Order the scanned b-tree is walked in
+    pub direction: Direction,
+    /// Rows still need sorting for the ORDER BY after the scan
+    pub sort: bool,
+}
+
+/// Plans the scan for the statement, walking a b-tree in ORDER BY order where
+/// one is available so that rows don't need sorting afterwards
+pub fn plan<'a>(
+    schema: &'a SqliteSchema,
+    stats: &SqliteStats,
+    statement: &SelectStatement,
+) -> QueryPlan<'a> {
+    let Some(table) = schema.fetch_table(&statement.table) else {
+        return QueryPlan {
+            scan: ScanPlan::FullTable,
+            direction: Direction::Forward,
+            sort: !statement.order_by.is_empty(),
+        };
+    };
+
+    let table_schema = table.columns();
+    let scan = choose_scan(schema, stats, statement, &table_schema);
+    let [term] = &statement.order_by[..] else {
+        let sort = !statement.order_by.is_empty();
+        return QueryPlan {
+            scan: if sort { sortable(scan) } else { scan },
+            direction: Direction::Forward,
+            sort,
+        };
+    };
+
+    let collation = term
+        .collation
+        .clone()
+        .unwrap_or_else(|| table_schema.column_collation(&term.column));
+
+    let ordered_scan = match scan {
+        ScanPlan::Index(index) | ScanPlan::CoveringIndex(index)
+            if index.index_keys(&table_schema).first().is_some_and(|key| {
+                same_name(&key.name, &term.column) && key.collation.as_ref() == Some(&collation)
+            }) =>
+        {
+            Some(scan)
+        }
+        ScanPlan::Index(_) | ScanPlan::CoveringIndex(_) | ScanPlan::IndexIntersection(..) => None,
+        ScanPlan::FullTable => {
+            let rowid_alias = table_schema.rowid_alias().map(|c| c.name.as_str());
+            if rowid_alias.is_some_and(|alias| same_name(alias, &term.column)) {
+                Some(scan)
+            } else {
+                schema
+                    .fetch_index(
+                        &statement.table,
+                        &term.column,
+                        &collation,
+                        &statement.where_clause,
+                    )
+                    .map(ScanPlan::Index)
+            }
+        }
+    };
+
+    // A descending index stores its keys back to front
+    let stored_order = match ordered_scan {
+        Some(ScanPlan::Index(index)) | Some(ScanPlan::CoveringIndex(index)) => index
+            .indexed_columns()
+            .first()
+            .map_or(SortOrder::Asc, |key| key.order),
+        _ => SortOrder::Asc,
+    };
+
+    let direction = if term.order == stored_order {
+        Direction::Forward
+    } else {
+        Direction::Reverse
+    };
+
+    match ordered_scan {
+        Some(scan) => QueryPlan {
+            scan,
+            direction,
+            sort: false,
+        },
+        None => QueryPlan {
+            scan: sortable(scan),
+            direction: Direction::Forward,
+            sort: true,
+        },
+    }
+}
+
+/// The scan to use when its rows still need sorting afterwards, which rules out a
+/// covering scan as sorted rows can't be produced from its formatted output
+fn sortable(scan: ScanPlan<'_>) -> ScanPlan<'_> {
+    match scan {
+        ScanPlan::CoveringIndex(index) => ScanPlan::Index(index),
+        other => other,
+    }
+}
+
+/// Picks between a full table scan and an index scan for the statement
+///
+/// Each row found through the index costs a descent of the table b-tree, so an
+/// index is only worth using when the estimated matches times the depth of that
+/// descent is cheaper than reading every row. Of the WHERE conditions an index
+/// can answer, the one expected to match the fewest rows is looked up, and the
+/// rows it finds are checked against the rest.
+fn choose_scan<'a>(
+    schema: &'a SqliteSchema,
+    stats: &SqliteStats,
+    statement: &SelectStatement,
+    table_schema: &CreateTable,
+) -> ScanPlan<'a> {
+    let candidates: Vec<Candidate> = statement
+        .where_clause
+        .iter()
+        .filter(|condition| condition.operator.is_comparison())
+        .filter_map(|condition| {
+            let collation = condition
+                .collation
+                .clone()
+                .unwrap_or_else(|| table_schema.column_collation(&condition.column));
+            let index = schema.fetch_index(
+                &statement.table,
+                &condition.column,
+                &collation,
+                &statement.where_clause,
+            )?;
+
+            let (rows, table_rows) = estimate(stats, index, condition.operator);
+            Some(Candidate {
+                index,
+                operator: condition.operator,
+                column: &condition.column,
+                rows,
+                table_rows,
+            })
+        })
+        .collect();
+
+    let best = candidates
+        .iter()
+        .filter(|candidate| worth_index(stats, candidate.index, candidate.operator))
+        .min_by_key(|candidate| candidate.rows);
+    if let Some(scan) = choose_intersection(&candidates, best) {
+        return scan;
+    }
+
+    let Some(&Candidate { index, .. }) = best else {
+        return ScanPlan::FullTable;
+    };
+
+    if is_covering(index, statement, table_schema) {
+        ScanPlan::CoveringIndex(index)
+    } else {
+        ScanPlan::Index(index)
+    }
+}
+
+/// A WHERE condition an index can answer, with the rows it is expected to find
+struct Candidate<'a, 's> {
+    index: &'a SchemaTable,
+    operator: Operator,
+    column: &'s str,
+    rows: u64,
+    /// Rows of the table the index is on
+    table_rows: u64,
+}
+
+impl Candidate<'_, '_> {
+    /// Depth of the table b-tree, which finding each row by its rowid descends
+    fn seek_cost(&self) -> u64 {
+        u64::from(self.table_rows.max(1).ilog2()) + 1
+    }
+}
+
+/// Two single-column indexes answering equality conditions on different columns,
+/// when probing both and fetching just the rows they have in common is cheaper than
+/// fetching the rows `best` finds alone, or reading the whole table without one
+///
+/// The conditions are assumed independent, so the rows matching both are estimated
+/// as their share of the table multiplied together.
+fn choose_intersection<'a>(
+    candidates: &[Candidate<'a, '_>],
+    best: Option<&Candidate<'a, '_>>,
+) -> Option<ScanPlan<'a>> {
+    let mut equalities: Vec<&Candidate> = candidates
+        .iter()
+        .filter(|candidate| {
+            candidate.operator == Operator::Eq && candidate.index.indexed_columns().len() == 1
+        })
+        .collect();
+    equalities.sort_by_key(|candidate| candidate.rows);
+
+    let first = *equalities.first()?;
+    let second = equalities.iter().find(|candidate| {
+        candidate.index.name != first.index.name && !same_name(candidate.column, first.column)
+    })?;
+
+    let table_rows = first.table_rows.max(1);
+    let shared_rows = first.rows.saturating_mul(second.rows) / table_rows;
+    let cost = first.rows + second.rows + shared_rows.max(1).saturating_mul(first.seek_cost());
+    let alternative = best.map_or(table_rows, |best| {
+        best.rows.saturating_mul(best.seek_cost())
+    });
+
+    (cost < alternative.min(table_rows))
+        .then_some(ScanPlan::IndexIntersection(first.index, second.index))
+}
+
+/// An index keyed by the expression one of the statement's row filters compares,
+/// such as one on `lower(name)` for `WHERE lower(name) = 'x'`, when it is cheaper
+/// to look the rows up through than to scan the table, along with that filter
+pub fn expression_index<'a, 's>(
+    schema: &'a SqliteSchema,
+    stats: &SqliteStats,
+    statement: &'s SelectStatement,
+) -> Option<(&'a SchemaTable, &'s ExprCondition)> {
+    statement
+        .row_filters
+        .iter()
+        .filter(|filter| filter.operator.is_comparison())
+        .find_map(|filter| {
+            let collation = filter.collation.clone().unwrap_or_default();
+            let index =
+                schema.fetch_expression_index(&statement.table, &filter.expr, &collation)?;
+            worth_index(stats, index, filter.operator).then_some((index, filter))
+        })
+}
+
+/// Whether finding the rows matching `operator` through `index` is cheaper than
+/// scanning the table it indexes
+fn worth_index(stats: &SqliteStats, index: &SchemaTable, operator: Operator) -> bool {
+    let (estimated_rows, table_rows) = estimate(stats, index, operator);
+    let seek_cost = u64::from(table_rows.ilog2()) + 1;
+    estimated_rows.saturating_mul(seek_cost) < table_rows
+}
+
+/// The rows `index` is expected to find for a comparison with `operator`, along
+/// with the rows of the table it indexes
+fn estimate(stats: &SqliteStats, index: &SchemaTable, operator: Operator) -> (u64, u64) {
+    let index_stat = stats.index(&index.name);
+    let table_rows = index_stat
+        .map(|stat| stat.rows)
+        .or_else(|| stats.table_rows(&index.table_name))
+        .unwrap_or(DEFAULT_TABLE_ROWS)
+        .max(1);
+
+    let estimated_rows = match operator {
+        Operator::Eq => index_stat
+            .and_then(|stat| stat.avg_eq.first().copied())
+            .unwrap_or(DEFAULT_EQ_ROWS),
+        Operator::NotEq
+        | Operator::Like { .. }
+        | Operator::Glob
+        | Operator::Is
+        | Operator::IsNot => table_rows,
+        Operator::Lt | Operator::LtEq | Operator::Gt | Operator::GtEq => {
+            table_rows / RANGE_SELECTIVITY
+        }
+    };
+
+    (estimated_rows, table_rows)
+}
+
+fn is_covering(
+    index: &SchemaTable,
+    statement: &SelectStatement,
+    table_schema: &CreateTable,
+) -> bool {
+    let rowid_alias = table_schema
+        .rowid_alias()
+        .map(|column| column.name.as_str());
+    let index_columns = index.index_columns();
+
+    // Only the condition the index is probed with is checked during the scan
+    statement.where_clause.len() <= 1
+        && statement.row_filters.is_empty()
+        && statement.columns.iter().all(|column| {
+            index_columns.iter().any(|c| same_name(c, column))
+                || rowid_alias.is_some_and(|alias| same_name(alias, column))
+        })
+}
@@ -0,0 +1,76 @@
+use anyhow::{bail, Result};
+
+use super::{
+    cell::RecordValue,
+    pager::Pager,
+    result::ResultSet,
+    sql::{self, CreateStatement, ForeignKey},
+    SqliteReader,
+};
+
+impl<P: Pager> SqliteReader<P> {
+    /// Runs `PRAGMA name(argument)`, answering with the rows SQLite gives for it
+    ///
+    /// Only pragmas that read the database are supported.
+    pub fn pragma(&self, name: &str, argument: Option<&str>) -> Result<ResultSet> {
+        match name.to_lowercase().as_str() {
+            "foreign_key_list" => self.foreign_key_list(argument),
+            _ => bail!("unsupported pragma '{name}'"),
+        }
+    }
+
+    /// The foreign keys declared on `table` in the order they were written, or none
+    /// when there is no such table
+    pub fn foreign_keys(&self, table: &str) -> Result<Vec<ForeignKey>> {
+        let schema = self.schema()?;
+        let Some(entry) = schema.fetch_table(table) else {
+            return Ok(Vec::new());
+        };
+
+        match sql::create_statement(&entry.sql) {
+            Ok((_, CreateStatement::Table(table))) => Ok(table.foreign_keys),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// A row for each column of each foreign key, numbered from the last declared
+    /// the way SQLite numbers them
+    fn foreign_key_list(&self, table: Option<&str>) -> Result<ResultSet> {
+        let columns = [
+            "id",
+            "seq",
+            "table",
+            "from",
+            "to",
+            "on_update",
+            "on_delete",
+            "match",
+        ];
+        let mut result = ResultSet::new(columns.map(String::from).to_vec());
+        let Some(table) = table else {
+            return Ok(result);
+        };
+
+        for (id, key) in self.foreign_keys(table)?.iter().rev().enumerate() {
+            for (seq, column) in key.columns.iter().enumerate() {
+                let to = key
+                    .parent_columns
+                    .get(seq)
+                    .map_or(RecordValue::Null, |to| RecordValue::String(to.clone()));
+
+                result.rows.push(vec![
+                    RecordValue::I64(id as i64),
+                    RecordValue::I64(seq as i64),
+                    RecordValue::String(key.parent.clone()),
+                    RecordValue::String(column.clone()),
+                    to,
+                    RecordValue::String(key.on_update.to_string()),
+                    RecordValue::String(key.on_delete.to_string()),
+                    RecordValue::String("NONE".to_string()),
+                ]);
+            }
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,428 @@
+use anyhow::Result;
+
+use super::{affinity::Affinity, cell::RecordValue};
+
+type PrintfFn = fn(&[RecordValue]) -> Result<RecordValue>;
+
+/// `format()` and the older name SQLite keeps for it
+pub const FUNCTIONS: [(&str, PrintfFn); 2] = [("format", format), ("printf", format)];
+
+/// Significant digits a real is rounded to before it's printed, or with the `!` flag
+const REAL_DIGITS: usize = 16;
+const ALTERNATE_REAL_DIGITS: usize = 26;
+
+/// How one conversion is to be written, from the flags, width and precision
+/// between the `%` and the conversion character
+#[derive(Debug, Default)]
+struct Spec {
+    left_align: bool,
+    plus: bool,
+    space: bool,
+    zero_pad: bool,
+    /// `#`, which prefixes hex and octal and keeps the trailing zeros of `%g`
+    alternate: bool,
+    /// `,`, which groups the digits of an integer in thousands
+    thousands: bool,
+    /// `!`, which counts characters rather than bytes and gives reals more digits
+    bang: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// `format(FORMAT, ...)` - the arguments written into FORMAT the way the C
+/// `printf()` would, with SQLite's `%q`, `%Q` and `%w` for quoting text
+///
+/// Missing arguments are taken as NULL, and a conversion SQLite doesn't know ends
+/// the text there.
+fn format(args: &[RecordValue]) -> Result<RecordValue> {
+    let Some((format, args)) = args.split_first() else {
+        return Ok(RecordValue::Null);
+    };
+    let RecordValue::String(format) = Affinity::Text.cast(format) else {
+        return Ok(RecordValue::Null);
+    };
+
+    let mut args = args.iter();
+    let mut next_arg = || args.next().unwrap_or(&RecordValue::Null);
+    let mut out = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        let mut spec = Spec::default();
+        while let Some(flag) = chars.next_if(|c| "-+ 0#,!".contains(*c)) {
+            match flag {
+                '-' => spec.left_align = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '0' => spec.zero_pad = true,
+                '#' => spec.alternate = true,
+                ',' => spec.thousands = true,
+                _ => spec.bang = true,
+            }
+        }
+
+        // A width from an argument aligns to the left when it's negative
+        if chars.next_if_eq(&'*').is_some() {
+            let width = integer(next_arg());
+            spec.left_align |= width < 0;
+            spec.width = width.unsigned_abs() as usize;
+        } else {
+            spec.width = number(&mut chars);
+        }
+        if chars.next_if_eq(&'.').is_some() {
+            spec.precision = Some(match chars.next_if_eq(&'*') {
+                Some(_) => integer(next_arg()).unsigned_abs() as usize,
+                None => number(&mut chars),
+            });
+        }
+        while chars.next_if_eq(&'l').is_some() {}
+
+        let Some(conversion) = chars.next() else {
+            break;
+        };
+        let written = match conversion {
+            '%' => "%".to_string(),
+            'd' | 'i' => {
+                let value = integer(next_arg());
+                signed(
+                    &spec,
+                    value < 0,
+                    group(&spec, value.unsigned_abs().to_string()),
+                )
+            }
+            'u' => signed(
+                &spec,
+                false,
+                group(&spec, (integer(next_arg()) as u64).to_string()),
+            ),
+            'x' | 'X' | 'o' => {
+                let value = integer(next_arg()) as u64;
+                let (digits, prefix) = match conversion {
+                    'x' => (format!("{value:x}"), "0x"),
+                    'X' => (format!("{value:X}"), "0X"),
+                    _ => (format!("{value:o}"), "0"),
+                };
+                let digits = zero_extend(&spec, digits);
+                match spec.alternate && value != 0 && !digits.starts_with(prefix) {
+                    true => pad(&spec, prefix, &digits, true),
+                    false => pad(&spec, "", &digits, true),
+                }
+            }
+            'f' | 'e' | 'E' | 'g' | 'G' => real(&spec, conversion, next_arg()),
+            's' | 'z' => {
+                let text = text(next_arg()).unwrap_or_default();
+                pad(&spec, "", truncate(&spec, &text), false)
+            }
+            'c' => {
+                // Empty text and NULL give a NUL character, as the C string would
+                let text = text(next_arg()).unwrap_or_default();
+                let c = text.chars().next().unwrap_or('\0');
+                let repeated = c.to_string().repeat(spec.precision.unwrap_or(1).max(1));
+                pad(&spec, "", &repeated, false)
+            }
+            'q' | 'Q' | 'w' => {
+                let quote = if conversion == 'w' { '"' } else { '\'' };
+                let quoted = match text(next_arg()) {
+                    None if conversion == 'Q' => "NULL".to_string(),
+                    None => "(NULL)".to_string(),
+                    Some(text) => {
+                        let escaped = text.replace(quote, &format!("{quote}{quote}"));
+                        match conversion {
+                            'Q' => format!("'{escaped}'"),
+                            _ => escaped,
+                        }
+                    }
+                };
+                pad(&spec, "", &quoted, false)
+            }
+            _ => break,
+        };
+        out.push_str(&written);
+    }
+
+    Ok(RecordValue::String(out))
+}
+
+/// The width or precision the digits next in the format spell out
+fn number(chars: &mut std::iter::Peekable<std::str::Chars>) -> usize {
+    let mut number = 0usize;
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        number = number
+            .saturating_mul(10)
+            .saturating_add(digit as usize - '0' as usize);
+    }
+    number
+}
+
+/// The argument as an integer, the way `CAST(X AS INTEGER)` reads it, or 0 for NULL
+fn integer(value: &RecordValue) -> i64 {
+    Affinity::Integer.cast(value).as_i64().unwrap_or(0)
+}
+
+/// The argument as text, or nothing for NULL
+fn text(value: &RecordValue) -> Option<String> {
+    match Affinity::Text.cast(value) {
+        RecordValue::String(text) => Some(text),
+        _ => None,
+    }
+}
+
+/// Text cut down to the precision, counted in bytes unless the `!` flag counts
+/// characters, without splitting a character
+fn truncate<'a>(spec: &Spec, text: &'a str) -> &'a str {
+    let Some(precision) = spec.precision else {
+        return text;
+    };
+
+    let end = match spec.bang {
+        true => text.char_indices().nth(precision).map(|(i, _)| i),
+        false => (precision < text.len())
+            .then(|| (0..=precision).rev().find(|&i| text.is_char_boundary(i)))
+            .flatten(),
+    };
+    &text[..end.unwrap_or(text.len())]
+}
+
+/// Digits grouped in threes with commas for the `,` flag
+fn group(spec: &Spec, digits: String) -> String {
+    let digits = zero_extend(spec, digits);
+    if !spec.thousands {
+        return digits;
+    }
+
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Digits padded with zeros to the precision, the fewest an integer is written with
+fn zero_extend(spec: &Spec, digits: String) -> String {
+    match spec.precision {
+        Some(precision) if precision > digits.len() => {
+            format!("{}{digits}", "0".repeat(precision - digits.len()))
+        }
+        _ => digits,
+    }
+}
+
+/// A number behind the sign the flags call for
+fn signed(spec: &Spec, negative: bool, digits: String) -> String {
+    let sign = match (negative, spec.plus, spec.space) {
+        (true, _, _) => "-",
+        (false, true, _) => "+",
+        (false, false, true) => " ",
+        _ => "",
+    };
+    pad(spec, sign, &digits, true)
+}
+
+/// `prefix` and `body` padded out to the width, with zeros between them for a
+/// number with the `0` flag and spaces otherwise
+fn pad(spec: &Spec, prefix: &str, body: &str, numeric: bool) -> String {
+    let len = match spec.bang {
+        true => prefix.chars().count() + body.chars().count(),
+        false => prefix.len() + body.len(),
+    };
+    let fill = spec.width.saturating_sub(len);
+    match (spec.left_align, spec.zero_pad && numeric) {
+        (true, _) => format!("{prefix}{body}{}", " ".repeat(fill)),
+        (false, true) => format!("{prefix}{}{body}", "0".repeat(fill)),
+        (false, false) => format!("{}{prefix}{body}", " ".repeat(fill)),
+    }
+}
+
+/// A real in fixed point, with an exponent, or for `%g` in whichever of the two
+/// suits its size
+fn real(spec: &Spec, conversion: char, value: &RecordValue) -> String {
+    let value = match Affinity::Real.cast(value) {
+        RecordValue::F64(value) => value,
+        _ => 0.0,
+    };
+    if value.is_nan() {
+        return pad(spec, "", "NaN", false);
+    }
+    if value.is_infinite() {
+        let signed = signed(&Spec { width: 0, ..*spec }, value < 0.0, "Inf".to_string());
+        return pad(spec, "", &signed, false);
+    }
+
+    let precision = spec.precision.unwrap_or(6);
+    let max_digits = match spec.bang {
+        true => ALTERNATE_REAL_DIGITS,
+        false => REAL_DIGITS,
+    };
+    // The `!` flag always writes the point, and leaves a zero after it when trimmed
+    let point = spec.alternate || spec.bang;
+    let (body, trim) = match conversion {
+        'f' => {
+            let decimal = Decimal::new(value.abs(), -(precision as i32), max_digits);
+            (decimal.fixed(precision, point), spec.bang)
+        }
+        'e' | 'E' => {
+            let decimal = Decimal::new(value.abs(), precision as i32 + 1, max_digits);
+            let written = decimal.exponent(precision, point, conversion == 'E');
+            (written, spec.bang)
+        }
+        _ => {
+            // The precision is the number of significant digits, with trailing zeros
+            // dropped unless the `#` flag keeps them
+            let significant = precision.max(1);
+            let decimal = Decimal::new(value.abs(), significant as i32, max_digits);
+            let exponent = decimal.point - 1;
+            let written = match exponent < -4 || exponent >= significant as i32 {
+                true => decimal.exponent(significant - 1, point, conversion == 'G'),
+                false => {
+                    let precision = (significant as i32 - 1 - exponent) as usize;
+                    decimal.fixed(precision, point)
+                }
+            };
+            (written, !spec.alternate || spec.bang)
+        }
+    };
+    let body = match trim {
+        true => trim_zeros(&body, spec.bang),
+        false => body,
+    };
+    signed(spec, value.is_sign_negative() && value != 0.0, body)
+}
+
+/// Drops the zeros ending the fraction of a real, keeping any exponent, and the
+/// point too once nothing's left after it unless `keep_point` leaves one zero
+fn trim_zeros(written: &str, keep_point: bool) -> String {
+    let (mantissa, exponent) = match written.find(['e', 'E']) {
+        Some(i) => written.split_at(i),
+        None => (written, ""),
+    };
+    if !mantissa.contains('.') {
+        return written.to_string();
+    }
+
+    let mantissa = mantissa.trim_end_matches('0');
+    match mantissa.strip_suffix('.') {
+        Some(whole) if keep_point => format!("{whole}.0{exponent}"),
+        Some(whole) => format!("{whole}{exponent}"),
+        None => format!("{mantissa}{exponent}"),
+    }
+}
+
+/// The decimal digits of a real, rounded the way SQLite rounds them for printing:
+/// half away from zero, and to no more than a set number of significant digits
+struct Decimal {
+    /// The significant digits, without leading zeros unless the value is zero
+    digits: Vec<u8>,
+    /// Digits before the decimal point, negative when there are zeros after it
+    /// before the first digit
+    point: i32,
+}
+
+impl Decimal {
+    /// Rounds `value` to `round` significant digits, or when `round` isn't positive
+    /// to `-round` digits after the decimal point
+    fn new(value: f64, round: i32, max_digits: usize) -> Self {
+        if value == 0.0 {
+            return Self {
+                digits: vec![b'0'],
+                point: 1,
+            };
+        }
+
+        let written = format!("{value:.*e}", ALTERNATE_REAL_DIGITS + 3);
+        let (mantissa, exponent) = written.split_once('e').expect("an exponent");
+        let mut digits: Vec<u8> = mantissa.bytes().filter(u8::is_ascii_digit).collect();
+        let mut point = exponent.parse::<i32>().expect("a whole exponent") + 1;
+
+        let mut round = round;
+        if round <= 0 {
+            round = point - round;
+            // Rounding to the digit before the first can still carry into it
+            if round == 0 && digits[0] >= b'5' {
+                digits.insert(0, b'0');
+                point += 1;
+                round = 1;
+            }
+        }
+        if round > 0 {
+            let round = (round as usize).min(max_digits);
+            if round < digits.len() {
+                let carry = digits[round] >= b'5';
+                digits.truncate(round);
+                if carry {
+                    Self::carry(&mut digits, &mut point);
+                }
+            }
+        }
+
+        while digits.len() > 1 && digits.last() == Some(&b'0') {
+            digits.pop();
+        }
+        if digits[0] == b'0' && digits.len() > 1 {
+            digits.remove(0);
+            point -= 1;
+        }
+        Self { digits, point }
+    }
+
+    /// Adds one to the last digit, carrying into a new first digit when they're all 9
+    fn carry(digits: &mut Vec<u8>, point: &mut i32) {
+        for digit in digits.iter_mut().rev() {
+            if *digit == b'9' {
+                *digit = b'0';
+            } else {
+                *digit += 1;
+                return;
+            }
+        }
+        digits.insert(0, b'1');
+        *point += 1;
+    }
+
+    /// The digit at `position` counting from the first significant one, which is
+    /// zero outside of the digits kept
+    fn digit(&self, position: i32) -> char {
+        usize::try_from(position)
+            .ok()
+            .and_then(|position| self.digits.get(position))
+            .map_or('0', |&digit| digit as char)
+    }
+
+    /// Written in fixed point with `precision` digits after the point
+    fn fixed(&self, precision: usize, alternate: bool) -> String {
+        let mut written: String = match self.point > 0 {
+            true => (0..self.point).map(|i| self.digit(i)).collect(),
+            false => "0".to_string(),
+        };
+        if precision > 0 || alternate {
+            written.push('.');
+        }
+        written.extend((0..precision as i32).map(|i| self.digit(self.point + i)));
+        written
+    }
+
+    /// Written as one digit before the point and `precision` after it, followed by
+    /// an exponent of at least two digits
+    fn exponent(&self, precision: usize, alternate: bool, upper: bool) -> String {
+        let mut written = self.digit(0).to_string();
+        if precision > 0 || alternate {
+            written.push('.');
+        }
+        written.extend((1..=precision as i32).map(|i| self.digit(i)));
+
+        let exponent = if self.digits == [b'0'] {
+            0
+        } else {
+            self.point - 1
+        };
+        let e = if upper { 'E' } else { 'e' };
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{written}{e}{sign}{:02}", exponent.unsigned_abs())
+    }
+}
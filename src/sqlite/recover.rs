@@ -0,0 +1,268 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+use super::{
+    cell::{self, LeafCell, RecordValue},
+    page::{self, BTreePageHeader, BTreePageType, Freeblock},
+    pager::Pager,
+    parse_signed_varint, parse_varint, SqliteReader, HEADER_SIZE,
+};
+
+/// Size of the next offset and size fields at the start of each freeblock
+const FREEBLOCK_HEADER: usize = 4;
+
+/// Number of columns in the `sqlite_schema` table
+const SCHEMA_COLUMNS: usize = 5;
+
+/// The shape of the rows a table leaf page holds, when the page belongs to a table
+#[derive(Debug, Clone, Copy)]
+struct RowShape {
+    columns: usize,
+    /// Whether the first column is the rowid alias, which is always stored as `NULL`
+    leading_rowid_alias: bool,
+}
+
+/// A row decoded from space the database no longer uses
+#[derive(Debug, Clone)]
+pub struct RecoveredRow {
+    /// Page number the row was found on, counting from 1
+    pub page: usize,
+    /// Offset of the row from the start of its page
+    pub offset: usize,
+    /// Rowid of the row when the start of its cell survived
+    pub row_id: Option<i64>,
+    pub values: Vec<RecordValue>,
+}
+
+impl std::fmt::Display for RecoveredRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}|{}|", self.page, self.offset)?;
+        if let Some(row_id) = self.row_id {
+            write!(f, "{row_id}")?;
+        }
+
+        for value in self.values.iter() {
+            write!(f, "|{value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<P: Pager> SqliteReader<P> {
+    /// Prints the deleted rows that can still be read from free space, each labelled
+    /// with the page and offset it was found at
+    pub fn recover(&self, out: &mut dyn io::Write) -> Result<()> {
+        for row in self.recovered_rows()? {
+            writeln!(out, "{row}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Carves deleted rows out of the freeblocks of table leaf pages and out of the
+    /// pages on the freelist
+    ///
+    /// SQLite overwrites the first four bytes of a deleted cell with the freeblock
+    /// header, so rows whose record header started within them can't be recovered.
+    pub fn recovered_rows(&self) -> Result<Vec<RecoveredRow>> {
+        let freelist: HashSet<usize> = self.freelist_pages()?.into_iter().collect();
+        let shapes = self.row_shapes()?;
+        let mut rows = Vec::new();
+        for page in 0..self.page_count() {
+            if self.pager.is_lock_byte_page(page) {
+                continue;
+            }
+
+            let buf = self.page_bytes(page)?;
+            let header = match buf.first() {
+                Some(&page_type) if page_type == BTreePageType::LeafTable as u8 => {
+                    BTreePageHeader::new(buf, page).ok()
+                }
+                _ => None,
+            };
+
+            let Some(header) = header else {
+                // Trunk pages and reused pages lose their header but keep old cells
+                if freelist.contains(&page) {
+                    carve_page(page, buf, &mut rows);
+                }
+
+                continue;
+            };
+
+            // Freed pages keep their cells, though nothing points at them anymore
+            if freelist.contains(&page) {
+                self.carve_cells(page, buf, &header, &mut rows);
+            }
+
+            carve_freeblocks(page, buf, &header, shapes.get(&page).copied(), &mut rows);
+        }
+
+        Ok(rows)
+    }
+
+    /// The row shape of every page belonging to a table
+    fn row_shapes(&self) -> Result<HashMap<usize, RowShape>> {
+        let mut shapes = HashMap::new();
+        let schema_shape = RowShape {
+            columns: SCHEMA_COLUMNS,
+            leading_rowid_alias: false,
+        };
+        shapes.extend(self.btree_pages(0)?.into_iter().map(|p| (p, schema_shape)));
+
+        let schema = self.schema()?;
+        for table in schema.entries().filter(|entry| entry.is_table()) {
+            let columns = table.columns().columns;
+            let shape = RowShape {
+                columns: columns.len(),
+                leading_rowid_alias: columns.first().is_some_and(|c| c.is_rowid_alias()),
+            };
+
+            let pages = self.btree_pages(table.root_page as usize)?;
+            shapes.extend(pages.into_iter().map(|p| (p, shape)));
+        }
+
+        Ok(shapes)
+    }
+
+    fn carve_cells(
+        &self,
+        page: usize,
+        buf: &[u8],
+        header: &BTreePageHeader,
+        rows: &mut Vec<RecoveredRow>,
+    ) {
+        let overflow = self.overflow();
+        let Ok(pointers) = page::cell_pointers(buf, header) else {
+            return;
+        };
+
+        for offset in pointers {
+            let Some(cell_buf) = page::page_offset(offset, page).and_then(|o| buf.get(o..)) else {
+                continue;
+            };
+
+            if let Ok(LeafCell {
+                row_id, payload, ..
+            }) = LeafCell::new(cell_buf, overflow)
+            {
+                rows.push(RecoveredRow {
+                    page: page + 1,
+                    offset,
+                    row_id: Some(row_id),
+                    values: payload,
+                });
+            }
+        }
+    }
+}
+
+/// Scans each freeblock for records, trying every offset past the freeblock header
+///
+/// Neighbouring deleted cells merge into one freeblock, so past the first one whole
+/// cells survive and are recognised by their payload size matching their record.
+fn carve_freeblocks(
+    page: usize,
+    buf: &[u8],
+    header: &BTreePageHeader,
+    shape: Option<RowShape>,
+    rows: &mut Vec<RecoveredRow>,
+) {
+    for Freeblock {
+        offset: start,
+        size,
+    } in page::freeblocks(buf, header, page)
+    {
+        let Some(freeblock) = page::page_offset(start, page).and_then(|o| buf.get(o..o + size))
+        else {
+            continue;
+        };
+
+        let mut offset = FREEBLOCK_HEADER;
+        while offset < freeblock.len() {
+            let carved = carve_cell(&freeblock[offset..], shape)
+                .or_else(|| carve_row(&freeblock[offset..], shape, offset == FREEBLOCK_HEADER));
+
+            match carved {
+                Some((row_id, values, len)) => {
+                    rows.push(RecoveredRow {
+                        page: page + 1,
+                        offset: start + offset,
+                        row_id,
+                        values,
+                    });
+                    offset += len;
+                }
+                None => offset += 1,
+            }
+        }
+    }
+}
+
+/// Scans a whole page for table leaf cells that survived it being repurposed
+fn carve_page(page: usize, buf: &[u8], rows: &mut Vec<RecoveredRow>) {
+    let mut offset = 0;
+    while offset < buf.len() {
+        match carve_cell(&buf[offset..], None) {
+            Some((row_id, values, len)) => {
+                rows.push(RecoveredRow {
+                    page: page + 1,
+                    offset: buffer_start(page) + offset,
+                    row_id,
+                    values,
+                });
+                offset += len;
+            }
+            None => offset += 1,
+        }
+    }
+}
+
+type Carved = (Option<i64>, Vec<RecordValue>, usize);
+
+/// Reads a whole table leaf cell whose payload size and rowid survived
+fn carve_cell(buf: &[u8], shape: Option<RowShape>) -> Option<Carved> {
+    let (payload_size, payload_len) = parse_varint(buf);
+    let (row_id, row_id_len) = parse_signed_varint(buf.get(payload_len..)?);
+    let record_start = payload_len + row_id_len;
+    let (values, len) = cell::carve_record(buf.get(record_start..)?)?;
+
+    let fits_shape = shape.map_or(true, |shape| shape.columns == values.len());
+    (len as u64 == payload_size && fits_shape).then_some((Some(row_id), values, record_start + len))
+}
+
+/// Reads a record without the cell around it, also trying to rebuild the first record
+/// of a freeblock whose header size the freeblock header overwrote
+fn carve_row(buf: &[u8], shape: Option<RowShape>, freeblock_start: bool) -> Option<Carved> {
+    let fits_shape = |values: &[RecordValue]| shape.map_or(true, |s| s.columns == values.len());
+    if let Some((values, len)) = cell::carve_record(buf).filter(|(values, _)| fits_shape(values)) {
+        return Some((None, values, len));
+    }
+
+    let shape = shape.filter(|_| freeblock_start)?;
+    if let Some((values, len)) = cell::carve_headless_record(buf, shape.columns) {
+        return Some((None, values, len));
+    }
+
+    // With one byte each for the payload size and rowid the first serial type is lost
+    // too, but it is known to be `NULL` when it's the rowid alias
+    if shape.leading_rowid_alias {
+        let (values, len) = cell::carve_headless_record(buf, shape.columns - 1)?;
+        let values = std::iter::once(RecordValue::Null).chain(values).collect();
+        return Some((None, values, len));
+    }
+
+    None
+}
+
+/// On-disk offset of the start of the page buffer, which for the first page begins
+/// after the database header
+fn buffer_start(page: usize) -> usize {
+    if page == 0 {
+        HEADER_SIZE
+    } else {
+        0
+    }
+}
@@ -0,0 +1,78 @@
+use super::cell::RecordValue;
+use std::fmt;
+
+/// The columns and rows produced by a query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    /// Where each column comes from, only known for the columns of a query
+    pub metadata: Vec<ColumnMetadata>,
+    pub rows: Vec<Vec<RecordValue>>,
+}
+
+/// The declared type and origin of a result column, as SQLite gives them through
+/// `sqlite3_column_decltype` and `sqlite3_column_origin_name`
+///
+/// Only a column read straight from a table has any of these, not one computed
+/// from an expression or aggregate.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnMetadata {
+    pub name: String,
+    /// The type the column was declared with, if it was given one
+    pub declared_type: Option<String>,
+    /// The database holding the table, as `main` or the name it was attached as
+    pub database: Option<String>,
+    pub table: Option<String>,
+    pub column: Option<String>,
+}
+
+impl ColumnMetadata {
+    /// A column with nothing known about where it came from
+    pub fn computed(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+impl ResultSet {
+    pub fn new(columns: Vec<String>) -> Self {
+        Self {
+            metadata: columns
+                .iter()
+                .map(|name| ColumnMetadata::computed(name))
+                .collect(),
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    /// A result with one column and a single row, as an aggregate produces
+    pub fn single(column: String, value: RecordValue) -> Self {
+        Self {
+            metadata: vec![ColumnMetadata::computed(&column)],
+            columns: vec![column],
+            rows: vec![vec![value]],
+        }
+    }
+
+    /// Keeps only the first `max_rows` rows, returning how many were dropped
+    pub fn truncate(&mut self, max_rows: usize) -> usize {
+        let dropped = self.rows.len().saturating_sub(max_rows);
+        self.rows.truncate(max_rows);
+        dropped
+    }
+}
+
+/// Each row on its own line with columns separated by `|`
+impl fmt::Display for ResultSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.rows.iter() {
+            let row: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+            writeln!(f, "{}", row.join("|"))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::{bail, Context, Result};
+use bytes::Buf;
+
+use super::{
+    cell::RecordValue,
+    cursor::BtCursor,
+    pager::Pager,
+    result::ResultSet,
+    sql::{CreateVirtualTable, SelectStatement},
+    vtab::{self, MemoryTable},
+    SqliteReader,
+};
+
+/// The node the tree starts from, which also records the depth of the tree
+const ROOT_NODE: i64 = 1;
+
+/// An R-Tree table declared in the schema
+///
+/// The tree is kept in the `%_node` shadow table, one blob per node, with the node
+/// holding each row listed in `%_rowid` along with the row's auxiliary columns.
+/// `%_parent` only links nodes back up the tree, which reading it doesn't need.
+#[derive(Debug, Clone)]
+pub struct RTreeTable {
+    pub name: String,
+    /// The integer id followed by a minimum and maximum for each dimension
+    pub columns: Vec<String>,
+    /// Columns declared with a leading `+`, which are stored in `%_rowid` rather
+    /// than in the tree
+    pub auxiliary: Vec<String>,
+    /// Declared with `rtree_i32`, storing coordinates as 32-bit integers rather than
+    /// 32-bit floats
+    pub integer: bool,
+}
+
+impl RTreeTable {
+    /// Reads the declaration of an R-Tree table, or `None` for any other module
+    pub fn new(declaration: &CreateVirtualTable) -> Option<Self> {
+        let integer = match declaration.module.to_ascii_lowercase().as_str() {
+            "rtree" => false,
+            "rtree_i32" => true,
+            _ => return None,
+        };
+
+        let mut columns = Vec::new();
+        let mut auxiliary = Vec::new();
+        for argument in declaration.arguments.iter() {
+            match argument.strip_prefix('+') {
+                Some(name) => auxiliary.push(column_name(name)),
+                None => columns.push(column_name(argument)),
+            }
+        }
+
+        Some(Self {
+            name: declaration.name.clone(),
+            columns,
+            auxiliary,
+            integer,
+        })
+    }
+
+    /// Number of dimensions, each with a minimum and maximum coordinate
+    fn dimensions(&self) -> usize {
+        self.columns.len().saturating_sub(1) / 2
+    }
+}
+
+/// The name of a column, leaving out any type it was declared with
+fn column_name(argument: &str) -> String {
+    let name = argument.split_whitespace().next().unwrap_or_default();
+    name.trim_matches(|c| c == '"' || c == '\'' || c == '`')
+        .to_string()
+}
+
+impl<P: Pager> SqliteReader<P> {
+    /// Answers a query against an R-Tree table by reading every row out of its nodes
+    pub fn rtree_select(
+        &self,
+        table: &RTreeTable,
+        statement: &SelectStatement,
+    ) -> Result<ResultSet> {
+        let mut columns = table.columns.clone();
+        columns.extend(table.auxiliary.iter().cloned());
+
+        let rows = self.rtree_rows(table)?;
+        vtab::select(&MemoryTable::new(columns, rows), statement)
+    }
+
+    /// Every row of an R-Tree table in the order its leaves hold them
+    pub fn rtree_rows(&self, table: &RTreeTable) -> Result<Vec<Vec<RecordValue>>> {
+        let schema = self.schema()?;
+        let shadow_root = |suffix: &str| {
+            let name = format!("{}_{suffix}", table.name);
+            schema
+                .fetch_table(&name)
+                .map(|entry| entry.root_page as usize)
+                .with_context(|| format!("r-tree table '{}' has no {name} table", table.name))
+        };
+
+        let mut nodes = BtCursor::table(self, shadow_root("node")?);
+        let mut row_ids = BtCursor::table(self, shadow_root("rowid")?);
+
+        let root = node_data(&mut nodes, ROOT_NODE)?;
+        if root.len() < 2 {
+            bail!("r-tree root node is too short");
+        }
+        let depth = (&root[..2]).get_u16();
+
+        let mut rows = Vec::new();
+        let mut pending = vec![(ROOT_NODE, depth)];
+        while let Some((node, depth)) = pending.pop() {
+            let data = node_data(&mut nodes, node)?;
+            let cells = node_cells(table, node, &data)?;
+
+            // Children are pushed in reverse so they come off the stack in order
+            if depth > 0 {
+                pending.extend(cells.into_iter().rev().map(|(child, _)| (child, depth - 1)));
+                continue;
+            }
+
+            for (row_id, coordinates) in cells {
+                let mut row = Vec::with_capacity(1 + coordinates.len() + table.auxiliary.len());
+                row.push(RecordValue::I64(row_id));
+                row.extend(coordinates);
+
+                if !table.auxiliary.is_empty() {
+                    row_ids.seek_rowid(row_id)?;
+                    let values = row_ids
+                        .row()
+                        .filter(|entry| entry.row_id == row_id)
+                        .map(|entry| entry.payload.iter().skip(2).cloned().collect::<Vec<_>>())
+                        .unwrap_or_default();
+                    row.extend(values);
+                    row.resize(
+                        1 + 2 * table.dimensions() + table.auxiliary.len(),
+                        RecordValue::Null,
+                    );
+                }
+
+                rows.push(row);
+            }
+        }
+
+        Ok(rows)
+    }
+}
+
+/// The blob a node is stored as in `%_node`
+fn node_data<P: Pager>(nodes: &mut BtCursor<'_, P>, node: i64) -> Result<Vec<u8>> {
+    nodes.seek_rowid(node)?;
+    match nodes.row().filter(|entry| entry.row_id == node) {
+        Some(entry) => match entry.payload.get(1) {
+            Some(RecordValue::Blob(data)) => Ok(data.to_vec()),
+            other => bail!("r-tree node {node} isn't a blob - found {other:?}"),
+        },
+        None => bail!("r-tree node {node} is missing"),
+    }
+}
+
+/// Decodes the cells of a node: a big-endian depth (only meaningful on the
+/// root) and cell count, then for each cell a 64-bit rowid or child node number
+/// followed by the coordinates
+fn node_cells(
+    table: &RTreeTable,
+    node: i64,
+    mut data: &[u8],
+) -> Result<Vec<(i64, Vec<RecordValue>)>> {
+    let coordinates = 2 * table.dimensions();
+    if data.remaining() < 4 {
+        bail!("r-tree node {node} is too short");
+    }
+
+    let _depth = data.get_u16();
+    let count = usize::from(data.get_u16());
+    if data.remaining() < count * (8 + 4 * coordinates) {
+        bail!("r-tree node {node} holds more cells than fit in it");
+    }
+
+    let cells = (0..count)
+        .map(|_| {
+            let id = data.get_i64();
+            let values = (0..coordinates)
+                .map(|_| {
+                    if table.integer {
+                        RecordValue::I64(i64::from(data.get_i32()))
+                    } else {
+                        RecordValue::F64(f64::from(data.get_f32()))
+                    }
+                })
+                .collect();
+            (id, values)
+        })
+        .collect();
+
+    Ok(cells)
+}
@@ -1,39 +1,143 @@
 use super::cell::{DatabaseCell, RecordValue};
-use super::page::{BTreePage, BTreePageType};
-use super::sql::{self, CreateStatement, CreateTable};
+use super::collation::Collation;
+use super::error::DecodeError;
+use super::sql::{
+    self, Condition, CreateStatement, CreateTable, CreateView, CreateVirtualTable, Expr,
+    IndexFilter, IndexedColumn,
+};
 use std::collections::BTreeMap;
 
+/// Start of the names SQLite keeps for its own tables and indexes
+const INTERNAL_PREFIX: &str = "sqlite_";
+
+/// Start of the names of the indexes SQLite makes for UNIQUE and PRIMARY KEY
+/// constraints, which go on to the table name and the number of the constraint
+const AUTOINDEX_PREFIX: &str = "sqlite_autoindex_";
+
 #[derive(Debug)]
 pub struct SqliteSchema {
     tables: BTreeMap<String, SchemaTable>,
 }
 
 impl SqliteSchema {
-    pub fn new(page: BTreePage) -> Self {
-        assert!(page.header.page_type == BTreePageType::LeafTable);
-
+    /// Reads the entries from the rows of the `sqlite_schema` table
+    pub fn new<'a>(rows: impl IntoIterator<Item = &'a DatabaseCell>) -> Result<Self, DecodeError> {
         let mut tables = BTreeMap::default();
-        for cell in page.cells.iter() {
-            let table = SchemaTable::new(cell);
+        for cell in rows {
+            let table = SchemaTable::new(cell)?;
             tables.insert(table.name.clone(), table);
         }
 
-        Self { tables }
+        Ok(Self { tables })
     }
 
-    pub fn fetch_index(&self, table: &str) -> Option<&SchemaTable> {
-        self.tables
-            .values()
-            .find(|&value| value.table_name == table && &value.sqlite_type == "index")
+    /// An index whose leading column is `column`, ordered by `collation` so that it
+    /// can answer comparisons made with that collation
+    ///
+    /// A partial index is only returned when one of `conditions` implies its `WHERE`
+    /// clause, as otherwise it is missing rows the query needs.
+    pub fn fetch_index(
+        &self,
+        table: &str,
+        column: &str,
+        collation: &Collation,
+        conditions: &[Condition],
+    ) -> Option<&SchemaTable> {
+        self.find_index(table, collation, conditions, |key| {
+            key.expr.is_none() && sql::same_name(&key.name, column)
+        })
+    }
+
+    /// An index whose leading key is the expression `expr`, ordered by `collation`
+    ///
+    /// Partial indexes are left out, as only a column condition can imply their
+    /// `WHERE` clause.
+    pub fn fetch_expression_index(
+        &self,
+        table: &str,
+        expr: &Expr,
+        collation: &Collation,
+    ) -> Option<&SchemaTable> {
+        self.find_index(table, collation, &[], |key| key.expr.as_ref() == Some(expr))
+    }
+
+    fn find_index(
+        &self,
+        table: &str,
+        collation: &Collation,
+        conditions: &[Condition],
+        is_key: impl Fn(&IndexedColumn) -> bool,
+    ) -> Option<&SchemaTable> {
+        let create = match self
+            .fetch_table(table)
+            .map(|entry| sql::create_statement(&entry.sql))
+        {
+            Some(Ok((_, CreateStatement::Table(create)))) => create,
+            _ => return None,
+        };
+
+        self.tables.values().find(|&value| {
+            sql::same_name(&value.table_name, table)
+                && &value.sqlite_type == "index"
+                && value.index_keys(&create).first().is_some_and(|key| {
+                    is_key(key) && key.collation.as_ref().unwrap_or(&Collation::Binary) == collation
+                })
+                && value.index_filter().map_or(true, |filter| {
+                    filter.expr.is_some_and(|expr| {
+                        conditions.iter().any(|condition| condition.implies(&expr))
+                    })
+                })
+        })
     }
 
     pub fn fetch_table(&self, table: &str) -> Option<&SchemaTable> {
-        self.tables.get(table)
+        self.tables.get(table).or_else(|| {
+            self.tables
+                .values()
+                .find(|entry| sql::same_name(&entry.name, table))
+        })
     }
 
     pub fn tables(&self) -> Vec<&str> {
         self.tables.keys().map(|t| t.as_str()).collect()
     }
+
+    /// Every table and index in the schema, ordered by name
+    pub fn entries(&self) -> impl Iterator<Item = &SchemaTable> {
+        self.tables.values()
+    }
+
+    /// The UNIQUE indexes on `table` with the columns each is keyed by, including
+    /// those SQLite made for the table's UNIQUE and PRIMARY KEY constraints
+    pub fn unique_indexes(&self, table: &SchemaTable) -> Vec<(&SchemaTable, Vec<IndexedColumn>)> {
+        // A constraint on the same columns as an earlier one shares its index, so
+        // isn't numbered
+        let mut keys: Vec<Vec<IndexedColumn>> = Vec::new();
+        for key in table.columns().unique_keys {
+            if !keys.contains(&key.columns) {
+                keys.push(key.columns);
+            }
+        }
+
+        let autoindex_prefix = format!("{AUTOINDEX_PREFIX}{}_", table.name);
+        self.tables
+            .values()
+            .filter(|entry| entry.is_index() && entry.table_name == table.name)
+            .filter_map(|index| {
+                if let Some(number) = index.name.strip_prefix(&autoindex_prefix) {
+                    let number = number.parse::<usize>().ok()?.checked_sub(1)?;
+                    return Some((index, keys.get(number)?.clone()));
+                }
+
+                match sql::create_statement(&index.sql) {
+                    Ok((_, CreateStatement::Index(create))) if create.unique => {
+                        Some((index, index.index_keys(&table.columns())))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug)]
@@ -43,48 +147,111 @@ pub struct SchemaTable {
     pub table_name: String,
     pub root_page: u64,
     pub sql: String,
+    /// Position of the entry in `sqlite_schema`, which is the order it was created in
+    pub row_id: i64,
 }
 
 impl SchemaTable {
-    pub fn new(cell: &DatabaseCell) -> Self {
-        match cell {
-            DatabaseCell::Leaf(inner) => {
-                assert!(inner.payload.len() == 5);
-                let RecordValue::String(sqlite_type) = &inner.payload[0] else {
-                    panic!("expected a string(sqlite_type)");
-                };
-
-                let RecordValue::String(name) = &inner.payload[1] else {
-                    panic!("expected a string(name)");
-                };
-
-                let RecordValue::String(table_name) = &inner.payload[2] else {
-                    panic!("expected a string(table_name)");
-                };
-
-                let root_page = match &inner.payload[3] {
-                    RecordValue::I8(value) => *value as u64,
-                    RecordValue::I16(value) => *value as u64,
-                    RecordValue::I24(value) => *value as u64,
-                    RecordValue::I32(value) => *value as u64,
-                    RecordValue::I48(value) => *value as u64,
-                    RecordValue::I64(value) => *value as u64,
-                    other => panic!("expected an integer(root_page) - found {other:#?}"),
-                };
-
-                let RecordValue::String(sql) = &inner.payload[4] else {
-                    panic!("exptected a string(sql)");
-                };
-
-                Self {
-                    sqlite_type: sqlite_type.clone(),
-                    name: name.clone(),
-                    table_name: table_name.clone(),
-                    root_page: root_page - 1,
-                    sql: sql.clone(),
-                }
+    pub fn new(cell: &DatabaseCell) -> Result<Self, DecodeError> {
+        let DatabaseCell::Leaf(inner) = cell else {
+            return Err(DecodeError::InvalidSchemaEntry(
+                "expected a table leaf cell".to_string(),
+            ));
+        };
+
+        let [sqlite_type, name, table_name, root_page, sql] = inner.payload.as_slice() else {
+            return Err(DecodeError::InvalidSchemaEntry(format!(
+                "expected 5 columns - found {}",
+                inner.payload.len()
+            )));
+        };
+
+        let text = |value: &RecordValue, column: &str| match value {
+            RecordValue::String(text) => Ok(text.clone()),
+            other => Err(DecodeError::InvalidSchemaEntry(format!(
+                "expected a string({column}) - found {other:?}"
+            ))),
+        };
+
+        // Virtual tables have no b-tree, so a root page of 0, which is stored as the
+        // constant 0 rather than an integer
+        let Some(root_page) = root_page.as_i64() else {
+            return Err(DecodeError::InvalidSchemaEntry(format!(
+                "expected an integer(root_page) - found {root_page:?}"
+            )));
+        };
+
+        // Indexes made for UNIQUE and PRIMARY KEY constraints have no SQL
+        let sql = match sql {
+            RecordValue::Null => String::new(),
+            sql => text(sql, "sql")?,
+        };
+
+        Ok(Self {
+            sqlite_type: text(sqlite_type, "sqlite_type")?,
+            name: text(name, "name")?,
+            table_name: text(table_name, "table_name")?,
+            root_page: (root_page as u64).saturating_sub(1),
+            sql,
+            row_id: inner.row_id,
+        })
+    }
+
+    /// A table whose rows are kept in a table b-tree keyed by rowid, which leaves
+    /// out virtual tables and `WITHOUT ROWID` tables
+    pub fn is_table(&self) -> bool {
+        self.sqlite_type == "table"
+            && match sql::create_statement(&self.sql) {
+                Ok((_, CreateStatement::Table(table))) => !table.without_rowid,
+                _ => false,
             }
-            _ => todo!(),
+    }
+
+    pub fn is_index(&self) -> bool {
+        self.sqlite_type == "index"
+    }
+
+    /// What the entry creates: a table, index, view or trigger
+    pub fn kind(&self) -> &str {
+        &self.sqlite_type
+    }
+
+    /// Tables and indexes keep their rows in a b-tree of their own, apart from
+    /// virtual tables
+    pub fn has_btree(&self) -> bool {
+        (self.sqlite_type == "table" || self.is_index()) && !self.is_virtual()
+    }
+
+    /// One of SQLite's own objects such as `sqlite_sequence` or `sqlite_stat1`, whose
+    /// names are reserved for it
+    pub fn is_internal(&self) -> bool {
+        self.name
+            .get(..INTERNAL_PREFIX.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(INTERNAL_PREFIX))
+    }
+
+    /// Tables of any kind and views, which are what `.tables` lists
+    pub fn is_relation(&self) -> bool {
+        self.sqlite_type == "table" || self.sqlite_type == "view"
+    }
+
+    /// A virtual table has no b-tree of its own, the module implementing it keeps its
+    /// rows elsewhere
+    pub fn is_virtual(&self) -> bool {
+        self.virtual_table().is_some()
+    }
+
+    pub fn virtual_table(&self) -> Option<CreateVirtualTable> {
+        match sql::create_statement(&self.sql) {
+            Ok((_, CreateStatement::VirtualTable(table))) => Some(table),
+            _ => None,
+        }
+    }
+
+    pub fn view(&self) -> Option<CreateView> {
+        match sql::create_statement(&self.sql) {
+            Ok((_, CreateStatement::View(view))) => Some(view),
+            _ => None,
         }
     }
 
@@ -94,6 +261,43 @@ impl SchemaTable {
 
         match create_statement {
             CreateStatement::Table(t) => t,
+            other => panic!("expected a create table statement - found {other:#?}"),
+        }
+    }
+
+    /// The keys of the index as declared, without a collation where they name none
+    pub fn indexed_columns(&self) -> Vec<IndexedColumn> {
+        match sql::create_statement(&self.sql) {
+            Ok((_, CreateStatement::Index(index))) => index.columns,
+            _ => Vec::new(),
+        }
+    }
+
+    /// The keys of the index on `table`, each with the collation the index orders it
+    /// by: its own, or else that of the column it is
+    pub fn index_keys(&self, table: &CreateTable) -> Vec<IndexedColumn> {
+        let mut keys = self.indexed_columns();
+        for key in keys.iter_mut().filter(|key| key.expr.is_none()) {
+            key.collation = key
+                .collation
+                .take()
+                .or_else(|| Some(table.column_collation(&key.name)));
         }
+        keys
+    }
+
+    /// The `WHERE` clause of a partial index
+    pub fn index_filter(&self) -> Option<IndexFilter> {
+        match sql::create_statement(&self.sql) {
+            Ok((_, CreateStatement::Index(index))) => index.where_clause,
+            _ => None,
+        }
+    }
+
+    pub fn index_columns(&self) -> Vec<String> {
+        self.indexed_columns()
+            .into_iter()
+            .map(|column| column.name)
+            .collect()
     }
 }
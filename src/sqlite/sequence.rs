@@ -0,0 +1,43 @@
+use super::cell::{LeafCell, RecordValue};
+use std::collections::HashMap;
+
+/// Table SQLite keeps the largest rowid ever given to each `AUTOINCREMENT` table in
+pub const SEQUENCE_TABLE: &str = "sqlite_sequence";
+
+/// The rows of `sqlite_sequence`, which only has a row for an `AUTOINCREMENT` table
+/// once a row has been inserted into it
+#[derive(Debug, Default)]
+pub struct SqliteSequence {
+    tables: HashMap<String, i64>,
+}
+
+impl SqliteSequence {
+    pub fn new(rows: &[LeafCell]) -> Self {
+        let tables = rows
+            .iter()
+            .filter_map(|row| match &row.payload[..] {
+                [RecordValue::String(table), seq] => Some((table.clone(), seq.as_i64()?)),
+                _ => None,
+            })
+            .collect();
+
+        Self { tables }
+    }
+
+    /// The largest rowid ever given to a row of `table`, even if that row has since
+    /// been deleted
+    pub fn last_rowid(&self, table: &str) -> Option<i64> {
+        self.tables.get(table).copied()
+    }
+
+    /// The rowid an insert into an `AUTOINCREMENT` table has to use, which is past
+    /// both the sequence and the largest rowid still in the table, or `None` once the
+    /// largest possible rowid has been used
+    pub fn next_rowid(&self, table: &str, max_rowid: Option<i64>) -> Option<i64> {
+        let last = self
+            .last_rowid(table)
+            .unwrap_or(0)
+            .max(max_rowid.unwrap_or(0));
+        last.checked_add(1)
+    }
+}
@@ -0,0 +1,392 @@
+#[cfg(unix)]
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex, Weak},
+};
+use std::{fs::File, io, path::Path};
+
+use super::wal::checksum;
+
+/// Appended to the database path to name the wal-index that connections to a
+/// database in WAL mode share
+pub const SHM_SUFFIX: &str = "-shm";
+
+/// Bytes of each of the two copies of the index header, the second of which a
+/// writer updates last so a reader can tell it caught the first mid-update
+const HEADER_SIZE: usize = 48;
+/// The header twice, then what checkpoints and readers have recorded
+const INDEX_PREFIX_SIZE: usize = 2 * HEADER_SIZE + 40;
+const INDEX_VERSION: u32 = 3_007_000;
+
+/// How many frames of the log each reader reads up to, where holding the first
+/// means reading the database file alone
+const READ_MARKS: usize = 5;
+const READ_MARKS_OFFSET: usize = 2 * HEADER_SIZE + 4;
+#[cfg_attr(not(unix), allow(dead_code))]
+const READ_MARK_UNUSED: u32 = 0xffff_ffff;
+
+/// Offset of the lock for the first read mark, following the write, checkpoint and
+/// recovery locks
+#[cfg(unix)]
+const READ_LOCK: u64 = 123;
+/// Offset of the lock every open connection holds shared
+#[cfg(unix)]
+const DMS_LOCK: u64 = 128;
+
+/// Times to read the index again while writers are changing it before giving up
+#[cfg(unix)]
+const ATTEMPTS: usize = 100;
+
+/// The latest commit to the log as the wal-index records it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexHeader {
+    /// Changes each time a transaction commits
+    change: u32,
+    /// Frames of the log up to the latest commit, counting from 1
+    pub max_frame: u32,
+    /// Size of the database in pages as of that commit
+    pub page_count: u32,
+    pub page_size: usize,
+    /// Salts the header of the log and each of its valid frames carry
+    pub salts: (u32, u32),
+}
+
+/// The wal-index as read at one moment
+#[derive(Debug, PartialEq, Eq)]
+struct IndexState {
+    header: IndexHeader,
+    /// Frames of the log already copied back into the database file
+    backfilled: u32,
+    read_marks: [u32; READ_MARKS],
+}
+
+impl IndexState {
+    /// Reads the start of the index, or `None` while its header is being written or
+    /// before it has been set up
+    fn read(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..INDEX_PREFIX_SIZE)?;
+        let (first, second) = (&bytes[..HEADER_SIZE], &bytes[HEADER_SIZE..2 * HEADER_SIZE]);
+        if first != second {
+            return None;
+        }
+
+        // The index is in the byte order of the machine, apart from the salts
+        // copied from the header of the log as they are
+        let word = |offset: usize| {
+            u32::from_ne_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes"))
+        };
+        let salt = |offset: usize| {
+            u32::from_be_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes"))
+        };
+        let initialized = first[12] == 1;
+        if word(0) != INDEX_VERSION || !initialized {
+            return None;
+        }
+
+        let big_endian = cfg!(target_endian = "big");
+        if checksum(big_endian, &first[..40], (0, 0)) != (word(40), word(44)) {
+            return None;
+        }
+
+        let page_size = match u16::from_ne_bytes([first[14], first[15]]) {
+            1 => 65536,
+            size => usize::from(size),
+        };
+        let mut read_marks = [0; READ_MARKS];
+        for (i, mark) in read_marks.iter_mut().enumerate() {
+            *mark = word(READ_MARKS_OFFSET + 4 * i);
+        }
+
+        Some(Self {
+            header: IndexHeader {
+                change: word(8),
+                max_frame: word(16),
+                page_count: word(20),
+                page_size,
+                salts: (salt(32), salt(36)),
+            },
+            backfilled: word(2 * HEADER_SIZE),
+            read_marks,
+        })
+    }
+}
+
+/// A reader's place among the connections sharing the wal-index of a database
+///
+/// The read mark it holds keeps writers from restarting the log and checkpoints
+/// from copying frames past its snapshot into the database file, so the pages it
+/// reads from either stay as they were when it opened. The locks go when it's
+/// dropped.
+#[derive(Debug)]
+pub struct WalIndex {
+    #[cfg(unix)]
+    inode: Arc<ShmInode>,
+    /// Offset of the lock on the read mark held
+    #[cfg(unix)]
+    read_lock: u64,
+    /// The commit read up to, or `None` when every frame of the log had been copied
+    /// back and the database file is read alone
+    pub snapshot: Option<IndexHeader>,
+}
+
+impl WalIndex {
+    /// Joins the connections sharing the wal-index of the database at `db_path`, or
+    /// `None` when there is no index or no other connection has it open
+    ///
+    /// An index nobody has open may be left over from a connection that crashed, so
+    /// can't be trusted and the log is read through instead, as SQLite recovers it.
+    /// SQLite only shares the index this way on Unix, so elsewhere it never is.
+    pub fn attach(db_path: &Path) -> io::Result<Option<Self>> {
+        #[cfg(unix)]
+        {
+            let mut path = db_path.as_os_str().to_owned();
+            path.push(SHM_SUFFIX);
+            match ShmInode::open(Path::new(&path))? {
+                Some(inode) => Self::begin_read(inode),
+                None => Ok(None),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = db_path;
+            Ok(None)
+        }
+    }
+
+    /// Takes a read mark on the latest commit, setting one up when none is close
+    /// enough and the index can be written, the way SQLite begins a read transaction
+    #[cfg(unix)]
+    fn begin_read(inode: Arc<ShmInode>) -> io::Result<Option<Self>> {
+        use super::lock::busy;
+
+        // Only a connection alone with the index can lock it exclusively, and this
+        // process is never alone with it while it holds the index open elsewhere
+        if inode.writable && inode.lock_exclusive(DMS_LOCK).is_ok() {
+            inode.unlock(DMS_LOCK)?;
+            return Ok(None);
+        }
+        inode.lock_shared(DMS_LOCK).map_err(busy)?;
+
+        match Self::take_read_mark(&inode) {
+            Ok((read_lock, snapshot)) => Ok(Some(Self {
+                inode,
+                read_lock,
+                snapshot,
+            })),
+            Err(e) => {
+                inode.unlock(DMS_LOCK)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Locks the read mark of the latest commit, answering with the offset of its
+    /// lock and the commit, or `None` for the mark reading the database file alone
+    #[cfg(unix)]
+    fn take_read_mark(inode: &ShmInode) -> io::Result<(u64, Option<IndexHeader>)> {
+        use super::lock::busy;
+
+        for _ in 0..ATTEMPTS {
+            let Some(state) = read_state(&inode.file)? else {
+                std::thread::yield_now();
+                continue;
+            };
+
+            let max_frame = state.header.max_frame;
+            let mut mark = (state.backfilled == max_frame)
+                .then_some((0, state.read_marks[0]))
+                .or_else(|| {
+                    (1..READ_MARKS)
+                        .map(|slot| (slot, state.read_marks[slot]))
+                        .filter(|&(_, frame)| frame != READ_MARK_UNUSED && frame <= max_frame)
+                        .max_by_key(|&(_, frame)| frame)
+                });
+            let current = mark.is_some_and(|(slot, frame)| slot == 0 || frame == max_frame);
+            if !current && inode.writable {
+                for slot in 1..READ_MARKS {
+                    let slot_lock = READ_LOCK + slot as u64;
+                    if inode.lock_exclusive(slot_lock).is_ok() {
+                        let written = write_read_mark(&inode.file, slot, max_frame);
+                        inode.unlock(slot_lock)?;
+                        written?;
+                        mark = Some((slot, max_frame));
+                        break;
+                    }
+                }
+            }
+
+            let Some((slot, frame)) = mark else {
+                std::thread::yield_now();
+                continue;
+            };
+            let slot_lock = READ_LOCK + slot as u64;
+            if inode.lock_shared(slot_lock).is_err() {
+                continue;
+            }
+
+            // Nothing may have committed or moved the mark before it was locked
+            match read_state(&inode.file)? {
+                Some(now) if now.header == state.header && now.read_marks[slot] == frame => {
+                    return Ok((slot_lock, (slot != 0).then_some(state.header)));
+                }
+                _ => inode.unlock(slot_lock)?,
+            }
+        }
+
+        Err(busy(io::ErrorKind::WouldBlock.into()))
+    }
+}
+
+impl Drop for WalIndex {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            let _ = self.inode.unlock(self.read_lock);
+            let _ = self.inode.unlock(DMS_LOCK);
+        }
+    }
+}
+
+/// How this process holds the lock on a byte of the wal-index
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Held {
+    /// By this many readers
+    Shared(usize),
+    Exclusive,
+}
+
+/// The wal-index of a database as this process has it open, shared by every
+/// [`WalIndex`] on the same file
+///
+/// POSIX locks belong to the process rather than to a descriptor, so locking the
+/// file again through a second descriptor would take over the locks held through
+/// the first, and closing it would release them. Like SQLite's `unixInodeInfo`, the
+/// file is opened once and the locks each byte is held with are counted, so the
+/// file is only locked for the first holder and unlocked after the last.
+#[cfg(unix)]
+#[derive(Debug)]
+struct ShmInode {
+    file: File,
+    writable: bool,
+    held: Mutex<HashMap<u64, Held>>,
+}
+
+/// The wal-indexes open in this process, by device and inode
+#[cfg(unix)]
+type ShmInodes = HashMap<(u64, u64), Weak<ShmInode>>;
+
+#[cfg(unix)]
+static SHM_INODES: LazyLock<Mutex<ShmInodes>> = LazyLock::new(Default::default);
+
+#[cfg(unix)]
+impl ShmInode {
+    /// The wal-index at `path` as this process has it open, opening it for the
+    /// first reader, or `None` when there is none
+    fn open(path: &Path) -> io::Result<Option<Arc<Self>>> {
+        use std::os::unix::fs::MetadataExt;
+
+        // Looked up before opening, as closing a second descriptor on a file this
+        // process has locked would release its locks
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut inodes = SHM_INODES.lock().expect("wal-index registry poisoned");
+        inodes.retain(|_, inode| inode.strong_count() > 0);
+        let key = (metadata.dev(), metadata.ino());
+        if let Some(inode) = inodes.get(&key).and_then(Weak::upgrade) {
+            return Ok(Some(inode));
+        }
+
+        let open = |write| File::options().read(true).write(write).open(path);
+        let (file, writable) = match open(true) {
+            Ok(file) => (file, true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(_) => match open(false) {
+                Ok(file) => (file, false),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e),
+            },
+        };
+        let metadata = file.metadata()?;
+        let inode = Arc::new(Self {
+            file,
+            writable,
+            held: Default::default(),
+        });
+        inodes.insert((metadata.dev(), metadata.ino()), Arc::downgrade(&inode));
+        Ok(Some(inode))
+    }
+
+    /// Locks the byte at `offset` shared, only locking the file if no other reader
+    /// in this process holds it
+    fn lock_shared(&self, offset: u64) -> io::Result<()> {
+        use super::lock::{lock, READ};
+
+        let mut held = self.held.lock().expect("wal-index locks poisoned");
+        match held.get(&offset).copied() {
+            Some(Held::Shared(readers)) => {
+                held.insert(offset, Held::Shared(readers + 1));
+            }
+            Some(Held::Exclusive) => return Err(io::ErrorKind::WouldBlock.into()),
+            None => {
+                lock(&self.file, READ, offset, 1)?;
+                held.insert(offset, Held::Shared(1));
+            }
+        }
+        Ok(())
+    }
+
+    /// Locks the byte at `offset` exclusively, which fails while anyone else in this
+    /// process or another holds it
+    fn lock_exclusive(&self, offset: u64) -> io::Result<()> {
+        use super::lock::{lock, WRITE};
+
+        let mut held = self.held.lock().expect("wal-index locks poisoned");
+        if held.contains_key(&offset) {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        lock(&self.file, WRITE, offset, 1)?;
+        held.insert(offset, Held::Exclusive);
+        Ok(())
+    }
+
+    /// Lets go of one hold on the byte at `offset`, unlocking the file after the last
+    fn unlock(&self, offset: u64) -> io::Result<()> {
+        use super::lock::{lock, UNLOCK};
+
+        let mut held = self.held.lock().expect("wal-index locks poisoned");
+        match held.get(&offset).copied() {
+            Some(Held::Shared(readers)) if readers > 1 => {
+                held.insert(offset, Held::Shared(readers - 1));
+                Ok(())
+            }
+            Some(_) => {
+                held.remove(&offset);
+                lock(&self.file, UNLOCK, offset, 1)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn read_state(file: &File) -> io::Result<Option<IndexState>> {
+    use std::os::unix::fs::FileExt;
+
+    let mut bytes = [0; INDEX_PREFIX_SIZE];
+    match file.read_exact_at(&mut bytes, 0) {
+        Ok(()) => Ok(IndexState::read(&bytes)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(unix)]
+fn write_read_mark(file: &File, slot: usize, frame: u32) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+
+    file.write_all_at(&frame.to_ne_bytes(), (READ_MARKS_OFFSET + 4 * slot) as u64)
+}
@@ -1,29 +1,446 @@
+use super::affinity::Affinity;
+use super::cell::RecordValue;
+use super::collation::Collation;
+use super::error::QueryError;
+use super::function;
+use super::pattern;
+use std::{cmp::Ordering, fmt};
+
 use nom::{
     branch::alt,
     bytes::{
-        complete::{tag, tag_no_case, take_while1},
+        complete::{is_not, tag, tag_no_case, take_while, take_while1, take_while_m_n},
         take_until,
     },
     character::complete::{char, multispace0, multispace1},
-    combinator::{map, opt},
-    multi::separated_list1,
-    sequence::{delimited, preceded},
+    combinator::{consumed, map, not, opt, recognize, rest, value, verify},
+    multi::{many0, separated_list0, separated_list1},
+    sequence::{delimited, preceded, terminated},
     IResult, Parser,
 };
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SelectStatement {
+    /// Tables the query names in a `WITH` clause, materialized before it runs
+    pub with: Vec<CommonTable>,
+    /// Rows written out in the query rather than read from a table, as `VALUES`
+    /// answers with and a SELECT without FROM computes its values from
+    pub values: Option<Vec<Vec<Expr>>>,
     pub operation: Option<SelectOperation>,
+    /// Columns read from the table
     pub columns: Vec<String>,
+    /// Values computed from the columns for each row, when the query selects more
+    /// than bare columns
+    pub projection: Option<Vec<Expr>>,
+    /// Database the table was qualified with, as in `aux.table`
+    pub schema: Option<String>,
+    pub table: String,
+    /// Name the table goes by in the query, as in `FROM orders AS o`
+    pub alias: Option<String>,
+    /// A second table joined to the first
+    pub join: Option<Join>,
+    /// The column comparisons ANDed together in the WHERE clause, any of which the
+    /// planner can find rows by through an index before checking them against the
+    /// rest
+    pub where_clause: Vec<Condition>,
+    /// WHERE comparisons against computed values rather than columns, checked once
+    /// each row has been read
+    pub row_filters: Vec<ExprCondition>,
+    /// `IN` tests of the WHERE clause, checked once each row has been read
+    pub in_filters: Vec<InCondition>,
+    /// WHERE comparisons between two columns, either of which may belong to the
+    /// query a subquery is part of rather than to the subquery's own table
+    pub column_filters: Vec<ColumnCondition>,
+    /// `EXISTS` tests of the WHERE clause
+    pub exists_filters: Vec<ExistsCondition>,
+    pub order_by: Vec<OrderingTerm>,
+}
+
+impl SelectStatement {
+    /// Whether some of the WHERE clause can only be checked once a row has been read,
+    /// rather than by the scan finding it
+    pub fn filters_rows(&self) -> bool {
+        !self.row_filters.is_empty()
+            || !self.in_filters.is_empty()
+            || !self.column_filters.is_empty()
+            || !self.exists_filters.is_empty()
+    }
+
+    /// Whether the query selects `*`, standing for every column of its table
+    pub fn selects_all(&self) -> bool {
+        self.columns.iter().any(|column| column == ALL_COLUMNS)
+            || self
+                .projection
+                .iter()
+                .flatten()
+                .any(|expr| matches!(expr, Expr::Column(name) if name == ALL_COLUMNS))
+    }
+
+    /// The query with `*` replaced by `columns`, those of its table in order
+    pub fn expand_all(&self, columns: &[String]) -> Self {
+        let expand = |name: &String| match name == ALL_COLUMNS {
+            true => columns.to_vec(),
+            false => vec![name.clone()],
+        };
+
+        let Some(projection) = &self.projection else {
+            return Self {
+                columns: self.columns.iter().flat_map(expand).collect(),
+                ..self.clone()
+            };
+        };
+
+        // The columns read for a projection are each listed once
+        let mut read: Vec<String> = Vec::new();
+        for column in self.columns.iter().flat_map(expand) {
+            if !read.iter().any(|c| same_name(c, &column)) {
+                read.push(column);
+            }
+        }
+        Self {
+            columns: read,
+            projection: Some(
+                projection
+                    .iter()
+                    .flat_map(|expr| match expr {
+                        Expr::Column(name) => expand(name).into_iter().map(Expr::Column).collect(),
+                        expr => vec![expr.clone()],
+                    })
+                    .collect(),
+            ),
+            ..self.clone()
+        }
+    }
+
+    /// A query reading a single row of no columns rather than a table, which the
+    /// values it selects are computed from
+    fn constant(with: Vec<CommonTable>) -> Self {
+        Self {
+            with,
+            values: Some(vec![Vec::new()]),
+            operation: None,
+            columns: Vec::new(),
+            projection: None,
+            schema: None,
+            table: String::new(),
+            alias: None,
+            join: None,
+            where_clause: Vec::new(),
+            row_filters: Vec::new(),
+            in_filters: Vec::new(),
+            column_filters: Vec::new(),
+            exists_filters: Vec::new(),
+            order_by: Vec::new(),
+        }
+    }
+
+    /// Whether the query or any of its subqueries reads a table called `name` without
+    /// qualifying it with a database
+    pub fn reads(&self, name: &str) -> bool {
+        let named =
+            |schema: &Option<String>, table: &str| schema.is_none() && same_name(table, name);
+        named(&self.schema, &self.table)
+            || self
+                .join
+                .as_ref()
+                .is_some_and(|join| named(&join.schema, &join.table))
+            || self.with.iter().any(|table| table.select.reads(name))
+            || self.in_filters.iter().any(|filter| match &filter.source {
+                InSource::Subquery(subquery) => subquery.reads(name),
+                InSource::List(_) => false,
+            })
+            || self
+                .exists_filters
+                .iter()
+                .any(|filter| filter.subquery.reads(name))
+    }
+}
+
+/// A value computed for each row of a query
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Column(String),
+    Literal(RecordValue),
+    /// A call to a scalar function, named in lowercase
+    Function {
+        name: String,
+        args: Vec<Expr>,
+    },
+    /// `CAST(expr AS type)`, converting to the affinity of the type
+    Cast {
+        expr: Box<Expr>,
+        type_name: String,
+    },
+    /// A comparison or pattern match, which is NULL when either side is
+    Compare {
+        lhs: Box<Expr>,
+        operator: Operator,
+        rhs: Box<Expr>,
+    },
+    /// Arithmetic or concatenation, which is NULL when either side is
+    Binary {
+        lhs: Box<Expr>,
+        operator: BinaryOperator,
+        rhs: Box<Expr>,
+    },
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// `expr IS NULL`, or `expr IS NOT NULL` when negated
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    /// `expr IN (list)`, or `expr NOT IN (list)` when negated
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+}
+
+impl Expr {
+    /// Every column the expression reads
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Self::Column(name) => vec![name.as_str()],
+            Self::Literal(_) => Vec::new(),
+            Self::Function { args, .. } => args.iter().flat_map(|arg| arg.columns()).collect(),
+            Self::Cast { expr, .. } | Self::Not(expr) | Self::IsNull { expr, .. } => expr.columns(),
+            Self::Compare { lhs, rhs, .. }
+            | Self::Binary { lhs, rhs, .. }
+            | Self::And(lhs, rhs)
+            | Self::Or(lhs, rhs) => {
+                let mut columns = lhs.columns();
+                columns.extend(rhs.columns());
+                columns
+            }
+            Self::InList { expr, list, .. } => {
+                let mut columns = expr.columns();
+                columns.extend(list.iter().flat_map(|item| item.columns()));
+                columns
+            }
+        }
+    }
+}
+
+impl Expr {
+    /// Drops the qualifier from the columns qualified with one of `names`, the names
+    /// a query's only table goes by
+    fn unqualify(&mut self, names: &[&str]) {
+        match self {
+            Self::Column(name) => unqualify(name, names),
+            Self::Literal(_) => {}
+            Self::Function { args, .. } => args.iter_mut().for_each(|arg| arg.unqualify(names)),
+            Self::Cast { expr, .. } | Self::Not(expr) | Self::IsNull { expr, .. } => {
+                expr.unqualify(names)
+            }
+            Self::Compare { lhs, rhs, .. }
+            | Self::Binary { lhs, rhs, .. }
+            | Self::And(lhs, rhs)
+            | Self::Or(lhs, rhs) => {
+                lhs.unqualify(names);
+                rhs.unqualify(names);
+            }
+            Self::InList { expr, list, .. } => {
+                expr.unqualify(names);
+                list.iter_mut().for_each(|item| item.unqualify(names));
+            }
+        }
+    }
+}
+
+/// Drops the qualifier from a column qualified as `table.column` with one of `names`
+fn unqualify(column: &mut String, names: &[&str]) {
+    let Some((qualifier, name)) = column.split_once('.') else {
+        return;
+    };
+    if names.iter().any(|table| same_name(table, qualifier)) {
+        *column = name.to_string();
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Column(name) => write!(f, "{name}"),
+            Self::Literal(value) => write!(f, "{}", value.to_sql_literal()),
+            Self::Function { name, args } => {
+                write!(f, "{name}(")?;
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+            Self::Cast { expr, type_name } => write!(f, "CAST({expr} AS {type_name})"),
+            Self::Compare { lhs, operator, rhs } => {
+                write!(f, "{lhs} {operator} {rhs}")?;
+                match operator {
+                    Operator::Like {
+                        escape: Some(escape),
+                    } => write!(f, " ESCAPE '{escape}'"),
+                    _ => Ok(()),
+                }
+            }
+            Self::Binary { lhs, operator, rhs } => write!(f, "({lhs} {operator} {rhs})"),
+            Self::And(lhs, rhs) => write!(f, "({lhs} AND {rhs})"),
+            Self::Or(lhs, rhs) => write!(f, "({lhs} OR {rhs})"),
+            Self::Not(expr) => write!(f, "NOT {expr}"),
+            Self::IsNull { expr, negated } => {
+                let not = if *negated { " NOT" } else { "" };
+                write!(f, "{expr} IS{not} NULL")
+            }
+            Self::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let not = if *negated { " NOT" } else { "" };
+                let list: Vec<String> = list.iter().map(Expr::to_string).collect();
+                write!(f, "{expr}{not} IN ({})", list.join(", "))
+            }
+        }
+    }
+}
+
+/// A comparison between a computed value and a literal
+#[derive(Debug, Clone)]
+pub struct ExprCondition {
+    pub expr: Expr,
+    pub operator: Operator,
+    pub value: RecordValue,
+    /// Explicit `COLLATE` the values are compared with
+    pub collation: Option<Collation>,
+}
+
+/// `JOIN table ON lhs = rhs`, an inner join keeping the pairs of rows whose
+/// columns are equal
+#[derive(Debug, Clone)]
+pub struct Join {
+    pub schema: Option<String>,
     pub table: String,
-    pub where_clause: Option<Condition>,
+    pub alias: Option<String>,
+    /// The columns the ON clause compares, either of which may be qualified with the
+    /// table it's from as `table.column`
+    pub on: (String, String),
+}
+
+/// `expr IN (...)` in a WHERE clause, or `expr NOT IN (...)` when negated
+#[derive(Debug, Clone)]
+pub struct InCondition {
+    pub expr: Expr,
+    pub source: InSource,
+    pub negated: bool,
+    /// Explicit `COLLATE` the values are compared with
+    pub collation: Option<Collation>,
+}
+
+impl InCondition {
+    /// Columns of the row the term refers to, on either side of `IN`
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = self.expr.columns();
+        if let InSource::List(list) = &self.source {
+            columns.extend(list.iter().flat_map(|item| item.columns()));
+        }
+        columns
+    }
+
+    /// The column tested, whose affinity the values are converted to
+    pub fn column<'a>(&self, table: Option<&'a CreateTable>) -> Option<&'a ColumnDefinition> {
+        let (Expr::Column(name), Some(table)) = (&self.expr, table) else {
+            return None;
+        };
+        table
+            .columns
+            .iter()
+            .find(|column| same_name(&column.name, name))
+    }
+
+    /// Collation the values are compared with, the term's own or else that of the
+    /// column tested
+    pub fn collation(&self, table: Option<&CreateTable>) -> Collation {
+        self.collation
+            .clone()
+            .or_else(|| self.column(table).map(|column| column.collation.clone()))
+            .unwrap_or_default()
+    }
+}
+
+/// A comparison between two columns, as in `b.a_id = a.id`
+#[derive(Debug, Clone)]
+pub struct ColumnCondition {
+    pub column: String,
+    pub operator: Operator,
+    pub other: String,
+    /// Explicit `COLLATE` the values are compared with
+    pub collation: Option<Collation>,
+}
+
+/// `name [(columns)] AS (select)` in the `WITH` clause of a query
+#[derive(Debug, Clone)]
+pub struct CommonTable {
+    pub name: String,
+    /// Names given to the columns, or else those the SELECT answers with
+    pub columns: Vec<String>,
+    pub select: Box<SelectStatement>,
+}
+
+/// `EXISTS (select)`, or `NOT EXISTS (select)` when negated
+#[derive(Debug, Clone)]
+pub struct ExistsCondition {
+    pub subquery: Box<SelectStatement>,
+    pub negated: bool,
+}
+
+/// Where the values an `IN` term tests against come from
+#[derive(Debug, Clone)]
+pub enum InSource {
+    List(Vec<Expr>),
+    Subquery(Box<SelectStatement>),
+}
+
+/// A statement the executor runs, as opposed to one read from the schema
+#[derive(Debug)]
+pub enum Statement {
+    Select(Box<SelectStatement>),
+    /// `EXPLAIN QUERY PLAN select`, describing how the query would run
+    ExplainQueryPlan(Box<SelectStatement>),
+    /// `ATTACH 'path' AS name`
+    Attach {
+        path: String,
+        name: String,
+    },
+    /// `DETACH name`
+    Detach(String),
+    /// `PRAGMA name` or `PRAGMA name(argument)`, optionally qualified with the
+    /// database to run it on
+    Pragma {
+        schema: Option<String>,
+        name: String,
+        argument: Option<String>,
+    },
+    /// `ANALYZE`, `ANALYZE schema`, or `ANALYZE [schema.]target` for a single table or
+    /// index, where a lone name is a schema when a database goes by it
+    ///
+    /// The statistics only last as long as the connection, `sqlite_stat1` isn't
+    /// written.
+    Analyze {
+        schema: Option<String>,
+        target: Option<String>,
+    },
 }
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum CreateStatement {
     Table(CreateTable),
+    Index(CreateIndex),
+    VirtualTable(CreateVirtualTable),
+    View(CreateView),
 }
 
 #[allow(dead_code)]
@@ -31,6 +448,150 @@ pub enum CreateStatement {
 pub struct CreateTable {
     pub name: String,
     pub columns: Vec<ColumnDefinition>,
+    /// Rows of a `WITHOUT ROWID` table are kept in an index b-tree keyed by the
+    /// primary key rather than a table b-tree keyed by rowid
+    pub without_rowid: bool,
+    /// Foreign keys declared on columns followed by those declared on the table, in
+    /// the order they were written
+    pub foreign_keys: Vec<ForeignKey>,
+    /// CHECK constraints on columns followed by those on the table
+    pub checks: Vec<Check>,
+    /// UNIQUE and PRIMARY KEY constraints other than an `INTEGER PRIMARY KEY`, each
+    /// kept in an index, in the order they were written
+    pub unique_keys: Vec<UniqueKey>,
+}
+
+/// The columns a UNIQUE or PRIMARY KEY constraint keeps distinct
+#[derive(Debug, Clone)]
+pub struct UniqueKey {
+    pub columns: Vec<IndexedColumn>,
+    pub primary_key: bool,
+}
+
+/// The `DEFAULT` of a column, the value rows leaving the column out take
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDefault {
+    /// The value as written
+    pub sql: String,
+    /// The parsed value, or `None` when it uses syntax the parser doesn't know
+    pub expr: Option<Expr>,
+}
+
+/// A column computed from the others with `GENERATED ALWAYS AS (expr)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedColumn {
+    /// The expression as written
+    pub sql: String,
+    /// The parsed expression, or `None` when it uses syntax the parser doesn't know
+    pub expr: Option<Expr>,
+    /// A `STORED` column is kept in the record, a `VIRTUAL` one computed on read
+    pub stored: bool,
+}
+
+/// A `CHECK (expr)` constraint, which every row written to the table has to satisfy
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    /// The name given with `CONSTRAINT name`
+    pub name: Option<String>,
+    /// The expression as written
+    pub sql: String,
+    /// The parsed expression, or `None` when it uses syntax the parser doesn't know
+    pub expr: Option<Expr>,
+}
+
+/// A constraint on a column or the table kept with the table rather than a column
+enum TableConstraint {
+    ForeignKey(ForeignKey),
+    Check(Check),
+    Unique(UniqueKey),
+}
+
+/// Columns of a table referring to the key of a parent table, declared with
+/// `REFERENCES` on a column or `FOREIGN KEY` on the table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    pub parent: String,
+    /// Columns of the parent the key refers to, or none for its primary key
+    pub parent_columns: Vec<String>,
+    pub on_update: ForeignKeyAction,
+    pub on_delete: ForeignKeyAction,
+}
+
+/// What happens to the rows referring to a parent key when it's updated or deleted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForeignKeyAction {
+    #[default]
+    NoAction,
+    Restrict,
+    SetNull,
+    SetDefault,
+    Cascade,
+}
+
+impl fmt::Display for ForeignKeyAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            Self::NoAction => "NO ACTION",
+            Self::Restrict => "RESTRICT",
+            Self::SetNull => "SET NULL",
+            Self::SetDefault => "SET DEFAULT",
+            Self::Cascade => "CASCADE",
+        };
+
+        f.write_str(action)
+    }
+}
+
+/// `CREATE VIRTUAL TABLE name USING module(arguments)`
+#[derive(Debug)]
+pub struct CreateVirtualTable {
+    pub name: String,
+    pub module: String,
+    /// The arguments to the module as written, split on the commas between them
+    pub arguments: Vec<String>,
+}
+
+/// `CREATE VIEW name [(columns)] AS select`
+#[derive(Debug)]
+pub struct CreateView {
+    pub name: String,
+    /// The names given to the columns of the query, if the view lists them
+    pub columns: Option<Vec<String>>,
+    /// The query as written
+    pub select: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct CreateIndex {
+    pub name: String,
+    pub table: String,
+    pub columns: Vec<IndexedColumn>,
+    /// `CREATE UNIQUE INDEX`, allowing no two rows the same key
+    pub unique: bool,
+    /// The `WHERE` clause of a partial index, which only holds the rows satisfying it
+    pub where_clause: Option<IndexFilter>,
+}
+
+/// The `WHERE` clause of a partial index
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexFilter {
+    /// The expression as written
+    pub sql: String,
+    /// The parsed expression, or `None` when it uses syntax the parser doesn't know
+    pub expr: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedColumn {
+    /// The column the key is, or the expression as written for an expression key
+    pub name: String,
+    /// The key's own collation, if it names one
+    pub collation: Option<Collation>,
+    pub order: SortOrder,
+    /// The expression an index on an expression such as `lower(name)` is keyed by
+    pub expr: Option<Expr>,
 }
 
 #[allow(dead_code)]
@@ -39,191 +600,2454 @@ pub struct ColumnDefinition {
     pub name: String,
     pub datatype: String,
     pub constraints: Vec<String>,
+    pub collation: Collation,
+    pub default: Option<ColumnDefault>,
+    pub generated: Option<GeneratedColumn>,
+}
+
+impl ColumnDefinition {
+    pub fn affinity(&self) -> Affinity {
+        Affinity::from_declared_type(&self.datatype)
+    }
+
+    /// An `INTEGER PRIMARY KEY` column is stored as the rowid rather than in the record
+    ///
+    /// As in SQLite, a column declared `INTEGER PRIMARY KEY DESC` isn't one.
+    pub fn is_rowid_alias(&self) -> bool {
+        self.datatype.eq_ignore_ascii_case("integer")
+            && self.constraints.iter().any(|c| c == "primary key")
+    }
+
+    /// A `VIRTUAL` generated column has no value in the record
+    pub fn is_virtual(&self) -> bool {
+        self.generated
+            .as_ref()
+            .is_some_and(|generated| !generated.stored)
+    }
+
+    pub fn is_primary_key(&self) -> bool {
+        self.constraints
+            .iter()
+            .any(|c| c.starts_with("primary key"))
+    }
+
+    /// The value a row leaving out the column takes, converted to the column's
+    /// affinity, which is NULL without a `DEFAULT`
+    pub fn default_value(&self) -> anyhow::Result<RecordValue> {
+        let Some(default) = &self.default else {
+            return Ok(RecordValue::Null);
+        };
+
+        let Some(expr) = &default.expr else {
+            anyhow::bail!("unsupported DEFAULT value: {}", default.sql);
+        };
+
+        let value = expr.evaluate(&|name| {
+            anyhow::bail!(
+                "default value of column {} refers to column {name}",
+                self.name
+            )
+        })?;
+        Ok(self.affinity().apply(&value))
+    }
+}
+
+impl CreateTable {
+    /// The collation comparisons against the column use unless overridden
+    pub fn column_collation(&self, column: &str) -> Collation {
+        self.columns
+            .iter()
+            .find(|c| same_name(&c.name, column))
+            .map(|c| c.collation.clone())
+            .unwrap_or_default()
+    }
+
+    /// The affinity of the column, or none for a column the table doesn't have
+    pub fn column_affinity(&self, column: &str) -> Affinity {
+        self.columns
+            .iter()
+            .find(|c| same_name(&c.name, column))
+            .map_or(Affinity::Blob, ColumnDefinition::affinity)
+    }
+
+    /// The `INTEGER PRIMARY KEY` column whose value is stored as the rowid
+    pub fn rowid_alias(&self) -> Option<&ColumnDefinition> {
+        self.columns.iter().find(|column| column.is_rowid_alias())
+    }
+
+    /// Whether the rowid alias is declared `AUTOINCREMENT`, so rowids are never
+    /// reused and the last one given out is kept in `sqlite_sequence`
+    pub fn is_autoincrement(&self) -> bool {
+        self.rowid_alias()
+            .is_some_and(|column| column.constraints.iter().any(|c| c == "autoincrement"))
+    }
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Condition {
     pub column: String,
-    pub value: String,
+    pub operator: Operator,
+    pub value: RecordValue,
+    /// Explicit `COLLATE` overriding the column's collation
+    pub collation: Option<Collation>,
 }
 
-#[derive(Debug)]
-pub enum SelectOperation {
-    Count, // For now, only COUNT(*) is supported
+impl Condition {
+    /// Whether every row satisfying the condition satisfies `expr` too, so that a
+    /// partial index with `expr` as its `WHERE` clause holds all the rows it matches
+    ///
+    /// Like SQLite this only recognises a few forms: each term of `expr` has to be the
+    /// condition itself, `column IS NOT NULL`, or hold when the column equals the
+    /// value an `=` condition looks up.
+    pub fn implies(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::And(lhs, rhs) => self.implies(lhs) && self.implies(rhs),
+            // No comparison matches a NULL
+            Expr::IsNull {
+                expr,
+                negated: true,
+            } => matches!(&**expr, Expr::Column(column) if same_name(column, &self.column)),
+            _ if self.collation.is_some() => false,
+            term => {
+                let condition = Expr::Compare {
+                    lhs: Box::new(Expr::Column(self.column.clone())),
+                    operator: self.operator,
+                    rhs: Box::new(Expr::Literal(self.value.clone())),
+                };
+                if *term == condition {
+                    return true;
+                }
+
+                self.operator == Operator::Eq
+                    && term
+                        .evaluate(&|name| match same_name(name, &self.column) {
+                            true => Ok(self.value.clone()),
+                            false => anyhow::bail!("no value for column {name}"),
+                        })
+                        .is_ok_and(|value| function::truth(&value) == Some(true))
+            }
+        }
+    }
 }
 
-fn identifier_with_quotes(input: &str) -> IResult<&str, String> {
-    map(
-        delimited(tag("\""), take_until("\""), tag("\"")),
-        |s: &str| s.to_string(),
-    )
-    .parse(input)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    /// `LIKE`, with the character given by any `ESCAPE` clause
+    Like {
+        escape: Option<char>,
+    },
+    Glob,
+    /// `IS`, which is `=` finding NULL equal to NULL rather than to nothing
+    Is,
+    /// `IS NOT`, which is `!=` finding NULL equal to NULL rather than to nothing
+    IsNot,
 }
 
-fn raw_identifier(input: &str) -> IResult<&str, String> {
-    let (input, ident) =
-        take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '*' || c == '\"')(input)?;
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            Self::Eq => "=",
+            Self::NotEq => "!=",
+            Self::Lt => "<",
+            Self::LtEq => "<=",
+            Self::Gt => ">",
+            Self::GtEq => ">=",
+            Self::Like { .. } => "LIKE",
+            Self::Glob => "GLOB",
+            Self::Is => "IS",
+            Self::IsNot => "IS NOT",
+        };
 
-    Ok((input, ident.to_string()))
+        f.write_str(operator)
+    }
 }
 
-fn identifier(input: &str) -> IResult<&str, String> {
-    alt((identifier_with_quotes, raw_identifier)).parse(input)
+/// An operator computing a value from two others
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    /// `||`, joining both sides as text
+    Concat,
+    /// `->`, the JSON text at a path into the JSON on the left
+    ExtractJson,
+    /// `->>`, the SQL value at a path into the JSON on the left
+    ExtractValue,
 }
 
-fn select_operation(input: &str) -> IResult<&str, Option<SelectOperation>> {
-    opt(map(
-        (
-            multispace0,
-            tag_no_case("count"),
-            tag("("),
-            char('*'),
-            tag(")"),
-            multispace0,
-        ),
-        |_| SelectOperation::Count,
-    ))
-    .parse(input)
-}
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Remainder => "%",
+            Self::Concat => "||",
+            Self::ExtractJson => "->",
+            Self::ExtractValue => "->>",
+        };
 
-fn column_list(input: &str) -> IResult<&str, Vec<String>> {
-    separated_list1(delimited(multispace0, char(','), multispace0), identifier).parse(input)
+        f.write_str(operator)
+    }
 }
 
-fn condition(input: &str) -> IResult<&str, Condition> {
-    let (input, (column, _, value)) = (
-        identifier,
-        delimited(multispace0, char('='), multispace0),
-        take_while1(|c: char| c.is_alphanumeric() || c == '\'' || c == '_' || c == ' '),
-    )
-        .parse(input)?;
+impl Operator {
+    /// The equivalent operator once the operands are compared the other way round
+    pub fn reversed(&self) -> Self {
+        match self {
+            Self::Lt => Self::Gt,
+            Self::LtEq => Self::GtEq,
+            Self::Gt => Self::Lt,
+            Self::GtEq => Self::LtEq,
+            other => *other,
+        }
+    }
 
-    Ok((
-        input,
-        Condition {
-            column,
-            value: value.trim_matches('\'').to_string(),
-        },
-    ))
+    /// Whether an ordering of `value` against the condition literal satisfies the operator
+    ///
+    /// Pattern operators aren't decided by an ordering, so never match here.
+    pub fn matches(&self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq | Self::Is => ordering == Ordering::Equal,
+            Self::NotEq | Self::IsNot => ordering != Ordering::Equal,
+            Self::Lt => ordering == Ordering::Less,
+            Self::LtEq => ordering != Ordering::Greater,
+            Self::Gt => ordering == Ordering::Greater,
+            Self::GtEq => ordering != Ordering::Less,
+            Self::Like { .. } | Self::Glob => false,
+        }
+    }
+
+    /// Whether the operator compares values by their order, so can be answered by
+    /// seeking an index, as opposed to matching them against a pattern or looking
+    /// for NULL
+    pub fn is_comparison(&self) -> bool {
+        !matches!(
+            self,
+            Self::Like { .. } | Self::Glob | Self::Is | Self::IsNot
+        )
+    }
+
+    /// Whether NULL is compared like any other value rather than matching nothing
+    pub fn is_null_safe(&self) -> bool {
+        matches!(self, Self::Is | Self::IsNot)
+    }
+
+    /// Whether `value` satisfies the operator against the condition literal, where
+    /// comparisons use `collation` and patterns ignore it
+    pub fn test(&self, value: &RecordValue, literal: &RecordValue, collation: &Collation) -> bool {
+        match self {
+            Self::Like { escape } => pattern::like(value, literal, *escape),
+            Self::Glob => pattern::glob(value, literal),
+            Self::Is | Self::IsNot
+                if *value == RecordValue::Null || *literal == RecordValue::Null =>
+            {
+                (value == literal) == (*self == Self::Is)
+            }
+            comparison => value
+                .compare(literal, collation)
+                .is_some_and(|ordering| comparison.matches(ordering)),
+        }
+    }
 }
 
-fn constraint(input: &str) -> IResult<&str, String> {
-    let keywords = alt((
-        tag_no_case("primary key"),
-        tag_no_case("autoincrement"),
-        tag_no_case("not null"),
-    ));
-    map(preceded(multispace1, keywords), |s: &str| s.to_lowercase()).parse(input)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
 }
 
-fn multiple_constraints(mut input: &str) -> IResult<&str, Vec<String>> {
-    let mut constraints = Vec::new();
-    while let Ok((next, cons)) = constraint(input) {
-        constraints.push(cons);
-        input = next;
-    }
+#[derive(Debug, Clone)]
+pub struct OrderingTerm {
+    pub column: String,
+    pub order: SortOrder,
+    /// Explicit `COLLATE` overriding the column's collation
+    pub collation: Option<Collation>,
+}
 
-    Ok((input, constraints))
+#[derive(Debug, Clone)]
+pub enum SelectOperation {
+    Count, // For now, only COUNT(*) is supported
+    Min(String),
+    Max(String),
 }
 
-fn column_definition(input: &str) -> IResult<&str, ColumnDefinition> {
-    let (input, _) = opt(multispace0).parse(input)?;
-    let (input, name) = identifier(input)?;
-    let (input, _) = multispace1(input)?;
-    let (input, datatype) = identifier(input)?;
-    let (input, constraints) = multiple_constraints(input)?;
+/// Whether two identifiers name the same table, column or index, which SQLite
+/// decides ignoring the case of ASCII letters, quoted or not
+pub fn same_name(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
 
-    Ok((
-        input,
-        ColumnDefinition {
-            name,
-            datatype,
-            constraints,
-        },
-    ))
+/// The words SQLite reserves, which a name has to be quoted to be
+const KEYWORDS: &str = "\
+    ABORT ACTION ADD AFTER ALL ALTER ALWAYS ANALYZE AND AS ASC ATTACH AUTOINCREMENT \
+    BEFORE BEGIN BETWEEN BY CASCADE CASE CAST CHECK COLLATE COLUMN COMMIT CONFLICT \
+    CONSTRAINT CREATE CROSS CURRENT CURRENT_DATE CURRENT_TIME CURRENT_TIMESTAMP \
+    DATABASE DEFAULT DEFERRABLE DEFERRED DELETE DESC DETACH DISTINCT DO DROP EACH \
+    ELSE END ESCAPE EXCEPT EXCLUDE EXCLUSIVE EXISTS EXPLAIN FAIL FILTER FIRST \
+    FOLLOWING FOR FOREIGN FROM FULL GENERATED GLOB GROUP GROUPS HAVING IF IGNORE \
+    IMMEDIATE IN INDEX INDEXED INITIALLY INNER INSERT INSTEAD INTERSECT INTO IS \
+    ISNULL JOIN KEY LAST LEFT LIKE LIMIT MATCH MATERIALIZED NATURAL NO NOT NOTHING \
+    NOTNULL NULL NULLS OF OFFSET ON OR ORDER OTHERS OUTER OVER PARTITION PLAN PRAGMA \
+    PRECEDING PRIMARY QUERY RAISE RANGE RECURSIVE REFERENCES REGEXP REINDEX RELEASE \
+    RENAME REPLACE RESTRICT RETURNING RIGHT ROLLBACK ROW ROWS SAVEPOINT SELECT SET \
+    TABLE TEMP TEMPORARY THEN TIES TO TRANSACTION TRIGGER UNBOUNDED UNION UNIQUE \
+    UPDATE USING VACUUM VALUES VIEW VIRTUAL WHEN WHERE WINDOW WITH WITHOUT";
+
+/// Double quotes a name unless it is a plain identifier, the way `sqlite3` and
+/// `sqldiff` write names out
+pub fn quote_identifier(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS
+            .split_whitespace()
+            .any(|keyword| same_name(keyword, name));
+
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
 }
 
-fn where_clause(input: &str) -> IResult<&str, Option<Condition>> {
-    opt(preceded(
-        (multispace0, tag_no_case("where"), multispace0),
-        condition,
+/// A name quoted as `"name"`, `` `name` `` or `[name]`, which may then hold spaces
+/// and keywords, with a doubled quote standing for the quote itself
+fn identifier_with_quotes(input: &str) -> IResult<&str, String> {
+    alt((
+        delimited(
+            char('"'),
+            map(
+                many0(alt((value("\"", tag("\"\"")), is_not("\"")))),
+                |parts| parts.concat(),
+            ),
+            char('"'),
+        ),
+        delimited(
+            char('`'),
+            map(many0(alt((value("`", tag("``")), is_not("`")))), |parts| {
+                parts.concat()
+            }),
+            char('`'),
+        ),
+        map(
+            delimited(char('['), take_until("]"), char(']')),
+            |s: &str| s.to_string(),
+        ),
     ))
     .parse(input)
 }
 
-pub fn select_statement(input: &str) -> IResult<&str, SelectStatement> {
-    let (input, _) = (tag_no_case("select"), multispace0).parse(input)?;
-    let (input, operation) = select_operation(input)?;
+fn raw_identifier(input: &str) -> IResult<&str, String> {
+    let (input, ident) =
+        take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '*' || c == '\"')(input)?;
 
-    // TODO: Fix this to be a bit cleaner
-    if operation.is_some() {
-        let (input, _) = (multispace0, tag_no_case("from"), multispace0).parse(input)?;
-        let (input, table) = identifier(input)?;
-        return Ok((
-            input,
-            SelectStatement {
-                operation,
-                columns: Vec::new(),
-                table,
-                where_clause: None,
-            },
-        ));
-    }
+    Ok((input, ident.to_string()))
+}
 
-    let (input, columns) = column_list(input)?;
-    let (input, _) = (multispace0, tag_no_case("from"), multispace0).parse(input)?;
-    let (input, table) = identifier(input)?;
-    let (input, where_clause) = where_clause(input)?;
-    let (input, _) = opt(char(';')).parse(input)?;
+fn identifier(input: &str) -> IResult<&str, String> {
+    alt((identifier_with_quotes, raw_identifier)).parse(input)
+}
 
-    Ok((
-        input,
-        SelectStatement {
-            operation: None,
-            columns,
-            table,
-            where_clause,
-        },
-    ))
+/// A name optionally qualified with the database it belongs to, as in `aux.table`
+fn qualified_name(input: &str) -> IResult<&str, (Option<String>, String)> {
+    let (input, first) = identifier(input)?;
+    let (input, second) = opt(preceded(char('.'), identifier)).parse(input)?;
+
+    Ok(match second {
+        Some(name) => (input, (Some(first), name)),
+        None => (input, (None, first)),
+    })
 }
 
-pub fn create_statement(input: &str) -> IResult<&str, CreateStatement> {
-    if input.contains("INDEX") || input.contains("index") {
-        return create_index_statement(input);
-    }
+/// A column, optionally qualified with the table or alias it's from as
+/// `table.column`, which is how the name is kept
+fn column_name(input: &str) -> IResult<&str, String> {
+    let (input, first) = identifier(input)?;
+    let (input, second) = opt(preceded(char('.'), identifier)).parse(input)?;
 
-    create_table_statement(input)
+    Ok(match second {
+        Some(column) => (input, format!("{first}.{column}")),
+        None => (input, first),
+    })
 }
 
-fn create_index_statement(_input: &str) -> IResult<&str, CreateStatement> {
-    todo!("create index statement")
+fn aggregate_column(input: &str) -> IResult<&str, String> {
+    delimited(
+        (char('('), multispace0),
+        column_name,
+        (multispace0, char(')')),
+    )
+    .parse(input)
 }
 
-fn create_table_statement(input: &str) -> IResult<&str, CreateStatement> {
-    let (input, (_, _, table_name, _)) = (
-        tag_no_case("create table"),
+fn select_operation(input: &str) -> IResult<&str, Option<SelectOperation>> {
+    opt(delimited(
         multispace0,
-        identifier,
+        alt((
+            map(
+                (tag_no_case("count"), tag("("), char('*'), tag(")")),
+                |_| SelectOperation::Count,
+            ),
+            map(
+                preceded(tag_no_case("min"), aggregate_column),
+                SelectOperation::Min,
+            ),
+            map(
+                preceded(tag_no_case("max"), aggregate_column),
+                SelectOperation::Max,
+            ),
+        )),
         multispace0,
+    ))
+    .parse(input)
+}
+
+fn column_list(input: &str) -> IResult<&str, Vec<Expr>> {
+    separated_list1(
+        delimited(multispace0, char(','), multispace0),
+        alt((boolean_expression, map(column_name, Expr::Column))),
     )
-        .parse(input)?;
+    .parse(input)
+}
 
-    let (input, column_definition) = delimited(
-        char('('),
+/// `VALUES (expr, ...), ...`, whose columns are named `column1`, `column2` and so on
+fn values_clause(input: &str) -> IResult<&str, Vec<Vec<Expr>>> {
+    let row = delimited(
+        (char('('), multispace0),
         separated_list1(
             delimited(multispace0, char(','), multispace0),
-            column_definition,
+            boolean_expression,
         ),
-        preceded(multispace0, char(')')),
+        (multispace0, char(')')),
+    );
+
+    preceded(
+        (tag_no_case("values"), multispace0),
+        separated_list1(delimited(multispace0, char(','), multispace0), row),
     )
+    .parse(input)
+}
+
+/// A value computed from the columns rather than a bare column
+fn scalar_expression(input: &str) -> IResult<&str, Expr> {
+    alt((cast_expression, function_call)).parse(input)
+}
+
+/// `CAST(expr AS type)`, where the type may be any declared column type such as
+/// `VARCHAR(10)` or `UNSIGNED BIG INT`
+fn cast_expression(input: &str) -> IResult<&str, Expr> {
+    let (input, _) = (tag_no_case("cast"), multispace0, char('('), multispace0).parse(input)?;
+    let (input, expr) = function_argument(input)?;
+    let (input, _) = (multispace1, tag_no_case("as"), multispace1).parse(input)?;
+    let (input, type_name) = recognize((
+        take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == ' '),
+        opt(delimited(char('('), parenthesized, char(')'))),
+    ))
     .parse(input)?;
+    let (input, _) = (multispace0, char(')')).parse(input)?;
 
     Ok((
         input,
-        CreateStatement::Table(CreateTable {
-            name: table_name,
-            columns: column_definition,
+        Expr::Cast {
+            expr: Box::new(expr),
+            type_name: type_name.trim().to_string(),
+        },
+    ))
+}
+
+/// A call to a scalar function such as `json_extract(data, '$.name')`
+fn function_call(input: &str) -> IResult<&str, Expr> {
+    let (input, name) = take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)?;
+    let (input, args) = delimited(
+        (multispace0, char('('), multispace0),
+        separated_list0(
+            delimited(multispace0, char(','), multispace0),
+            function_argument,
+        ),
+        (multispace0, char(')')),
+    )
+    .parse(input)?;
+
+    Ok((
+        input,
+        Expr::Function {
+            name: name.to_ascii_lowercase(),
+            args,
+        },
+    ))
+}
+
+fn expression_identifier(input: &str) -> IResult<&str, String> {
+    alt((
+        identifier_with_quotes,
+        map(
+            take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+            String::from,
+        ),
+    ))
+    .parse(input)
+}
+
+fn function_argument(input: &str) -> IResult<&str, Expr> {
+    alt((
+        scalar_expression,
+        map(string_literal, |s| Expr::Literal(RecordValue::String(s))),
+        map(blob_literal, |blob| Expr::Literal(RecordValue::Blob(blob))),
+        map(
+            verify(
+                take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+                |word: &str| word.eq_ignore_ascii_case("null"),
+            ),
+            |_| Expr::Literal(RecordValue::Null),
+        ),
+        map(numeric_literal, Expr::Literal),
+        // Unlike a selected column, one in an expression can't be `*`
+        map(
+            (
+                expression_identifier,
+                opt(preceded(char('.'), expression_identifier)),
+            ),
+            |(first, second)| match second {
+                Some(column) => Expr::Column(format!("{first}.{column}")),
+                None => Expr::Column(first),
+            },
+        ),
+    ))
+    .parse(input)
+}
+
+fn operator(input: &str) -> IResult<&str, Operator> {
+    alt((
+        map(tag("<="), |_| Operator::LtEq),
+        map(tag(">="), |_| Operator::GtEq),
+        map(tag("!="), |_| Operator::NotEq),
+        map(tag("<>"), |_| Operator::NotEq),
+        map(tag("=="), |_| Operator::Eq),
+        map(tag("="), |_| Operator::Eq),
+        map(tag("<"), |_| Operator::Lt),
+        map(tag(">"), |_| Operator::Gt),
+        map(tag_no_case("like"), |_| Operator::Like { escape: None }),
+        map(tag_no_case("glob"), |_| Operator::Glob),
+        map(
+            (
+                tag_no_case("is"),
+                multispace1,
+                tag_no_case("not"),
+                multispace1,
+            ),
+            |_| Operator::IsNot,
+        ),
+        map((tag_no_case("is"), multispace1), |_| Operator::Is),
+    ))
+    .parse(input)
+}
+
+/// The `ESCAPE 'x'` following the pattern of a LIKE, which only LIKE accepts
+fn escape_clause(input: &str, operator: Operator) -> IResult<&str, Operator> {
+    let (rest, escape) = opt(preceded(
+        (multispace1, tag_no_case("escape"), multispace0),
+        verify(string_literal, |escape: &str| escape.chars().count() == 1),
+    ))
+    .parse(input)?;
+
+    match (operator, escape) {
+        (_, None) => Ok((rest, operator)),
+        (Operator::Like { .. }, Some(escape)) => Ok((
+            rest,
+            Operator::Like {
+                escape: escape.chars().next(),
+            },
+        )),
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        ))),
+    }
+}
+
+/// A quoted string is text and `X'..'` a blob, while a bare word is the number or
+/// NULL it spells or otherwise taken as text
+fn literal(input: &str) -> IResult<&str, RecordValue> {
+    alt((
+        map(string_literal, RecordValue::String),
+        map(blob_literal, RecordValue::Blob),
+        map(
+            take_while1(|c: char| c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '+')),
+            |word: &str| match numeric_literal(word) {
+                Ok(("", value)) => value,
+                _ if word.eq_ignore_ascii_case("null") => RecordValue::Null,
+                _ => RecordValue::String(word.to_string()),
+            },
+        ),
+    ))
+    .parse(input)
+}
+
+/// A string between single quotes, where a doubled quote stands for the quote itself
+fn string_literal(input: &str) -> IResult<&str, String> {
+    delimited(
+        char('\''),
+        map(many0(alt((value("'", tag("''")), is_not("'")))), |parts| {
+            parts.concat()
+        }),
+        char('\''),
+    )
+    .parse(input)
+}
+
+/// A blob written as `X'..'`, with two hex digits to each byte
+fn blob_literal(input: &str) -> IResult<&str, Vec<u8>> {
+    let (rest, hex) = preceded(
+        alt((char('x'), char('X'))),
+        delimited(
+            char('\''),
+            take_while(|c: char| c.is_ascii_hexdigit()),
+            char('\''),
+        ),
+    )
+    .parse(input)?;
+
+    if hex.len() % 2 != 0 {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::HexDigit,
+        )));
+    }
+
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("hex digits"))
+        .collect();
+    Ok((rest, bytes))
+}
+
+/// A number, optionally signed, which can't run on into a word as `12abc` would
+fn numeric_literal(input: &str) -> IResult<&str, RecordValue> {
+    let (rest, value) = alt((hex_integer, decimal_number)).parse(input)?;
+    if rest.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Digit,
+        )));
+    }
+
+    Ok((rest, value))
+}
+
+/// An integer written as `0x` and up to 16 hex digits, taken as the 64 bits of a
+/// two's complement integer like SQLite does, so `0xFFFFFFFFFFFFFFFF` is -1
+fn hex_integer(input: &str) -> IResult<&str, RecordValue> {
+    let (rest, (sign, _, digits)) = (
+        opt(alt((char('-'), char('+')))),
+        tag_no_case("0x"),
+        take_while_m_n(1, 16, |c: char| c.is_ascii_hexdigit()),
+    )
+        .parse(input)?;
+
+    let bits = u64::from_str_radix(digits, 16).expect("hex digits") as i64;
+    let value = match sign {
+        Some('-') => bits.wrapping_neg(),
+        _ => bits,
+    };
+    Ok((rest, RecordValue::I64(value)))
+}
+
+/// An integer, or a real when it has a fraction or an exponent or is too large for
+/// an integer
+fn decimal_number(input: &str) -> IResult<&str, RecordValue> {
+    let digits = || take_while1(|c: char| c.is_ascii_digit());
+    let sign = || opt(alt((char('-'), char('+'))));
+    let (rest, number) = recognize((
+        sign(),
+        alt((
+            recognize((digits(), opt((char('.'), opt(digits()))))),
+            recognize((char('.'), digits())),
+        )),
+        opt((alt((char('e'), char('E'))), sign(), digits())),
+    ))
+    .parse(input)?;
+
+    let value = match number.parse::<i64>() {
+        Ok(integer) => RecordValue::I64(integer),
+        Err(_) => RecordValue::F64(number.parse().expect("recognized as a number")),
+    };
+    Ok((rest, value))
+}
+
+fn condition(input: &str) -> IResult<&str, Condition> {
+    let (input, (column, column_collation, operator, value, value_collation)) = (
+        column_name,
+        opt(collation),
+        delimited(multispace0, operator, multispace0),
+        literal,
+        opt(collation),
+    )
+        .parse(input)?;
+    let (input, operator) = escape_clause(input, operator)?;
+
+    Ok((
+        input,
+        Condition {
+            column,
+            operator,
+            value,
+            collation: value_collation.or(column_collation),
+        },
+    ))
+}
+
+fn constraint(input: &str) -> IResult<&str, String> {
+    let keywords = alt((
+        primary_key_constraint,
+        map(
+            alt((
+                tag_no_case("autoincrement"),
+                tag_no_case("not null"),
+                tag_no_case("unique"),
+            )),
+            |s: &str| s.to_lowercase(),
+        ),
+    ));
+    preceded(multispace1, keywords).parse(input)
+}
+
+/// `PRIMARY KEY` on a column, with the order of its index when it's given
+fn primary_key_constraint(input: &str) -> IResult<&str, String> {
+    let (input, (_, _, _, order)) = (
+        tag_no_case("primary"),
+        multispace1,
+        tag_no_case("key"),
+        sort_order,
+    )
+        .parse(input)?;
+
+    let constraint = match order {
+        SortOrder::Asc => "primary key",
+        SortOrder::Desc => "primary key desc",
+    };
+    Ok((input, constraint.to_string()))
+}
+
+/// The constraints of a column that are kept on its definition
+#[derive(Default)]
+struct ColumnConstraints {
+    constraints: Vec<String>,
+    collation: Collation,
+    default: Option<ColumnDefault>,
+    generated: Option<GeneratedColumn>,
+}
+
+fn multiple_constraints(
+    mut input: &str,
+) -> IResult<&str, (ColumnConstraints, Vec<TableConstraint>)> {
+    let mut column = ColumnConstraints::default();
+    let mut table_constraints = Vec::new();
+    let mut name = None;
+    loop {
+        if let Ok((next, cons)) = constraint(input) {
+            column.constraints.push(cons);
+            input = next;
+        } else if let Ok((next, coll)) = collation(input) {
+            column.collation = coll;
+            input = next;
+        } else if let Ok((next, default)) = preceded(multispace1, default_constraint).parse(input) {
+            column.default = Some(default);
+            input = next;
+        } else if let Ok((next, generated)) = preceded(multispace1, generated_column).parse(input) {
+            column.generated = Some(generated);
+            input = next;
+        } else if let Ok((next, key)) = preceded(multispace1, references_clause).parse(input) {
+            table_constraints.push(TableConstraint::ForeignKey(key));
+            input = next;
+        } else if let Ok((next, check)) = preceded(multispace1, check_constraint).parse(input) {
+            table_constraints.push(TableConstraint::Check(Check {
+                name: name.take(),
+                ..check
+            }));
+            input = next;
+        } else if let Ok((next, constraint_name)) =
+            preceded(multispace1, constraint_name).parse(input)
+        {
+            name = Some(constraint_name);
+            input = next;
+        } else {
+            break;
+        }
+    }
+
+    Ok((input, (column, table_constraints)))
+}
+
+/// `DEFAULT value`, where the value is a literal, a parenthesized expression or one
+/// of the `CURRENT_TIME` keywords
+fn default_constraint(input: &str) -> IResult<&str, ColumnDefault> {
+    let (input, _) = (tag_no_case("default"), multispace0).parse(input)?;
+    if let Ok((rest, sql)) = delimited(char('('), parenthesized, char(')')).parse(input) {
+        let sql = sql.trim();
+        let expr = match boolean_expression(sql) {
+            Ok((rest, expr)) if rest.trim().is_empty() => Some(expr),
+            _ => None,
+        };
+        let sql = sql.to_string();
+
+        return Ok((rest, ColumnDefault { sql, expr }));
+    }
+
+    let (rest, expr) = function_argument(input)?;
+    let sql = input[..input.len() - rest.len()].to_string();
+
+    // A bare word is the keyword it spells or otherwise a string, as in SQLite
+    let expr = match expr {
+        Expr::Column(word) => match word.to_ascii_lowercase().as_str() {
+            "true" => Expr::Literal(RecordValue::I64(1)),
+            "false" => Expr::Literal(RecordValue::I64(0)),
+            keyword @ ("current_time" | "current_date" | "current_timestamp") => Expr::Function {
+                name: keyword.to_string(),
+                args: Vec::new(),
+            },
+            _ => Expr::Literal(RecordValue::String(word)),
+        },
+        expr => expr,
+    };
+
+    Ok((
+        rest,
+        ColumnDefault {
+            sql,
+            expr: Some(expr),
+        },
+    ))
+}
+
+/// `[GENERATED ALWAYS] AS (expr) [VIRTUAL | STORED]`, where a column is `VIRTUAL`
+/// unless it says otherwise
+fn generated_column(input: &str) -> IResult<&str, GeneratedColumn> {
+    let (input, sql) = preceded(
+        (
+            opt((
+                tag_no_case("generated"),
+                multispace1,
+                tag_no_case("always"),
+                multispace1,
+            )),
+            tag_no_case("as"),
+            multispace0,
+        ),
+        delimited(char('('), parenthesized, char(')')),
+    )
+    .parse(input)?;
+    let (input, storage) = opt(preceded(
+        multispace1,
+        alt((tag_no_case("stored"), tag_no_case("virtual"))),
+    ))
+    .parse(input)?;
+
+    let sql = sql.trim();
+    let expr = match boolean_expression(sql) {
+        Ok((rest, expr)) if rest.trim().is_empty() => Some(expr),
+        _ => None,
+    };
+
+    Ok((
+        input,
+        GeneratedColumn {
+            sql: sql.to_string(),
+            expr,
+            stored: storage.is_some_and(|storage| storage.eq_ignore_ascii_case("stored")),
+        },
+    ))
+}
+
+/// `CONSTRAINT name`, naming the constraint that follows
+fn constraint_name(input: &str) -> IResult<&str, String> {
+    preceded((tag_no_case("constraint"), multispace1), identifier).parse(input)
+}
+
+/// `CHECK (expr)`, keeping the expression as written along with its parsed form
+/// when the whole of it parses
+fn check_constraint(input: &str) -> IResult<&str, Check> {
+    let (input, sql) = preceded(
+        (tag_no_case("check"), multispace0),
+        delimited(char('('), parenthesized, char(')')),
+    )
+    .parse(input)?;
+
+    let sql = sql.trim();
+    let expr = match boolean_expression(sql) {
+        Ok((rest, expr)) if rest.trim().is_empty() => Some(expr),
+        _ => None,
+    };
+
+    Ok((
+        input,
+        Check {
+            name: None,
+            sql: sql.to_string(),
+            expr,
+        },
+    ))
+}
+
+/// An expression joining comparisons with AND, OR and NOT, as a CHECK constraint
+/// holds
+fn boolean_expression(input: &str) -> IResult<&str, Expr> {
+    let keyword = |word| (multispace1, tag_no_case(word), multispace1);
+    let (mut input, mut expr) = and_expression(input)?;
+    while let Ok((next, rhs)) = preceded(keyword("or"), and_expression).parse(input) {
+        expr = Expr::Or(Box::new(expr), Box::new(rhs));
+        input = next;
+    }
+
+    Ok((input, expr))
+}
+
+fn and_expression(input: &str) -> IResult<&str, Expr> {
+    let keyword = |word| (multispace1, tag_no_case(word), multispace1);
+    let (mut input, mut expr) = not_expression(input)?;
+    while let Ok((next, rhs)) = preceded(keyword("and"), not_expression).parse(input) {
+        expr = Expr::And(Box::new(expr), Box::new(rhs));
+        input = next;
+    }
+
+    Ok((input, expr))
+}
+
+fn not_expression(input: &str) -> IResult<&str, Expr> {
+    alt((
+        map(
+            preceded((tag_no_case("not"), multispace1), not_expression),
+            |expr| Expr::Not(Box::new(expr)),
+        ),
+        comparison_expression,
+    ))
+    .parse(input)
+}
+
+/// An operand on its own or followed by a comparison, IS NULL, IN or BETWEEN
+fn comparison_expression(input: &str) -> IResult<&str, Expr> {
+    let (input, expr) = additive_expression(input)?;
+    let not = || opt((tag_no_case("not"), multispace1)).map(|not| not.is_some());
+
+    if let Ok((rest, (_, _, _, negated, _))) = (
+        multispace1,
+        tag_no_case("is"),
+        multispace1,
+        not(),
+        tag_no_case("null"),
+    )
+        .parse(input)
+    {
+        let expr = Box::new(expr);
+        return Ok((rest, Expr::IsNull { expr, negated }));
+    }
+
+    if let Ok((rest, (_, negated, _, list))) = (
+        multispace1,
+        not(),
+        tag_no_case("in"),
+        delimited(
+            (multispace0, char('('), multispace0),
+            separated_list1(
+                delimited(multispace0, char(','), multispace0),
+                additive_expression,
+            ),
+            (multispace0, char(')')),
+        ),
+    )
+        .parse(input)
+    {
+        let expr = Box::new(expr);
+        return Ok((
+            rest,
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            },
+        ));
+    }
+
+    if let Ok((rest, (_, negated, _, _, low, _, _, _, high))) = (
+        multispace1,
+        not(),
+        tag_no_case("between"),
+        multispace1,
+        additive_expression,
+        multispace1,
+        tag_no_case("and"),
+        multispace1,
+        additive_expression,
+    )
+        .parse(input)
+    {
+        let compare = |operator, bound| Expr::Compare {
+            lhs: Box::new(expr.clone()),
+            operator,
+            rhs: Box::new(bound),
+        };
+        let between = Expr::And(
+            Box::new(compare(Operator::GtEq, low)),
+            Box::new(compare(Operator::LtEq, high)),
+        );
+        return Ok((
+            rest,
+            if negated {
+                Expr::Not(Box::new(between))
+            } else {
+                between
+            },
+        ));
+    }
+
+    if let Ok((rest, (operator, rhs))) = (
+        delimited(multispace0, operator, multispace0),
+        additive_expression,
+    )
+        .parse(input)
+    {
+        let (rest, operator) = escape_clause(rest, operator)?;
+        return Ok((
+            rest,
+            Expr::Compare {
+                lhs: Box::new(expr),
+                operator,
+                rhs: Box::new(rhs),
+            },
+        ));
+    }
+
+    Ok((input, expr))
+}
+
+/// Operands joined by `+` and `-`, which bind the loosest of the binary operators
+fn additive_expression(input: &str) -> IResult<&str, Expr> {
+    binary_expression(
+        input,
+        multiplicative_expression,
+        alt((
+            value(BinaryOperator::Add, char('+')),
+            value(BinaryOperator::Subtract, char('-')),
+        )),
+    )
+}
+
+fn multiplicative_expression(input: &str) -> IResult<&str, Expr> {
+    binary_expression(
+        input,
+        concat_expression,
+        alt((
+            value(BinaryOperator::Multiply, char('*')),
+            value(BinaryOperator::Divide, char('/')),
+            value(BinaryOperator::Remainder, char('%')),
+        )),
+    )
+}
+
+/// `||`, `->` and `->>`, which bind tightest of the binary operators
+fn concat_expression(input: &str) -> IResult<&str, Expr> {
+    binary_expression(
+        input,
+        operand,
+        alt((
+            value(BinaryOperator::Concat, tag("||")),
+            value(BinaryOperator::ExtractValue, tag("->>")),
+            value(BinaryOperator::ExtractJson, tag("->")),
+        )),
+    )
+}
+
+/// Terms parsed with `term` joined left to right by the operators `operator` parses
+fn binary_expression<'a>(
+    input: &'a str,
+    mut term: impl Parser<&'a str, Output = Expr, Error = nom::error::Error<&'a str>>,
+    mut operator: impl Parser<&'a str, Output = BinaryOperator, Error = nom::error::Error<&'a str>>,
+) -> IResult<&'a str, Expr> {
+    let (mut input, mut expr) = term.parse(input)?;
+    while let Ok((next, (operator, rhs))) = (
+        delimited(multispace0, |i| operator.parse(i), multispace0),
+        |i| term.parse(i),
+    )
+        .parse(input)
+    {
+        expr = Expr::Binary {
+            lhs: Box::new(expr),
+            operator,
+            rhs: Box::new(rhs),
+        };
+        input = next;
+    }
+
+    Ok((input, expr))
+}
+
+/// A value in a boolean expression, which may be a parenthesized expression itself
+fn operand(input: &str) -> IResult<&str, Expr> {
+    alt((
+        delimited(
+            (char('('), multispace0),
+            boolean_expression,
+            (multispace0, char(')')),
+        ),
+        function_argument,
+        // Negating is subtracting from zero, which SQLite does too
+        map(preceded((char('-'), multispace0), operand), |expr| {
+            Expr::Binary {
+                lhs: Box::new(Expr::Literal(RecordValue::I64(0))),
+                operator: BinaryOperator::Subtract,
+                rhs: Box::new(expr),
+            }
+        }),
+    ))
+    .parse(input)
+}
+
+fn foreign_key_action(input: &str) -> IResult<&str, ForeignKeyAction> {
+    use ForeignKeyAction::*;
+
+    alt((
+        value(
+            SetNull,
+            (tag_no_case("set"), multispace1, tag_no_case("null")),
+        ),
+        value(
+            SetDefault,
+            (tag_no_case("set"), multispace1, tag_no_case("default")),
+        ),
+        value(Cascade, tag_no_case("cascade")),
+        value(Restrict, tag_no_case("restrict")),
+        value(
+            NoAction,
+            (tag_no_case("no"), multispace1, tag_no_case("action")),
+        ),
+    ))
+    .parse(input)
+}
+
+/// A parenthesized list of column names, as a foreign key refers to
+fn column_names(input: &str) -> IResult<&str, Vec<String>> {
+    delimited(
+        (char('('), multispace0),
+        separated_list1(delimited(multispace0, char(','), multispace0), identifier),
+        (multispace0, char(')')),
+    )
+    .parse(input)
+}
+
+/// `REFERENCES parent(columns)` and the clauses after it, as a foreign key whose
+/// own columns are filled in by the caller
+fn references_clause(input: &str) -> IResult<&str, ForeignKey> {
+    let (mut input, (_, _, parent, parent_columns)) = (
+        tag_no_case("references"),
+        multispace1,
+        created_name,
+        opt(preceded(multispace0, column_names)),
+    )
+        .parse(input)?;
+
+    let mut foreign_key = ForeignKey {
+        columns: Vec::new(),
+        parent,
+        parent_columns: parent_columns.unwrap_or_default(),
+        on_update: ForeignKeyAction::default(),
+        on_delete: ForeignKeyAction::default(),
+    };
+
+    loop {
+        let mut on = (
+            multispace1,
+            tag_no_case("on"),
+            multispace1,
+            alt((tag_no_case("delete"), tag_no_case("update"))),
+            multispace1,
+            foreign_key_action,
+        );
+        if let Ok((next, (_, _, _, event, _, action))) = on.parse(input) {
+            if event.eq_ignore_ascii_case("delete") {
+                foreign_key.on_delete = action;
+            } else {
+                foreign_key.on_update = action;
+            }
+            input = next;
+            continue;
+        }
+
+        // MATCH is parsed but, as in SQLite, has no effect
+        let matching = (multispace1, tag_no_case("match"), multispace1, identifier);
+        let deferrable = (
+            multispace1,
+            opt((tag_no_case("not"), multispace1)),
+            tag_no_case("deferrable"),
+            opt((
+                multispace1,
+                tag_no_case("initially"),
+                multispace1,
+                alt((tag_no_case("deferred"), tag_no_case("immediate"))),
+            )),
+        );
+        match alt((recognize(matching), recognize(deferrable))).parse(input) {
+            Ok((next, _)) => input = next,
+            Err(_) => return Ok((input, foreign_key)),
+        }
+    }
+}
+
+/// Words that start a constraint, so can't be a column's name or type
+/// Name a `*` in the select list is parsed as, until it is expanded into the columns
+/// of the table
+pub const ALL_COLUMNS: &str = "*";
+
+const CONSTRAINT_KEYWORDS: [&str; 11] = [
+    "primary",
+    "not",
+    "unique",
+    "check",
+    "default",
+    "collate",
+    "references",
+    "constraint",
+    "foreign",
+    "generated",
+    "as",
+];
+
+fn is_constraint_keyword(word: &str) -> bool {
+    CONSTRAINT_KEYWORDS
+        .iter()
+        .any(|keyword| word.eq_ignore_ascii_case(keyword))
+}
+
+/// A column of a CREATE TABLE statement along with the foreign keys and CHECK
+/// constraints declared on it
+fn column_definition(input: &str) -> IResult<&str, (ColumnDefinition, Vec<TableConstraint>)> {
+    let (input, _) = opt(multispace0).parse(input)?;
+    let (input, name) =
+        verify(identifier, |name: &str| !is_constraint_keyword(name)).parse(input)?;
+    let (input, datatype) = opt(preceded(
+        multispace1,
+        verify(identifier, |datatype: &str| {
+            !is_constraint_keyword(datatype)
+        }),
+    ))
+    .parse(input)?;
+    let datatype = datatype.unwrap_or_default();
+    let (input, (column_constraints, mut table_constraints)) = multiple_constraints(input)?;
+    for constraint in table_constraints.iter_mut() {
+        if let TableConstraint::ForeignKey(key) = constraint {
+            key.columns = vec![name.clone()];
+        }
+    }
+
+    let column = ColumnDefinition {
+        name,
+        datatype,
+        constraints: column_constraints.constraints,
+        collation: column_constraints.collation,
+        default: column_constraints.default,
+        generated: column_constraints.generated,
+    };
+
+    // Every key besides the rowid is kept in an index of its own
+    for constraint in column.constraints.iter() {
+        let (primary_key, order) = match constraint.as_str() {
+            "primary key" if column.is_rowid_alias() => continue,
+            "primary key" => (true, SortOrder::Asc),
+            "primary key desc" => (true, SortOrder::Desc),
+            "unique" => (false, SortOrder::Asc),
+            _ => continue,
+        };
+
+        table_constraints.push(TableConstraint::Unique(UniqueKey {
+            columns: vec![IndexedColumn {
+                name: column.name.clone(),
+                collation: Some(column.collation.clone()),
+                order,
+                expr: None,
+            }],
+            primary_key,
+        }));
+    }
+
+    Ok((input, (column, table_constraints)))
+}
+
+/// A comparison against a function of the columns, as in `json_extract(data, '$.a') = 1`
+/// or `data ->> 'a' = 1`
+fn expr_condition(input: &str) -> IResult<&str, ExprCondition> {
+    let json_operator = verify(concat_expression, |expr| {
+        matches!(
+            expr,
+            Expr::Binary {
+                operator: BinaryOperator::ExtractJson | BinaryOperator::ExtractValue,
+                ..
+            }
+        )
+    });
+    let (input, (expr, expr_collation, operator, value, value_collation)) = (
+        alt((json_operator, scalar_expression)),
+        opt(collation),
+        delimited(multispace0, operator, multispace0),
+        literal,
+        opt(collation),
+    )
+        .parse(input)?;
+    let (input, operator) = escape_clause(input, operator)?;
+
+    Ok((
+        input,
+        ExprCondition {
+            expr,
+            operator,
+            value,
+            collation: value_collation.or(expr_collation),
+        },
+    ))
+}
+
+/// `expr [NOT] IN (values)` or `expr [NOT] IN (select)`
+fn in_condition(input: &str) -> IResult<&str, InCondition> {
+    let source = alt((
+        map(select_statement, |select| {
+            InSource::Subquery(Box::new(select))
+        }),
+        map(
+            separated_list0(
+                delimited(multispace0, char(','), multispace0),
+                additive_expression,
+            ),
+            InSource::List,
+        ),
+    ));
+    let (input, (expr, collation, _, negated, _, source)) = (
+        additive_expression,
+        opt(collation),
+        multispace1,
+        opt((tag_no_case("not"), multispace1)).map(|not| not.is_some()),
+        tag_no_case("in"),
+        delimited(
+            (multispace0, char('('), multispace0),
+            source,
+            (multispace0, char(')')),
+        ),
+    )
+        .parse(input)?;
+
+    Ok((
+        input,
+        InCondition {
+            expr,
+            source,
+            negated,
+            collation,
+        },
+    ))
+}
+
+/// `[NOT] EXISTS (select)`
+fn exists_condition(input: &str) -> IResult<&str, ExistsCondition> {
+    let (input, (negated, _, subquery)) = (
+        opt((tag_no_case("not"), multispace1)).map(|not| not.is_some()),
+        tag_no_case("exists"),
+        delimited(
+            (multispace0, char('('), multispace0),
+            select_statement,
+            (multispace0, char(')')),
+        ),
+    )
+        .parse(input)?;
+
+    Ok((
+        input,
+        ExistsCondition {
+            subquery: Box::new(subquery),
+            negated,
+        },
+    ))
+}
+
+/// A comparison between two columns, where a bare word on the right is a column as
+/// in SQLite rather than text
+fn column_condition(input: &str) -> IResult<&str, ColumnCondition> {
+    let other = verify(terminated(column_name, not(char('\''))), |name: &str| {
+        !name.starts_with(|c: char| c.is_ascii_digit())
+            && !["null", "true", "false"]
+                .iter()
+                .any(|keyword| name.eq_ignore_ascii_case(keyword))
+    });
+    let (input, (column, column_collation, operator, other, other_collation)) = (
+        column_name,
+        opt(collation),
+        delimited(multispace0, operator, multispace0),
+        other,
+        opt(collation),
+    )
+        .parse(input)?;
+
+    Ok((
+        input,
+        ColumnCondition {
+            column,
+            operator,
+            other,
+            collation: other_collation.or(column_collation),
+        },
+    ))
+}
+
+/// A term of a WHERE clause, sorted by how the rows it keeps are found
+enum WhereTerm {
+    Condition(Condition),
+    Filter(ExprCondition),
+    In(InCondition),
+    Columns(ColumnCondition),
+    Exists(ExistsCondition),
+}
+
+/// The terms of a WHERE clause ANDed together, split by how the rows they keep are
+/// found
+#[derive(Default)]
+struct WhereClause {
+    conditions: Vec<Condition>,
+    row_filters: Vec<ExprCondition>,
+    in_filters: Vec<InCondition>,
+    column_filters: Vec<ColumnCondition>,
+    exists_filters: Vec<ExistsCondition>,
+}
+
+impl WhereClause {
+    fn unqualify(&mut self, names: &[&str]) {
+        for condition in self.conditions.iter_mut() {
+            unqualify(&mut condition.column, names);
+        }
+        for filter in self.row_filters.iter_mut() {
+            filter.expr.unqualify(names);
+        }
+        for filter in self.in_filters.iter_mut() {
+            filter.expr.unqualify(names);
+            if let InSource::List(list) = &mut filter.source {
+                list.iter_mut().for_each(|item| item.unqualify(names));
+            }
+        }
+        for filter in self.column_filters.iter_mut() {
+            unqualify(&mut filter.column, names);
+            unqualify(&mut filter.other, names);
+        }
+    }
+
+    /// Columns of the row the terms checked after reading it refer to
+    fn columns(&self) -> impl Iterator<Item = &str> {
+        self.row_filters
+            .iter()
+            .flat_map(|filter| filter.expr.columns())
+            .chain(self.in_filters.iter().flat_map(|filter| filter.columns()))
+            .chain(
+                self.column_filters
+                    .iter()
+                    .flat_map(|filter| [filter.column.as_str(), filter.other.as_str()]),
+            )
+    }
+}
+
+fn where_clause(input: &str) -> IResult<&str, WhereClause> {
+    let (input, terms) = opt(preceded(
+        (multispace0, tag_no_case("where"), multispace0),
+        separated_list1(
+            (multispace1, tag_no_case("and"), multispace1),
+            alt((
+                map(exists_condition, WhereTerm::Exists),
+                map(in_condition, WhereTerm::In),
+                map(expr_condition, WhereTerm::Filter),
+                map(column_condition, WhereTerm::Columns),
+                map(condition, WhereTerm::Condition),
+            )),
+        ),
+    ))
+    .parse(input)?;
+
+    let mut clause = WhereClause::default();
+    for term in terms.into_iter().flatten() {
+        match term {
+            WhereTerm::Condition(condition) => clause.conditions.push(condition),
+            WhereTerm::Filter(filter) => clause.row_filters.push(filter),
+            WhereTerm::In(filter) => clause.in_filters.push(filter),
+            WhereTerm::Columns(filter) => clause.column_filters.push(filter),
+            WhereTerm::Exists(filter) => clause.exists_filters.push(filter),
+        }
+    }
+
+    Ok((input, clause))
+}
+
+fn sort_order(input: &str) -> IResult<&str, SortOrder> {
+    let (input, order) = opt(preceded(
+        multispace1,
+        alt((
+            map(tag_no_case("asc"), |_| SortOrder::Asc),
+            map(tag_no_case("desc"), |_| SortOrder::Desc),
+        )),
+    ))
+    .parse(input)?;
+
+    Ok((input, order.unwrap_or(SortOrder::Asc)))
+}
+
+fn collation(input: &str) -> IResult<&str, Collation> {
+    let (input, (_, _, _, name)) =
+        (multispace1, tag_no_case("collate"), multispace1, identifier).parse(input)?;
+
+    match Collation::from_name(&name) {
+        Some(collation) => Ok((input, collation)),
+        None => Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        ))),
+    }
+}
+
+/// A key of an index or of a UNIQUE constraint, which is a column or an expression
+/// computed from the columns
+fn indexed_column(input: &str) -> IResult<&str, IndexedColumn> {
+    let (input, (sql, expr)) = consumed(boolean_expression).parse(input)?;
+    let (input, collation) = opt(collation).parse(input)?;
+    let (input, order) = sort_order(input)?;
+
+    let (name, expr) = match expr {
+        Expr::Column(name) => (name, None),
+        expr => (sql.trim().to_string(), Some(expr)),
+    };
+
+    Ok((
+        input,
+        IndexedColumn {
+            name,
+            collation,
+            order,
+            expr,
+        },
+    ))
+}
+
+fn ordering_term(input: &str) -> IResult<&str, OrderingTerm> {
+    let (input, column) = column_name(input)?;
+    let (input, collation) = opt(collation).parse(input)?;
+    let (input, order) = sort_order(input)?;
+
+    Ok((
+        input,
+        OrderingTerm {
+            column,
+            order,
+            collation,
+        },
+    ))
+}
+
+fn order_by_clause(input: &str) -> IResult<&str, Vec<OrderingTerm>> {
+    let (input, terms) = opt(preceded(
+        (
+            multispace0,
+            tag_no_case("order"),
+            multispace1,
+            tag_no_case("by"),
+            multispace1,
+        ),
+        separated_list1(
+            delimited(multispace0, char(','), multispace0),
+            ordering_term,
+        ),
+    ))
+    .parse(input)?;
+
+    Ok((input, terms.unwrap_or_default()))
+}
+
+/// Keywords that can follow the table of a SELECT, so aren't taken for its alias
+const CLAUSE_KEYWORDS: [&str; 19] = [
+    "where",
+    "order",
+    "group",
+    "having",
+    "limit",
+    "offset",
+    "window",
+    "union",
+    "intersect",
+    "except",
+    "join",
+    "inner",
+    "left",
+    "right",
+    "full",
+    "cross",
+    "natural",
+    "on",
+    "using",
+];
+
+/// The alias a table is given in `FROM table AS alias` or `FROM table alias`
+fn table_alias(input: &str) -> IResult<&str, Option<String>> {
+    opt(preceded(
+        (multispace1, opt((tag_no_case("as"), multispace1))),
+        verify(identifier, |alias: &str| {
+            !CLAUSE_KEYWORDS
+                .iter()
+                .any(|keyword| alias.eq_ignore_ascii_case(keyword))
+        }),
+    ))
+    .parse(input)
+}
+
+/// `[INNER] JOIN table [AS alias] ON lhs = rhs`
+fn join_clause(input: &str) -> IResult<&str, Join> {
+    let (input, _) = (
+        multispace1,
+        opt((tag_no_case("inner"), multispace1)),
+        tag_no_case("join"),
+        multispace1,
+    )
+        .parse(input)?;
+    let (input, (schema, table)) = qualified_name(input)?;
+    let (input, alias) = table_alias(input)?;
+    let (input, (_, _, _, lhs, _, _, _, rhs)) = (
+        multispace1,
+        tag_no_case("on"),
+        multispace1,
+        column_name,
+        multispace0,
+        char('='),
+        multispace0,
+        column_name,
+    )
+        .parse(input)?;
+
+    Ok((
+        input,
+        Join {
+            schema,
+            table,
+            alias,
+            on: (lhs, rhs),
+        },
+    ))
+}
+
+/// `WITH [RECURSIVE] name [(columns)] AS [[NOT] MATERIALIZED] (select), ...`
+///
+/// None of the tables may refer to itself, so `RECURSIVE` changes nothing.
+fn with_clause(input: &str) -> IResult<&str, Vec<CommonTable>> {
+    let common_table = map(
+        (
+            identifier,
+            opt(preceded(multispace0, column_names)),
+            (multispace0, tag_no_case("as"), multispace0),
+            opt((
+                opt((tag_no_case("not"), multispace1)),
+                tag_no_case("materialized"),
+                multispace0,
+            )),
+            delimited(
+                (char('('), multispace0),
+                select_statement,
+                (multispace0, char(')')),
+            ),
+        ),
+        |(name, columns, _, _, select)| CommonTable {
+            name,
+            columns: columns.unwrap_or_default(),
+            select: Box::new(select),
+        },
+    );
+
+    delimited(
+        (
+            tag_no_case("with"),
+            multispace1,
+            opt((tag_no_case("recursive"), multispace1)),
+        ),
+        separated_list1(delimited(multispace0, char(','), multispace0), common_table),
+        multispace0,
+    )
+    .parse(input)
+}
+
+pub fn select_statement(input: &str) -> IResult<&str, SelectStatement> {
+    let (input, with) = opt(with_clause).parse(input)?;
+    let with = with.unwrap_or_default();
+    if let Ok((input, rows)) = values_clause(input) {
+        let width = rows.first().map_or(0, Vec::len);
+        return Ok((
+            input,
+            SelectStatement {
+                columns: (1..=width).map(|i| format!("column{i}")).collect(),
+                values: Some(rows),
+                ..SelectStatement::constant(with)
+            },
+        ));
+    }
+
+    let (input, _) = (tag_no_case("select"), multispace0).parse(input)?;
+    let (input, operation) = select_operation(input)?;
+
+    // TODO: Fix this to be a bit cleaner
+    if operation.is_some() {
+        let (input, _) = (multispace0, tag_no_case("from"), multispace0).parse(input)?;
+        let (input, (schema, table)) = qualified_name(input)?;
+        let (input, alias) = table_alias(input)?;
+        let (input, join) = opt(join_clause).parse(input)?;
+        let (input, mut clause) = where_clause(input)?;
+
+        let mut operation = operation;
+        if join.is_none() {
+            let names: Vec<&str> = std::iter::once(table.as_str())
+                .chain(alias.as_deref())
+                .collect();
+            clause.unqualify(&names);
+            if let Some(SelectOperation::Min(column) | SelectOperation::Max(column)) =
+                &mut operation
+            {
+                unqualify(column, &names);
+            }
+        }
+
+        return Ok((
+            input,
+            SelectStatement {
+                with,
+                values: None,
+                operation,
+                columns: Vec::new(),
+                projection: None,
+                schema,
+                table,
+                alias,
+                join,
+                where_clause: clause.conditions,
+                row_filters: clause.row_filters,
+                in_filters: clause.in_filters,
+                column_filters: clause.column_filters,
+                exists_filters: clause.exists_filters,
+                order_by: Vec::new(),
+            },
+        ));
+    }
+
+    let (input, mut expressions) = column_list(input)?;
+    let (input, from) = opt((multispace0, tag_no_case("from"), multispace0)).parse(input)?;
+    if from.is_none() {
+        // Without a table the values are computed once, from no columns at all
+        let (input, _) = opt(char(';')).parse(input)?;
+        let statement = match expressions
+            .iter()
+            .all(|expr| matches!(expr, Expr::Column(_)))
+        {
+            true => SelectStatement {
+                columns: expressions.iter().map(Expr::to_string).collect(),
+                ..SelectStatement::constant(with)
+            },
+            false => SelectStatement {
+                projection: Some(expressions),
+                ..SelectStatement::constant(with)
+            },
+        };
+        return Ok((input, statement));
+    }
+    let (input, (schema, table)) = qualified_name(input)?;
+    let (input, alias) = table_alias(input)?;
+    let (input, join) = opt(join_clause).parse(input)?;
+    let (input, mut clause) = where_clause(input)?;
+    let (input, mut order_by) = order_by_clause(input)?;
+    let (input, _) = opt(char(';')).parse(input)?;
+
+    // Columns of a query over a single table may be qualified with its name
+    if join.is_none() {
+        let names: Vec<&str> = std::iter::once(table.as_str())
+            .chain(alias.as_deref())
+            .collect();
+        clause.unqualify(&names);
+        for expr in expressions.iter_mut() {
+            expr.unqualify(&names);
+        }
+        for term in order_by.iter_mut() {
+            unqualify(&mut term.column, &names);
+        }
+    }
+
+    // Bare columns are read straight from the table, anything else is computed from
+    // the columns it refers to
+    let bare = clause.row_filters.is_empty()
+        && clause.in_filters.is_empty()
+        && clause.column_filters.is_empty()
+        && expressions
+            .iter()
+            .all(|expr| matches!(expr, Expr::Column(_)));
+    let (columns, projection) = if bare {
+        let columns = expressions
+            .into_iter()
+            .map(|expr| match expr {
+                Expr::Column(name) => name,
+                _ => unreachable!("only bare columns are selected"),
+            })
+            .collect();
+        (columns, None)
+    } else {
+        let mut columns: Vec<String> = Vec::new();
+        let referenced = expressions
+            .iter()
+            .flat_map(|expr| expr.columns())
+            .chain(clause.columns());
+        for column in referenced {
+            if !columns.iter().any(|c| c == column) {
+                columns.push(column.to_string());
+            }
+        }
+        (columns, Some(expressions))
+    };
+
+    Ok((
+        input,
+        SelectStatement {
+            with,
+            values: None,
+            operation: None,
+            columns,
+            projection,
+            schema,
+            table,
+            alias,
+            join,
+            where_clause: clause.conditions,
+            row_filters: clause.row_filters,
+            in_filters: clause.in_filters,
+            column_filters: clause.column_filters,
+            exists_filters: clause.exists_filters,
+            order_by,
+        },
+    ))
+}
+
+pub fn statement(input: &str) -> IResult<&str, Statement> {
+    alt((
+        map(select_statement, |select| {
+            Statement::Select(Box::new(select))
+        }),
+        map(preceded(query_plan_keywords, select_statement), |select| {
+            Statement::ExplainQueryPlan(Box::new(select))
+        }),
+        attach_statement,
+        detach_statement,
+        pragma_statement,
+        analyze_statement,
+    ))
+    .parse(input)
+}
+
+/// Parses the whole of `text` as a statement, failing on anything after it rather
+/// than ignoring it
+pub fn parse_statement(text: &str) -> Result<Statement, QueryError> {
+    complete(text, statement(text))
+}
+
+/// Parses the whole of `text` as a SELECT, failing on anything after it rather than
+/// ignoring it
+pub fn parse_select(text: &str) -> Result<SelectStatement, QueryError> {
+    complete(text, select_statement(text))
+}
+
+/// Clauses and operators SQLite has that statements here can't use yet, as the
+/// words they start with
+const UNSUPPORTED_SYNTAX: &[&[&str]] = &[
+    &["group", "by"],
+    &["having"],
+    &["limit"],
+    &["offset"],
+    &["window"],
+    &["union"],
+    &["intersect"],
+    &["except"],
+    &["left", "join"],
+    &["left", "outer"],
+    &["right", "join"],
+    &["right", "outer"],
+    &["full", "join"],
+    &["full", "outer"],
+    &["cross", "join"],
+    &["natural"],
+    &["between"],
+    &["case"],
+    &["or"],
+    &["not"],
+    &["is"],
+];
+
+/// Checks the parser got to the end of `text`, apart from a closing `;` and any
+/// comments, telling clauses it doesn't support yet apart from plain mistakes
+fn complete<T>(text: &str, parsed: IResult<&str, T>) -> Result<T, QueryError> {
+    let (rest, parsed) = parsed.map_err(|e| QueryError::parse(text, e))?;
+    let rest = skip_comments(rest);
+    let rest = skip_comments(rest.strip_prefix(';').unwrap_or(rest));
+    if rest.is_empty() {
+        return Ok(parsed);
+    }
+
+    let words = words(rest);
+    for (i, _) in words.iter().enumerate() {
+        let unsupported = UNSUPPORTED_SYNTAX.iter().find(|syntax| {
+            words[i..].len() >= syntax.len()
+                && syntax
+                    .iter()
+                    .zip(&words[i..])
+                    .all(|(keyword, word)| keyword.eq_ignore_ascii_case(word))
+        });
+        if let Some(syntax) = unsupported {
+            return Err(QueryError::Unsupported(syntax.join(" ").to_uppercase()));
+        }
+    }
+
+    Err(QueryError::parse(text, format_args!("unexpected '{rest}'")))
+}
+
+/// `input` past any whitespace and `--` or `/* */` comments
+fn skip_comments(mut input: &str) -> &str {
+    loop {
+        input = input.trim_start();
+        if let Some(comment) = input.strip_prefix("--") {
+            input = comment.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(comment) = input.strip_prefix("/*") {
+            input = comment.split_once("*/").map_or("", |(_, rest)| rest);
+        } else {
+            return input;
+        }
+    }
+}
+
+/// The words of `input`, leaving out those inside quotes
+fn words(input: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut quote = None;
+    let mut start = None;
+    for (i, c) in input.char_indices() {
+        if let Some(close) = quote {
+            if c == close {
+                quote = None;
+            }
+            continue;
+        }
+
+        if c.is_alphanumeric() || c == '_' {
+            start.get_or_insert(i);
+            continue;
+        }
+        if let Some(start) = start.take() {
+            words.push(&input[start..i]);
+        }
+        quote = match c {
+            '\'' | '"' | '`' => Some(c),
+            '[' => Some(']'),
+            _ => None,
+        };
+    }
+    words.extend(start.map(|start| &input[start..]));
+
+    words
+}
+
+/// Splits a query wrapped in `format(mode, query)` into the output mode to print its
+/// rows in and the query itself
+pub fn format_modifier(input: &str) -> Option<(&str, &str)> {
+    let input = input.trim();
+    if !input.get(..6)?.eq_ignore_ascii_case("format") {
+        return None;
+    }
+
+    let arguments = input[6..]
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')?;
+    let (mode, query) = arguments.split_once(',')?;
+    Some((mode.trim(), query.trim()))
+}
+
+fn query_plan_keywords(input: &str) -> IResult<&str, ()> {
+    let (input, _) = (
+        tag_no_case("explain"),
+        multispace1,
+        tag_no_case("query"),
+        multispace1,
+        tag_no_case("plan"),
+        multispace1,
+    )
+        .parse(input)?;
+    Ok((input, ()))
+}
+
+/// Whether a statement is `EXPLAIN QUERY PLAN`, whose rows are printed as a tree
+/// rather than in the output mode
+pub fn explains_query_plan(input: &str) -> bool {
+    query_plan_keywords(input.trim_start()).is_ok()
+}
+
+/// Keywords starting the statements that change the database or its schema
+const WRITE_KEYWORDS: &[&str] = &[
+    "alter", "create", "delete", "drop", "insert", "reindex", "replace", "update", "vacuum",
+];
+
+/// The keyword starting a statement that would change the database, such as
+/// `INSERT` or `CREATE`, or `None` for a statement that only reads it
+pub fn write_keyword(input: &str) -> Option<&str> {
+    let input = input.trim_start();
+    let end = input
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(input.len());
+    let word = &input[..end];
+
+    WRITE_KEYWORDS
+        .iter()
+        .any(|keyword| keyword.eq_ignore_ascii_case(word))
+        .then_some(word)
+}
+
+fn attach_statement(input: &str) -> IResult<&str, Statement> {
+    let (input, (_, _, _, path, _, _, _, name, _, _)) = (
+        tag_no_case("attach"),
+        multispace1,
+        opt((tag_no_case("database"), multispace1)),
+        string_literal,
+        multispace1,
+        tag_no_case("as"),
+        multispace1,
+        identifier,
+        multispace0,
+        opt(char(';')),
+    )
+        .parse(input)?;
+
+    Ok((input, Statement::Attach { path, name }))
+}
+
+fn detach_statement(input: &str) -> IResult<&str, Statement> {
+    let (input, (_, _, _, name, _, _)) = (
+        tag_no_case("detach"),
+        multispace1,
+        opt((tag_no_case("database"), multispace1)),
+        identifier,
+        multispace0,
+        opt(char(';')),
+    )
+        .parse(input)?;
+
+    Ok((input, Statement::Detach(name)))
+}
+
+fn pragma_statement(input: &str) -> IResult<&str, Statement> {
+    let (input, (_, _, (schema, name), argument, _, _)) = (
+        tag_no_case("pragma"),
+        multispace1,
+        qualified_name,
+        opt(delimited(
+            (multispace0, char('('), multispace0),
+            created_name,
+            (multispace0, char(')')),
+        )),
+        multispace0,
+        opt(char(';')),
+    )
+        .parse(input)?;
+
+    Ok((
+        input,
+        Statement::Pragma {
+            schema,
+            name,
+            argument,
+        },
+    ))
+}
+
+fn analyze_statement(input: &str) -> IResult<&str, Statement> {
+    let (input, (_, name, _, _)) = (
+        tag_no_case("analyze"),
+        opt(preceded(multispace1, qualified_name)),
+        multispace0,
+        opt(char(';')),
+    )
+        .parse(input)?;
+
+    let (schema, target) = match name {
+        Some((schema, target)) => (schema, Some(target)),
+        None => (None, None),
+    };
+
+    Ok((input, Statement::Analyze { schema, target }))
+}
+
+/// Splits a string of SQL into its statements on the semicolons outside of quotes
+pub fn split_statements(input: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, ';') => {
+                statements.push(&input[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&input[start..]);
+
+    statements
+        .into_iter()
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+pub fn create_statement(input: &str) -> IResult<&str, CreateStatement> {
+    alt((
+        create_index_statement,
+        create_table_statement,
+        create_virtual_table_statement,
+        create_view_statement,
+    ))
+    .parse(input)
+}
+
+fn create_index_statement(input: &str) -> IResult<&str, CreateStatement> {
+    let (input, (_, _, unique, _, _, index_name, _, _, _, table_name, _)) = (
+        tag_no_case("create"),
+        multispace1,
+        opt((tag_no_case("unique"), multispace1)),
+        tag_no_case("index"),
+        multispace1,
+        identifier,
+        multispace1,
+        tag_no_case("on"),
+        multispace1,
+        identifier,
+        multispace0,
+    )
+        .parse(input)?;
+
+    let (input, columns) = delimited(
+        (char('('), multispace0),
+        separated_list1(
+            delimited(multispace0, char(','), multispace0),
+            indexed_column,
+        ),
+        (multispace0, char(')')),
+    )
+    .parse(input)?;
+    let (input, where_clause) = opt(preceded(
+        (multispace1, tag_no_case("where"), multispace1),
+        rest,
+    ))
+    .parse(input)?;
+
+    let where_clause = where_clause.map(|sql| {
+        let sql = sql.trim().trim_end_matches(';').trim_end();
+        let expr = match boolean_expression(sql) {
+            Ok((rest, expr)) if rest.trim().is_empty() => Some(expr),
+            _ => None,
+        };
+
+        IndexFilter {
+            sql: sql.to_string(),
+            expr,
+        }
+    });
+
+    Ok((
+        input,
+        CreateStatement::Index(CreateIndex {
+            name: index_name,
+            table: table_name,
+            columns,
+            unique: unique.is_some(),
+            where_clause,
+        }),
+    ))
+}
+
+/// A table name in a CREATE statement, which SQLite also accepts in single quotes
+fn created_name(input: &str) -> IResult<&str, String> {
+    alt((identifier, string_literal)).parse(input)
+}
+
+/// Text up to the parenthesis closing one already consumed, keeping any nested
+/// parentheses and quoted text
+fn parenthesized(input: &str) -> IResult<&str, &str> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') if depth == 0 => return Ok((&input[i..], &input[..i])),
+            (None, ')') => depth -= 1,
+            _ => {}
+        }
+    }
+
+    Err(nom::Err::Error(nom::error::Error::new(
+        input,
+        nom::error::ErrorKind::Char,
+    )))
+}
+
+/// A constraint on the table as a whole, giving the foreign key or CHECK it
+/// declares while others such as `PRIMARY KEY (a, b)` are skipped over
+fn table_constraint(input: &str) -> IResult<&str, Option<TableConstraint>> {
+    let (input, name) = opt((constraint_name, multispace1)).parse(input)?;
+    if let Ok((input, check)) = check_constraint(input) {
+        let name = name.map(|(name, _)| name);
+        return Ok((input, Some(TableConstraint::Check(Check { name, ..check }))));
+    }
+
+    alt((
+        map(table_foreign_key, |key| {
+            Some(TableConstraint::ForeignKey(key))
+        }),
+        map(table_unique_key, |key| Some(TableConstraint::Unique(key))),
+    ))
+    .parse(input)
+}
+
+/// `FOREIGN KEY (columns) REFERENCES parent(columns)`
+fn table_foreign_key(input: &str) -> IResult<&str, ForeignKey> {
+    let (input, (_, _, _, _, columns, _, foreign_key)) = (
+        tag_no_case("foreign"),
+        multispace1,
+        tag_no_case("key"),
+        multispace0,
+        column_names,
+        multispace0,
+        references_clause,
+    )
+        .parse(input)?;
+
+    Ok((
+        input,
+        ForeignKey {
+            columns,
+            ..foreign_key
+        },
+    ))
+}
+
+/// `PRIMARY KEY (columns)` or `UNIQUE (columns)`
+fn table_unique_key(input: &str) -> IResult<&str, UniqueKey> {
+    let (input, primary_key) = alt((
+        value(
+            true,
+            (tag_no_case("primary"), multispace1, tag_no_case("key")),
+        ),
+        value(false, tag_no_case("unique")),
+    ))
+    .parse(input)?;
+    let (input, columns) = delimited(
+        (multispace0, char('('), multispace0),
+        separated_list1(
+            delimited(multispace0, char(','), multispace0),
+            indexed_column,
+        ),
+        (multispace0, char(')')),
+    )
+    .parse(input)?;
+
+    // Whatever follows the columns, such as an ON CONFLICT clause
+    let (input, _) = take_while(|c: char| c != ',' && c != ')').parse(input)?;
+    Ok((
+        input,
+        UniqueKey {
+            columns,
+            primary_key,
+        },
+    ))
+}
+
+fn create_table_statement(input: &str) -> IResult<&str, CreateStatement> {
+    let (input, (_, _, table_name, _)) = (
+        tag_no_case("create table"),
+        multispace0,
+        created_name,
+        multispace0,
+    )
+        .parse(input)?;
+
+    let separator = || delimited(multispace0, char(','), multispace0);
+    let (input, (_, column_definitions, table_constraints, _, _)) = (
+        char('('),
+        separated_list1(separator(), column_definition),
+        many0(preceded(separator(), table_constraint)),
+        multispace0,
+        char(')'),
+    )
+        .parse(input)?;
+
+    let (mut columns, column_constraints): (Vec<ColumnDefinition>, Vec<_>) =
+        column_definitions.into_iter().unzip();
+    let (input, without_rowid) = opt((
+        multispace0,
+        tag_no_case("without"),
+        multispace1,
+        tag_no_case("rowid"),
+    ))
+    .parse(input)?;
+
+    let mut foreign_keys = Vec::new();
+    let mut checks = Vec::new();
+    let mut unique_keys = Vec::new();
+    let column_constraints = column_constraints.into_iter().flatten();
+    let table_constraints = table_constraints.into_iter().flatten();
+    for (on_table, constraint) in column_constraints
+        .map(|constraint| (false, constraint))
+        .chain(table_constraints.map(|constraint| (true, constraint)))
+    {
+        match constraint {
+            TableConstraint::ForeignKey(key) => foreign_keys.push(key),
+            TableConstraint::Check(check) => checks.push(check),
+            TableConstraint::Unique(mut key) => {
+                for key_column in key.columns.iter_mut() {
+                    let column = columns
+                        .iter()
+                        .find(|c| same_name(&c.name, &key_column.name));
+                    // Columns of a table constraint compare with the column's
+                    // collation unless given one of their own
+                    if let Some(column) = column.filter(|_| key_column.collation.is_none()) {
+                        key_column.collation = Some(column.collation.clone());
+                    }
+                }
+
+                // A table's primary key on a single INTEGER column is its rowid alias
+                // just as when declared on the column itself
+                let on_rowid_table = on_table && without_rowid.is_none();
+                if let (true, [key_column]) =
+                    (key.primary_key && on_rowid_table, key.columns.as_slice())
+                {
+                    let column = columns
+                        .iter_mut()
+                        .find(|c| same_name(&c.name, &key_column.name));
+                    if let Some(column) =
+                        column.filter(|c| c.datatype.eq_ignore_ascii_case("integer"))
+                    {
+                        column.constraints.push("primary key".to_string());
+                        continue;
+                    }
+                }
+
+                unique_keys.push(key);
+            }
+        }
+    }
+
+    Ok((
+        input,
+        CreateStatement::Table(CreateTable {
+            name: table_name,
+            columns,
+            without_rowid: without_rowid.is_some(),
+            foreign_keys,
+            checks,
+            unique_keys,
+        }),
+    ))
+}
+
+fn create_virtual_table_statement(input: &str) -> IResult<&str, CreateStatement> {
+    let (input, (_, _, _, _, _, _, name, _, _, _, module, _)) = (
+        tag_no_case("create"),
+        multispace1,
+        tag_no_case("virtual"),
+        multispace1,
+        tag_no_case("table"),
+        multispace1,
+        created_name,
+        multispace1,
+        tag_no_case("using"),
+        multispace1,
+        identifier,
+        multispace0,
+    )
+        .parse(input)?;
+
+    let (input, arguments) = opt(delimited(char('('), parenthesized, char(')'))).parse(input)?;
+
+    Ok((
+        input,
+        CreateStatement::VirtualTable(CreateVirtualTable {
+            name,
+            module,
+            arguments: arguments.map(split_arguments).unwrap_or_default(),
         }),
     ))
 }
+
+fn create_view_statement(input: &str) -> IResult<&str, CreateStatement> {
+    let (input, (_, _, _, _, _, name, _, columns, _, _, select)) = (
+        tag_no_case("create"),
+        multispace1,
+        tag_no_case("view"),
+        multispace1,
+        opt((tag_no_case("if not exists"), multispace1)),
+        created_name,
+        multispace0,
+        opt(terminated(column_names, multispace0)),
+        tag_no_case("as"),
+        multispace1,
+        rest,
+    )
+        .parse(input)?;
+
+    Ok((
+        input,
+        CreateStatement::View(CreateView {
+            name,
+            columns,
+            select: select.trim().trim_end_matches(';').trim_end().to_string(),
+        }),
+    ))
+}
+
+/// Splits module arguments on the commas outside of quotes and parentheses
+fn split_arguments(input: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                arguments.push(input[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    arguments.push(input[start..].trim().to_string());
+
+    arguments.retain(|argument| !argument.is_empty());
+    arguments
+}
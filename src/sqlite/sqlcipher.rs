@@ -0,0 +1,107 @@
+use aes::cipher::{block_padding::NoPadding, BlockDecryptMut, KeyIvInit};
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use super::{
+    pager::{PageCodec, HEADER_MAGIC},
+    SqliteReader,
+};
+
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+type HmacSha512 = Hmac<Sha512>;
+
+/// SQLCipher 4's default settings, which databases created by it use unless told
+/// otherwise with `PRAGMA cipher_*`
+pub const PAGE_SIZE: usize = 4096;
+pub const KDF_ITERATIONS: u32 = 256_000;
+
+/// Bytes at the start of the file holding the salt instead of the header magic
+const SALT_SIZE: usize = 16;
+const KEY_SIZE: usize = 32;
+const IV_SIZE: usize = 16;
+const HMAC_SIZE: usize = 64;
+/// The IV and HMAC kept at the end of every page, rounded up to a whole AES block
+const RESERVED_SIZE: usize = (IV_SIZE + HMAC_SIZE).div_ceil(16) * 16;
+
+/// Decrypts the pages of a database encrypted by SQLCipher 4 with its default
+/// settings: AES-256 in CBC mode with a key derived by PBKDF2-HMAC-SHA512, and an
+/// HMAC-SHA512 checked on every page before it is decrypted
+pub struct SqlCipher {
+    key: [u8; KEY_SIZE],
+    hmac_key: [u8; KEY_SIZE],
+}
+
+impl SqlCipher {
+    /// Derives the keys for a passphrase, using the salt the database file starts with
+    pub fn new(passphrase: &[u8], salt: &[u8; SALT_SIZE]) -> Self {
+        Self::with_iterations(passphrase, salt, KDF_ITERATIONS)
+    }
+
+    /// Derives the keys for a database created with `PRAGMA kdf_iter` set
+    pub fn with_iterations(passphrase: &[u8], salt: &[u8; SALT_SIZE], iterations: u32) -> Self {
+        let mut key = [0; KEY_SIZE];
+        pbkdf2::pbkdf2_hmac::<Sha512>(passphrase, salt, iterations, &mut key);
+
+        let hmac_salt = salt.map(|byte| byte ^ 0x3a);
+        let mut hmac_key = [0; KEY_SIZE];
+        pbkdf2::pbkdf2_hmac::<Sha512>(&key, &hmac_salt, 2, &mut hmac_key);
+
+        Self { key, hmac_key }
+    }
+}
+
+impl PageCodec for SqlCipher {
+    fn decode(&self, n: usize, page: &mut [u8]) -> io::Result<()> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        // The salt takes the place of the header magic on the first page and isn't
+        // encrypted
+        let start = if n == 0 { SALT_SIZE } else { 0 };
+        let end = page
+            .len()
+            .checked_sub(RESERVED_SIZE)
+            .filter(|end| *end > start)
+            .ok_or_else(|| invalid(format!("page size {} is too small", page.len())))?;
+
+        let mut mac = HmacSha512::new_from_slice(&self.hmac_key).expect("HMAC takes any key size");
+        mac.update(&page[start..end + IV_SIZE]);
+        mac.update(&(n as u32 + 1).to_le_bytes());
+        mac.verify_slice(&page[end + IV_SIZE..end + IV_SIZE + HMAC_SIZE])
+            .map_err(|_| {
+                invalid(format!(
+                    "page {} failed its integrity check, the key may be wrong",
+                    n + 1
+                ))
+            })?;
+
+        let (data, reserved) = page.split_at_mut(end);
+        Aes256CbcDec::new(&self.key.into(), reserved[..IV_SIZE].into())
+            .decrypt_padded_mut::<NoPadding>(&mut data[start..])
+            .map_err(|_| invalid(format!("page {} isn't a whole number of blocks", n + 1)))?;
+
+        if n == 0 {
+            page[..SALT_SIZE].copy_from_slice(HEADER_MAGIC);
+        }
+
+        Ok(())
+    }
+}
+
+impl SqliteReader {
+    /// Opens a database encrypted by SQLCipher 4 with its default settings
+    pub fn open_sqlcipher(path: impl AsRef<Path>, passphrase: &[u8]) -> Result<Self> {
+        let path = path.as_ref();
+        let mut salt = [0; SALT_SIZE];
+        File::open(path)?
+            .read_exact(&mut salt)
+            .with_context(|| format!("reading the salt of {}", path.display()))?;
+
+        SqliteReader::with_codec(path, PAGE_SIZE, SqlCipher::new(passphrase, &salt))
+    }
+}
@@ -0,0 +1,93 @@
+use super::cell::{LeafCell, RecordValue};
+use std::collections::HashMap;
+
+/// Table `ANALYZE` writes its per-index statistics into
+pub const STAT1_TABLE: &str = "sqlite_stat1";
+
+/// A row of `sqlite_stat1`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stat1Row {
+    pub table: String,
+    /// The index the row describes, or `None` for the row counting a table without
+    /// any indexes
+    pub index: Option<String>,
+    /// The rows in the index followed by the average rows sharing each prefix of its
+    /// key, as in `"100 15 5"`
+    pub stat: String,
+}
+
+impl Stat1Row {
+    /// Reads a row of `sqlite_stat1`, skipping those that aren't laid out as SQLite
+    /// writes them
+    pub fn new(row: &LeafCell) -> Option<Self> {
+        let [RecordValue::String(table), index, RecordValue::String(stat)] = &row.payload[..]
+        else {
+            return None;
+        };
+
+        Some(Self {
+            table: table.clone(),
+            index: match index {
+                RecordValue::String(index) => Some(index.clone()),
+                _ => None,
+            },
+            stat: stat.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexStat {
+    /// Approximate number of rows in the indexed table
+    pub rows: u64,
+    /// Average number of rows matching an equality on the first N index columns
+    pub avg_eq: Vec<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct SqliteStats {
+    tables: HashMap<String, u64>,
+    indexes: HashMap<String, IndexStat>,
+}
+
+impl SqliteStats {
+    pub fn new(rows: &[Stat1Row]) -> Self {
+        let mut stats = Self::default();
+        for Stat1Row { table, index, stat } in rows.iter() {
+            // Trailing options such as `unordered` or `sz=N` carry no estimates
+            let mut estimates = stat
+                .split_whitespace()
+                .map_while(|value| value.parse::<u64>().ok());
+
+            let Some(table_rows) = estimates.next() else {
+                continue;
+            };
+
+            match index {
+                Some(index) if index != table => {
+                    stats.tables.entry(table.clone()).or_insert(table_rows);
+                    stats.indexes.insert(
+                        index.clone(),
+                        IndexStat {
+                            rows: table_rows,
+                            avg_eq: estimates.collect(),
+                        },
+                    );
+                }
+                _ => {
+                    stats.tables.insert(table.clone(), table_rows);
+                }
+            }
+        }
+
+        stats
+    }
+
+    pub fn table_rows(&self, table: &str) -> Option<u64> {
+        self.tables.get(table).copied()
+    }
+
+    pub fn index(&self, index: &str) -> Option<&IndexStat> {
+        self.indexes.get(index)
+    }
+}
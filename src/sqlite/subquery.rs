@@ -0,0 +1,430 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use tracing::debug;
+
+use super::{
+    affinity::Affinity,
+    cell::RecordValue,
+    collation::Collation,
+    function,
+    membership::{InSet, Strategy},
+    pager::Pager,
+    sql::{self, Condition, Expr, InSource, Operator, SelectStatement},
+    vtab::VirtualTable,
+    SqliteReader,
+};
+
+/// A subquery comparing its columns with those of the row of the query it's part of,
+/// as in `EXISTS (SELECT 1 FROM b WHERE b.a_id = a.id)`
+pub struct Correlated {
+    /// The subquery without its comparisons with the outer row
+    template: SelectStatement,
+    /// Each comparison with the outer row, as the subquery's column, the operator and
+    /// the outer column
+    bindings: Vec<(String, Operator, String, Option<Collation>)>,
+}
+
+impl Correlated {
+    /// Columns of the outer query the subquery compares with
+    pub fn outer_columns(&self) -> impl Iterator<Item = &str> {
+        self.bindings.iter().map(|(_, _, outer, _)| outer.as_str())
+    }
+
+    /// The subquery for one row of the outer query, comparing with the row's values
+    /// rather than its columns, along with those values written out as a key to
+    /// cache the answer by
+    pub fn bind(
+        &self,
+        column: &dyn Fn(&str) -> Result<RecordValue>,
+    ) -> Result<(Vec<String>, SelectStatement)> {
+        let mut key = Vec::new();
+        let mut statement = self.template.clone();
+        for (inner, operator, outer, collation) in self.bindings.iter() {
+            let value = column(outer)?;
+            key.push(value.to_sql_literal());
+            statement.where_clause.push(Condition {
+                column: inner.clone(),
+                operator: *operator,
+                value,
+                collation: collation.clone(),
+            });
+        }
+
+        Ok((key, statement))
+    }
+}
+
+/// How an `IN` term is tested against each row
+enum InTest {
+    /// Against values gathered before the scan
+    Values(InSet),
+    /// Against a list referring to the columns, evaluated as any expression is
+    PerRow(Vec<Expr>),
+    /// Against the rows of a correlated subquery, run once for each distinct set of
+    /// outer values it compares with
+    Correlated {
+        subquery: Box<Correlated>,
+        collation: Collation,
+        affinity: Option<Affinity>,
+        cache: HashMap<Vec<String>, InSet>,
+    },
+}
+
+/// How an `EXISTS` term is tested against each row
+enum ExistsTest {
+    /// The subquery doesn't depend on the row, so was run once before the scan
+    Known(bool),
+    Correlated {
+        subquery: Box<Correlated>,
+        cache: HashMap<Vec<String>, bool>,
+    },
+}
+
+/// The WHERE terms of a query that are checked once a row has been read and need
+/// more than a literal to compare with: `IN`, `EXISTS` and comparisons between two
+/// columns
+pub struct RowTests<'a> {
+    statement: &'a SelectStatement,
+    in_tests: Vec<InTest>,
+    exists_tests: Vec<ExistsTest>,
+    /// Collation and affinity of each side of each comparison between columns
+    column_tests: Vec<(Collation, [Option<Affinity>; 2])>,
+    /// Columns of the query that correlated subqueries compare with, which each row
+    /// has to be read with
+    pub outer_columns: Vec<String>,
+}
+
+impl<P: Pager> SqliteReader<P> {
+    /// Prepares the tests of a query's WHERE clause that can't be checked by the scan,
+    /// running each subquery that doesn't depend on the row it's tested for
+    ///
+    /// `IN` values are converted to the affinity of a column tested against them and
+    /// compared with its collation, unless the term gives its own.
+    pub fn row_tests<'a>(&self, statement: &'a SelectStatement) -> Result<RowTests<'a>> {
+        let schema = self.schema()?;
+        let table_schema = schema
+            .fetch_table(&statement.table)
+            .filter(|table| table.is_table() && table.virtual_table().is_none())
+            .filter(|_| statement.join.is_none())
+            .map(|table| table.columns());
+
+        let mut outer_columns = Vec::new();
+        let mut in_tests = Vec::new();
+        for filter in statement.in_filters.iter() {
+            let column = filter.column(table_schema.as_ref());
+            let collation = match (&filter.source, column, &filter.collation) {
+                (InSource::Subquery(subquery), None, None) => self.selected_collation(subquery),
+                _ => filter.collation(table_schema.as_ref()),
+            };
+            let strategy = Strategy::of(&filter.source, &collation);
+            let affinity = column.map(|column| column.affinity());
+
+            let values = match &filter.source {
+                InSource::List(list) if list.iter().any(|item| !item.columns().is_empty()) => {
+                    in_tests.push(InTest::PerRow(list.clone()));
+                    continue;
+                }
+                InSource::List(list) => super::evaluate_constants(list)?,
+                InSource::Subquery(subquery) => {
+                    if let Some(subquery) = self.correlate(statement, subquery)? {
+                        outer_columns.extend(subquery.outer_columns().map(String::from));
+                        in_tests.push(InTest::Correlated {
+                            subquery: Box::new(subquery),
+                            collation,
+                            affinity,
+                            cache: HashMap::new(),
+                        });
+                        continue;
+                    }
+                    self.subquery_values(subquery)?
+                }
+            };
+
+            debug!(expr = %filter.expr, values = values.len(), %strategy, "gathered IN values");
+            in_tests.push(InTest::Values(in_set(
+                values, collation, affinity, strategy,
+            )));
+        }
+
+        let mut exists_tests = Vec::new();
+        for filter in statement.exists_filters.iter() {
+            match self.correlate(statement, &filter.subquery)? {
+                Some(subquery) => {
+                    outer_columns.extend(subquery.outer_columns().map(String::from));
+                    exists_tests.push(ExistsTest::Correlated {
+                        subquery: Box::new(subquery),
+                        cache: HashMap::new(),
+                    });
+                }
+                None => {
+                    let found = !self.execute_select(&filter.subquery)?.rows.is_empty();
+                    exists_tests.push(ExistsTest::Known(found));
+                }
+            }
+        }
+
+        let column_tests = statement
+            .column_filters
+            .iter()
+            .map(|filter| {
+                let definition = |name: &str| {
+                    let table_schema = table_schema.as_ref()?;
+                    table_schema
+                        .columns
+                        .iter()
+                        .find(|column| sql::same_name(&column.name, name))
+                };
+                let (lhs, rhs) = (definition(&filter.column), definition(&filter.other));
+                let collation = filter
+                    .collation
+                    .clone()
+                    .or_else(|| lhs.map(|c| c.collation.clone()))
+                    .filter(|collation| *collation != Collation::Binary)
+                    .or_else(|| rhs.map(|c| c.collation.clone()))
+                    .unwrap_or_default();
+                let affinities = match (lhs, rhs) {
+                    (Some(lhs), Some(rhs)) => [
+                        lhs.affinity().compared_with(rhs.affinity()),
+                        rhs.affinity().compared_with(lhs.affinity()),
+                    ],
+                    _ => [None, None],
+                };
+                (collation, affinities)
+            })
+            .collect();
+
+        Ok(RowTests {
+            statement,
+            in_tests,
+            exists_tests,
+            column_tests,
+            outer_columns,
+        })
+    }
+
+    /// Whether a row read for the query `tests` were prepared for passes them, running
+    /// any correlated subquery for values it hasn't been run for yet
+    pub fn passes(
+        &self,
+        tests: &mut RowTests,
+        column: &dyn Fn(&str) -> Result<RecordValue>,
+    ) -> Result<bool> {
+        let statement = tests.statement;
+        for (filter, (collation, affinities)) in
+            statement.column_filters.iter().zip(&tests.column_tests)
+        {
+            let convert = |value: RecordValue, affinity: &Option<Affinity>| match affinity {
+                Some(affinity) => affinity.apply(&value),
+                None => value,
+            };
+            let lhs = convert(column(&filter.column)?, &affinities[0]);
+            let rhs = convert(column(&filter.other)?, &affinities[1]);
+            if !filter.operator.test(&lhs, &rhs, collation) {
+                return Ok(false);
+            }
+        }
+
+        for (filter, test) in statement.in_filters.iter().zip(tests.in_tests.iter_mut()) {
+            let found = match test {
+                InTest::Values(set) => set.contains(&filter.expr.evaluate(column)?),
+                InTest::PerRow(list) => function::truth(
+                    &Expr::InList {
+                        expr: Box::new(filter.expr.clone()),
+                        list: list.clone(),
+                        negated: false,
+                    }
+                    .evaluate(column)?,
+                ),
+                InTest::Correlated {
+                    subquery,
+                    collation,
+                    affinity,
+                    cache,
+                } => {
+                    let (key, bound) = subquery.bind(column)?;
+                    if !cache.contains_key(&key) {
+                        let values = self.subquery_values(&bound)?;
+                        let strategy = Strategy::of(&filter.source, collation);
+                        let set = in_set(values, collation.clone(), *affinity, strategy);
+                        cache.insert(key.clone(), set);
+                    }
+                    cache[&key].contains(&filter.expr.evaluate(column)?)
+                }
+            };
+            if found.map(|found| found != filter.negated) != Some(true) {
+                return Ok(false);
+            }
+        }
+
+        for (filter, test) in statement
+            .exists_filters
+            .iter()
+            .zip(tests.exists_tests.iter_mut())
+        {
+            let found = match test {
+                ExistsTest::Known(found) => *found,
+                ExistsTest::Correlated { subquery, cache } => {
+                    let (key, bound) = subquery.bind(column)?;
+                    match cache.get(&key) {
+                        Some(found) => *found,
+                        None => {
+                            let found = !self.execute_select(&bound)?.rows.is_empty();
+                            cache.insert(key, found);
+                            found
+                        }
+                    }
+                }
+            };
+            if found == filter.negated {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Splits the comparisons a subquery makes with the columns of the `outer` query
+    /// from the rest, or `None` when it makes none
+    ///
+    /// A column is the outer query's when qualified with its name, or when it isn't
+    /// one of the subquery's own. Only comparisons between two columns can refer to
+    /// the outer query.
+    pub fn correlate(
+        &self,
+        outer: &SelectStatement,
+        subquery: &SelectStatement,
+    ) -> Result<Option<Correlated>> {
+        if outer.join.is_some() || subquery.join.is_some() {
+            return Ok(None);
+        }
+
+        let schema = self.schema()?;
+        let columns = |statement: &SelectStatement| -> Option<Vec<String>> {
+            if let Some(table) = statement
+                .schema
+                .is_none()
+                .then(|| self.common_table(&statement.table))
+                .flatten()
+            {
+                return Some(table.columns());
+            }
+
+            let table = schema
+                .fetch_table(&statement.table)
+                .filter(|table| table.is_table() && table.virtual_table().is_none())?;
+            Some(
+                table
+                    .columns()
+                    .columns
+                    .into_iter()
+                    .map(|c| c.name)
+                    .collect(),
+            )
+        };
+        let (Some(inner_table), Some(outer_table)) = (columns(subquery), columns(outer)) else {
+            return Ok(None);
+        };
+
+        let outer_names: Vec<&str> = std::iter::once(outer.table.as_str())
+            .chain(outer.alias.as_deref())
+            .collect();
+        let outer_column = |reference: &str| match reference.split_once('.') {
+            Some((qualifier, name)) => outer_names
+                .iter()
+                .any(|table| sql::same_name(table, qualifier))
+                .then(|| declared(&outer_table, name))
+                .flatten(),
+            None if declared(&inner_table, reference).is_some() => None,
+            None => declared(&outer_table, reference),
+        };
+
+        let mut template = subquery.clone();
+        let mut bindings = Vec::new();
+        template.column_filters.clear();
+        for filter in subquery.column_filters.iter() {
+            let binding = match (outer_column(&filter.column), outer_column(&filter.other)) {
+                (None, None) => {
+                    template.column_filters.push(filter.clone());
+                    continue;
+                }
+                (None, Some(outer)) => (filter.column.clone(), filter.operator, outer),
+                (Some(outer), None) => (filter.other.clone(), filter.operator.reversed(), outer),
+                (Some(_), Some(_)) => {
+                    bail!("a subquery can only compare the outer query's columns with its own")
+                }
+            };
+            let (inner, operator, outer) = binding;
+            bindings.push((inner, operator, outer, filter.collation.clone()));
+        }
+        if bindings.is_empty() {
+            return Ok(None);
+        }
+
+        template
+            .columns
+            .retain(|column| outer_column(column).is_none());
+        Ok(Some(Correlated { template, bindings }))
+    }
+
+    /// Collation of the column a subquery selects, which values not from a column are
+    /// compared with its rows under
+    fn selected_collation(&self, subquery: &SelectStatement) -> Collation {
+        let Ok(schema) = self.schema() else {
+            return Collation::default();
+        };
+        let column = match (&subquery.projection, subquery.columns.first()) {
+            (None, Some(column)) => Some(column),
+            (Some(projection), _) => match &projection[..] {
+                [Expr::Column(column)] => Some(column),
+                _ => None,
+            },
+            (None, None) => None,
+        };
+        match column {
+            Some(column) => schema
+                .fetch_table(&subquery.table)
+                .filter(|table| table.is_table())
+                .map(|table| table.columns().column_collation(column))
+                .unwrap_or_default(),
+            _ => Collation::default(),
+        }
+    }
+
+    /// The values of a subquery's single column
+    fn subquery_values(&self, subquery: &SelectStatement) -> Result<Vec<RecordValue>> {
+        let result = self.execute_select(subquery)?;
+        if result.columns.len() != 1 {
+            bail!(
+                "sub-select returns {} columns - expected 1",
+                result.columns.len()
+            );
+        }
+
+        Ok(result
+            .rows
+            .into_iter()
+            .map(|mut row| row.swap_remove(0))
+            .collect())
+    }
+}
+
+/// The name a column was given by the table it's one of `columns` of
+fn declared(columns: &[String], name: &str) -> Option<String> {
+    columns
+        .iter()
+        .find(|column| sql::same_name(column, name))
+        .cloned()
+}
+
+fn in_set(
+    values: Vec<RecordValue>,
+    collation: Collation,
+    affinity: Option<Affinity>,
+    strategy: Strategy,
+) -> InSet {
+    let values = match affinity {
+        Some(affinity) => values.iter().map(|value| affinity.apply(value)).collect(),
+        None => values,
+    };
+    InSet::new(values, collation, strategy)
+}
@@ -0,0 +1,291 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock};
+
+use super::{
+    cell::RecordValue,
+    collation::Collation,
+    error::QueryError,
+    result::ResultSet,
+    sql::{same_name, Condition, SelectOperation, SelectStatement, SortOrder},
+};
+
+/// Rows produced by a virtual table, each holding a value for every column
+pub type Rows<'a> = Box<dyn Iterator<Item = Result<Vec<RecordValue>>> + 'a>;
+
+/// A table whose rows come from somewhere other than the database file
+///
+/// Once registered with [`register_virtual_table`] it can be queried with `SELECT`
+/// like any table in the database, though a table in the database with the same
+/// name takes precedence.
+pub trait VirtualTable: Send + Sync {
+    /// Names of the columns, in the order values appear in each row
+    fn columns(&self) -> Vec<String>;
+
+    /// Every row of the table
+    fn rows(&self) -> Result<Rows<'_>>;
+
+    /// The rows that may satisfy a WHERE condition
+    ///
+    /// Tables that can use the condition to skip rows, such as a source that is
+    /// sorted or indexed, override this to do so. The rows returned are still
+    /// checked against the condition so they don't have to all match.
+    fn filter(&self, condition: &Condition) -> Result<Rows<'_>> {
+        let _ = condition;
+        self.rows()
+    }
+}
+
+/// Virtual tables registered through [`register_virtual_table`], keyed by lowercase name
+static VIRTUAL_TABLES: LazyLock<RwLock<HashMap<String, Arc<dyn VirtualTable>>>> =
+    LazyLock::new(Default::default);
+
+/// Registers a virtual table that queries can refer to by `name`, replacing any
+/// previous registration of the name
+pub fn register_virtual_table(name: &str, table: impl VirtualTable + 'static) {
+    VIRTUAL_TABLES
+        .write()
+        .expect("virtual table registry poisoned")
+        .insert(name.to_ascii_lowercase(), Arc::new(table));
+}
+
+pub fn virtual_table(name: &str) -> Option<Arc<dyn VirtualTable>> {
+    VIRTUAL_TABLES
+        .read()
+        .expect("virtual table registry poisoned")
+        .get(&name.to_ascii_lowercase())
+        .cloned()
+}
+
+/// A virtual table over rows held in memory
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTable {
+    columns: Vec<String>,
+    rows: Vec<Vec<RecordValue>>,
+}
+
+impl MemoryTable {
+    pub fn new(columns: Vec<String>, rows: Vec<Vec<RecordValue>>) -> Self {
+        Self { columns, rows }
+    }
+}
+
+impl VirtualTable for MemoryTable {
+    fn columns(&self) -> Vec<String> {
+        self.columns.clone()
+    }
+
+    fn rows(&self) -> Result<Rows<'_>> {
+        Ok(Box::new(self.rows.iter().cloned().map(Ok)))
+    }
+}
+
+/// A virtual table over a CSV file whose first line names the columns
+///
+/// The file is read again on every query. Fields that look like integers or reals
+/// are read as numbers and everything else as text.
+#[derive(Debug, Clone)]
+pub struct CsvTable {
+    path: PathBuf,
+    columns: Vec<String>,
+}
+
+impl CsvTable {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("reading {}", path.display()))?;
+
+        let Some(header) = parse_csv(&contents).next() else {
+            bail!("{} has no header line", path.display());
+        };
+
+        Ok(Self {
+            path,
+            columns: header?,
+        })
+    }
+}
+
+impl VirtualTable for CsvTable {
+    fn columns(&self) -> Vec<String> {
+        self.columns.clone()
+    }
+
+    fn rows(&self) -> Result<Rows<'_>> {
+        let contents = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("reading {}", self.path.display()))?;
+
+        let columns = self.columns.len();
+        let rows: Vec<Result<Vec<RecordValue>>> = parse_csv(&contents)
+            .skip(1)
+            .map(|record| {
+                let mut values: Vec<RecordValue> =
+                    record?.iter().map(|field| csv_value(field)).collect();
+                values.resize(columns, RecordValue::Null);
+                Ok(values)
+            })
+            .collect();
+
+        Ok(Box::new(rows.into_iter()))
+    }
+}
+
+/// Splits CSV text into records of fields, allowing quoted fields to hold commas,
+/// newlines and doubled quotes
+fn parse_csv(contents: &str) -> impl Iterator<Item = Result<Vec<String>>> + '_ {
+    let mut chars = contents.chars().peekable();
+    let mut line = 1;
+    std::iter::from_fn(move || {
+        chars.peek()?;
+
+        let start = line;
+        let mut record = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' if quoted => quoted = false,
+                '"' if field.is_empty() => quoted = true,
+                ',' if !quoted => record.push(std::mem::take(&mut field)),
+                '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+                '\n' => {
+                    line += 1;
+                    if !quoted {
+                        break;
+                    }
+                    field.push(c);
+                }
+                c => field.push(c),
+            }
+        }
+
+        if quoted {
+            return Some(Err(anyhow!(
+                "unterminated quoted field starting on line {start}"
+            )));
+        }
+
+        record.push(field);
+        Some(Ok(record))
+    })
+}
+
+fn csv_value(field: &str) -> RecordValue {
+    if let Ok(integer) = field.parse::<i64>() {
+        return RecordValue::I64(integer);
+    }
+
+    match field.parse::<f64>() {
+        Ok(real) if real.is_finite() => RecordValue::F64(real),
+        _ => RecordValue::String(field.to_string()),
+    }
+}
+
+/// Runs a SELECT against a virtual table, handing the first of its WHERE conditions
+/// to the table and checking each row against all of them
+pub fn select(table: &dyn VirtualTable, statement: &SelectStatement) -> Result<ResultSet> {
+    let columns = table.columns();
+    let position = |name: &str| {
+        columns
+            .iter()
+            .position(|column| same_name(column, name))
+            .ok_or_else(|| QueryError::NoSuchColumn(name.to_string()).into())
+    };
+
+    let mut conditions = Vec::with_capacity(statement.where_clause.len());
+    for condition in statement.where_clause.iter() {
+        conditions.push((condition, position(&condition.column)?));
+    }
+
+    let rows = match statement.where_clause.first() {
+        Some(condition) => table.filter(condition)?,
+        None => table.rows()?,
+    };
+
+    let mut matching = Vec::new();
+    for row in rows {
+        let row = row?;
+        let matches = conditions.iter().all(|(condition, idx)| {
+            let collation = condition.collation.clone().unwrap_or_default();
+            condition
+                .operator
+                .test(&row[*idx], &condition.value, &collation)
+        });
+
+        if matches {
+            matching.push(row);
+        }
+    }
+
+    match &statement.operation {
+        Some(SelectOperation::Count) => {
+            return Ok(ResultSet::single(
+                "count(*)".to_string(),
+                RecordValue::I64(matching.len() as i64),
+            ));
+        }
+        Some(SelectOperation::Min(column)) | Some(SelectOperation::Max(column)) => {
+            let idx = position(column)?;
+            let values = matching
+                .into_iter()
+                .map(|mut row| row.swap_remove(idx))
+                .filter(|value| *value != RecordValue::Null);
+
+            let binary =
+                |lhs: &RecordValue, rhs: &RecordValue| lhs.sort_cmp(rhs, &Collation::Binary);
+            let (function, value) = match statement.operation {
+                Some(SelectOperation::Min(_)) => ("min", values.min_by(binary)),
+                _ => ("max", values.max_by(binary)),
+            };
+
+            return Ok(ResultSet::single(
+                format!("{function}({column})"),
+                value.unwrap_or(RecordValue::Null),
+            ));
+        }
+        None => {}
+    }
+
+    let mut keys = Vec::with_capacity(statement.order_by.len());
+    for term in statement.order_by.iter() {
+        let collation = term.collation.clone().unwrap_or_default();
+        keys.push((position(&term.column)?, term.order, collation));
+    }
+
+    matching.sort_by(|lhs, rhs| {
+        for (idx, order, collation) in keys.iter() {
+            let ordering = lhs[*idx].sort_cmp(&rhs[*idx], collation);
+            let ordering = match order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            };
+
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        Ordering::Equal
+    });
+
+    let selected = statement
+        .columns
+        .iter()
+        .map(|column| position(column))
+        .collect::<Result<Vec<usize>>>()?;
+
+    let mut result = ResultSet::new(statement.columns.clone());
+    result.rows = matching
+        .into_iter()
+        .map(|row| selected.iter().map(|&idx| row[idx].clone()).collect())
+        .collect();
+
+    Ok(result)
+}
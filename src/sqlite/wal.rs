@@ -0,0 +1,220 @@
+use bytes::Buf;
+use std::{collections::HashMap, io, path::Path};
+
+use super::{
+    pager::Pager,
+    shm::{IndexHeader, WalIndex, SHM_SUFFIX},
+};
+
+/// Appended to the database path to name its write-ahead log
+pub const WAL_SUFFIX: &str = "-wal";
+
+/// Whether the database at `db_path` has a log or wal-index next to it, which a
+/// connection in WAL mode leaves there while it has the database open
+pub fn in_wal_mode(db_path: &Path) -> bool {
+    [WAL_SUFFIX, SHM_SUFFIX].iter().any(|suffix| {
+        let mut path = db_path.as_os_str().to_owned();
+        path.push(suffix);
+        Path::new(&path).exists()
+    })
+}
+
+const WAL_HEADER_SIZE: usize = 32;
+const FRAME_HEADER_SIZE: usize = 24;
+
+/// Magic numbers at the start of a log, the last bit choosing the byte order
+/// checksums are computed in
+const MAGIC_LITTLE_ENDIAN: u32 = 0x377f0682;
+const MAGIC_BIG_ENDIAN: u32 = 0x377f0683;
+
+/// The pages committed to a write-ahead log that have not been checkpointed back
+/// into the database file yet
+#[derive(Debug, Default)]
+pub struct Wal {
+    /// The latest committed copy of each page in the log, counting pages from 0
+    pages: HashMap<usize, Box<[u8]>>,
+    /// Size of the database in pages as of the last commit, or 0 when nothing is
+    /// read from the log
+    page_count: usize,
+    /// The wal-index shared with other connections, held while the pages are read
+    _index: Option<WalIndex>,
+}
+
+impl Wal {
+    /// Reads the log next to the database at `db_path`, if there is one holding
+    /// committed transactions
+    ///
+    /// While other connections have the database open, only the transactions their
+    /// wal-index records as committed are read, and the index is held on to so they
+    /// leave those pages as they are.
+    pub fn open(db_path: &Path, page_size: usize) -> io::Result<Option<Self>> {
+        let index = WalIndex::attach(db_path)?;
+        let snapshot = match &index {
+            Some(WalIndex { snapshot: None, .. }) => {
+                return Ok(Some(Self {
+                    _index: index,
+                    ..Self::default()
+                }))
+            }
+            Some(WalIndex { snapshot, .. }) => snapshot.clone(),
+            None => None,
+        };
+
+        let mut wal_path = db_path.as_os_str().to_owned();
+        wal_path.push(WAL_SUFFIX);
+        let bytes = match std::fs::read(wal_path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let wal = Self::replay(&bytes, page_size, snapshot.as_ref());
+        match (wal, index) {
+            (Some(wal), index) => Ok(Some(Self {
+                _index: index,
+                ..wal
+            })),
+            (None, Some(index)) => Ok(Some(Self {
+                _index: Some(index),
+                ..Self::default()
+            })),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Replays the frames of a log up to its last valid commit
+    ///
+    /// Frames are only valid while their salts match the header and the running
+    /// checksum holds, so whatever is left over from before the log was last reset
+    /// is ignored. Returns `None` when no transaction was committed.
+    pub fn read(bytes: &[u8], page_size: usize) -> Option<Self> {
+        Self::replay(bytes, page_size, None)
+    }
+
+    /// Replays the frames of a log up to the commit `snapshot` names, or its last
+    /// valid commit without one
+    ///
+    /// A snapshot for another log, such as one a writer has since restarted, is
+    /// ignored.
+    fn replay(bytes: &[u8], page_size: usize, snapshot: Option<&IndexHeader>) -> Option<Self> {
+        let mut header = bytes.get(..WAL_HEADER_SIZE)?;
+        let big_endian = match header.get_u32() {
+            MAGIC_LITTLE_ENDIAN => false,
+            MAGIC_BIG_ENDIAN => true,
+            _ => return None,
+        };
+
+        let _version = header.get_u32();
+        if header.get_u32() as usize != page_size {
+            return None;
+        }
+
+        let _checkpoint_sequence = header.get_u32();
+        let salts = (header.get_u32(), header.get_u32());
+        let expected = (header.get_u32(), header.get_u32());
+        let mut sums = checksum(big_endian, &bytes[..WAL_HEADER_SIZE - 8], (0, 0));
+        if sums != expected {
+            return None;
+        }
+
+        let frames = match snapshot {
+            Some(snapshot) if snapshot.salts == salts && snapshot.page_size == page_size => {
+                snapshot.max_frame as usize
+            }
+            _ => usize::MAX,
+        };
+
+        let mut wal = Self::default();
+        let mut uncommitted = Vec::new();
+        for frame in bytes[WAL_HEADER_SIZE..]
+            .chunks_exact(FRAME_HEADER_SIZE + page_size)
+            .take(frames)
+        {
+            let (mut frame_header, page) = frame.split_at(FRAME_HEADER_SIZE);
+            let page_no = frame_header.get_u32() as usize;
+            let commit_size = frame_header.get_u32() as usize;
+            let frame_salts = (frame_header.get_u32(), frame_header.get_u32());
+            let expected = (frame_header.get_u32(), frame_header.get_u32());
+
+            sums = checksum(big_endian, &frame[..8], sums);
+            sums = checksum(big_endian, page, sums);
+            if page_no == 0 || frame_salts != salts || sums != expected {
+                break;
+            }
+
+            uncommitted.push((page_no - 1, page));
+            if commit_size != 0 {
+                wal.pages.extend(
+                    uncommitted
+                        .drain(..)
+                        .map(|(page_no, page)| (page_no, Box::from(page))),
+                );
+                wal.page_count = commit_size;
+            }
+        }
+
+        (wal.page_count != 0).then_some(wal)
+    }
+}
+
+/// The checksum SQLite keeps over the log and the wal-index header, continuing on
+/// from `sums`
+pub(super) fn checksum(big_endian: bool, mut data: &[u8], sums: (u32, u32)) -> (u32, u32) {
+    let (mut s0, mut s1) = sums;
+    while data.remaining() >= 8 {
+        let (x0, x1) = if big_endian {
+            (data.get_u32(), data.get_u32())
+        } else {
+            (data.get_u32_le(), data.get_u32_le())
+        };
+
+        s0 = s0.wrapping_add(x0).wrapping_add(s1);
+        s1 = s1.wrapping_add(x1).wrapping_add(s0);
+    }
+
+    (s0, s1)
+}
+
+/// Pages of the database file overlaid with the newer copies committed to its
+/// write-ahead log, giving the database as SQLite itself would read it
+pub struct WalPager<P: Pager> {
+    base: P,
+    wal: Wal,
+}
+
+impl<P: Pager> WalPager<P> {
+    pub fn new(base: P, wal: Wal) -> Self {
+        Self { base, wal }
+    }
+}
+
+impl<P: Pager> Pager for WalPager<P> {
+    fn page_size(&self) -> usize {
+        self.base.page_size()
+    }
+
+    fn page_count(&self) -> usize {
+        match self.wal.page_count {
+            0 => self.base.page_count(),
+            pages => pages,
+        }
+    }
+
+    fn get_page(&self, n: usize) -> io::Result<&[u8]> {
+        if n >= self.page_count() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("page {} is past the end of the database", n + 1),
+            ));
+        }
+
+        match self.wal.pages.get(&n) {
+            Some(page) => Ok(page),
+            None => self.base.get_page(n),
+        }
+    }
+
+    fn shrink_cache(&mut self, pages: usize) {
+        self.base.shrink_cache(pages)
+    }
+}
@@ -0,0 +1,591 @@
+//! Uses the library API on databases `sqlite3` builds, checking what each part of
+//! it answers against what `sqlite3` says of the same data
+//!
+//! The tests are skipped when `sqlite3` isn't installed, and another binary can be
+//! used by setting `SQLITE3`.
+
+#[macro_use]
+mod common;
+
+use codecrafters_sqlite::sqlite::{
+    cell::RecordValue,
+    database::Database,
+    interrupt::Interrupted,
+    map::PageKind,
+    options::OpenOptions,
+    page::BTreePageType,
+    pager::{PageCodec, Pager, WindowedPager},
+    vtab::{self, CsvTable, MemoryTable},
+    SqliteReader,
+};
+use common::{build, run_sqlite3, TempFile};
+use std::{
+    fs::{self, File},
+    io,
+    ops::ControlFlow,
+    path::Path,
+    process::Command,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// The lines the connection prints for `sql`, in the same list mode as `sqlite3`
+fn query(database: &mut Database, sql: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    database
+        .execute_to(sql, &mut out)
+        .unwrap_or_else(|e| panic!("{sql} failed: {e:#}"));
+
+    String::from_utf8(out)
+        .expect("the output is UTF-8")
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn virtual_tables_answer_like_tables() {
+    let sqlite3 = require_sqlite3!("API");
+
+    // Fields with commas, quotes and line breaks, and one left empty
+    let csv = TempFile::new("people.csv");
+    fs::write(
+        csv.path(),
+        "name,age,score\r\n\
+         alice,30,1.5\r\n\
+         \"smith, bob\",41,2.25\r\n\
+         \"carol \"\"cc\"\"\",25,\r\n\
+         \"dave\nsecond line\",41,-0.5\r\n\
+         erin,19,10.75\r\n",
+    )
+    .expect("writing the CSV file");
+
+    // Columns with numeric affinity read the fields as the CSV table does
+    let expected = build(
+        &sqlite3,
+        "virtual_tables",
+        "CREATE TABLE people (name NUMERIC, age NUMERIC, score NUMERIC);
+         CREATE TABLE points (x, y, label);
+         INSERT INTO points VALUES (1, 2.5, 'a'), (3, NULL, 'b'), (-2, 7.0, x'00ff'), (3, 0.5, NULL);",
+    );
+    run_sqlite3(
+        &sqlite3,
+        expected.path(),
+        &[&format!(
+            ".import --csv --skip 1 {} people",
+            csv.path().display()
+        )],
+    );
+
+    vtab::register_virtual_table(
+        "people",
+        CsvTable::open(csv.path()).expect("opening the CSV file"),
+    );
+    vtab::register_virtual_table(
+        "points",
+        MemoryTable::new(
+            vec!["x".to_string(), "y".to_string(), "label".to_string()],
+            vec![
+                vec![
+                    RecordValue::I64(1),
+                    RecordValue::F64(2.5),
+                    RecordValue::String("a".to_string()),
+                ],
+                vec![
+                    RecordValue::I64(3),
+                    RecordValue::Null,
+                    RecordValue::String("b".to_string()),
+                ],
+                vec![
+                    RecordValue::I64(-2),
+                    RecordValue::F64(7.0),
+                    RecordValue::Blob(vec![0x00, 0xff]),
+                ],
+                vec![
+                    RecordValue::I64(3),
+                    RecordValue::F64(0.5),
+                    RecordValue::Null,
+                ],
+            ],
+        ),
+    );
+
+    // The tables are only registered, so the database queried holds neither
+    let empty = build(&sqlite3, "virtual_tables_empty", "CREATE TABLE other (a);");
+    let mut database = Database::open(empty.path()).expect("opening the database");
+    for sql in [
+        "SELECT name, age, score FROM people",
+        "SELECT name, score FROM people WHERE age = 41 ORDER BY score",
+        "SELECT name FROM people WHERE age > 20 ORDER BY age DESC, name",
+        "SELECT count(*) FROM people WHERE score > 1",
+        "SELECT max(score) FROM people",
+        "SELECT min(name) FROM people",
+        "SELECT x, y, hex(label) FROM points ORDER BY x, y",
+        "SELECT label FROM points WHERE x = 3 ORDER BY y DESC",
+        "SELECT count(*) FROM points WHERE y < 3",
+        "SELECT min(y) FROM points",
+        "SELECT max(x) FROM points",
+    ] {
+        assert_eq!(
+            query(&mut database, sql),
+            run_sqlite3(&sqlite3, expected.path(), &[sql]),
+            "{sql}"
+        );
+    }
+}
+
+#[test]
+fn backup_copies_the_log_into_a_complete_database() {
+    let sqlite3 = require_sqlite3!("API");
+
+    // Closing without a checkpoint leaves the last transaction in the log alone
+    let source = TempFile::new("backup_source.db");
+    run_sqlite3(
+        &sqlite3,
+        source.path(),
+        &[
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+             WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 500)
+             INSERT INTO t SELECT i, 'row ' || i FROM seq;",
+            ".dbconfig no_ckpt_on_close on",
+            "UPDATE t SET label = 'changed' WHERE id % 50 = 0;
+             CREATE INDEX tl ON t (label);",
+        ],
+    );
+    assert!(
+        Path::new(&format!("{}-wal", source.path().display())).exists(),
+        "the last transaction is only in the log"
+    );
+
+    let copy = TempFile::new("backup_copy.db");
+    let mut progress = Vec::new();
+    Database::open(source.path())
+        .expect("opening the database")
+        .backup_to(copy.path(), |copied, total| progress.push((copied, total)))
+        .expect("backing up the database");
+
+    let pages = run_sqlite3(&sqlite3, copy.path(), &["PRAGMA page_count"]);
+    let total = progress.len();
+    assert_eq!(pages, [total.to_string()]);
+    assert_eq!(
+        progress,
+        (1..=total)
+            .map(|copied| (copied, total))
+            .collect::<Vec<_>>()
+    );
+
+    assert_eq!(
+        run_sqlite3(&sqlite3, copy.path(), &["PRAGMA integrity_check"]),
+        ["ok"]
+    );
+    for sql in [
+        "SELECT type, name, sql FROM sqlite_schema ORDER BY name",
+        "SELECT id, label FROM t ORDER BY id",
+        "SELECT count(*) FROM t WHERE label = 'changed'",
+    ] {
+        assert_eq!(
+            run_sqlite3(&sqlite3, copy.path(), &[sql]),
+            run_sqlite3(&sqlite3, source.path(), &[sql]),
+            "{sql}"
+        );
+    }
+}
+
+/// Scrambles each byte with the number of the page holding it and its offset
+struct Scrambled;
+
+impl Scrambled {
+    fn mask(n: usize, offset: usize) -> u8 {
+        (n * 31 + offset * 7) as u8 ^ 0x5a
+    }
+}
+
+impl PageCodec for Scrambled {
+    fn decode(&self, n: usize, page: &mut [u8]) -> io::Result<()> {
+        for (offset, byte) in page.iter_mut().enumerate() {
+            *byte ^= Self::mask(n, offset);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn codec_decodes_every_page_read() {
+    let sqlite3 = require_sqlite3!("API");
+
+    let plain = build(
+        &sqlite3,
+        "codec",
+        "PRAGMA page_size = 1024;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT, body BLOB);
+         CREATE INDEX tl ON t (label);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 300)
+         INSERT INTO t SELECT i, 'row ' || i, zeroblob(i * 7) FROM seq;",
+    );
+    let page_size = 1024;
+    let mut contents = fs::read(plain.path()).expect("reading the fixture");
+    for (n, page) in contents.chunks_mut(page_size).enumerate() {
+        for (offset, byte) in page.iter_mut().enumerate() {
+            *byte ^= Scrambled::mask(n, offset);
+        }
+    }
+    let scrambled = TempFile::new("codec_scrambled.db");
+    fs::write(scrambled.path(), contents).expect("writing the scrambled database");
+
+    // The first page is decoded before the header in it says how big pages are
+    let reader = SqliteReader::with_codec(scrambled.path(), page_size, Scrambled)
+        .expect("opening the scrambled database");
+    let mut database = Database::new(reader);
+    for sql in [
+        "SELECT id, label, length(body) FROM t ORDER BY id",
+        "SELECT id FROM t WHERE label = 'row 250'",
+        "SELECT count(*) FROM t",
+        "SELECT max(label) FROM t",
+    ] {
+        assert_eq!(
+            query(&mut database, sql),
+            run_sqlite3(&sqlite3, plain.path(), &[sql]),
+            "{sql}"
+        );
+    }
+}
+
+#[test]
+fn windowed_pager_reads_pages_across_windows() {
+    let sqlite3 = require_sqlite3!("API");
+
+    // Windows hold sixteen pages of the smaller sizes and one page of the largest
+    for page_size in [512, 4096, 65536] {
+        let database = build(
+            &sqlite3,
+            &format!("windowed_{page_size}"),
+            &format!(
+                "PRAGMA page_size = {page_size};
+                 CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT, body BLOB);
+                 CREATE INDEX tl ON t (label);
+                 WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 3000)
+                 INSERT INTO t SELECT i, 'row ' || i, randomblob(i % 300) FROM seq;"
+            ),
+        );
+
+        let contents = fs::read(database.path()).expect("reading the fixture");
+        assert!(contents.len() > 4 * WindowedPager::WINDOW_ALIGN);
+        let pager =
+            WindowedPager::new(File::open(database.path()).expect("opening the fixture"), 1)
+                .expect("mapping the fixture");
+        assert_eq!(pager.page_size(), page_size);
+        assert_eq!(pager.page_count(), contents.len() / page_size);
+        for (n, page) in contents.chunks(page_size).enumerate() {
+            assert_eq!(
+                pager.get_page(n).expect("reading a page"),
+                page,
+                "page {}",
+                n + 1
+            );
+        }
+        assert!(pager.get_page(pager.page_count()).is_err());
+
+        let options = OpenOptions::new().mmap_window(Some(1));
+        let mut windowed =
+            Database::open_with(database.path(), options).expect("opening the database");
+        for sql in [
+            "SELECT id, label, hex(body) FROM t ORDER BY id",
+            "SELECT id FROM t WHERE label = 'row 2999'",
+            "SELECT count(*) FROM t",
+            "SELECT max(label) FROM t",
+        ] {
+            assert_eq!(
+                query(&mut windowed, sql),
+                run_sqlite3(&sqlite3, database.path(), &[sql]),
+                "{sql} with {page_size} byte pages"
+            );
+        }
+    }
+}
+
+/// A row as `sqlite3` prints it in list mode, NULL as nothing
+fn list_row(values: &[RecordValue]) -> String {
+    values
+        .iter()
+        .map(|value| match value {
+            RecordValue::Null => String::new(),
+            value => value.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+#[test]
+fn execute_hands_over_rows_until_told_to_stop() {
+    let sqlite3 = require_sqlite3!("API");
+
+    let fixture = build(
+        &sqlite3,
+        "execute",
+        "PRAGMA page_size = 512;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT, score REAL);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 2000)
+         INSERT INTO t SELECT i, CASE WHEN i % 5 THEN 'row ' || i END, i / 8.0 FROM seq;",
+    );
+    let mut database = Database::open(fixture.path()).expect("opening the database");
+
+    // Rows of every statement are handed over in turn
+    let sql = "SELECT id, label, score FROM t WHERE id < 30 ORDER BY id;
+               SELECT count(*) FROM t WHERE label IS NULL;
+               SELECT label FROM t WHERE id = 3";
+    let mut rows = Vec::new();
+    database
+        .execute(sql, |values| {
+            rows.push(list_row(values));
+            ControlFlow::Continue(())
+        })
+        .expect("running the statements");
+    assert_eq!(rows, run_sqlite3(&sqlite3, fixture.path(), &[sql]));
+
+    // Breaking stops the scan part way through the table, and skips the statements
+    // after it
+    let mut rows = Vec::new();
+    database
+        .execute(
+            "SELECT id, label FROM t; SELECT count(*) FROM t",
+            |values| {
+                rows.push(list_row(values));
+                match rows.len() {
+                    3 => ControlFlow::Break(()),
+                    _ => ControlFlow::Continue(()),
+                }
+            },
+        )
+        .expect("running the statements");
+    let expected = run_sqlite3(&sqlite3, fixture.path(), &["SELECT id, label FROM t"]);
+    assert_eq!(rows, expected[..3]);
+
+    let metrics = database.last_query().expect("the scan was measured");
+    let pages = run_sqlite3(
+        &sqlite3,
+        fixture.path(),
+        &["SELECT count(*) FROM dbstat WHERE name = 't'"],
+    );
+    let pages: u64 = pages[0].parse().expect("a number");
+    assert!(
+        metrics.pages_read < pages / 4,
+        "read {} of the table's {pages} pages",
+        metrics.pages_read
+    );
+}
+
+#[test]
+fn progress_handler_can_interrupt_a_query() {
+    let sqlite3 = require_sqlite3!("API");
+
+    let fixture = build(
+        &sqlite3,
+        "progress",
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 2000)
+         INSERT INTO t SELECT i, 'row ' || i FROM seq;",
+    );
+    let mut database = Database::open(fixture.path()).expect("opening the database");
+    let sql = "SELECT label FROM t WHERE label LIKE '%7' ORDER BY id";
+    let expected = run_sqlite3(&sqlite3, fixture.path(), &[sql]);
+
+    // Each row takes at least a step of the cursor
+    let calls = Arc::new(AtomicU64::new(0));
+    let counter = Arc::clone(&calls);
+    database.set_progress_handler(100, move || {
+        counter.fetch_add(1, Ordering::Relaxed);
+        ControlFlow::Continue(())
+    });
+    assert_eq!(query(&mut database, sql), expected);
+    assert!(calls.load(Ordering::Relaxed) >= 2000 / 100, "{calls:?}");
+
+    let calls = Arc::new(AtomicU64::new(0));
+    let counter = Arc::clone(&calls);
+    database.set_progress_handler(100, move || match counter.fetch_add(1, Ordering::Relaxed) {
+        0..2 => ControlFlow::Continue(()),
+        _ => ControlFlow::Break(()),
+    });
+    let error = database
+        .execute_to(sql, &mut Vec::new())
+        .expect_err("the handler interrupts the query");
+    assert!(error.downcast_ref::<Interrupted>().is_some(), "{error:#}");
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+    // The next statement starts uninterrupted, with the handler removed
+    database.set_progress_handler(0, || ControlFlow::Break(()));
+    assert_eq!(query(&mut database, sql), expected);
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn check_constraints_agree_with_sqlite3() {
+    let sqlite3 = require_sqlite3!("API");
+
+    // Column and table constraints, each with and without a name
+    let database = build(
+        &sqlite3,
+        "checks",
+        "CREATE TABLE items (
+             id INTEGER PRIMARY KEY,
+             qty INTEGER CHECK (qty >= 0),
+             price REAL CONSTRAINT positive_price CHECK (price > 0),
+             low INTEGER,
+             high INTEGER,
+             CHECK (low <= high),
+             CONSTRAINT narrow CHECK (high - low < 100)
+         );",
+    );
+    let reader = SqliteReader::new(database.path()).expect("opening the fixture");
+    let table = reader
+        .schema()
+        .expect("reading the schema")
+        .fetch_table("items")
+        .expect("the table is in the schema")
+        .columns();
+    let names: Vec<_> = table
+        .checks
+        .iter()
+        .map(|check| check.name.as_deref())
+        .collect();
+    assert_eq!(names, [None, Some("positive_price"), None, Some("narrow")]);
+
+    // A constraint that is NULL passes, only a false one fails
+    for row in [
+        [Some(1), Some(2), Some(0), Some(10)],
+        [Some(-1), Some(2), Some(0), Some(10)],
+        [Some(1), Some(0), Some(0), Some(10)],
+        [Some(1), Some(2), Some(5), Some(1)],
+        [Some(1), Some(2), Some(0), Some(500)],
+        [None, Some(2), None, Some(500)],
+        [Some(-1), Some(-2), Some(5), Some(1)],
+    ] {
+        let value = |value: Option<i64>| value.map_or(RecordValue::Null, RecordValue::I64);
+        let ours = table
+            .check_row(&|name| {
+                let idx = ["qty", "price", "low", "high"]
+                    .iter()
+                    .position(|column| *column == name)
+                    .expect("a column of the table");
+                Ok(value(row[idx]))
+            })
+            .err()
+            .map(|e| e.to_string());
+
+        let literals: Vec<String> = row.iter().map(|&v| value(v).to_sql_literal()).collect();
+        let insert = format!(
+            "INSERT INTO items (qty, price, low, high) VALUES ({})",
+            literals.join(", ")
+        );
+        let output = Command::new(&sqlite3)
+            .arg(database.path())
+            .arg(&insert)
+            .output()
+            .expect("sqlite3 runs");
+        let theirs = (!output.status.success()).then(|| {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.trim().trim_start_matches("Error: stepping, ");
+            message.trim_end_matches(" (19)").to_string()
+        });
+
+        assert_eq!(ours, theirs, "{insert}");
+    }
+}
+
+/// A database past 1 GiB without the gigabyte, where every page but the first two
+/// is blank except the lock-byte page, which holds bytes that would decode as a
+/// broken b-tree page, and the page after it, which is the second page again
+struct LockBytePager {
+    pages: Vec<u8>,
+    blank: Vec<u8>,
+    lock_byte: Vec<u8>,
+}
+
+impl LockBytePager {
+    const PAGE_SIZE: usize = 4096;
+    /// The lock-byte page, counting from 1
+    const LOCK_BYTE_PAGE: usize = 0x4000_0000 / Self::PAGE_SIZE + 1;
+}
+
+impl Pager for LockBytePager {
+    fn page_size(&self) -> usize {
+        Self::PAGE_SIZE
+    }
+
+    fn page_count(&self) -> usize {
+        Self::LOCK_BYTE_PAGE + 1
+    }
+
+    fn get_page(&self, n: usize) -> io::Result<&[u8]> {
+        let page = |n: usize| &self.pages[n * Self::PAGE_SIZE..(n + 1) * Self::PAGE_SIZE];
+        Ok(match n + 1 {
+            1 | 2 => page(n),
+            Self::LOCK_BYTE_PAGE => &self.lock_byte,
+            page_no if page_no == Self::LOCK_BYTE_PAGE + 1 => page(1),
+            _ => &self.blank,
+        })
+    }
+}
+
+#[test]
+fn skips_the_lock_byte_page() {
+    let sqlite3 = require_sqlite3!("API");
+
+    // The table is moved past the lock-byte page, as the first page SQLite would
+    // allocate after it
+    let database = TempFile::new("lock_byte.db");
+    run_sqlite3(
+        &sqlite3,
+        database.path(),
+        &[
+            ".dbconfig defensive off",
+            &format!(
+                "PRAGMA page_size = {};
+                 CREATE TABLE t (a);
+                 INSERT INTO t VALUES ('one'), ('two');
+                 PRAGMA writable_schema = ON;
+                 UPDATE sqlite_schema SET rootpage = {} WHERE name = 't';",
+                LockBytePager::PAGE_SIZE,
+                LockBytePager::LOCK_BYTE_PAGE + 1
+            ),
+        ],
+    );
+    let pages = fs::read(database.path()).expect("reading the fixture");
+    assert_eq!(pages.len(), 2 * LockBytePager::PAGE_SIZE);
+
+    let reader = SqliteReader::with_pager(LockBytePager {
+        pages,
+        blank: vec![0; LockBytePager::PAGE_SIZE],
+        lock_byte: vec![0x0d; LockBytePager::PAGE_SIZE],
+    })
+    .expect("opening the database");
+
+    let rows = reader.select("SELECT a FROM t").expect("reading the table");
+    assert_eq!(
+        rows.rows,
+        [
+            [RecordValue::String("one".into())],
+            [RecordValue::String("two".into())]
+        ]
+    );
+
+    // Walking every page passes over it rather than decoding it
+    let map = reader.page_map().expect("mapping the pages");
+    assert_eq!(
+        map[LockBytePager::LOCK_BYTE_PAGE - 1].kind,
+        PageKind::LockByte
+    );
+    assert_eq!(
+        map[LockBytePager::LOCK_BYTE_PAGE].kind,
+        PageKind::BTree(BTreePageType::LeafTable)
+    );
+    reader.recovered_rows().expect("carving the free space");
+
+    let mut out = Vec::new();
+    let error = reader
+        .page_info(LockBytePager::LOCK_BYTE_PAGE, &mut out)
+        .expect_err("the lock-byte page isn't a b-tree page");
+    assert!(format!("{error:#}").contains("lock-byte page"), "{error:#}");
+}
@@ -0,0 +1,1350 @@
+//! Runs the dot commands and entry points whose output `sqlite3` can't print the
+//! same way, and statements needing more than one fixture, checking what they report
+//! against what `sqlite3` says of the databases
+//!
+//! Each test builds its fixture database with `sqlite3`, so the tests are skipped
+//! when it isn't installed, and another binary can be used by setting `SQLITE3`.
+
+#[macro_use]
+mod common;
+
+use common::{lines, run_sqlite3, TempFile, BINARY};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// A fixture database, removed once the test is done with it
+struct Fixture {
+    sqlite3: String,
+    file: TempFile,
+}
+
+impl Fixture {
+    /// Builds the database `name` from `sql`
+    fn build(sqlite3: &str, name: &str, sql: &str) -> Self {
+        Self {
+            sqlite3: sqlite3.to_string(),
+            file: common::build(sqlite3, name, sql),
+        }
+    }
+
+    fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// The lines `sqlite3` prints for `sql`
+    fn sqlite3(&self, sql: &str) -> Vec<String> {
+        run_sqlite3(&self.sqlite3, self.path(), &[sql])
+    }
+
+    /// The lines this crate prints for the dot command or query
+    fn run(&self, command: &str) -> Vec<String> {
+        common::run(self.path(), command)
+    }
+}
+
+/// The lines this crate prints for `command` run on the databases in `args`
+fn run(args: &[&Path], command: &str) -> Vec<String> {
+    lines(Command::new(BINARY).args(args).arg(command), command)
+}
+
+#[test]
+fn recover_carves_deleted_rows() {
+    let sqlite3 = require_sqlite3!("command");
+
+    // Deleting a run of rows frees whole pages, deleting every tenth leaves freeblocks
+    let db = Fixture::build(
+        &sqlite3,
+        "recover",
+        "PRAGMA page_size = 512;
+         PRAGMA secure_delete = OFF;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 300)
+         INSERT INTO t SELECT i, 'label ' || i FROM seq;
+         DELETE FROM t WHERE id > 100;
+         DELETE FROM t WHERE id % 10 = 0;",
+    );
+
+    let freelist = db.run(".map");
+    let freelist = freelist
+        .iter()
+        .filter(|line| line.contains("|freelist_"))
+        .count();
+    assert_eq!(freelist.to_string(), db.sqlite3("PRAGMA freelist_count")[0]);
+
+    // Freed pages can also hold stale copies of rows moved elsewhere, which are found
+    // along with the deleted ones
+    let live: HashSet<String> = db.sqlite3("SELECT label FROM t").into_iter().collect();
+    let mut deleted = HashSet::new();
+    for row in db.run(".recover") {
+        let fields: Vec<&str> = row.split('|').collect();
+        let [_page, _offset, row_id, _id, label] = fields[..] else {
+            panic!("unexpected recovered row {row}");
+        };
+
+        // Rows on a freelist trunk page lose bytes to its list of leaves
+        let Some(id) = label.strip_prefix("label ") else {
+            continue;
+        };
+        if !row_id.is_empty() {
+            assert_eq!(id, row_id, "in {row}");
+        }
+        if !live.contains(label) {
+            deleted.insert(label.to_string());
+        }
+    }
+
+    // Whole pages of rows were freed, and their cells survive with their rowids
+    assert!(
+        deleted.len() > (300 - live.len()) / 2,
+        "only recovered {} deleted rows",
+        deleted.len()
+    );
+}
+
+#[test]
+fn diff_turns_one_database_into_the_other() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let schema = "CREATE TABLE t (id INTEGER PRIMARY KEY, a TEXT, b REAL);";
+    let old = Fixture::build(
+        &sqlite3,
+        "diff_old",
+        &format!(
+            "{schema}
+             INSERT INTO t VALUES (1, 'x', 1.5), (2, 'y', 2), (3, 'it''s', NULL);
+             CREATE TABLE gone (a);
+             INSERT INTO gone VALUES (1);"
+        ),
+    );
+    let new = Fixture::build(
+        &sqlite3,
+        "diff_new",
+        &format!(
+            "{schema}
+             INSERT INTO t VALUES (1, 'x', 1.5), (2, 'z', 2), (4, 'new', x'00ff');
+             CREATE TABLE fresh (a, b);
+             INSERT INTO fresh VALUES (1, 'one'), (NULL, 2.25);
+             CREATE INDEX ta ON t (a);"
+        ),
+    );
+
+    let diff = run(&[Path::new("diff"), old.path(), new.path()], "--summary");
+    assert_eq!(
+        diff,
+        [
+            "- table gone",
+            "+ table fresh",
+            "+ index ta",
+            "fresh: 2 added, 0 deleted, 0 changed",
+            "t: 1 added, 1 deleted, 1 changed",
+        ]
+    );
+
+    // Running the SQL on the old database leaves it holding what the new one does
+    let sql = lines(
+        Command::new(BINARY)
+            .arg("diff")
+            .arg(old.path())
+            .arg(new.path()),
+        "diff",
+    );
+    assert_eq!(sql, old.run(&format!(".diff {}", new.path().display())));
+    old.sqlite3(&sql.join("\n"));
+    for query in [
+        "SELECT type, name, tbl_name, sql FROM sqlite_schema ORDER BY name",
+        "SELECT id, a, b, typeof(b) FROM t ORDER BY id",
+        "SELECT rowid, a, typeof(a), b, typeof(b) FROM fresh ORDER BY rowid",
+    ] {
+        assert_eq!(old.sqlite3(query), new.sqlite3(query), "{query}");
+    }
+}
+
+/// The value `.page` prints after `label` on one of its header lines
+fn page_field(page: &[String], label: &str) -> usize {
+    page.iter()
+        .find_map(|line| line.strip_prefix(label)?.strip_prefix(": "))
+        .unwrap_or_else(|| panic!("no {label} in {page:?}"))
+        .parse()
+        .expect("a number")
+}
+
+#[test]
+fn page_matches_dbstat() {
+    let sqlite3 = require_sqlite3!("command");
+
+    // Deleting every seventh row leaves freeblocks on each leaf
+    let db = Fixture::build(
+        &sqlite3,
+        "page",
+        "PRAGMA page_size = 512;
+         PRAGMA secure_delete = OFF;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 200)
+         INSERT INTO t SELECT i, 'row ' || i FROM seq;
+         DELETE FROM t WHERE id % 7 = 0;",
+    );
+
+    let mut rowids = Vec::new();
+    for stat in db.sqlite3(
+        "SELECT pageno, pagetype, ncell, payload, unused FROM dbstat WHERE name = 't' ORDER BY path",
+    ) {
+        let stat: Vec<usize> = stat
+            .split('|')
+            .filter_map(|field| field.parse().ok())
+            .collect();
+        let [page_no, cells, payload, unused] = stat[..] else {
+            panic!("unexpected dbstat row {stat:?}");
+        };
+        let page = db.run(&format!(".page {page_no}"));
+        assert_eq!(page_field(&page, "number of cells"), cells, "{page:?}");
+
+        // Free space is the gap between the cell pointers and the cell content
+        // area, the freeblocks and the fragments
+        let leaf = page[0].ends_with("Leaf Page");
+        let header = if leaf { 8 } else { 12 };
+        let gap = page_field(&page, "cell content area") - header - 2 * cells;
+        let freeblocks: usize = page
+            .iter()
+            .find_map(|line| line.strip_prefix("freeblocks: "))
+            .expect("a freeblock line")
+            .split(", ")
+            .filter_map(|block| block.split_once(" (")?.1.strip_suffix(" bytes)"))
+            .map(|size| size.parse::<usize>().expect("a number"))
+            .sum();
+        let fragmented = page_field(&page, "fragmented free bytes");
+        assert_eq!(gap + freeblocks + fragmented, unused, "{page:?}");
+
+        let summaries: Vec<&String> = page
+            .iter()
+            .filter(|line| line.contains(" at "))
+            .collect();
+        assert_eq!(summaries.len(), cells, "{page:?}");
+        if leaf {
+            let mut payloads = 0;
+            for cell in summaries {
+                let (_, rest) = cell.split_once(", rowid ").expect("a table leaf cell");
+                let (rowid, rest) = rest.split_once(", payload ").expect("a payload");
+                rowids.push(rowid.to_string());
+                payloads += rest
+                    .trim_end_matches(" bytes")
+                    .parse::<usize>()
+                    .expect("a number");
+            }
+            assert_eq!(payloads, payload, "{page:?}");
+        }
+    }
+    assert_eq!(rowids, db.sqlite3("SELECT id FROM t"));
+}
+
+#[test]
+fn btree_lists_the_pages_dbstat_finds() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "btree",
+        "PRAGMA page_size = 512;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         CREATE INDEX tl ON t (label);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 2000)
+         INSERT INTO t SELECT i, 'row ' || i FROM seq;",
+    );
+
+    for name in ["t", "tl"] {
+        // Pages in the order a depth-first walk meets them, as the outline lists them
+        let pages = db.sqlite3(&format!(
+            "SELECT pageno, CASE pagetype
+                 WHEN 'leaf' THEN 'leaf, ' || ncell || ' cells'
+                 ELSE 'interior, ' || (ncell + 1) || ' children' END
+             FROM dbstat WHERE name = '{name}' ORDER BY path"
+        ));
+        assert!(pages.len() > 20, "{name} spans a few levels");
+        let expected: Vec<String> = pages
+            .iter()
+            .map(|page| {
+                let (page_no, summary) = page.split_once('|').expect("two columns");
+                format!("page {page_no}: {summary}")
+            })
+            .collect();
+        let outline: Vec<String> = db
+            .run(&format!(".btree {name}"))
+            .iter()
+            .map(|line| line.trim_start())
+            .filter(|line| line.starts_with("page "))
+            .map(str::to_string)
+            .collect();
+        assert_eq!(outline, expected, "{name}");
+
+        // Each page but the root hangs off one edge of the graph
+        let dot = db.run(&format!(".btree --dot {name}"));
+        let edges = dot.iter().filter(|line| line.contains(" -> ")).count();
+        assert_eq!(edges, pages.len() - 1, "{name}");
+    }
+}
+
+#[test]
+fn dbstat_matches_the_dbstat_table() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "dbstat",
+        "PRAGMA page_size = 512;
+         PRAGMA secure_delete = OFF;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         CREATE INDEX tl ON t (label);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 500)
+         INSERT INTO t SELECT i, 'row ' || i FROM seq;
+         DELETE FROM t WHERE id % 3 = 0;
+         CREATE TABLE big (a);
+         INSERT INTO big VALUES (randomblob(2000)), (zeroblob(700)), ('small');",
+    );
+
+    let mut report = db.run(".dbstat");
+    assert_eq!(report.remove(0), "name|pages|payload|unused|overflow pages");
+    let mut expected = db.sqlite3(
+        "SELECT name, count(*), sum(payload), sum(unused), sum(pagetype = 'overflow')
+         FROM dbstat GROUP BY name",
+    );
+    report.sort();
+    expected.sort();
+    assert_eq!(report, expected);
+}
+
+#[test]
+fn attach_reads_tables_from_another_database() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let main = Fixture::build(
+        &sqlite3,
+        "attach_main",
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT);
+         INSERT INTO t VALUES (1, 'main 1'), (2, 'main 2');
+         CREATE TABLE only_main (x);
+         INSERT INTO only_main VALUES (9);",
+    );
+    let other = Fixture::build(
+        &sqlite3,
+        "attach_other",
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT);
+         CREATE INDEX tv ON t (v);
+         INSERT INTO t VALUES (1, 'other 1'), (3, 'other 3');
+         CREATE TABLE only_other (y);
+         INSERT INTO only_other VALUES ('z');",
+    );
+    let attach = format!("ATTACH DATABASE '{}' AS other;", other.path().display());
+
+    // Unqualified names are looked up in the main database first
+    let script = format!(
+        "{attach}
+         SELECT v FROM other.t ORDER BY id;
+         SELECT v FROM t ORDER BY id;
+         SELECT v FROM main.t WHERE id = 2;
+         SELECT y FROM only_other;
+         SELECT x FROM main.only_main;
+         SELECT count(*) FROM other.t WHERE v = 'other 3';
+         SELECT max(v) FROM other.t;"
+    );
+    assert_eq!(main.run(&script), main.sqlite3(&script));
+
+    for script in [
+        format!("{attach} DETACH DATABASE other; SELECT v FROM other.t;"),
+        format!("{attach} {attach}"),
+        "SELECT y FROM other.only_other;".to_string(),
+    ] {
+        for binary in [BINARY, sqlite3.as_str()] {
+            let output = Command::new(binary)
+                .arg(main.path())
+                .arg(&script)
+                .output()
+                .expect("command runs");
+            assert!(!output.status.success(), "{binary} ran {script}");
+        }
+    }
+}
+
+#[test]
+fn verify_header_finds_the_size_sqlite3_would_distrust() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "verify_header",
+        "PRAGMA page_size = 1024;
+         CREATE TABLE t (a);
+         INSERT INTO t VALUES (randomblob(5000));",
+    );
+    assert_eq!(db.run(".verify-header"), ["ok"]);
+    let pages = &db.sqlite3("PRAGMA page_count")[0];
+
+    // sqlite3 goes by the size in the header while it's up to date, ignoring a page
+    // tacked onto the end of the file
+    let mut contents = fs::read(db.path()).expect("reading the fixture");
+    contents.extend_from_slice(&[0; 1024]);
+    fs::write(db.path(), &contents).expect("writing the fixture");
+    assert_eq!(&db.sqlite3("PRAGMA page_count")[0], pages);
+    let file_pages = contents.len() / 1024;
+    assert_eq!(
+        db.run(".verify-header"),
+        [format!(
+            "header gives the database size as {pages} pages but the file has {file_pages}"
+        )]
+    );
+
+    // Once the change counter moves on without the version-valid-for number, it goes
+    // by the length of the file instead
+    let valid_for = u32::from_be_bytes(contents[92..96].try_into().expect("4 bytes"));
+    contents[24..28].copy_from_slice(&(valid_for + 1).to_be_bytes());
+    fs::write(db.path(), &contents).expect("writing the fixture");
+    assert_eq!(db.sqlite3("PRAGMA page_count"), [file_pages.to_string()]);
+    assert_eq!(
+        db.run(".verify-header"),
+        [format!(
+            "file change counter {} differs from version-valid-for number {valid_for}, \
+             so the database size in the header is stale",
+            valid_for + 1
+        )]
+    );
+}
+
+/// The value `sqlite3`'s `.dbinfo` gives for `field`
+fn dbinfo_field(db: &Fixture, field: &str) -> String {
+    db.sqlite3(".dbinfo")
+        .iter()
+        .find_map(|line| Some(line.strip_prefix(field)?.trim_start_matches(':').trim()))
+        .unwrap_or_else(|| panic!("no {field} in .dbinfo"))
+        .to_string()
+}
+
+#[test]
+fn version_matches_dbinfo() {
+    let sqlite3 = require_sqlite3!("command");
+
+    for (name, sql, journal) in [
+        (
+            "version_rollback",
+            "CREATE TABLE t (a);",
+            "1 (rollback journal)",
+        ),
+        (
+            "version_wal",
+            "PRAGMA journal_mode = WAL; CREATE TABLE t (a);",
+            "2 (write-ahead log)",
+        ),
+    ] {
+        let db = Fixture::build(&sqlite3, name, sql);
+        let version = db.run(".version");
+        let field = |label: &str| {
+            version
+                .iter()
+                .find_map(|line| line.strip_prefix(label)?.strip_prefix(": "))
+                .unwrap_or_else(|| panic!("no {label} in {version:?}"))
+                .to_string()
+        };
+        let number = |label: &str| field(label).split(' ').next().map(str::to_string);
+
+        // The header holds the version of the library that last wrote it, which
+        // sqlite3 reading it doesn't change
+        let release = &db.sqlite3("SELECT sqlite_version()")[0];
+        assert_eq!(field("last written by"), format!("SQLite {release}"));
+        let [major, minor, patch] = release
+            .split('.')
+            .map(|part| part.parse::<u32>().expect("a number"))
+            .collect::<Vec<_>>()[..]
+        else {
+            panic!("unexpected release {release}");
+        };
+        assert_eq!(
+            dbinfo_field(&db, "software version"),
+            (major * 1_000_000 + minor * 1000 + patch).to_string()
+        );
+
+        assert_eq!(field("write version"), journal);
+        assert_eq!(field("read version"), journal);
+        assert_eq!(
+            number("write version"),
+            Some(dbinfo_field(&db, "write format"))
+        );
+        assert_eq!(
+            number("read version"),
+            Some(dbinfo_field(&db, "read format"))
+        );
+        assert_eq!(
+            number("schema format"),
+            Some(dbinfo_field(&db, "schema format"))
+        );
+    }
+}
+
+#[test]
+fn dbinfo_shows_the_application_id_and_user_version() {
+    let sqlite3 = require_sqlite3!("command");
+
+    // GeoPackage's id is `GPKG`, and the pragmas take both as signed
+    for (name, application_id, user_version, format) in [
+        ("dbinfo_default", 0, 0, None),
+        ("dbinfo_geopackage", 0x4750_4b47, 3, Some("GeoPackage")),
+        ("dbinfo_negative", -1, -5, None),
+    ] {
+        let db = Fixture::build(
+            &sqlite3,
+            name,
+            &format!(
+                "PRAGMA application_id = {application_id};
+                 PRAGMA user_version = {user_version};
+                 CREATE TABLE t (a);"
+            ),
+        );
+
+        let dbinfo = db.run(".dbinfo");
+        let id = dbinfo_field(&db, "application id");
+        let expected = match format {
+            Some(format) => format!("application id: {id} ({format})"),
+            None => format!("application id: {id}"),
+        };
+        assert!(dbinfo.contains(&expected), "{expected} in {dbinfo:?}");
+
+        let version = &db.sqlite3("PRAGMA user_version")[0];
+        assert_eq!(version, &user_version.to_string());
+        assert!(
+            dbinfo.contains(&format!("user version: {version}")),
+            "{dbinfo:?}"
+        );
+    }
+}
+
+#[test]
+fn cellhex_decodes_the_bytes_of_each_cell() {
+    let sqlite3 = require_sqlite3!("command");
+
+    // Page 2 holds every row, the last spilling onto overflow pages
+    let db = Fixture::build(
+        &sqlite3,
+        "cellhex",
+        "PRAGMA page_size = 512;
+         CREATE TABLE t (a, b, c, d, e);
+         INSERT INTO t VALUES (NULL, 0, 1, 300, -70000);
+         INSERT INTO t VALUES (2.5, 'hi', x'00ff', 1099511627776, 'x');
+         INSERT INTO t VALUES (-0.125, 'it''s', 127, -129, 'é');
+         INSERT INTO t VALUES (1, randomblob(600), 2, 3, 4);",
+    );
+    let contents = fs::read(db.path()).expect("reading the fixture");
+    let page = &contents[512..1024];
+
+    for cell in 0..3 {
+        let dump = db.run(&format!(".cellhex 2 {cell}"));
+
+        // Each line shows the bytes at its offset, with longer values wrapping onto
+        // lines of their own
+        let mut next = None;
+        for line in &dump[1..] {
+            let offset = match line[..5].trim() {
+                "" => next.expect("a wrapped line follows a value"),
+                offset => offset.parse::<usize>().expect("an offset"),
+            };
+            let bytes: Vec<u8> = line[7..31]
+                .split_whitespace()
+                .map(|byte| u8::from_str_radix(byte, 16).expect("a hex byte"))
+                .collect();
+            assert_eq!(page[offset..offset + bytes.len()], bytes, "{line}");
+            next = Some(offset + bytes.len());
+        }
+
+        // sqlite3 quotes blobs in upper case
+        let rowid = dump
+            .iter()
+            .find_map(|line| line.split_once("rowid ").map(|(_, rowid)| rowid))
+            .expect("a rowid line");
+        let values: Vec<&str> = dump
+            .iter()
+            .filter_map(|line| {
+                line.split_once(": ")
+                    .filter(|(label, _)| label.contains("column"))
+            })
+            .map(|(_, value)| value)
+            .collect();
+        let expected = db.sqlite3(&format!(
+            "SELECT quote(a), quote(b), quote(c), quote(d), quote(e) FROM t WHERE rowid = {rowid}"
+        ));
+        assert_eq!(values.join("|").to_lowercase(), expected[0].to_lowercase());
+    }
+
+    let dump = db.run(".cellhex 2 3");
+    let overflow = db.sqlite3(
+        "SELECT pageno FROM dbstat WHERE name = 't' AND pagetype = 'overflow' ORDER BY path LIMIT 1",
+    );
+    assert!(
+        dump.last()
+            .is_some_and(|line| line.ends_with(&format!("first overflow page {}", overflow[0]))),
+        "{dump:?}"
+    );
+}
+
+/// The dbstat path of the page pointing at the page at `path`: a b-tree page's
+/// parent, or the page before it in an overflow chain
+fn dbstat_parent(path: &str) -> Option<String> {
+    if let Some((cell, step)) = path.split_once('+') {
+        let step: usize = step.parse().expect("an overflow step");
+        return Some(match step {
+            0 => format!("{}/", cell.rsplit_once('/').expect("a cell").0),
+            _ => format!("{cell}+{:06}", step - 1),
+        });
+    }
+
+    let (parent, _) = path.strip_suffix('/')?.rsplit_once('/')?;
+    Some(format!("{parent}/"))
+}
+
+#[test]
+fn map_matches_dbstat() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "map",
+        "PRAGMA page_size = 512;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         CREATE INDEX tl ON t (label);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 1000)
+         INSERT INTO t SELECT i, 'row ' || i FROM seq;
+         DELETE FROM t WHERE id > 600;
+         CREATE TABLE big (a);
+         INSERT INTO big VALUES (randomblob(2000)), (zeroblob(700)), ('small');",
+    );
+
+    let stats = db.sqlite3(
+        "SELECT d.name, path, pageno, pagetype, ncell, unused, coalesce(s.type, 'table')
+         FROM dbstat AS d LEFT JOIN sqlite_schema AS s ON s.name = d.name",
+    );
+    let pages: HashMap<(&str, &str), &str> = stats
+        .iter()
+        .map(|stat| {
+            let fields: Vec<&str> = stat.split('|').collect();
+            ((fields[0], fields[1]), fields[2])
+        })
+        .collect();
+    let mut expected: Vec<(usize, String)> = stats
+        .iter()
+        .map(|stat| {
+            let fields: Vec<&str> = stat.split('|').collect();
+            let [name, path, page_no, page_type, cells, unused, kind] = fields[..] else {
+                panic!("unexpected dbstat row {stat}");
+            };
+            let page_type = match page_type {
+                "overflow" => "overflow".to_string(),
+                "internal" => format!("{kind}_interior"),
+                _ => format!("{kind}_leaf"),
+            };
+            let parent = dbstat_parent(path).map(|parent| pages[&(name, parent.as_str())]);
+            let parent = parent.unwrap_or_default();
+            let line = format!("{page_no}|{page_type}|{parent}|{cells}|{unused}|{name}");
+            (page_no.parse().expect("a page number"), line)
+        })
+        .collect();
+    expected.sort();
+    let expected: Vec<String> = expected.into_iter().map(|(_, line)| line).collect();
+
+    // Every page dbstat walks is in a b-tree, the rest are free
+    let mut map = db.run(".map");
+    assert_eq!(map.remove(0), "page|type|parent|cells|free bytes|owner");
+    let (free, used): (Vec<String>, Vec<String>) = map
+        .iter()
+        .cloned()
+        .partition(|line| line.contains("|freelist_"));
+    assert_eq!(used, expected);
+    assert_eq!(
+        free.len().to_string(),
+        db.sqlite3("PRAGMA freelist_count")[0]
+    );
+    assert_eq!(map.len().to_string(), db.sqlite3("PRAGMA page_count")[0]);
+
+    let json = db.run(".map --json").join("\n");
+    let json: Vec<serde_json::Value> = serde_json::from_str(&json).expect("an array of pages");
+    let from_json: Vec<String> = json
+        .iter()
+        .map(|page| {
+            let text = |key: &str| match &page[key] {
+                serde_json::Value::Null => String::new(),
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            ["page", "type", "parent", "cells", "free_bytes", "owner"]
+                .map(text)
+                .join("|")
+        })
+        .collect();
+    assert_eq!(from_json, map);
+}
+
+#[test]
+fn analyze_statistics_change_the_plan() {
+    let sqlite3 = require_sqlite3!("command");
+
+    // Half the rows share each value of `a`, against a hundred for each of `b`
+    let db = Fixture::build(
+        &sqlite3,
+        "stat1",
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, a INTEGER, b INTEGER, c TEXT);
+         CREATE INDEX ta ON t (a);
+         CREATE INDEX tb ON t (b);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 1000)
+         INSERT INTO t SELECT i, i % 2, i / 100, 'row ' || i FROM seq;",
+    );
+    let both = "select c from t where a = 1 and b = 3";
+    let plan = |sql: &str| db.run(&format!("explain query plan {sql}"));
+
+    // Without statistics every index is taken to be selective
+    assert_eq!(
+        plan(both),
+        [
+            "QUERY PLAN",
+            "`--SEARCH t USING INTERSECTION OF INDEX ta (a=?) AND INDEX tb (b=?)"
+        ]
+    );
+    assert_eq!(
+        plan("select c from t where a = 1")[1],
+        "`--SEARCH t USING INDEX ta (a=?)"
+    );
+    let rows = db.run(both);
+
+    db.sqlite3("ANALYZE");
+    assert_eq!(
+        db.sqlite3("SELECT idx, stat FROM sqlite_stat1 ORDER BY idx"),
+        ["ta|1000 500", "tb|1000 91"]
+    );
+    assert_eq!(
+        plan(both),
+        db.sqlite3(&format!("EXPLAIN QUERY PLAN {both}"))
+    );
+    assert_eq!(plan("select c from t where a = 1")[1], "`--SCAN t");
+    assert_eq!(db.run(both), rows);
+}
+
+#[test]
+fn nullvalue_and_blob_change_how_values_print() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "blobs",
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, b);
+         INSERT INTO t VALUES (1, x''), (2, x'41'), (3, x'4100ff'), (4, NULL), (5, 'text');",
+    );
+    let session = |commands: &[&str]| {
+        lines(
+            Command::new(BINARY).arg(db.path()).args(commands),
+            &format!("{commands:?}"),
+        )
+    };
+
+    // NULL shows as the text given, and text is never shown as a blob
+    let select = "SELECT id, b FROM t WHERE id > 3";
+    assert_eq!(
+        session(&[".nullvalue NIL", select]),
+        run_sqlite3(&sqlite3, db.path(), &[".nullvalue NIL", select])
+    );
+
+    let select = "SELECT b FROM t WHERE id <= 3";
+    assert_eq!(
+        session(&[".blob hex", select]),
+        db.sqlite3("SELECT quote(b) FROM t WHERE id <= 3")
+    );
+    assert_eq!(
+        session(&[".blob base64", select]),
+        db.sqlite3("SELECT rtrim(base64(b), char(10)) FROM t WHERE id <= 3")
+    );
+    assert_eq!(
+        session(&[".blob size", select, ".blob"]),
+        [
+            "blob (0 bytes)",
+            "blob (1 byte)",
+            "blob (3 bytes)",
+            "current blob format: size"
+        ]
+    );
+
+    // The bytes themselves stop at the first NUL, as sqlite3 prints them
+    assert_eq!(session(&[".blob text", select]), db.sqlite3(select));
+}
+
+#[test]
+fn tables_matches_sqlite3() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "tables",
+        "CREATE TABLE apples (id INTEGER PRIMARY KEY, name TEXT);
+         CREATE TABLE bananas (a, b);
+         CREATE TABLE cherry_pie (x);
+         CREATE TABLE a_b (z);
+         CREATE INDEX apples_name ON apples (name);
+         CREATE VIEW fruit AS SELECT name FROM apples;
+         CREATE TRIGGER t1 AFTER INSERT ON bananas BEGIN SELECT 1; END;",
+    );
+
+    // Indexes and triggers aren't listed, and the pattern is a LIKE pattern
+    for command in [
+        ".tables",
+        ".tables a%",
+        ".tables %an%",
+        ".tables a_b",
+        ".tables nosuch",
+    ] {
+        assert_eq!(db.run(command), db.sqlite3(command), "{command}");
+    }
+}
+
+#[test]
+fn schema_matches_sqlite3() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "schema",
+        "CREATE TABLE apples (id INTEGER PRIMARY KEY, name TEXT);
+         CREATE TABLE bananas(a, b);
+         CREATE INDEX apples_name ON apples (name);
+         CREATE VIEW fruit AS SELECT name FROM apples;
+         CREATE VIEW pairs(first, second) AS SELECT a, b FROM bananas;
+         CREATE VIEW everything AS SELECT * FROM bananas;
+         CREATE VIEW broken AS SELECT nosuch FROM apples;
+         CREATE TRIGGER t1 AFTER INSERT ON bananas BEGIN SELECT 1; END;
+         CREATE VIRTUAL TABLE notes USING fts5(title, body);",
+    );
+
+    // Views and virtual tables are followed by their columns, and the shadow tables
+    // of virtual tables are only created if they don't exist
+    for command in [
+        ".schema",
+        ".schema apples",
+        ".schema ban%",
+        ".schema fruit",
+        ".schema notes%",
+        ".schema nosuch",
+    ] {
+        assert_eq!(db.run(command), db.sqlite3(command), "{command}");
+    }
+}
+
+#[test]
+fn timeout_interrupts_a_long_query() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "timeout",
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 200000)
+         INSERT INTO t SELECT i, 'label ' || i FROM seq;",
+    );
+    let run = |timeout: &str, sql: &str| {
+        Command::new(BINARY)
+            .args(["--timeout", timeout])
+            .arg(db.path())
+            .arg(sql)
+            .output()
+            .expect("the binary runs")
+    };
+
+    // Reading every row takes far longer than a millisecond
+    let output = run("0.001", "select count(*) from t where label like '%9999%'");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(7), "stderr: {stderr}");
+    assert!(stderr.contains("interrupted"), "{stderr}");
+
+    let output = run("10", "select label from t where id = 9999");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "label 9999\n");
+}
+
+#[test]
+fn sequence_matches_sqlite_sequence() {
+    let sqlite3 = require_sqlite3!("command");
+
+    // Deleting the last row doesn't give its rowid back, and an explicit rowid moves
+    // the sequence on
+    let db = Fixture::build(
+        &sqlite3,
+        "sequence",
+        "CREATE TABLE orders (id INTEGER PRIMARY KEY AUTOINCREMENT, item TEXT);
+         CREATE TABLE invoices (id INTEGER PRIMARY KEY AUTOINCREMENT, total REAL);
+         CREATE TABLE plain (id INTEGER PRIMARY KEY, note TEXT);
+         INSERT INTO orders (item) VALUES ('a'), ('b'), ('c');
+         DELETE FROM orders WHERE id = 3;
+         INSERT INTO invoices VALUES (100, 1.5);
+         INSERT INTO invoices (total) VALUES (2.5);
+         INSERT INTO plain (note) VALUES ('x');",
+    );
+
+    let sorted = |mut lines: Vec<String>| {
+        lines.sort_unstable();
+        lines
+    };
+    assert_eq!(
+        sorted(db.run(".sequence")),
+        sorted(db.sqlite3("SELECT * FROM sqlite_sequence"))
+    );
+    assert_eq!(
+        db.run(".sequence inv%"),
+        db.sqlite3("SELECT * FROM sqlite_sequence WHERE name LIKE 'inv%'")
+    );
+
+    // A table that has never had a row has no entry yet, and none were assigned
+    db.sqlite3("CREATE TABLE fresh (id INTEGER PRIMARY KEY AUTOINCREMENT)");
+    assert_eq!(db.run(".sequence fresh"), ["fresh|0"]);
+}
+
+#[test]
+fn modes_match_sqlite3() {
+    let sqlite3 = require_sqlite3!("command");
+
+    // Values a mode has to quote, escape or line up
+    let db = Fixture::build(
+        &sqlite3,
+        "modes",
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT, score REAL, note);
+         INSERT INTO t VALUES
+             (1, 'plain', 1.5, NULL),
+             (2, 'a, \"quoted\" one', -2.0, 'tab' || char(9) || 'here'),
+             (3, 'it''s', NULL, 42);",
+    );
+    let select = "SELECT id, label, score, note FROM t ORDER BY id";
+    // The whole of what's printed, as CSV rows end in CRLF
+    let stdout = |command: &mut Command| {
+        let output = command.output().expect("the binary runs");
+        assert!(output.status.success(), "{command:?} failed");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    for mode in ["list", "table", "box", "quote", "csv", "tabs", "json"] {
+        let set_mode = format!(".mode {mode}");
+        for commands in [&[set_mode.as_str()][..], &[&set_mode, ".headers on"]] {
+            assert_eq!(
+                stdout(
+                    Command::new(BINARY)
+                        .arg(db.path())
+                        .args(commands)
+                        .arg(select)
+                ),
+                stdout(
+                    Command::new(&sqlite3)
+                        .arg(db.path())
+                        .args(commands)
+                        .arg(select)
+                ),
+                "{commands:?}"
+            );
+        }
+
+        // sqlite3's `-csv` leaves rows ending in LF, unlike `.mode csv`
+        assert_eq!(
+            lines(
+                Command::new(BINARY)
+                    .args(["--format", mode])
+                    .arg(db.path())
+                    .arg(select),
+                mode
+            ),
+            lines(
+                Command::new(&sqlite3)
+                    .arg(format!("-{mode}"))
+                    .arg(db.path())
+                    .arg(select),
+                mode
+            ),
+            "-{mode}"
+        );
+    }
+}
+
+#[test]
+fn read_runs_scripts_within_scripts() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "read",
+        "CREATE TABLE t (a); INSERT INTO t VALUES (1), (2);",
+    );
+    let inner = TempFile::new("inner.sql");
+    fs::write(
+        inner.path(),
+        "SELECT 'inner';\n.mode csv\nSELECT a, 'x,y' FROM t;\n",
+    )
+    .expect("writing the inner script");
+    let outer = TempFile::new("outer.sql");
+    fs::write(
+        outer.path(),
+        format!(
+            "SELECT 'outer';\n.read {}\nSELECT a,\n  'after' FROM t;\n",
+            inner.path().display()
+        ),
+    )
+    .expect("writing the outer script");
+
+    // The inner script runs where it's read, and its settings carry on after it
+    let read = format!(".read {}", outer.path().display());
+    assert_eq!(db.run(&read), db.sqlite3(&read));
+
+    // A script that isn't there fails the command, and the ones after it don't run
+    let missing = TempFile::new("missing.sql");
+    let output = Command::new(BINARY)
+        .arg(db.path())
+        .arg(format!(".read {}", missing.path().display()))
+        .arg("SELECT 'not run'")
+        .output()
+        .expect("the binary runs");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "stderr: {stderr}");
+    assert!(output.stdout.is_empty());
+    assert!(stderr.contains("missing.sql"), "{stderr}");
+}
+
+#[test]
+fn output_and_once_redirect_results() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "output",
+        "CREATE TABLE t (a); INSERT INTO t VALUES (1), (2);",
+    );
+    let file = TempFile::new("output.txt");
+    let path = file.path().display();
+    let session = |commands: &[&str]| {
+        lines(
+            Command::new(BINARY).arg(db.path()).args(commands),
+            &format!("{commands:?}"),
+        )
+    };
+
+    // Everything goes to the file until `.output stdout` sends it back
+    let commands = [
+        &format!(".output {path}"),
+        "SELECT a FROM t",
+        "SELECT 'also to the file'",
+        ".output stdout",
+        "SELECT 'back'",
+    ];
+    assert_eq!(session(&commands), ["back"]);
+    let written = fs::read_to_string(file.path()).expect("reading the output");
+    assert_eq!(run_sqlite3(&sqlite3, db.path(), &commands), ["back"]);
+    assert_eq!(
+        written,
+        fs::read_to_string(file.path()).expect("reading sqlite3's output")
+    );
+    assert_eq!(written, "1\n2\nalso to the file\n");
+
+    // `.once` only takes the next statement
+    let commands = [&format!(".once {path}"), "SELECT 'once'", "SELECT 'after'"];
+    assert_eq!(session(&commands), ["after"]);
+    assert_eq!(
+        fs::read_to_string(file.path()).expect("reading the output"),
+        "once\n"
+    );
+}
+
+#[test]
+fn output_appends_with_a() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "append",
+        "CREATE TABLE t (a); INSERT INTO t VALUES (1);",
+    );
+    let file = TempFile::new("append.txt");
+    let path = file.path().display();
+    let session = |commands: &[&str]| {
+        lines(
+            Command::new(BINARY).arg(db.path()).args(commands),
+            &format!("{commands:?}"),
+        )
+    };
+    let written = || fs::read_to_string(file.path()).expect("reading the output");
+
+    // Without `-a` the file is started again
+    fs::write(file.path(), "before\n").expect("writing the file");
+    session(&[&format!(".output {path}"), "SELECT a FROM t"]);
+    assert_eq!(written(), "1\n");
+
+    fs::write(file.path(), "before\n").expect("writing the file");
+    let commands = [
+        &format!(".output -a {path}"),
+        "SELECT a FROM t",
+        &format!(".once --append {path}"),
+        "SELECT 'once'",
+        "SELECT 'after'",
+    ];
+    assert_eq!(session(&commands), ["after"]);
+    assert_eq!(written(), "before\n1\nonce\n");
+
+    let output = Command::new(BINARY)
+        .arg(db.path())
+        .arg(".output -a")
+        .output()
+        .expect("the binary runs");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("usage: .output [-a] [FILE]"));
+}
+
+#[test]
+fn exit_codes_tell_failures_apart() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "exit",
+        "CREATE TABLE t (a); INSERT INTO t VALUES (1), (2);",
+    );
+    let exit = |database: &Path| {
+        let output = Command::new(BINARY)
+            .arg(database)
+            .arg("select a from t")
+            .output()
+            .expect("the binary runs");
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        (output.status.code(), stderr)
+    };
+    let status = |command: &str| {
+        Command::new(BINARY)
+            .arg(db.path())
+            .arg(command)
+            .status()
+            .expect("the binary runs")
+            .code()
+    };
+
+    assert_eq!(status("select a from t"), Some(0));
+    assert_eq!(status("selec a from t"), Some(3));
+    assert_eq!(status("select a from t group by a"), Some(3));
+    assert_eq!(status("select a from nosuch"), Some(4));
+    assert_eq!(status("select nosuch from t"), Some(4));
+    assert_eq!(status("insert into t values (3)"), Some(6));
+    // A statement interrupted at its `--timeout` exits with 7, as
+    // `timeout_interrupts_a_long_query` checks
+
+    // A file that isn't there, or isn't a database without its key, can't be opened
+    let missing = TempFile::new("missing.db");
+    let (code, stderr) = exit(missing.path());
+    assert_eq!(code, Some(1), "{stderr}");
+
+    let encrypted = TempFile::new("encrypted.db");
+    let mut noise = fs::read(db.path()).expect("reading the fixture");
+    for (n, byte) in noise.iter_mut().enumerate() {
+        *byte = (n * 151 % 251) as u8;
+    }
+    fs::write(encrypted.path(), noise).expect("writing the encrypted file");
+    let (code, stderr) = exit(encrypted.path());
+    assert_eq!(code, Some(1), "{stderr}");
+    assert!(stderr.contains("not a database"), "{stderr}");
+
+    // The table's root page no longer holds a b-tree page
+    let corrupt = TempFile::new("corrupt.db");
+    let mut bytes = fs::read(db.path()).expect("reading the fixture");
+    let page_size = usize::from(u16::from_be_bytes([bytes[16], bytes[17]]));
+    bytes[page_size] = 0xff;
+    fs::write(corrupt.path(), bytes).expect("writing the corrupt file");
+    let (code, stderr) = exit(corrupt.path());
+    assert_eq!(code, Some(5), "{stderr}");
+}
+
+#[test]
+fn readonly_leaves_no_log_behind() {
+    let sqlite3 = require_sqlite3!("command");
+
+    // sqlite3 removes the log and wal-index of a WAL database when it closes it
+    let db = Fixture::build(
+        &sqlite3,
+        "readonly",
+        "PRAGMA journal_mode = WAL;
+         CREATE TABLE t (a); INSERT INTO t VALUES (1), (2);",
+    );
+    let beside = |suffix: &str| {
+        let mut file = db.path().as_os_str().to_owned();
+        file.push(suffix);
+        Path::new(&file).exists()
+    };
+    assert!(!beside("-wal") && !beside("-shm"));
+
+    let readonly = |sql: &str| {
+        Command::new(BINARY)
+            .arg("--readonly")
+            .arg(db.path())
+            .arg(sql)
+            .output()
+            .expect("the binary runs")
+    };
+    let select = "SELECT a FROM t";
+    let output = readonly(select);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .collect::<Vec<_>>(),
+        db.sqlite3(select)
+    );
+    assert!(!beside("-wal") && !beside("-shm"));
+
+    let output = readonly("INSERT INTO t VALUES (3)");
+    assert_eq!(output.status.code(), Some(6));
+    assert!(!beside("-wal") && !beside("-shm"));
+
+    let status = Command::new(BINARY)
+        .args(["--readonly", "--readwrite"])
+        .arg(db.path())
+        .arg(select)
+        .status()
+        .expect("the binary runs");
+    assert_eq!(status.code(), Some(2));
+}
+
+#[test]
+fn piped_scripts_run_past_errors() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "piped",
+        "CREATE TABLE t (a); INSERT INTO t VALUES (1), (2);",
+    );
+    let script = "SELECT a FROM t;\n\
+                  SELECT nosuch FROM t;\n\
+                  .mode csv\n\
+                  SELECT a,\n  'x,y' FROM t;\n\
+                  selec 1;\n\
+                  SELECT 'last';\n";
+    let pipe = |program: &str| {
+        let mut child = Command::new(program)
+            .arg(db.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("the binary runs");
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(script.as_bytes())
+            .expect("writing the script");
+        child.wait_with_output().expect("the binary finishes")
+    };
+
+    // Each error is reported and the rest of the script still runs, with no prompts
+    let output = pipe(BINARY);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&pipe(&sqlite3).stdout)
+    );
+    let errors = stderr.lines().filter(|line| line.starts_with("Error: "));
+    assert_eq!(errors.count(), 2, "{stderr}");
+
+    // The exit status is the first failure's, the missing column rather than the
+    // syntax error after it
+    assert_eq!(output.status.code(), Some(4), "{stderr}");
+
+    // Commands given as arguments stop at the first to fail, as in sqlite3
+    let commands = [
+        "SELECT a FROM t",
+        "SELECT nosuch FROM t",
+        "SELECT 'not run'",
+    ];
+    let output = Command::new(BINARY)
+        .arg(db.path())
+        .args(commands)
+        .output()
+        .expect("the binary runs");
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(
+            &Command::new(&sqlite3)
+                .arg(db.path())
+                .args(commands)
+                .output()
+                .expect("sqlite3 runs")
+                .stdout
+        )
+    );
+}
+
+#[test]
+fn format_prints_one_query_in_a_mode() {
+    let sqlite3 = require_sqlite3!("command");
+
+    let db = Fixture::build(
+        &sqlite3,
+        "format",
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         INSERT INTO t VALUES (1, 'a,b'), (2, NULL);",
+    );
+    let select = "SELECT id, label FROM t ORDER BY id";
+    let session = |commands: &[&str]| {
+        lines(
+            Command::new(BINARY).arg(db.path()).args(commands),
+            &format!("{commands:?}"),
+        )
+    };
+
+    // The mode only lasts for the query it wraps
+    let mut expected = run_sqlite3(&sqlite3, db.path(), &[".mode json", select]);
+    expected.extend(db.sqlite3(select));
+    assert_eq!(
+        session(&[&format!("format(json, {select})"), select]),
+        expected
+    );
+    assert_eq!(
+        session(&[&format!("FORMAT ( csv , {select} )")]),
+        run_sqlite3(&sqlite3, db.path(), &[".mode csv", select])
+    );
+
+    // The SQL function of the same name is left alone
+    let printf = "SELECT format('%d:%s', id, label) FROM t ORDER BY id";
+    assert_eq!(session(&[printf]), db.sqlite3(printf));
+}
+
+#[test]
+fn dbinfo_counts_match_sqlite3() {
+    let sqlite3 = require_sqlite3!("command");
+
+    // SQLite's own tables and automatic indexes count too, and deleting most of the
+    // rows leaves their pages on the freelist
+    let db = Fixture::build(
+        &sqlite3,
+        "dbinfo_counts",
+        "CREATE TABLE a (x);
+         CREATE TABLE b (id INTEGER PRIMARY KEY AUTOINCREMENT, y UNIQUE, z);
+         CREATE INDEX ax ON a (x);
+         CREATE INDEX byz ON b (y, z);
+         CREATE VIEW v AS SELECT x FROM a;
+         CREATE VIEW w AS SELECT y FROM b;
+         CREATE TRIGGER ta AFTER INSERT ON a BEGIN SELECT 1; END;
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 2000)
+         INSERT INTO a SELECT randomblob(100) FROM seq;
+         DELETE FROM a WHERE rowid > 100;
+         ANALYZE;",
+    );
+    assert_ne!(dbinfo_field(&db, "freelist page count"), "0");
+
+    let dbinfo = db.run(".dbinfo");
+    for field in [
+        "database page size",
+        "database page count",
+        "freelist page count",
+        "number of tables",
+        "number of indexes",
+        "number of triggers",
+        "number of views",
+    ] {
+        let expected = format!("{field}: {}", dbinfo_field(&db, field));
+        assert!(dbinfo.contains(&expected), "{expected} in {dbinfo:?}");
+    }
+}
@@ -0,0 +1,103 @@
+//! Helpers the integration tests share for building fixtures with `sqlite3` and
+//! running this crate on them
+//!
+//! Each test binary uses only some of them.
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+pub const BINARY: &str = env!("CARGO_BIN_EXE_codecrafters-sqlite");
+
+/// The `sqlite3` binary to build fixtures and compare against, if there is one
+pub fn sqlite3() -> Option<String> {
+    let binary = env::var("SQLITE3").unwrap_or_else(|_| "sqlite3".to_string());
+    Command::new(&binary)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+        .then_some(binary)
+}
+
+/// The `sqlite3` binary, returning from the test when there isn't one
+macro_rules! require_sqlite3 {
+    ($tests:literal) => {
+        match common::sqlite3() {
+            Some(sqlite3) => sqlite3,
+            None => {
+                eprintln!("skipping the {} tests, sqlite3 isn't installed", $tests);
+                return;
+            }
+        }
+    };
+}
+
+/// A file the test writes, removed once the test is done with it along with any
+/// log or wal-index beside it
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    pub fn new(name: &str) -> Self {
+        let file = Self(
+            env::temp_dir().join(format!("codecrafters-sqlite-{}-{name}", std::process::id())),
+        );
+        file.remove();
+        file
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    fn remove(&self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = self.0.as_os_str().to_owned();
+            file.push(suffix);
+            fs::remove_file(file).ok();
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// A fixture database `sqlite3` builds from `sql`
+pub fn build(sqlite3: &str, name: &str, sql: &str) -> TempFile {
+    let database = TempFile::new(&format!("{name}.db"));
+    run_sqlite3(sqlite3, database.path(), &[sql]);
+    database
+}
+
+/// The lines `sqlite3` prints for the commands run on `database` in turn
+pub fn run_sqlite3(sqlite3: &str, database: &Path, commands: &[&str]) -> Vec<String> {
+    lines(
+        Command::new(sqlite3).arg(database).args(commands),
+        &format!("{commands:?}"),
+    )
+}
+
+/// The lines this crate prints for `command` run on `database`
+pub fn run(database: &Path, command: &str) -> Vec<String> {
+    lines(Command::new(BINARY).arg(database).arg(command), command)
+}
+
+/// The lines the command prints, failing the test if it doesn't succeed
+pub fn lines(command: &mut Command, what: &str) -> Vec<String> {
+    let output = command.output().expect("command runs");
+    assert!(
+        output.status.success(),
+        "{what} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
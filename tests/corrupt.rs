@@ -0,0 +1,199 @@
+//! Runs queries against databases `sqlite3` builds and then corrupts, checking they
+//! fail with the exit status for a corrupt database rather than panicking, looping
+//! or answering from the wrong pages
+//!
+//! The tests are skipped when `sqlite3` isn't installed, and another binary can be
+//! used by setting `SQLITE3`.
+
+#[macro_use]
+mod common;
+
+use common::{run_sqlite3, TempFile, BINARY};
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+/// Exit status for a database that doesn't decode
+const CORRUPT: i32 = 5;
+
+/// Builds the database `name` from `sql`, with the schema left writable so the
+/// script can point its entries at the wrong pages
+fn build_fixture(sqlite3: &str, name: &str, sql: &str) -> TempFile {
+    let database = TempFile::new(&format!("{name}.db"));
+    run_sqlite3(sqlite3, database.path(), &[".dbconfig defensive off", sql]);
+    database
+}
+
+/// Overwrites the bytes at `offset` of the file
+fn patch(database: &Path, offset: usize, bytes: &[u8]) {
+    let mut contents = fs::read(database).expect("reading the fixture");
+    contents[offset..offset + bytes.len()].copy_from_slice(bytes);
+    fs::write(database, contents).expect("writing the fixture");
+}
+
+fn run(database: &Path, command: &str) -> Output {
+    Command::new(BINARY)
+        .arg(database)
+        .arg(command)
+        .output()
+        .expect("the binary runs")
+}
+
+fn assert_corrupt(output: &Output, command: &str) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        output.status.code(),
+        Some(CORRUPT),
+        "{command} should fail as corrupt, stderr: {stderr}"
+    );
+    assert!(!stderr.contains("panicked"), "{command} panicked: {stderr}");
+}
+
+#[test]
+fn table_rooted_at_an_index_page() {
+    let sqlite3 = require_sqlite3!("corrupt database");
+
+    let database = build_fixture(
+        &sqlite3,
+        "table_rooted_at_index",
+        "CREATE TABLE t (a);
+         CREATE INDEX i ON t (a);
+         INSERT INTO t VALUES (1), (2), (3);
+         PRAGMA writable_schema = ON;
+         UPDATE sqlite_schema SET rootpage = (SELECT rootpage FROM sqlite_schema WHERE name = 'i')
+         WHERE name = 't';",
+    );
+
+    for command in ["select count(*) from t", "select a from t"] {
+        assert_corrupt(&run(database.path(), command), command);
+    }
+}
+
+#[test]
+fn index_rooted_at_a_table_page() {
+    let sqlite3 = require_sqlite3!("corrupt database");
+
+    let database = build_fixture(
+        &sqlite3,
+        "index_rooted_at_table",
+        "CREATE TABLE t (a);
+         CREATE INDEX i ON t (a);
+         INSERT INTO t VALUES (1), (2), (3);
+         PRAGMA writable_schema = ON;
+         UPDATE sqlite_schema SET rootpage = (SELECT rootpage FROM sqlite_schema WHERE name = 't')
+         WHERE name = 'i';",
+    );
+
+    for command in ["select a from t where a = 2", "select max(a) from t"] {
+        assert_corrupt(&run(database.path(), command), command);
+    }
+}
+
+#[test]
+fn index_entries_without_a_key() {
+    let sqlite3 = require_sqlite3!("corrupt database");
+
+    // The entries of a WITHOUT ROWID table keyed by an integer read as an index's
+    // entries holding only a rowid
+    let database = build_fixture(
+        &sqlite3,
+        "keyless_index",
+        "CREATE TABLE t (a);
+         CREATE INDEX i ON t (a);
+         INSERT INTO t VALUES (1), (2), (3);
+         CREATE TABLE w (k INTEGER PRIMARY KEY) WITHOUT ROWID;
+         INSERT INTO w VALUES (1), (2), (3);
+         PRAGMA writable_schema = ON;
+         UPDATE sqlite_schema SET rootpage = (SELECT rootpage FROM sqlite_schema WHERE name = 'w')
+         WHERE name = 'i';",
+    );
+
+    for command in [
+        "select a from t where a = 2",
+        "select a from t where a < 3 order by a desc",
+    ] {
+        assert_corrupt(&run(database.path(), command), command);
+    }
+}
+
+#[test]
+fn child_pointer_looping_back_to_the_root() {
+    let sqlite3 = require_sqlite3!("corrupt database");
+
+    let database = build_fixture(
+        &sqlite3,
+        "looping_child",
+        "PRAGMA page_size = 512;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 200)
+         INSERT INTO t SELECT i, 'row ' || i FROM seq;",
+    );
+
+    // Page 2, the root of `t`, becomes its own rightmost child
+    patch(database.path(), 512 + 8, &2u32.to_be_bytes());
+    for command in [
+        "select id, label from t",
+        "select label from t where id = 200",
+        "select max(id) from t",
+        "select count(*) from t",
+        ".btree t",
+        ".btree --dot t",
+    ] {
+        assert_corrupt(&run(database.path(), command), command);
+    }
+}
+
+#[test]
+fn child_pointer_of_zero() {
+    let sqlite3 = require_sqlite3!("corrupt database");
+
+    // Page 2 is the root of `t`, an interior page with 512 byte pages and this many rows
+    let script = "PRAGMA page_size = 512;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 200)
+         INSERT INTO t SELECT i, 'row ' || i FROM seq;";
+    let root = 512;
+
+    let database = build_fixture(&sqlite3, "zero_left_child", script);
+    let contents = fs::read(database.path()).expect("reading the fixture");
+    assert_eq!(contents[root], 0x05, "the root of t is an interior page");
+    let first_cell = u16::from_be_bytes([contents[root + 12], contents[root + 13]]);
+    patch(database.path(), root + first_cell as usize, &[0; 4]);
+    let command = "select count(*) from t";
+    assert_corrupt(&run(database.path(), command), command);
+
+    let database = build_fixture(&sqlite3, "zero_rightmost_child", script);
+    patch(database.path(), root + 8, &[0; 4]);
+    let command = "select id from t where id = 200";
+    assert_corrupt(&run(database.path(), command), command);
+}
+
+#[test]
+fn freelist_leaf_of_zero() {
+    let sqlite3 = require_sqlite3!("corrupt database");
+
+    let database = build_fixture(
+        &sqlite3,
+        "zero_freelist_leaf",
+        "PRAGMA page_size = 512;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT);
+         WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 200)
+         INSERT INTO t SELECT i, 'row ' || i FROM seq;
+         DELETE FROM t WHERE id > 20;",
+    );
+
+    // The first trunk page is in the header, and holds the next trunk and its count
+    // of leaves before the leaves themselves
+    let contents = fs::read(database.path()).expect("reading the fixture");
+    let be_u32 = |offset: usize| {
+        u32::from_be_bytes(contents[offset..offset + 4].try_into().expect("4 bytes"))
+    };
+    let trunk = (be_u32(32) as usize - 1) * 512;
+    assert!(be_u32(trunk + 4) > 0, "the trunk page has leaves");
+    patch(database.path(), trunk + 8, &[0; 4]);
+    for command in [".recover", ".map"] {
+        assert_corrupt(&run(database.path(), command), command);
+    }
+}
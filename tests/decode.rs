@@ -0,0 +1,173 @@
+//! Property tests for page and record decoding: well-formed cells decode to the
+//! values they were built from, and arbitrary bytes decode to errors, not panics
+
+use codecrafters_sqlite::sqlite::{
+    cell::{IndexLeafCell, InteriorIndexCell, InteriorTableCell, LeafCell, Overflow, RecordValue},
+    page::BTreePage,
+    pager::{MemoryPager, Pager},
+    parse_varint, DatabaseHeader,
+};
+use proptest::prelude::*;
+
+const PAGE_SIZE: usize = 4096;
+
+/// A database of empty pages for the overflow chains of the cells under test
+fn pager() -> MemoryPager {
+    let mut database = vec![0; 2 * PAGE_SIZE];
+    database[..16].copy_from_slice(b"SQLite format 3\0");
+    database[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    MemoryPager::new(database).expect("the database has a valid page size")
+}
+
+fn varint(value: u64) -> Vec<u8> {
+    if value >> 56 != 0 {
+        let mut bytes: Vec<u8> = (0..8)
+            .map(|idx| ((value >> (8 + 7 * (7 - idx))) as u8 & 0x7f) | 0x80)
+            .collect();
+        bytes.push(value as u8);
+        return bytes;
+    }
+
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest != 0 {
+        bytes.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.reverse();
+    bytes
+}
+
+/// Values with the serial types they decode back to, small enough that a record of
+/// a few of them stays on its page
+fn value() -> impl Strategy<Value = RecordValue> {
+    prop_oneof![
+        Just(RecordValue::Null),
+        any::<i64>().prop_map(RecordValue::I64),
+        any::<f64>()
+            .prop_filter("NaN never equals itself", |real| !real.is_nan())
+            .prop_map(RecordValue::F64),
+        "[a-zA-Z0-9 ]{0,32}".prop_map(RecordValue::String),
+        prop::collection::vec(any::<u8>(), 0..32).prop_map(RecordValue::Blob),
+    ]
+}
+
+fn record(values: &[RecordValue]) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body = Vec::new();
+    for value in values {
+        let serial_type = match value {
+            RecordValue::Null => 0,
+            RecordValue::I64(integer) => {
+                body.extend(integer.to_be_bytes());
+                6
+            }
+            RecordValue::F64(real) => {
+                body.extend(real.to_be_bytes());
+                7
+            }
+            RecordValue::Blob(blob) => {
+                body.extend(blob);
+                12 + 2 * blob.len() as u64
+            }
+            RecordValue::String(text) => {
+                body.extend(text.as_bytes());
+                13 + 2 * text.len() as u64
+            }
+            other => unreachable!("the strategy doesn't generate {other:?}"),
+        };
+        types.extend(varint(serial_type));
+    }
+
+    // Records of a handful of values have headers under 128 bytes
+    let mut record = varint(types.len() as u64 + 1);
+    record.extend(types);
+    record.extend(body);
+    record
+}
+
+proptest! {
+    #[test]
+    fn varints_round_trip(value: u64) {
+        let bytes = varint(value);
+        prop_assert_eq!(parse_varint(&bytes), (value, bytes.len()));
+    }
+
+    #[test]
+    fn table_leaf_cells_round_trip(
+        row_id: i64,
+        values in prop::collection::vec(value(), 0..8),
+    ) {
+        let payload = record(&values);
+        let mut cell = varint(payload.len() as u64);
+        cell.extend(varint(row_id as u64));
+        cell.extend(&payload);
+
+        let pager = pager();
+        let leaf = LeafCell::new(&cell, Overflow::new(&pager, 0)).unwrap();
+        prop_assert_eq!(leaf.row_id, row_id);
+        prop_assert_eq!(leaf.payload, values);
+        prop_assert_eq!(leaf.overflow_page, None);
+    }
+
+    #[test]
+    fn index_leaf_cells_round_trip(
+        row_id: i64,
+        columns in prop::collection::vec(value(), 0..8),
+    ) {
+        let mut values = columns.clone();
+        values.push(RecordValue::I64(row_id));
+        let payload = record(&values);
+        let mut cell = varint(payload.len() as u64);
+        cell.extend(&payload);
+
+        let pager = pager();
+        let leaf = IndexLeafCell::new(&cell, Overflow::new(&pager, 0)).unwrap();
+        prop_assert_eq!(leaf.row_id, row_id);
+        prop_assert_eq!(leaf.columns, columns);
+    }
+
+    #[test]
+    fn arbitrary_cells_do_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+        let pager = pager();
+        let overflow = Overflow::new(&pager, 0);
+        let _ = LeafCell::new(&bytes, overflow);
+        let _ = InteriorTableCell::new(&bytes);
+        let _ = InteriorIndexCell::new(&bytes, overflow);
+        let _ = IndexLeafCell::new(&bytes, overflow);
+    }
+
+    #[test]
+    fn arbitrary_pages_do_not_panic(
+        page_type in prop::sample::select(vec![2u8, 5, 10, 13]),
+        mut bytes in prop::collection::vec(any::<u8>(), PAGE_SIZE),
+    ) {
+        // Starting with a real page type gets past the first check to the cells
+        bytes[0] = page_type;
+        let pager = pager();
+        let _ = BTreePage::new(&bytes, 1, Overflow::new(&pager, 0));
+    }
+
+    #[test]
+    fn lenient_pages_agree_with_strict(
+        page_type in prop::sample::select(vec![2u8, 5, 10, 13]),
+        mut bytes in prop::collection::vec(any::<u8>(), PAGE_SIZE),
+    ) {
+        bytes[0] = page_type;
+        let pager = pager();
+        let lenient = BTreePage::lenient(&bytes, 1, Overflow::new(&pager, 0));
+        if let Ok(strict) = BTreePage::new(&bytes, 1, Overflow::new(&pager, 0)) {
+            prop_assert_eq!(lenient.map(|page| page.cells.len()).ok(), Some(strict.cells.len()));
+        }
+    }
+
+    #[test]
+    fn pages_past_the_end_are_errors(n in 2..usize::MAX) {
+        prop_assert!(pager().get_page(n).is_err());
+    }
+
+    #[test]
+    fn arbitrary_headers_do_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..128)) {
+        let _ = DatabaseHeader::new(&bytes);
+    }
+}
@@ -0,0 +1,150 @@
+//! Calls the C interface the way a C program would, checking the rows handed to the
+//! callback against what `sqlite3` prints for the same queries
+//!
+//! The tests are skipped when `sqlite3` isn't installed, and another binary can be
+//! used by setting `SQLITE3`.
+
+#[macro_use]
+mod common;
+
+use codecrafters_sqlite::ffi::{
+    csql_close, csql_errmsg, csql_exec, csql_open, CsqlDatabase, CSQL_ABORT, CSQL_ERROR,
+    CSQL_MISUSE, CSQL_OK,
+};
+use std::{
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    path::Path,
+    ptr,
+};
+
+/// What `sqlite3` prints in place of NULL, which the callback gets as a null pointer
+const NULL: &str = "<null>";
+
+/// The rows `sqlite3` prints for `sql`, with NULL shown the way [`collect`] shows it
+fn run_sqlite3(sqlite3: &str, database: &Path, sql: &str) -> Vec<String> {
+    common::run_sqlite3(sqlite3, database, &[&format!(".nullvalue {NULL}"), sql])
+}
+
+/// Rows the callback was handed, with the names of the columns of the last one
+#[derive(Default)]
+struct Collected {
+    rows: Vec<String>,
+    names: Vec<String>,
+    /// Rows to take before asking `csql_exec` to stop
+    limit: Option<usize>,
+}
+
+unsafe extern "C" fn collect(
+    user_data: *mut c_void,
+    columns: c_int,
+    values: *mut *mut c_char,
+    names: *mut *mut c_char,
+) -> c_int {
+    let collected = &mut *user_data.cast::<Collected>();
+    let text = |ptr: *mut c_char| match ptr.is_null() {
+        true => NULL.to_string(),
+        false => CStr::from_ptr(ptr).to_string_lossy().into_owned(),
+    };
+
+    let columns = columns as usize;
+    let values = std::slice::from_raw_parts(values, columns);
+    collected.rows.push(
+        values
+            .iter()
+            .map(|&ptr| text(ptr))
+            .collect::<Vec<_>>()
+            .join("|"),
+    );
+    collected.names = std::slice::from_raw_parts(names, columns)
+        .iter()
+        .map(|&ptr| text(ptr))
+        .collect();
+
+    c_int::from(collected.limit == Some(collected.rows.len()))
+}
+
+unsafe fn errmsg(db: *const CsqlDatabase) -> String {
+    CStr::from_ptr(csql_errmsg(db))
+        .to_string_lossy()
+        .into_owned()
+}
+
+unsafe fn exec(db: *mut CsqlDatabase, sql: &str, collected: &mut Collected) -> c_int {
+    let sql = CString::new(sql).expect("no NUL in the SQL");
+    csql_exec(
+        db,
+        sql.as_ptr(),
+        Some(collect),
+        ptr::from_mut(collected).cast(),
+    )
+}
+
+#[test]
+fn exec_hands_rows_to_the_callback() {
+    let sqlite3 = require_sqlite3!("C interface");
+
+    let database = common::build(
+        &sqlite3,
+        "ffi",
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT, score REAL);
+         INSERT INTO t VALUES (1, 'one', 1.5), (2, NULL, -2.0), (3, 'three', NULL), (4, 'four', 4.25);",
+    );
+
+    unsafe {
+        let path = database.path();
+        let c_path = CString::new(path.to_str().expect("a UTF-8 path")).expect("no NUL");
+        let mut db = ptr::null_mut();
+        assert_eq!(csql_open(c_path.as_ptr(), &mut db), CSQL_OK);
+        assert_eq!(errmsg(db), "not an error");
+
+        // Every row of every statement goes to the callback in turn
+        let sql = "SELECT id, label, score FROM t ORDER BY id; SELECT count(*) FROM t";
+        let mut collected = Collected::default();
+        assert_eq!(exec(db, sql, &mut collected), CSQL_OK);
+        assert_eq!(collected.rows, run_sqlite3(&sqlite3, path, sql));
+        assert_eq!(collected.names, ["count(*)"]);
+
+        let sql = "SELECT label, id FROM t WHERE score > 0 ORDER BY score DESC";
+        let mut collected = Collected::default();
+        assert_eq!(exec(db, sql, &mut collected), CSQL_OK);
+        assert_eq!(collected.rows, run_sqlite3(&sqlite3, path, sql));
+        assert_eq!(collected.names, ["label", "id"]);
+
+        // A non-zero return stops the query and the statements after it
+        let mut collected = Collected {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let sql = "SELECT id FROM t ORDER BY id; SELECT count(*) FROM t";
+        assert_eq!(exec(db, sql, &mut collected), CSQL_ABORT);
+        assert_eq!(collected.rows, ["1", "2"]);
+        assert_eq!(errmsg(db), "query aborted");
+
+        let mut collected = Collected::default();
+        assert_eq!(
+            exec(db, "SELECT a FROM missing", &mut collected),
+            CSQL_ERROR
+        );
+        assert!(errmsg(db).contains("missing"), "{}", errmsg(db));
+        assert!(collected.rows.is_empty());
+
+        assert_eq!(
+            csql_exec(db, ptr::null(), None, ptr::null_mut()),
+            CSQL_MISUSE
+        );
+        csql_close(db);
+
+        // A handle is stored for a database that fails to open, to read the reason from
+        let missing = CString::new(format!("{}-missing", path.display())).expect("no NUL");
+        let mut db = ptr::null_mut();
+        assert_eq!(csql_open(missing.as_ptr(), &mut db), CSQL_ERROR);
+        assert!(!db.is_null());
+        assert_ne!(errmsg(db), "not an error");
+        let select = CString::new("SELECT 1").expect("no NUL");
+        assert_eq!(
+            csql_exec(db, select.as_ptr(), None, ptr::null_mut()),
+            CSQL_MISUSE
+        );
+        csql_close(db);
+    }
+}
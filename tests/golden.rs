@@ -0,0 +1,140 @@
+//! Runs the queries in `tests/golden` through both this crate and the `sqlite3`
+//! binary and compares what they print
+//!
+//! Each `NAME.sql` script builds a fixture database with `sqlite3`, and `NAME.test`
+//! holds the queries to run on it, one to a line, with `--` starting a comment. The
+//! rows of a query without an ORDER BY can come back in any order. The test is
+//! skipped when `sqlite3` isn't installed, and another binary can be used by
+//! setting `SQLITE3`.
+
+#[macro_use]
+mod common;
+
+use common::{run_sqlite3, BINARY};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+
+/// Builds the fixture database from `script` in `dir`
+fn build_fixture(sqlite3: &str, script: &Path, dir: &Path) -> PathBuf {
+    let name = script.file_stem().expect("scripts have names");
+    let database = dir.join(name).with_extension("db");
+    fs::remove_file(&database).ok();
+    run_sqlite3(
+        sqlite3,
+        &database,
+        &[&format!(".read '{}'", script.display())],
+    );
+    database
+}
+
+/// What a query printed and whether it succeeded
+#[derive(PartialEq)]
+struct Outcome {
+    success: bool,
+    lines: Vec<String>,
+}
+
+impl Outcome {
+    fn run(command: &mut Command, ordered: bool) -> Self {
+        let output = command.output().expect("command runs");
+        let mut lines: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect();
+        if !ordered {
+            lines.sort_unstable();
+        }
+
+        Self {
+            success: output.status.success(),
+            lines,
+        }
+    }
+
+    /// Where `self` first differs from `expected`, for the failure message
+    fn describe_difference(&self, expected: &Outcome) -> String {
+        let status = |success| if success { "succeeded" } else { "failed" };
+        if self.success != expected.success {
+            return format!(
+                "sqlite3 {}, this crate {}",
+                status(expected.success),
+                status(self.success)
+            );
+        }
+
+        let line = self
+            .lines
+            .iter()
+            .zip(&expected.lines)
+            .position(|(actual, expected)| actual != expected)
+            .unwrap_or(self.lines.len().min(expected.lines.len()));
+        let shorten = |lines: &[String]| {
+            let text = lines.get(line).map_or("<none>", String::as_str);
+            text.chars().take(120).collect::<String>()
+        };
+
+        format!(
+            "{} lines against {}, first differing at line {}:\n    expected: {}\n    actual:   {}",
+            self.lines.len(),
+            expected.lines.len(),
+            line + 1,
+            shorten(&expected.lines),
+            shorten(&self.lines),
+        )
+    }
+}
+
+#[test]
+fn matches_sqlite3() {
+    let sqlite3 = require_sqlite3!("golden");
+
+    let dir = env::temp_dir().join(format!("codecrafters-sqlite-golden-{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("creating the fixture directory");
+
+    let mut scripts: Vec<PathBuf> = fs::read_dir(GOLDEN_DIR)
+        .expect("reading the golden directory")
+        .map(|entry| entry.expect("reading a golden file").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sql"))
+        .collect();
+    scripts.sort();
+
+    let mut failures = Vec::new();
+    let mut queries = 0;
+    for script in &scripts {
+        let database = build_fixture(&sqlite3, script, &dir);
+        let corpus = fs::read_to_string(script.with_extension("test"))
+            .unwrap_or_else(|e| panic!("reading the queries for {}: {e}", script.display()));
+
+        for query in corpus
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("--"))
+        {
+            queries += 1;
+            let ordered = query.to_lowercase().contains("order by");
+            let expected = Outcome::run(Command::new(&sqlite3).arg(&database).arg(query), ordered);
+            let actual = Outcome::run(Command::new(BINARY).arg(&database).arg(query), ordered);
+            if actual != expected {
+                let name = database.file_name().expect("fixtures have names");
+                failures.push(format!(
+                    "[{}] {query}\n  {}",
+                    name.to_string_lossy(),
+                    actual.describe_difference(&expected)
+                ));
+            }
+        }
+    }
+
+    fs::remove_dir_all(&dir).ok();
+    assert!(
+        failures.is_empty(),
+        "{} of {queries} queries differ from sqlite3:\n\n{}",
+        failures.len(),
+        failures.join("\n\n")
+    );
+}
@@ -0,0 +1,9 @@
+CREATE TABLE t (id INTEGER PRIMARY KEY, n INTEGER, s TEXT, r REAL, b BLOB);
+INSERT INTO t VALUES
+    (1, 5, '5', 1.5, '5'),
+    (2, '7', 7, 2, 5),
+    (3, 300, 'abc', '1.75', 'x'),
+    (4, -12, '-12', -0.5, -12),
+    (5, 'text', '10', 1e3, 10),
+    (6, 4.0, '4.0', 4, '4.0'),
+    (7, 2.5, '2.50', 2.5, 2.5);
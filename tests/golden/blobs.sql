@@ -0,0 +1,8 @@
+CREATE TABLE h (id INTEGER PRIMARY KEY, b BLOB, n INT);
+CREATE INDEX hb ON h (b);
+
+INSERT INTO h VALUES
+    (1, X'DEADBEEF', 31),
+    (2, X'', -1),
+    (3, X'00FF', 255),
+    (4, 'text', 16);
@@ -0,0 +1,14 @@
+CREATE TABLE items (
+    id INTEGER PRIMARY KEY,
+    qty INTEGER CHECK (qty >= 0),
+    price REAL CONSTRAINT positive_price CHECK (price > 0) NOT NULL,
+    low INTEGER,
+    high INTEGER CHECK (high IS NULL OR high < 1000),
+    CHECK (low <= high),
+    CONSTRAINT narrow CHECK (high - low < 100)
+);
+
+INSERT INTO items (qty, price, low, high) VALUES
+    (1, 2.5, 0, 10),
+    (0, 0.5, NULL, 99),
+    (NULL, 1.0, 5, 5);
@@ -0,0 +1,13 @@
+CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT COLLATE NOCASE, city TEXT);
+CREATE INDEX idx_people_city ON people (city);
+INSERT INTO people (name, city) VALUES
+    ('alice', 'Paris'), ('Alice', 'paris'), ('ALICE', 'Oslo'), ('bob', 'oslo'),
+    ('Bob ', 'Lima'), ('carol', 'lima'), ('Dave', 'Quito'), ('erin', 'quito ');
+
+CREATE TABLE pets (id INTEGER PRIMARY KEY, name TEXT, kind TEXT COLLATE RTRIM);
+INSERT INTO pets (name, kind) VALUES
+    ('Rex', 'dog'), ('rex', 'dog  '), ('Fluffy', 'cat'), ('fluffy', 'Cat'), ('Nemo', 'fish ');
+
+CREATE TABLE tags (id INTEGER PRIMARY KEY, a TEXT COLLATE NOCASE);
+CREATE INDEX ia ON tags (a DESC);
+INSERT INTO tags (a) VALUES ('abc'), ('ABC'), ('Abd'), ('b'), ('B'), ('aBC'), ('c');
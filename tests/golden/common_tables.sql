@@ -0,0 +1,11 @@
+CREATE TABLE staff (id INTEGER PRIMARY KEY, name TEXT, team TEXT COLLATE NOCASE, salary INTEGER);
+CREATE TABLE teams (id INTEGER PRIMARY KEY, team TEXT, floor INTEGER);
+CREATE INDEX staff_team ON staff (team);
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 120)
+INSERT INTO staff
+SELECT i, 'person' || i, CASE i % 4 WHEN 0 THEN 'Red' WHEN 1 THEN 'blue' WHEN 2 THEN 'GREEN' ELSE NULL END,
+       30000 + (i * 977) % 40000
+FROM n;
+
+INSERT INTO teams VALUES (1, 'red', 1), (2, 'blue', 2), (3, 'green', 2), (4, 'gold', 3);
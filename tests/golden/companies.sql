@@ -0,0 +1,7 @@
+-- Enough rows for the table and its index to need interior pages
+CREATE TABLE companies (id INTEGER PRIMARY KEY, name TEXT, country TEXT, size INTEGER);
+CREATE INDEX idx_companies_country ON companies (country);
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 20000)
+INSERT INTO companies
+SELECT i, 'company ' || i, char(97 + i % 26, 97 + i * 7 % 23), i * 37 % 1000 FROM n;
@@ -0,0 +1,3 @@
+CREATE TABLE prices (id INTEGER PRIMARY KEY, item TEXT, cents INTEGER, rate REAL);
+
+INSERT INTO prices VALUES (1, 'tea', 250, 0.2), (2, 'cake', 425, 0.2), (3, 'water', 100, NULL);
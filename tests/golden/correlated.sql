@@ -0,0 +1,14 @@
+CREATE TABLE authors (id INTEGER PRIMARY KEY, name TEXT, born INTEGER, debut INTEGER);
+CREATE TABLE books (id INTEGER PRIMARY KEY, author_id INTEGER, title TEXT COLLATE NOCASE, year INTEGER);
+CREATE INDEX books_author ON books (author_id);
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 60)
+INSERT INTO authors
+SELECT i, 'author' || i, 1900 + i, CASE WHEN i % 7 = 0 THEN NULL ELSE 1920 + (i * 13) % 50 END
+FROM n;
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 300)
+INSERT INTO books
+SELECT i, CASE WHEN i % 23 = 0 THEN NULL ELSE (i * 7) % 45 + 1 END,
+       CASE WHEN i % 3 THEN 'Book' ELSE 'book' END || (i % 10), 1920 + i % 80
+FROM n;
@@ -0,0 +1,9 @@
+-- Small pages so the table and index b-trees are three levels deep, with every
+-- interior page's right-most child holding rows of its own
+PRAGMA page_size = 512;
+CREATE TABLE t (id INTEGER PRIMARY KEY, k INTEGER, label TEXT, flag INTEGER);
+CREATE INDEX idx_t_k ON t (k);
+CREATE INDEX idx_t_flag ON t (flag);
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 6000)
+INSERT INTO t SELECT i, i % 97, 'row ' || i || ' ' || hex(zeroblob(i % 13)), i % 2 FROM n;
@@ -0,0 +1,13 @@
+CREATE TABLE item (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+
+INSERT INTO item VALUES (1, 'bolt'), (2, 'nut'), (3, 'washer');
+
+ALTER TABLE item ADD COLUMN price REAL DEFAULT 1;
+ALTER TABLE item ADD COLUMN code TEXT DEFAULT 42;
+ALTER TABLE item ADD COLUMN stock INTEGER DEFAULT '7';
+ALTER TABLE item ADD COLUMN unit DEFAULT each;
+ALTER TABLE item ADD COLUMN active DEFAULT TRUE;
+ALTER TABLE item ADD COLUMN weight DEFAULT (-2.5);
+ALTER TABLE item ADD COLUMN note TEXT;
+
+INSERT INTO item (id, name, price, stock) VALUES (4, 'spring', 0.25, 100);
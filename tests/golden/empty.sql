@@ -0,0 +1,7 @@
+-- Tables and indexes with no rows, whose root pages are leaves without cells
+CREATE TABLE t (a, b);
+CREATE INDEX idx_t_a ON t (a);
+CREATE TABLE u (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE emptied (id INTEGER PRIMARY KEY, v);
+INSERT INTO emptied VALUES (1, 'x'), (2, 'y');
+DELETE FROM emptied;
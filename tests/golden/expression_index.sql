@@ -0,0 +1,8 @@
+CREATE TABLE person (id INTEGER PRIMARY KEY, name TEXT, age INTEGER);
+CREATE INDEX person_lower_name ON person (lower(name));
+CREATE INDEX person_name_length ON person (length(name) DESC);
+CREATE INDEX person_double_age ON person (age * 2, name);
+CREATE UNIQUE INDEX person_upper_name ON person (upper(name) COLLATE NOCASE);
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 80)
+INSERT INTO person SELECT i, CASE i % 4 WHEN 0 THEN 'Ada' WHEN 1 THEN 'BOB' WHEN 2 THEN 'cy' ELSE 'Dee' END || i, i % 30 FROM n;
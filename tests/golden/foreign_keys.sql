@@ -0,0 +1,20 @@
+CREATE TABLE artist (id INTEGER PRIMARY KEY, name TEXT, UNIQUE (id, name));
+CREATE TABLE album (
+    id INTEGER PRIMARY KEY,
+    artist_id INTEGER NOT NULL REFERENCES artist (id) ON DELETE CASCADE,
+    artist_name TEXT,
+    title TEXT,
+    FOREIGN KEY (artist_id, artist_name) REFERENCES artist (id, name)
+        ON UPDATE SET NULL ON DELETE RESTRICT DEFERRABLE INITIALLY DEFERRED
+);
+CREATE TABLE track (
+    id INTEGER PRIMARY KEY,
+    album_id INTEGER,
+    name TEXT COLLATE NOCASE,
+    CONSTRAINT track_album FOREIGN KEY (album_id) REFERENCES "album" ("id")
+        MATCH SIMPLE ON UPDATE SET DEFAULT ON DELETE NO ACTION
+);
+
+INSERT INTO artist VALUES (1, 'Nina'), (2, 'Miles');
+INSERT INTO album VALUES (1, 1, 'Nina', 'Pastel Blues'), (2, 2, 'Miles', 'Kind of Blue');
+INSERT INTO track VALUES (1, 1, 'Sinnerman'), (2, 2, 'So What'), (3, 2, 'Blue in Green');
@@ -0,0 +1,10 @@
+-- Full-text tables keeping their own content, and one indexing another table's
+CREATE VIRTUAL TABLE notes USING fts5 (title, body UNINDEXED);
+INSERT INTO notes VALUES
+    ('groceries', 'milk, eggs and bread'), ('todo', 'fix the fence'),
+    ('ideas', NULL), ('Groceries', 'more eggs');
+
+CREATE TABLE posts (id INTEGER PRIMARY KEY, title TEXT, body TEXT);
+INSERT INTO posts VALUES (3, 'hello', 'first post'), (7, 'again', 'second post'), (9, 'bye', '');
+CREATE VIRTUAL TABLE posts_fts USING fts5 (title, body, content = 'posts', content_rowid = 'id');
+INSERT INTO posts_fts (posts_fts) VALUES ('rebuild');
@@ -0,0 +1,16 @@
+CREATE TABLE box (
+    id INTEGER PRIMARY KEY,
+    w REAL,
+    h REAL,
+    area REAL GENERATED ALWAYS AS (w * h) VIRTUAL,
+    label TEXT GENERATED ALWAYS AS (coalesce(name, 'box') || '-' || id) STORED,
+    name TEXT,
+    wide AS (w > h),
+    qty INTEGER,
+    packs INTEGER AS (qty / 6),
+    loose AS (qty % 6),
+    spare AS (-qty + 10 - w * 2)
+);
+CREATE INDEX box_wide ON box (wide);
+
+INSERT INTO box (id, w, h, name, qty) VALUES (1, 2, 3, 'small', 14), (2, 4, 1.5, NULL, 6), (3, 1, 1, 'cube', NULL);
@@ -0,0 +1,6 @@
+CREATE TABLE Apples (id INTEGER PRIMARY KEY, name TEXT, Color TEXT);
+CREATE INDEX apples_color ON apples (COLOR);
+CREATE TABLE "My Table" ("Some Col" INT, [odd "name"] TEXT, `a``b` INT);
+
+INSERT INTO Apples VALUES (1, 'Granny', 'green'), (2, 'Fuji', 'red'), (3, 'Gala', 'red');
+INSERT INTO "My Table" VALUES (1, 'one', 10), (3, 'three', 30);
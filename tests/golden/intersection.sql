@@ -0,0 +1,8 @@
+CREATE TABLE orders (id INTEGER PRIMARY KEY, customer INTEGER, product INTEGER, qty INTEGER);
+CREATE INDEX orders_customer ON orders (customer);
+CREATE INDEX orders_product ON orders (product);
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 20000)
+INSERT INTO orders SELECT i, i % 150, (i / 7) % 170, i % 9 FROM n;
+
+ANALYZE;
@@ -0,0 +1,18 @@
+CREATE TABLE customer (id INTEGER PRIMARY KEY, name TEXT, city TEXT COLLATE NOCASE, code TEXT);
+CREATE TABLE purchase (id INTEGER PRIMARY KEY, customer INT, amount REAL, code TEXT, note);
+CREATE TABLE tag (label TEXT COLLATE NOCASE, note TEXT);
+CREATE INDEX purchase_customer ON purchase (customer);
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 150)
+INSERT INTO customer
+SELECT i, 'c' || i, CASE i % 3 WHEN 0 THEN 'York' WHEN 1 THEN 'HULL' ELSE 'leeds' END,
+       CASE WHEN i % 9 = 0 THEN NULL ELSE 'k' || (i % 20) END
+FROM n;
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1200)
+INSERT INTO purchase
+SELECT i, CASE WHEN i % 13 = 0 THEN NULL ELSE i % 180 END, i * 1.5, 'k' || (i % 25),
+       CASE i % 4 WHEN 0 THEN '1' WHEN 1 THEN 1 WHEN 2 THEN 'a' ELSE NULL END
+FROM n;
+
+INSERT INTO tag VALUES ('York', 'x'), ('hull', 'y'), ('LEEDS', 'z'), ('york', 'w'), ('1', 'one'), ('a', 'A');
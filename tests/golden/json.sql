@@ -0,0 +1,11 @@
+CREATE TABLE docs (id INTEGER PRIMARY KEY, body TEXT);
+
+INSERT INTO docs VALUES
+    (1, '{"name":"ada","age":36,"tags":["math","code"],"addr":{"city":"London","zip":null},"ok":true,"score":1.5}'),
+    (2, '{"name":"bob","age":null,"tags":[],"ok":false}'),
+    (3, '[1, 2.5, "three", null, {"a": [true]}]'),
+    (4, '{"name": "unterminated'),
+    (5, 'not json'),
+    (6, NULL),
+    (7, '"just a string"'),
+    (8, '42');
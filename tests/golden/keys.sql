@@ -0,0 +1,7 @@
+CREATE TABLE account (email TEXT UNIQUE COLLATE NOCASE, handle TEXT NOT NULL UNIQUE, id INTEGER, PRIMARY KEY (id));
+CREATE TABLE ledger (id INTEGER PRIMARY KEY DESC, account_id INTEGER, amount INTEGER, UNIQUE (account_id, amount));
+CREATE TABLE tag (name TEXT PRIMARY KEY, uses INTEGER);
+
+INSERT INTO account VALUES ('ada@example.com', 'ada', 10), ('Bob@example.com', 'bob', 20), (NULL, 'anon', 30);
+INSERT INTO ledger VALUES (3, 10, 500), (1, 20, -25), (2, 10, 75);
+INSERT INTO tag VALUES ('rust', 3), ('sqlite', 5), ('btree', 1);
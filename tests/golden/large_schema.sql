@@ -0,0 +1,244 @@
+-- Enough tables and indexes that sqlite_schema outgrows the first page and gets
+-- an interior root
+CREATE TABLE t0 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t0_v ON t0 (v);
+CREATE TABLE t1 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t1_v ON t1 (v);
+CREATE TABLE t2 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t2_v ON t2 (v);
+CREATE TABLE t3 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t3_v ON t3 (v);
+CREATE TABLE t4 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t4_v ON t4 (v);
+CREATE TABLE t5 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t5_v ON t5 (v);
+CREATE TABLE t6 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t6_v ON t6 (v);
+CREATE TABLE t7 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t7_v ON t7 (v);
+CREATE TABLE t8 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t8_v ON t8 (v);
+CREATE TABLE t9 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t9_v ON t9 (v);
+CREATE TABLE t10 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t10_v ON t10 (v);
+CREATE TABLE t11 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t11_v ON t11 (v);
+CREATE TABLE t12 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t12_v ON t12 (v);
+CREATE TABLE t13 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t13_v ON t13 (v);
+CREATE TABLE t14 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t14_v ON t14 (v);
+CREATE TABLE t15 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t15_v ON t15 (v);
+CREATE TABLE t16 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t16_v ON t16 (v);
+CREATE TABLE t17 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t17_v ON t17 (v);
+CREATE TABLE t18 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t18_v ON t18 (v);
+CREATE TABLE t19 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t19_v ON t19 (v);
+CREATE TABLE t20 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t20_v ON t20 (v);
+CREATE TABLE t21 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t21_v ON t21 (v);
+CREATE TABLE t22 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t22_v ON t22 (v);
+CREATE TABLE t23 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t23_v ON t23 (v);
+CREATE TABLE t24 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t24_v ON t24 (v);
+CREATE TABLE t25 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t25_v ON t25 (v);
+CREATE TABLE t26 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t26_v ON t26 (v);
+CREATE TABLE t27 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t27_v ON t27 (v);
+CREATE TABLE t28 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t28_v ON t28 (v);
+CREATE TABLE t29 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t29_v ON t29 (v);
+CREATE TABLE t30 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t30_v ON t30 (v);
+CREATE TABLE t31 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t31_v ON t31 (v);
+CREATE TABLE t32 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t32_v ON t32 (v);
+CREATE TABLE t33 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t33_v ON t33 (v);
+CREATE TABLE t34 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t34_v ON t34 (v);
+CREATE TABLE t35 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t35_v ON t35 (v);
+CREATE TABLE t36 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t36_v ON t36 (v);
+CREATE TABLE t37 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t37_v ON t37 (v);
+CREATE TABLE t38 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t38_v ON t38 (v);
+CREATE TABLE t39 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t39_v ON t39 (v);
+CREATE TABLE t40 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t40_v ON t40 (v);
+CREATE TABLE t41 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t41_v ON t41 (v);
+CREATE TABLE t42 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t42_v ON t42 (v);
+CREATE TABLE t43 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t43_v ON t43 (v);
+CREATE TABLE t44 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t44_v ON t44 (v);
+CREATE TABLE t45 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t45_v ON t45 (v);
+CREATE TABLE t46 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t46_v ON t46 (v);
+CREATE TABLE t47 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t47_v ON t47 (v);
+CREATE TABLE t48 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t48_v ON t48 (v);
+CREATE TABLE t49 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t49_v ON t49 (v);
+CREATE TABLE t50 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t50_v ON t50 (v);
+CREATE TABLE t51 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t51_v ON t51 (v);
+CREATE TABLE t52 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t52_v ON t52 (v);
+CREATE TABLE t53 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t53_v ON t53 (v);
+CREATE TABLE t54 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t54_v ON t54 (v);
+CREATE TABLE t55 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t55_v ON t55 (v);
+CREATE TABLE t56 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t56_v ON t56 (v);
+CREATE TABLE t57 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t57_v ON t57 (v);
+CREATE TABLE t58 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t58_v ON t58 (v);
+CREATE TABLE t59 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t59_v ON t59 (v);
+CREATE TABLE t60 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t60_v ON t60 (v);
+CREATE TABLE t61 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t61_v ON t61 (v);
+CREATE TABLE t62 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t62_v ON t62 (v);
+CREATE TABLE t63 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t63_v ON t63 (v);
+CREATE TABLE t64 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t64_v ON t64 (v);
+CREATE TABLE t65 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t65_v ON t65 (v);
+CREATE TABLE t66 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t66_v ON t66 (v);
+CREATE TABLE t67 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t67_v ON t67 (v);
+CREATE TABLE t68 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t68_v ON t68 (v);
+CREATE TABLE t69 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t69_v ON t69 (v);
+CREATE TABLE t70 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t70_v ON t70 (v);
+CREATE TABLE t71 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t71_v ON t71 (v);
+CREATE TABLE t72 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t72_v ON t72 (v);
+CREATE TABLE t73 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t73_v ON t73 (v);
+CREATE TABLE t74 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t74_v ON t74 (v);
+CREATE TABLE t75 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t75_v ON t75 (v);
+CREATE TABLE t76 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t76_v ON t76 (v);
+CREATE TABLE t77 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t77_v ON t77 (v);
+CREATE TABLE t78 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t78_v ON t78 (v);
+CREATE TABLE t79 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t79_v ON t79 (v);
+CREATE TABLE t80 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t80_v ON t80 (v);
+CREATE TABLE t81 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t81_v ON t81 (v);
+CREATE TABLE t82 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t82_v ON t82 (v);
+CREATE TABLE t83 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t83_v ON t83 (v);
+CREATE TABLE t84 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t84_v ON t84 (v);
+CREATE TABLE t85 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t85_v ON t85 (v);
+CREATE TABLE t86 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t86_v ON t86 (v);
+CREATE TABLE t87 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t87_v ON t87 (v);
+CREATE TABLE t88 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t88_v ON t88 (v);
+CREATE TABLE t89 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t89_v ON t89 (v);
+CREATE TABLE t90 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t90_v ON t90 (v);
+CREATE TABLE t91 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t91_v ON t91 (v);
+CREATE TABLE t92 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t92_v ON t92 (v);
+CREATE TABLE t93 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t93_v ON t93 (v);
+CREATE TABLE t94 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t94_v ON t94 (v);
+CREATE TABLE t95 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t95_v ON t95 (v);
+CREATE TABLE t96 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t96_v ON t96 (v);
+CREATE TABLE t97 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t97_v ON t97 (v);
+CREATE TABLE t98 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t98_v ON t98 (v);
+CREATE TABLE t99 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t99_v ON t99 (v);
+CREATE TABLE t100 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t100_v ON t100 (v);
+CREATE TABLE t101 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t101_v ON t101 (v);
+CREATE TABLE t102 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t102_v ON t102 (v);
+CREATE TABLE t103 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t103_v ON t103 (v);
+CREATE TABLE t104 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t104_v ON t104 (v);
+CREATE TABLE t105 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t105_v ON t105 (v);
+CREATE TABLE t106 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t106_v ON t106 (v);
+CREATE TABLE t107 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t107_v ON t107 (v);
+CREATE TABLE t108 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t108_v ON t108 (v);
+CREATE TABLE t109 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t109_v ON t109 (v);
+CREATE TABLE t110 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t110_v ON t110 (v);
+CREATE TABLE t111 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t111_v ON t111 (v);
+CREATE TABLE t112 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t112_v ON t112 (v);
+CREATE TABLE t113 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t113_v ON t113 (v);
+CREATE TABLE t114 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t114_v ON t114 (v);
+CREATE TABLE t115 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t115_v ON t115 (v);
+CREATE TABLE t116 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t116_v ON t116 (v);
+CREATE TABLE t117 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t117_v ON t117 (v);
+CREATE TABLE t118 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t118_v ON t118 (v);
+CREATE TABLE t119 (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX idx_t119_v ON t119 (v);
+INSERT INTO t0 VALUES (1, 'first');
+INSERT INTO t119 VALUES (1, 'last'), (2, 'after');
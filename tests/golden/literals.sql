@@ -0,0 +1,8 @@
+CREATE TABLE person (id INTEGER PRIMARY KEY, name TEXT, born TEXT, score REAL, note);
+CREATE INDEX person_name ON person (name);
+
+INSERT INTO person VALUES
+    (1, 'O''Brien', '1990-01-02', 1.5, NULL),
+    (2, 'a,b; c!', '2001-12-31', -2, 'x'),
+    (3, 'plain', '1990-01-02', 1e3, 3),
+    (4, 'It''s ''quoted''', 'x', 0.5, 'NULL');
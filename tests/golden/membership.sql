@@ -0,0 +1,13 @@
+CREATE TABLE parts (id INTEGER PRIMARY KEY, bin INTEGER, weight REAL, code TEXT, label TEXT COLLATE NOCASE);
+CREATE INDEX parts_bin ON parts (bin);
+CREATE TABLE wanted (bin);
+CREATE TABLE mixed (value);
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500)
+INSERT INTO parts
+SELECT i, CASE WHEN i % 19 = 0 THEN NULL ELSE i % 45 END, i / 4.0, 'c' || (i % 30),
+       CASE WHEN i % 2 THEN 'Bolt' ELSE 'bolt' END || (i % 12)
+FROM n;
+
+INSERT INTO wanted VALUES (3), (7), ('11'), (12.0), (40);
+INSERT INTO mixed VALUES (1), (NULL), (2);
@@ -0,0 +1,7 @@
+CREATE TABLE reading (id INTEGER PRIMARY KEY, sensor TEXT, value REAL, raw BLOB);
+
+INSERT INTO reading VALUES
+    (1, 'north', 1.5, x'68656c6c6f'),
+    (2, NULL, 2.25, NULL),
+    (3, 'south', NULL, x'6869000a'),
+    (4, 'east', NULL, x'');
@@ -0,0 +1,9 @@
+-- Payloads spilling onto chains of overflow pages, in the table and the index
+CREATE TABLE t (id INTEGER PRIMARY KEY, k TEXT, body TEXT);
+CREATE INDEX idx_t_k ON t (k);
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 40)
+INSERT INTO t
+SELECT i, char(97 + i % 5) || replace(hex(zeroblob(i * 40)), '00', 'k'),
+       replace(hex(zeroblob(i * 500)), '00', char(65 + i % 26))
+FROM n;
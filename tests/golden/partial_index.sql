@@ -0,0 +1,7 @@
+CREATE TABLE task (id INTEGER PRIMARY KEY, status TEXT, priority INTEGER);
+CREATE INDEX task_open ON task (status) WHERE status = 'open';
+CREATE INDEX task_urgent ON task (priority) WHERE priority IS NOT NULL AND status = 'open';
+CREATE INDEX task_assigned ON task (status COLLATE NOCASE) WHERE status IS NOT NULL;
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 60)
+INSERT INTO task SELECT i, CASE i % 3 WHEN 0 THEN 'open' WHEN 1 THEN 'closed' END, i % 5 FROM n;
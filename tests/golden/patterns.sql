@@ -0,0 +1,6 @@
+CREATE TABLE words (id INTEGER PRIMARY KEY, w TEXT);
+
+INSERT INTO words VALUES
+    (1, 'apple'), (2, 'Apple'), (3, 'banana'), (4, 'cherry'), (5, 'a1'),
+    (6, 'a-b'), (7, 'a]b'), (8, '50%'), (9, '5_0'), (10, '500'),
+    (11, 'a_b'), (12, 'a%b'), (13, 'a\b'), (14, 'x^y'), (15, '');
@@ -0,0 +1,11 @@
+CREATE TABLE staff (id INTEGER PRIMARY KEY, name TEXT, dept TEXT, grade INTEGER, city TEXT);
+CREATE INDEX staff_grade ON staff (grade);
+CREATE INDEX staff_dept ON staff (dept COLLATE NOCASE);
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 400)
+INSERT INTO staff
+SELECT i, 'e' || i, CASE i % 4 WHEN 0 THEN 'ops' WHEN 1 THEN 'Dev' WHEN 2 THEN 'sales' ELSE NULL END,
+       i % 25, CASE i % 3 WHEN 0 THEN 'Leeds' WHEN 1 THEN 'York' ELSE 'Hull' END
+FROM n;
+
+ANALYZE;
@@ -0,0 +1,9 @@
+CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT, price REAL, qty INTEGER);
+
+INSERT INTO items VALUES
+    (1, 'widget', 3.14159, 42),
+    (2, 'it''s', 2.675, -7),
+    (3, 'a "quoted" name', 1234567.891, 1234567),
+    (4, NULL, NULL, NULL),
+    (5, 'zero', 0.0, 0),
+    (6, 'tiny', 0.000012345, 255);
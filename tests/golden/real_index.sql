@@ -0,0 +1,5 @@
+CREATE TABLE t (id INTEGER PRIMARY KEY, c REAL, r REAL, label TEXT);
+CREATE INDEX idx_t_c ON t (c DESC);
+CREATE INDEX idx_t_r ON t (r);
+WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 400)
+INSERT INTO t SELECT i, i % 19, (i % 7) / 2.0, 'row ' || i FROM seq;
@@ -0,0 +1,9 @@
+CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT, v INTEGER);
+INSERT INTO t VALUES
+    (-9223372036854775808, 'min', 1), (-5, 'minus five', 2), (0, 'zero', 3),
+    (1, 'one', 5), (2147483648, 'big', 8), (9223372036854775807, 'max', 13);
+CREATE INDEX idx_t_v ON t (v);
+
+WITH RECURSIVE n(i) AS (SELECT 10 UNION ALL SELECT i + 1 FROM n WHERE i < 3000)
+INSERT INTO t SELECT i, 'row ' || i, i % 17 FROM n;
+DELETE FROM t WHERE id % 3 = 0 AND id BETWEEN 100 AND 2000;
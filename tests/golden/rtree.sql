@@ -0,0 +1,11 @@
+-- R-Trees of one and two dimensions, the second with auxiliary columns and enough
+-- rows for the tree to grow interior nodes
+CREATE VIRTUAL TABLE spans USING rtree (id, lo, hi);
+INSERT INTO spans VALUES (1, 0, 10), (2, 5.5, 7.25), (3, -3, -1), (7, 100, 100);
+
+CREATE VIRTUAL TABLE boxes USING rtree (id, min_x, max_x, min_y, max_y, +label);
+WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 200)
+INSERT INTO boxes SELECT i, i, i + 2, -i, -i + 0.5, 'box ' || i FROM seq;
+
+CREATE VIRTUAL TABLE grid USING rtree_i32 (id, x0, x1, y0, y1);
+INSERT INTO grid VALUES (1, 0, 4, 0, 4), (2, -8, 8, 3, 9);
@@ -0,0 +1,10 @@
+CREATE TABLE customer (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE customer_address (customer_id INTEGER, line TEXT);
+CREATE TABLE invoice (id INTEGER PRIMARY KEY, customer_id INTEGER, total REAL);
+CREATE TABLE invoice_line (invoice_id INTEGER, item TEXT, quantity INTEGER);
+CREATE TABLE product (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE product_category (product_id INTEGER, category TEXT);
+CREATE TABLE supplier (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE warehouse_stock_level (product_id INTEGER, level INTEGER);
+CREATE INDEX invoice_customer ON invoice (customer_id);
+CREATE VIEW big_invoice AS SELECT id FROM invoice WHERE total > 100;
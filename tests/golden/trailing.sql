@@ -0,0 +1,8 @@
+CREATE TABLE fruit (id INTEGER PRIMARY KEY, name TEXT, colour TEXT);
+CREATE INDEX fruit_colour ON fruit (colour);
+
+INSERT INTO fruit VALUES
+    (1, 'apple', 'red'),
+    (2, 'banana', 'yellow'),
+    (3, 'cherry', 'red'),
+    (4, 'durian', NULL);
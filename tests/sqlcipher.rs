@@ -0,0 +1,118 @@
+//! Reads a database encrypted the way SQLCipher 4 encrypts one with its default
+//! settings, checking the rows against what `sqlite3` reads from the plain database
+//!
+//! There's seldom a SQLCipher build to encrypt the fixture with, so the test encrypts
+//! the pages of a database `sqlite3` builds itself. The test is skipped when `sqlite3`
+//! isn't installed, and another binary can be used by setting `SQLITE3`.
+#![cfg(feature = "sqlcipher")]
+
+#[macro_use]
+mod common;
+
+use aes::cipher::{block_padding::NoPadding, BlockEncryptMut, KeyIvInit};
+use codecrafters_sqlite::sqlite::{
+    database::Database,
+    sqlcipher::{KDF_ITERATIONS, PAGE_SIZE},
+    SqliteReader,
+};
+use common::{run_sqlite3, TempFile, BINARY};
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use std::{fs, process::Command};
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type HmacSha512 = Hmac<Sha512>;
+
+const PASSPHRASE: &str = "correct horse battery staple";
+const SALT: [u8; 16] = *b"0123456789abcdef";
+/// The IV then the HMAC of each page, as SQLCipher reserves them
+const RESERVED: usize = 80;
+
+/// Encrypts each page with AES-256 in CBC mode behind an HMAC-SHA512 of the page,
+/// with the salt in place of the header magic
+fn encrypt(plain: &[u8]) -> Vec<u8> {
+    let mut key = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha512>(PASSPHRASE.as_bytes(), &SALT, KDF_ITERATIONS, &mut key);
+    let mut hmac_key = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha512>(&key, &SALT.map(|byte| byte ^ 0x3a), 2, &mut hmac_key);
+
+    let mut encrypted = plain.to_vec();
+    for (n, page) in encrypted.chunks_mut(PAGE_SIZE).enumerate() {
+        let start = if n == 0 { SALT.len() } else { 0 };
+        let end = PAGE_SIZE - RESERVED;
+        let iv: [u8; 16] = std::array::from_fn(|idx| (n * 16 + idx) as u8);
+
+        let (data, reserved) = page.split_at_mut(end);
+        Aes256CbcEnc::new(&key.into(), &iv.into())
+            .encrypt_padded_mut::<NoPadding>(&mut data[start..], end - start)
+            .expect("pages are whole AES blocks");
+        reserved[..16].copy_from_slice(&iv);
+
+        let mut mac = HmacSha512::new_from_slice(&hmac_key).expect("HMAC takes any key size");
+        mac.update(&page[start..end + 16]);
+        mac.update(&(n as u32 + 1).to_le_bytes());
+        let tag = mac.finalize().into_bytes();
+        page[end + 16..].copy_from_slice(&tag);
+    }
+    encrypted[..SALT.len()].copy_from_slice(&SALT);
+    encrypted
+}
+
+#[test]
+fn reads_an_encrypted_database() {
+    let sqlite3 = require_sqlite3!("SQLCipher");
+
+    let plain = TempFile::new("plain.db");
+    run_sqlite3(
+        &sqlite3,
+        plain.path(),
+        &[
+            &format!(".filectrl reserve_bytes {RESERVED}"),
+            &format!(
+                "PRAGMA page_size = {PAGE_SIZE};
+                 CREATE TABLE t (id INTEGER PRIMARY KEY, label TEXT, body BLOB);
+                 CREATE INDEX tl ON t (label);
+                 WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < 500)
+                 INSERT INTO t SELECT i, 'row ' || i, randomblob(i % 40) FROM seq;"
+            ),
+        ],
+    );
+
+    let encrypted = TempFile::new("encrypted.db");
+    let contents = fs::read(plain.path()).expect("reading the fixture");
+    assert_eq!(
+        contents[20] as usize, RESERVED,
+        "the pages have room for SQLCipher"
+    );
+    fs::write(encrypted.path(), encrypt(&contents)).expect("writing the encrypted database");
+
+    let reader = SqliteReader::open_sqlcipher(encrypted.path(), PASSPHRASE.as_bytes())
+        .expect("opening the encrypted database");
+    let mut database = Database::new(reader);
+    for sql in [
+        "SELECT id, label, hex(body) FROM t ORDER BY id",
+        "SELECT id FROM t WHERE label = 'row 321'",
+        "SELECT count(*) FROM t",
+    ] {
+        let mut out = Vec::new();
+        database
+            .execute_to(sql, &mut out)
+            .unwrap_or_else(|e| panic!("{sql} failed: {e:#}"));
+        assert_eq!(
+            String::from_utf8_lossy(&out).lines().collect::<Vec<_>>(),
+            run_sqlite3(&sqlite3, plain.path(), &[sql]),
+            "{sql}"
+        );
+    }
+
+    // A wrong passphrase fails the HMAC of the first page read
+    let output = Command::new(BINARY)
+        .args(["--key", "wrong"])
+        .arg(encrypted.path())
+        .arg("select count(*) from t")
+        .output()
+        .expect("the binary runs");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("the key may be wrong"), "{stderr}");
+}
@@ -0,0 +1,153 @@
+//! Reads a database in WAL mode while a `sqlite3` process has it open and is
+//! writing to it, through the wal-index shared with that connection
+//!
+//! The tests are skipped when `sqlite3` isn't installed, and another binary can be
+//! used by setting `SQLITE3`. SQLite only shares the wal-index on Unix.
+#![cfg(unix)]
+
+#[macro_use]
+mod common;
+
+use codecrafters_sqlite::sqlite::shm::WalIndex;
+use common::{run, TempFile, BINARY};
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdout, Command, Stdio},
+};
+
+/// Printed by the connection once it has run everything sent before it
+const READY: &str = "ready";
+
+/// A `sqlite3` process, or a session of this crate, holding the database open and
+/// fed statements one batch at a time
+struct Connection {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Connection {
+    fn open(program: &str, database: &Path) -> Self {
+        let mut child = Command::new(program)
+            .arg(database)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("the connection runs");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        Self { child, stdout }
+    }
+
+    /// Runs `sql`, waiting until the connection is done with it
+    fn execute(&mut self, sql: &str) {
+        self.query(sql);
+    }
+
+    /// The lines the connection prints for `sql`, once it's done with it
+    fn query(&mut self, sql: &str) -> Vec<String> {
+        let stdin = self.child.stdin.as_mut().expect("stdin is piped");
+        writeln!(stdin, "{sql}\nSELECT '{READY}';").expect("writing to the connection");
+        stdin.flush().expect("writing to the connection");
+
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read = self
+                .stdout
+                .read_line(&mut line)
+                .expect("reading from the connection");
+            assert!(read > 0, "the connection exited");
+            match line.trim_end() {
+                READY => return lines,
+                line => lines.push(line.to_string()),
+            }
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        drop(self.child.stdin.take());
+        self.child.wait().ok();
+    }
+}
+
+#[test]
+fn reads_the_snapshot_of_a_live_connection() {
+    let sqlite3 = require_sqlite3!("WAL");
+
+    let database = TempFile::new("live.db");
+    let path = database.path();
+    let mut connection = Connection::open(&sqlite3, path);
+
+    // Nothing is checkpointed, so every row is only in the log
+    connection.execute(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA wal_autocheckpoint = 0;
+         CREATE TABLE t (a);
+         INSERT INTO t VALUES (1), (2);",
+    );
+
+    let index = WalIndex::attach(path)
+        .expect("reading the wal-index")
+        .expect("another connection has the index open");
+    let snapshot = index.snapshot.as_ref().expect("the log holds commits");
+    assert!(snapshot.max_frame > 0, "{snapshot:?}");
+    drop(index);
+
+    assert_eq!(run(path, "select count(*) from t"), ["2"]);
+
+    // An open write transaction is invisible until it commits
+    connection.execute("BEGIN; INSERT INTO t VALUES (3);");
+    assert_eq!(run(path, "select count(*) from t"), ["2"]);
+    connection.execute("COMMIT;");
+    assert_eq!(run(path, "select count(*) from t"), ["3"]);
+
+    // Checkpointing copies the log into the file and resets it
+    connection.execute("PRAGMA wal_checkpoint(TRUNCATE); INSERT INTO t VALUES (4);");
+    assert_eq!(
+        run(path, "select a from t order by a"),
+        ["1", "2", "3", "4"]
+    );
+}
+
+#[test]
+fn keeps_its_read_mark_through_a_backup() {
+    let sqlite3 = require_sqlite3!("WAL");
+
+    let database = TempFile::new("backed-up.db");
+    let copy = TempFile::new("backup.db");
+    let path = database.path();
+    let mut writer = Connection::open(&sqlite3, path);
+    writer.execute(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA wal_autocheckpoint = 0;
+         CREATE TABLE t (a);
+         INSERT INTO t VALUES (1), (2);",
+    );
+
+    // The backup opens the wal-index a second time in the same process, and
+    // closing it mustn't let go of the read mark of the session
+    let mut session = Connection::open(BINARY, path);
+    assert_eq!(session.query("select count(*) from t;"), ["2"]);
+    let backup = format!(".backup {}", copy.path().display());
+    assert_eq!(session.query(&backup).len(), 1);
+    assert_eq!(run(copy.path(), "select count(*) from t"), ["2"]);
+
+    // So the log can't be restarted under the session, and it still reads its
+    // snapshot after the writer commits again
+    writer.execute("INSERT INTO t VALUES (3);");
+    let checkpoint = writer.query("PRAGMA wal_checkpoint(TRUNCATE);");
+    assert!(
+        checkpoint.first().is_some_and(|row| row.starts_with("1|")),
+        "the read mark of the session blocks the checkpoint: {checkpoint:?}"
+    );
+    writer.execute("INSERT INTO t VALUES (4);");
+    assert_eq!(session.query("select count(*) from t;"), ["2"]);
+    drop(session);
+
+    assert_eq!(
+        run(path, "select a from t order by a"),
+        ["1", "2", "3", "4"]
+    );
+}